@@ -0,0 +1,34 @@
+//! Resamples a converted `.bin` file to 5-minute buckets of median close with
+//! `resample::resample_with`, and prints the first few.
+//!
+//! ```bash
+//! cargo run --release --example median_resample -- /path/to/output/data.soa.bin
+//! ```
+
+use csv_to_flatbuffer::{cli, read_flatbuffers, resample};
+
+fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("Usage: median_resample <file.aos.bin|file.soa.bin>"))?;
+    let bars = read_flatbuffers::load_bars(&path)?;
+
+    let medians = resample::resample_with(
+        bars,
+        resample::Timeframe::Intraday(300),
+        &cli::BucketEdge::Left,
+        0,
+        |closes: &mut Vec<f64>, bar| closes.push(bar.close),
+        |bucket_start, mut closes| {
+            closes.sort_by(f64::total_cmp);
+            let mid = closes.len() / 2;
+            let median = if closes.len() % 2 == 0 { (closes[mid - 1] + closes[mid]) / 2.0 } else { closes[mid] };
+            (bucket_start, median)
+        },
+    );
+
+    for (bucket_start, median) in medians.iter().take(5) {
+        println!("{} median close {}", bucket_start, median);
+    }
+    anyhow::Ok(())
+}
@@ -0,0 +1,102 @@
+use crate::file_processing;
+
+/// Corporate actions loaded from an adjustments file, used to compute adjusted closes.
+///
+/// The file is plain text with one `YYYY-MM-DD,factor` pair per line. Blank lines and
+/// lines starting with `#` are ignored, like the trading calendar file.
+///
+/// # Convention
+///
+/// Each line is a corporate action effective on its date (the ex-date): `factor` is the
+/// price multiplier applied to every bar *before* that date. Factors are cumulative
+/// backward, so a bar's adjusted close is its close times the product of the factors of
+/// all actions dated after the bar's day. Bars on or after the latest action are
+/// unadjusted (`adj_close == close`).
+///
+/// * A 2-for-1 split is `0.5`, a 1-for-10 reverse split is `10`.
+/// * A cash dividend `D` is `1 - D / P`, where `P` is the close of the trading day before
+///   the ex-date.
+///
+/// Several actions on the same date are combined by multiplying their factors.
+///
+/// # Timezone
+///
+/// Bar days are UTC calendar dates of their timestamps, the same convention as the
+/// `daily_index` keys, so action dates must use that trading-date convention too.
+#[derive(Debug, Clone, Default)]
+pub struct Adjustments {
+    /// Action dates in ascending order, with the cumulative factor applying to bars
+    /// before each date (the product of that action's factor and all later ones).
+    cumulative: Vec<(chrono::NaiveDate, f64)>,
+}
+
+impl Adjustments {
+    /// Loads corporate actions from a file with one `YYYY-MM-DD,factor` pair per line.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the adjustments file.
+    ///
+    /// # Returns
+    /// * `anyhow::Result<Adjustments>` - Parsed adjustments or an error.
+    ///
+    /// # Errors
+    /// * If the file can't be read.
+    /// * If a non-comment line isn't a valid date and a positive, finite factor.
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path.as_ref()).map_err(|e| file_processing::io_error(e, "read adjustments", &path))?;
+        Self::parse(&content)
+    }
+
+    /// Parses the contents of an adjustments file (see `load`).
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let mut factors: std::collections::BTreeMap<chrono::NaiveDate, f64> = std::collections::BTreeMap::new();
+
+        for (line_no, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (date, factor) = line
+                .split_once(',')
+                .ok_or_else(|| anyhow::anyhow!("Invalid adjustment '{}' at line {}: expected YYYY-MM-DD,factor", line, line_no + 1))?;
+            let date = chrono::NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                .map_err(|e| anyhow::anyhow!("Invalid adjustment date '{}' at line {}: {}", date.trim(), line_no + 1, e))?;
+            let factor: f64 = factor
+                .trim()
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid adjustment factor '{}' at line {}: {}", factor.trim(), line_no + 1, e))?;
+            if !factor.is_finite() || factor <= 0.0 {
+                return Err(anyhow::anyhow!("Adjustment factor at line {} must be positive, got {}", line_no + 1, factor));
+            }
+            *factors.entry(date).or_insert(1.0) *= factor;
+        }
+
+        let mut cumulative: Vec<(chrono::NaiveDate, f64)> = Vec::with_capacity(factors.len());
+        let mut product = 1.0;
+        for (date, factor) in factors.into_iter().rev() {
+            product *= factor;
+            cumulative.push((date, product));
+        }
+        cumulative.reverse();
+
+        anyhow::Ok(Self { cumulative })
+    }
+
+    /// Returns the factor a close on `date` is multiplied by: the product of the factors
+    /// of every action dated after `date` (1 if there are none).
+    pub fn factor_for(&self, date: chrono::NaiveDate) -> f64 {
+        let next = self.cumulative.partition_point(|(action_date, _)| *action_date <= date);
+        self.cumulative.get(next).map_or(1.0, |(_, factor)| *factor)
+    }
+
+    /// Returns the adjusted close of a bar at `timestamp` (see the type-level convention).
+    ///
+    /// # Errors
+    /// * If `timestamp` can't be converted to a date.
+    pub fn adjusted_close(&self, timestamp: u64, close: f64) -> anyhow::Result<f64> {
+        let date = chrono::DateTime::from_timestamp(timestamp as i64, 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid timestamp: {}", timestamp))?
+            .date_naive();
+        anyhow::Ok(close * self.factor_for(date))
+    }
+}
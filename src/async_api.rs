@@ -0,0 +1,43 @@
+//! Async entry points for running conversions inside tokio services.
+//!
+//! Conversion is CPU-bound, so it is moved off the async workers with
+//! `tokio::task::spawn_blocking`; the sync API in `csv_processor` stays the source of truth.
+
+use crate::csv_processor;
+
+/// Converts one input file to FlatBuffers without blocking the async runtime.
+///
+/// Runs `csv_processor::convert_csv_to_flatbuffer` on tokio's blocking thread pool
+/// and resolves to its `ConversionStats`.
+///
+/// # Cancellation
+///
+/// Dropping the returned future does not stop the conversion: a blocking task can't be
+/// interrupted, so it runs to completion in the background unless `options.cancel` is set,
+/// which stops it with `csv_processor::Cancelled` within `csv_processor::CANCEL_CHECK_ROWS`
+/// rows. Output files are written atomically (temporary file + rename), so a dropped,
+/// cancelled or failed conversion never leaves a half-written `.bin` or `.idx` behind; each
+/// file is either absent, the previous version, or complete.
+///
+/// # Arguments
+/// * `input_path` - Path to the input file.
+/// * `output_path` - Path for the output .bin file.
+/// * `options` - Conversion options.
+///
+/// # Returns
+/// * `anyhow::Result<ConversionStats>` - Conversion summary or an error.
+///
+/// # Errors
+/// * Propagates errors from `convert_csv_to_flatbuffer`.
+/// * If the blocking task panics.
+pub async fn convert_csv_to_flatbuffer_async(
+    input_path: std::path::PathBuf,
+    output_path: std::path::PathBuf,
+    options: csv_processor::ConversionOptions,
+) -> anyhow::Result<csv_processor::ConversionStats> {
+    tokio::task::spawn_blocking(move || {
+        csv_processor::convert_csv_to_flatbuffer(&input_path, &output_path, &options)
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Conversion task failed: {}", e))?
+}
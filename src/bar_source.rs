@@ -0,0 +1,345 @@
+use crate::ohlcv_generated;
+use crate::ohlcv_soa_generated;
+use crate::resample;
+use crate::resample::OHLCVBar;
+
+/// Positional read access to stored bars, implemented for both storage layouts.
+///
+/// An AOS file stores a vector of `OHLCV` tables (`items.get(i).open()`), an SOA file one
+/// vector per field (`opens.get(i)`). The resamplers and printers are written once over
+/// `B: BarSource` and monomorphized per layout, so the accessors inline to the same reads
+/// the per-layout code did.
+///
+/// Positions are storage positions. Like `flatbuffers::Vector::get`, the accessors panic
+/// past `len`; callers check against it first.
+pub trait BarSource {
+    /// Number of bars.
+    fn len(&self) -> usize;
+
+    /// `true` if there are no bars.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Timestamp of bar `i`.
+    fn timestamp(&self, i: usize) -> u64;
+
+    /// Open price of bar `i`.
+    fn open(&self, i: usize) -> f64;
+
+    /// High price of bar `i`.
+    fn high(&self, i: usize) -> f64;
+
+    /// Low price of bar `i`.
+    fn low(&self, i: usize) -> f64;
+
+    /// Close price of bar `i`.
+    fn close(&self, i: usize) -> f64;
+
+    /// Volume of bar `i`; 0 in volumeless files.
+    fn volume(&self, i: usize) -> u64;
+
+    /// The whole of bar `i`, with a `count` of 1.
+    #[inline]
+    fn bar(&self, i: usize) -> OHLCVBar {
+        OHLCVBar {
+            timestamp: self.timestamp(i),
+            open: self.open(i),
+            high: self.high(i),
+            low: self.low(i),
+            close: self.close(i),
+            volume: self.volume(i),
+            count: 1,
+        }
+    }
+}
+
+impl<'a> BarSource for flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV<'a>>> {
+    #[inline]
+    fn len(&self) -> usize {
+        flatbuffers::Vector::len(self)
+    }
+
+    #[inline]
+    fn timestamp(&self, i: usize) -> u64 {
+        self.get(i).timestamp()
+    }
+
+    #[inline]
+    fn open(&self, i: usize) -> f64 {
+        self.get(i).open()
+    }
+
+    #[inline]
+    fn high(&self, i: usize) -> f64 {
+        self.get(i).high()
+    }
+
+    #[inline]
+    fn low(&self, i: usize) -> f64 {
+        self.get(i).low()
+    }
+
+    #[inline]
+    fn close(&self, i: usize) -> f64 {
+        self.get(i).close()
+    }
+
+    #[inline]
+    fn volume(&self, i: usize) -> u64 {
+        self.get(i).volume()
+    }
+
+    /// Follows the table offset once rather than once per field.
+    #[inline]
+    fn bar(&self, i: usize) -> OHLCVBar {
+        OHLCVBar::from(self.get(i))
+    }
+}
+
+/// The field vectors of an SOA `OHLCVSOA` table, read as one `BarSource`.
+///
+/// Missing columns read as empty; a missing volumes column (volumeless files) reads as 0.
+#[derive(Debug, Clone, Copy)]
+pub struct SoaColumns<'a> {
+    timestamps: flatbuffers::Vector<'a, u64>,
+    opens: flatbuffers::Vector<'a, f64>,
+    highs: flatbuffers::Vector<'a, f64>,
+    lows: flatbuffers::Vector<'a, f64>,
+    closes: flatbuffers::Vector<'a, f64>,
+    volumes: Option<flatbuffers::Vector<'a, u64>>,
+    len: usize,
+}
+
+impl<'a> SoaColumns<'a> {
+    /// Wraps the columns of `data_soa`, checking that every column has the same length.
+    ///
+    /// Reading a column past its end would panic or silently mix values of different bars,
+    /// so a corrupt file is rejected up front instead.
+    ///
+    /// # Errors
+    ///
+    /// * If the columns have different lengths (all of them are reported).
+    pub fn new(data_soa: ohlcv_soa_generated::OHLCVSOA<'a>) -> anyhow::Result<Self> {
+        let columns = Self::truncated(data_soa);
+        let len = columns.timestamps.len();
+        let lengths = [
+            ("opens", columns.opens.len()),
+            ("highs", columns.highs.len()),
+            ("lows", columns.lows.len()),
+            ("closes", columns.closes.len()),
+            ("volumes", columns.volumes.map_or(len, |v| v.len())),
+        ];
+        if lengths.iter().any(|&(_, column_len)| column_len != len) {
+            let lengths: Vec<String> = lengths.iter().map(|(name, column_len)| format!("{} {}", name, column_len)).collect();
+            return Err(anyhow::anyhow!(
+                "SOA columns have mismatched lengths (timestamps {}, {}); the file is corrupt",
+                len,
+                lengths.join(", ")
+            ));
+        }
+        anyhow::Ok(columns)
+    }
+
+    /// Wraps the columns of `data_soa`, exposing only the bars present in every column.
+    ///
+    /// For display of raw bars, which shows what is there rather than rejecting the file.
+    pub fn truncated(data_soa: ohlcv_soa_generated::OHLCVSOA<'a>) -> Self {
+        let timestamps = data_soa.timestamps().unwrap_or_default();
+        let opens = data_soa.opens().unwrap_or_default();
+        let highs = data_soa.highs().unwrap_or_default();
+        let lows = data_soa.lows().unwrap_or_default();
+        let closes = data_soa.closes().unwrap_or_default();
+        let volumes = data_soa.volumes();
+
+        let len = [timestamps.len(), opens.len(), highs.len(), lows.len(), closes.len()]
+            .into_iter()
+            .chain(volumes.map(|v| v.len()))
+            .min()
+            .unwrap_or(0);
+        SoaColumns { timestamps, opens, highs, lows, closes, volumes, len }
+    }
+
+    /// Columns without bars, for an SOA file written with no `data` table (zero bars).
+    pub fn empty() -> Self {
+        SoaColumns {
+            timestamps: flatbuffers::Vector::default(),
+            opens: flatbuffers::Vector::default(),
+            highs: flatbuffers::Vector::default(),
+            lows: flatbuffers::Vector::default(),
+            closes: flatbuffers::Vector::default(),
+            volumes: None,
+            len: 0,
+        }
+    }
+}
+
+impl BarSource for SoaColumns<'_> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn timestamp(&self, i: usize) -> u64 {
+        self.timestamps.get(i)
+    }
+
+    #[inline]
+    fn open(&self, i: usize) -> f64 {
+        self.opens.get(i)
+    }
+
+    #[inline]
+    fn high(&self, i: usize) -> f64 {
+        self.highs.get(i)
+    }
+
+    #[inline]
+    fn low(&self, i: usize) -> f64 {
+        self.lows.get(i)
+    }
+
+    #[inline]
+    fn close(&self, i: usize) -> f64 {
+        self.closes.get(i)
+    }
+
+    #[inline]
+    fn volume(&self, i: usize) -> u64 {
+        self.volumes.map_or(0, |v| v.get(i))
+    }
+}
+
+/// Bars already materialized, e.g. collected from the segments of a segmented SOA file.
+impl BarSource for [OHLCVBar] {
+    #[inline]
+    fn len(&self) -> usize {
+        <[OHLCVBar]>::len(self)
+    }
+
+    #[inline]
+    fn timestamp(&self, i: usize) -> u64 {
+        self[i].timestamp
+    }
+
+    #[inline]
+    fn open(&self, i: usize) -> f64 {
+        self[i].open
+    }
+
+    #[inline]
+    fn high(&self, i: usize) -> f64 {
+        self[i].high
+    }
+
+    #[inline]
+    fn low(&self, i: usize) -> f64 {
+        self[i].low
+    }
+
+    #[inline]
+    fn close(&self, i: usize) -> f64 {
+        self[i].close
+    }
+
+    #[inline]
+    fn volume(&self, i: usize) -> u64 {
+        self[i].volume
+    }
+
+    /// Keeps the stored `count`, so already aggregated bars aggregate correctly.
+    #[inline]
+    fn bar(&self, i: usize) -> OHLCVBar {
+        self[i]
+    }
+}
+
+/// A `BarSource` whose NaN highs and lows read as -inf and +inf (`resample::skip_nan_extremes`),
+/// for resampling under `--nan-policy skip`.
+#[derive(Debug, Clone, Copy)]
+pub struct NanSkipping<'s, B>(pub &'s B);
+
+impl<B: BarSource> BarSource for NanSkipping<'_, B> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    fn timestamp(&self, i: usize) -> u64 {
+        self.0.timestamp(i)
+    }
+
+    #[inline]
+    fn open(&self, i: usize) -> f64 {
+        self.0.open(i)
+    }
+
+    #[inline]
+    fn high(&self, i: usize) -> f64 {
+        let high = self.0.high(i);
+        if high.is_nan() { f64::NEG_INFINITY } else { high }
+    }
+
+    #[inline]
+    fn low(&self, i: usize) -> f64 {
+        let low = self.0.low(i);
+        if low.is_nan() { f64::INFINITY } else { low }
+    }
+
+    #[inline]
+    fn close(&self, i: usize) -> f64 {
+        self.0.close(i)
+    }
+
+    #[inline]
+    fn volume(&self, i: usize) -> u64 {
+        self.0.volume(i)
+    }
+
+    #[inline]
+    fn bar(&self, i: usize) -> OHLCVBar {
+        resample::skip_nan_extremes(self.0.bar(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use crate::csv_processor;
+    use crate::utils;
+
+    fn records() -> Vec<csv_processor::ProcessedRecord> {
+        [
+            OHLCVBar { timestamp: 1705311000, open: 100.0, high: 101.0, low: 99.0, close: 100.5, volume: 10, count: 1 },
+            OHLCVBar { timestamp: 1705311060, open: 100.5, high: 102.0, low: 100.0, close: 101.5, volume: 20, count: 1 },
+        ]
+        .iter()
+        .map(csv_processor::ProcessedRecord::from)
+        .collect()
+    }
+
+    fn bars<B: BarSource>(source: &B) -> Vec<(u64, f64, f64, f64, f64, u64)> {
+        (0..source.len())
+            .map(|i| {
+                let bar = source.bar(i);
+                (bar.timestamp, bar.open, bar.high, bar.low, bar.close, bar.volume)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn aos_and_soa_sources_read_the_same_bars() {
+        let aos = csv_processor::build_flatbuffer(&records(), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let soa = csv_processor::build_flatbuffer(&records(), cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&aos).unwrap().items().unwrap();
+        let columns = SoaColumns::new(ohlcv_soa_generated::root_as_ohlcvlist_soa(&soa).unwrap().data().unwrap()).unwrap();
+
+        assert_eq!(BarSource::len(&items), 2);
+        assert_eq!(bars(&items), bars(&columns));
+        assert_eq!(columns.volume(1), 20);
+    }
+}
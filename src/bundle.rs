@@ -0,0 +1,208 @@
+use crate::cli;
+use crate::index;
+use crate::utils;
+use crate::file_processing;
+use crate::read_flatbuffers;
+
+/// Magic bytes at the start of every `.ohlcv` bundle.
+pub const BUNDLE_MAGIC: &[u8; 8] = b"OHLCVBDL";
+
+/// Version of the bundle header written by this binary.
+pub const BUNDLE_VERSION: u8 = 1;
+
+/// Size of the bundle header; the FlatBuffer payload starts right after it.
+pub const BUNDLE_HEADER_LEN: usize = 48;
+
+/// Final extension of bundle files, e.g. `data.aos.ohlcv`.
+pub const BUNDLE_EXTENSION: &str = "ohlcv";
+
+/// A `.ohlcv` bundle: a `.bin` and its `.idx` in one file (`--bundle`), sliced out of a
+/// mapped buffer without copying.
+///
+/// # Layout
+///
+/// All integers are little-endian.
+///
+/// | Offset | Size | Field |
+/// |---|---|---|
+/// | 0 | 8 | `BUNDLE_MAGIC` |
+/// | 8 | 1 | Header version (`BUNDLE_VERSION`) |
+/// | 9 | 1 | Storage format: 0 AOS, 1 SOA |
+/// | 10 | 6 | Reserved, zero |
+/// | 16 | 8 | Payload offset |
+/// | 24 | 8 | Payload length |
+/// | 32 | 8 | Index offset |
+/// | 40 | 8 | Index length |
+///
+/// The payload is the `.bin` byte for byte, written at `BUNDLE_HEADER_LEN` so it stays
+/// 8-byte aligned in the mapping, and `segment_offsets` keep counting from its start. The
+/// index is the `.idx` byte for byte (plain or compact) and follows the payload. Readers
+/// go by the stored offsets, so later versions may add fields or padding in between.
+#[derive(Debug, Clone)]
+pub struct Bundle<'a> {
+    pub storage_format: cli::StorageFormat,
+    /// The FlatBuffer data, as in a `.bin`.
+    pub payload: &'a [u8],
+    /// The encoded index, as in a `.idx`.
+    pub index: &'a [u8],
+}
+
+impl<'a> Bundle<'a> {
+    /// Parses the header of `buf` and slices out both sections.
+    ///
+    /// # Returns
+    /// * `anyhow::Result<Option<Bundle>>` - The bundle, or `None` if `buf` doesn't start
+    ///   with `BUNDLE_MAGIC` (e.g. a plain `.bin`).
+    ///
+    /// # Errors
+    /// * If the header is truncated, has an unknown version or storage format, or a
+    ///   section lies outside `buf`.
+    pub fn parse(buf: &'a [u8]) -> anyhow::Result<Option<Bundle<'a>>> {
+        if !buf.starts_with(BUNDLE_MAGIC) {
+            return anyhow::Ok(None);
+        }
+        if buf.len() < BUNDLE_HEADER_LEN {
+            return Err(anyhow::anyhow!("Bundle header is truncated ({} bytes)", buf.len()));
+        }
+        if buf[8] != BUNDLE_VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported bundle version {} (this binary reads version {})",
+                buf[8],
+                BUNDLE_VERSION
+            ));
+        }
+        let storage_format = match buf[9] {
+            0 => cli::StorageFormat::Aos,
+            1 => cli::StorageFormat::Soa,
+            other => return Err(anyhow::anyhow!("Unknown storage format {} in bundle header", other)),
+        };
+        let word = |at: usize| u64::from_le_bytes(buf[at..at + 8].try_into().unwrap());
+        let section = move |offset: u64, len: u64, name: &str| {
+            offset
+                .checked_add(len)
+                .filter(|&end| end <= buf.len() as u64)
+                .map(|end| &buf[offset as usize..end as usize])
+                .ok_or_else(|| anyhow::anyhow!("Bundle {} section ({} bytes at {}) is out of bounds", name, len, offset))
+        };
+        anyhow::Ok(Some(Bundle {
+            storage_format,
+            payload: section(word(16), word(24), "payload")?,
+            index: section(word(32), word(40), "index")?,
+        }))
+    }
+
+    /// Decodes the bundled index (see `utils::decode_full_index`).
+    pub fn full_index(&self) -> anyhow::Result<index::FullIndex> {
+        utils::decode_full_index(self.index)
+    }
+}
+
+/// Builds the header of a bundle holding `payload_len` bytes of FlatBuffer data followed
+/// by `index_len` bytes of index (see `Bundle`).
+fn encode_header(storage_format: &cli::StorageFormat, payload_len: u64, index_len: u64) -> [u8; BUNDLE_HEADER_LEN] {
+    let mut header = [0u8; BUNDLE_HEADER_LEN];
+    header[..8].copy_from_slice(BUNDLE_MAGIC);
+    header[8] = BUNDLE_VERSION;
+    header[9] = match storage_format {
+        cli::StorageFormat::Aos => 0,
+        cli::StorageFormat::Soa => 1,
+    };
+    let payload_offset = BUNDLE_HEADER_LEN as u64;
+    header[16..24].copy_from_slice(&payload_offset.to_le_bytes());
+    header[24..32].copy_from_slice(&payload_len.to_le_bytes());
+    header[32..40].copy_from_slice(&(payload_offset + payload_len).to_le_bytes());
+    header[40..48].copy_from_slice(&index_len.to_le_bytes());
+    header
+}
+
+/// Writes `payload` and `index` as a bundle to `path`, atomically.
+///
+/// # Errors
+/// * If the file can't be written.
+pub fn write_bundle<P: AsRef<std::path::Path>>(
+    path: P,
+    storage_format: &cli::StorageFormat,
+    payload: &[u8],
+    index: &[u8],
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let header = encode_header(storage_format, payload.len() as u64, index.len() as u64);
+    file_processing::write_atomic_with(path, |file| {
+        for section in [&header[..], payload, index] {
+            std::io::Write::write_all(&mut *file, section).map_err(|e| file_processing::io_error(e, "write", path))?;
+        }
+        anyhow::Ok(())
+    })
+}
+
+/// Path of the bundle for a `.bin`: the same name with the `ohlcv` extension, e.g.
+/// `data.aos.bin` → `data.aos.ohlcv`.
+pub fn bundle_path(bin_path: &std::path::Path) -> std::path::PathBuf {
+    bin_path.with_extension(BUNDLE_EXTENSION)
+}
+
+/// Packs an existing `.bin` and the `.idx` next to it into a `.ohlcv` bundle.
+///
+/// This is both how `--bundle` conversions produce their output and the migration for
+/// files converted before bundles existed. The storage format is taken from the file name,
+/// or the contents when the name doesn't say (`read_flatbuffers::storage_format_of`). With
+/// `remove_originals` the `.bin` and `.idx` are deleted once the bundle is written.
+///
+/// # Arguments
+/// * `bin_path` - Path to the `.bin` (or `--output-suffix` file).
+/// * `remove_originals` - Delete the `.bin` and `.idx` afterwards.
+///
+/// # Returns
+/// * `anyhow::Result<std::path::PathBuf>` - Path of the written bundle.
+///
+/// # Errors
+/// * If the `.bin` or `.idx` can't be read, or the storage format can't be determined.
+/// * If the `.idx` doesn't decode, so a broken pair is never packed.
+/// * If the bundle can't be written or the originals removed.
+pub fn bundle_files(bin_path: &std::path::Path, remove_originals: bool) -> anyhow::Result<std::path::PathBuf> {
+    let extension = bin_path.extension().and_then(|ext| ext.to_str()).unwrap_or(cli::DEFAULT_OUTPUT_SUFFIX);
+    if extension == BUNDLE_EXTENSION {
+        return Err(anyhow::anyhow!("{} is already a bundle", bin_path.display()));
+    }
+    let storage_format = read_flatbuffers::storage_format_of(bin_path, extension)?
+        .ok_or_else(|| anyhow::anyhow!("Unknown storage format for file {}", bin_path.display()))?;
+    let payload = std::fs::read(bin_path).map_err(|e| file_processing::io_error(e, "read", bin_path))?;
+    let idx_path = bin_path.with_extension("idx");
+    let index = std::fs::read(&idx_path).map_err(|e| file_processing::io_error(e, "read index", &idx_path))?;
+    utils::decode_full_index(&index).map_err(|e| anyhow::anyhow!("Invalid index {}: {}", idx_path.display(), e))?;
+
+    let path = bundle_path(bin_path);
+    write_bundle(&path, &storage_format, &payload, &index)?;
+    if remove_originals {
+        std::fs::remove_file(bin_path).map_err(|e| file_processing::io_error(e, "remove", bin_path))?;
+        std::fs::remove_file(&idx_path).map_err(|e| file_processing::io_error(e, "remove", &idx_path))?;
+    }
+    anyhow::Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trips_and_bad_headers_are_rejected() {
+        let payload = [1u8, 2, 3, 4, 5, 6, 7, 8, 9];
+        let index = [10u8, 11, 12];
+        let mut buf = encode_header(&cli::StorageFormat::Soa, payload.len() as u64, index.len() as u64).to_vec();
+        buf.extend(payload);
+        buf.extend(index);
+
+        let bundle = Bundle::parse(&buf).unwrap().unwrap();
+        assert!(matches!(bundle.storage_format, cli::StorageFormat::Soa));
+        assert_eq!(bundle.payload, payload);
+        assert_eq!(bundle.index, index);
+        assert_eq!(bundle.payload.as_ptr() as usize - buf.as_ptr() as usize, BUNDLE_HEADER_LEN);
+
+        assert!(Bundle::parse(&payload).unwrap().is_none());
+        assert!(Bundle::parse(&buf[..BUNDLE_HEADER_LEN - 1]).is_err());
+        assert!(Bundle::parse(&buf[..buf.len() - 1]).is_err());
+        let mut future = buf.clone();
+        future[8] = BUNDLE_VERSION + 1;
+        assert!(Bundle::parse(&future).is_err());
+    }
+}
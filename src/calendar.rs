@@ -0,0 +1,175 @@
+use crate::index;
+use crate::file_processing;
+
+/// Set of expected trading dates loaded from an exchange calendar file.
+///
+/// The calendar file is plain text with one ISO date (`YYYY-MM-DD`) per line.
+/// Blank lines and lines starting with `#` are ignored, so holidays can be
+/// commented inline. Weekends and holidays are simply absent from the file.
+///
+/// # Timezone
+///
+/// Calendar dates are compared against the `daily_index` keys, which are derived
+/// from the `<DATE>` column of the input as-is (interpreted as UTC). The calendar
+/// must therefore use the same trading-date convention as the input files.
+#[derive(Debug, Clone, Default)]
+pub struct TradingCalendar {
+    dates: std::collections::BTreeSet<chrono::NaiveDate>,
+}
+
+impl TradingCalendar {
+    /// Loads a trading calendar from a file with one ISO date per line.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the calendar file.
+    ///
+    /// # Returns
+    /// * `anyhow::Result<TradingCalendar>` - Parsed calendar or an error.
+    ///
+    /// # Errors
+    /// * If the file can't be read.
+    /// * If a non-comment line isn't a valid `YYYY-MM-DD` date.
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path.as_ref()).map_err(|e| file_processing::io_error(e, "read calendar", &path))?;
+        let mut dates = std::collections::BTreeSet::new();
+
+        for (line_no, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let date = chrono::NaiveDate::parse_from_str(line, "%Y-%m-%d")
+                .map_err(|e| anyhow::anyhow!("Invalid calendar date '{}' at line {}: {}", line, line_no + 1, e))?;
+            dates.insert(date);
+        }
+
+        anyhow::Ok(Self { dates })
+    }
+
+    /// Returns calendar trading days that have no entry in `daily_index`.
+    ///
+    /// Only dates between the first and last day present in the index are considered,
+    /// so a file covering one month is not reported as missing the rest of the year.
+    /// Dates absent from the calendar (weekends, holidays) are never reported.
+    ///
+    /// # Arguments
+    /// * `daily_index` - Daily index produced during conversion.
+    ///
+    /// # Returns
+    /// * `anyhow::Result<Vec<NaiveDate>>` - Unexpectedly missing trading days in ascending order.
+    pub fn missing_days(&self, daily_index: &[index::DailyIndexEntry]) -> anyhow::Result<Vec<chrono::NaiveDate>> {
+        let mut present = std::collections::BTreeSet::new();
+        for entry in daily_index {
+            present.insert(chrono::NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d")?);
+        }
+
+        let (first, last) = match (present.first(), present.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return anyhow::Ok(Vec::new()),
+        };
+
+        let missing = self.dates
+            .range(first..=last)
+            .filter(|date| !present.contains(date))
+            .cloned()
+            .collect();
+
+        anyhow::Ok(missing)
+    }
+}
+
+impl TradingCalendar {
+    /// Returns the calendar trading days strictly between `after` and `before`.
+    ///
+    /// # Arguments
+    /// * `after` - Exclusive lower bound.
+    /// * `before` - Exclusive upper bound.
+    ///
+    /// # Returns
+    /// * `Vec<NaiveDate>` - Trading days in ascending order; empty if `after >= before`.
+    pub fn trading_days_between(&self, after: chrono::NaiveDate, before: chrono::NaiveDate) -> Vec<chrono::NaiveDate> {
+        if after >= before {
+            return Vec::new();
+        }
+        self.dates
+            .range((std::ops::Bound::Excluded(after), std::ops::Bound::Excluded(before)))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Returns the weekdays (Monday to Friday) strictly between `after` and `before`.
+///
+/// The fallback notion of "expected trading days" when no calendar is given: weekends
+/// are never reported, but holidays are.
+///
+/// # Arguments
+/// * `after` - Exclusive lower bound.
+/// * `before` - Exclusive upper bound.
+///
+/// # Returns
+/// * `Vec<NaiveDate>` - Weekdays in ascending order; empty if `after >= before`.
+pub fn weekdays_between(after: chrono::NaiveDate, before: chrono::NaiveDate) -> Vec<chrono::NaiveDate> {
+    use chrono::Datelike;
+
+    after
+        .iter_days()
+        .skip(1)
+        .take_while(|date| *date < before)
+        .filter(|date| date.weekday().number_from_monday() <= 5)
+        .collect()
+}
+
+/// Regular trading hours as a local time-of-day window, e.g. `09:30-16:00`.
+///
+/// The start is inclusive and the end exclusive, so with bars stamped at their open
+/// `09:30-16:00` keeps the 09:30 bar and drops the 16:00 one. A window whose end is
+/// before its start crosses midnight (`18:00-17:00` for futures).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionWindow {
+    pub start: chrono::NaiveTime,
+    pub end: chrono::NaiveTime,
+}
+
+impl SessionWindow {
+    /// Parses a `HH:MM-HH:MM` window.
+    ///
+    /// # Arguments
+    /// * `s` - Window as written on the command line.
+    ///
+    /// # Returns
+    /// * `anyhow::Result<SessionWindow>` - The parsed window.
+    ///
+    /// # Errors
+    /// * If either bound isn't a valid `HH:MM` time.
+    /// * If start and end are equal (an empty or full-day window is almost certainly a typo).
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow::anyhow!("Invalid session '{}': expected HH:MM-HH:MM", s))?;
+        let parse_time = |t: &str| {
+            chrono::NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .map_err(|e| anyhow::anyhow!("Invalid session time '{}': {}", t, e))
+        };
+        let window = Self { start: parse_time(start)?, end: parse_time(end)? };
+        if window.start == window.end {
+            return Err(anyhow::anyhow!("Invalid session '{}': start and end are equal", s));
+        }
+        anyhow::Ok(window)
+    }
+
+    /// Returns true if the local time of day `time` falls inside the window.
+    pub fn contains(&self, time: chrono::NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl std::fmt::Display for SessionWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
@@ -6,14 +6,268 @@ pub enum StorageFormat {
     Soa
 }
 
+impl StorageFormat {
+    /// File name suffix that encodes the storage format, e.g. `.aos.bin`.
+    pub fn file_suffix(&self) -> &'static str {
+        match self {
+            StorageFormat::Aos => ".aos.bin",
+            StorageFormat::Soa => ".soa.bin",
+        }
+    }
+
+    /// File name suffix with a custom final extension (`--output-suffix`), e.g. `.aos.fb`.
+    ///
+    /// The storage format stays in the name so readers can still tell AOS from SOA.
+    pub fn file_suffix_with(&self, extension: &str) -> String {
+        match self {
+            StorageFormat::Aos => format!(".aos.{}", extension),
+            StorageFormat::Soa => format!(".soa.{}", extension),
+        }
+    }
+}
+
+/// Default final extension of FlatBuffer output files (`--output-suffix`).
+pub const DEFAULT_OUTPUT_SUFFIX: &str = "bin";
+
+/// Format of the input files.
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum InputFormat {
+    Csv,
+    Ndjson,
+    /// Gzipped tarballs of CSV files, each entry converted on its own (`convert_tarball`).
+    TarGz,
+}
+
+impl InputFormat {
+    /// Extension of the input files picked up from the input directory.
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            InputFormat::Csv => "txt",
+            InputFormat::Ndjson => "ndjson",
+            InputFormat::TarGz => "gz",
+        }
+    }
+}
+
+/// What the rows of the input files are.
+#[derive(Debug, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputKind {
+    /// OHLCV bars, stored as they are.
+    Bars,
+    /// Raw trades (timestamp, price, volume), aggregated into bars at `--resample` on ingest.
+    Ticks,
+}
+
+/// How empty numeric CSV fields (`,,`) are handled.
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum EmptyField {
+    /// Empty volume becomes 0, empty prices take the previous close.
+    Default,
+    /// Any empty numeric field fails the conversion.
+    Error,
+}
+
+/// Decimal separator of numeric CSV fields.
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum DecimalSeparator {
+    /// `1234.56`, read as is.
+    Point,
+    /// `1.234,56`: points are thousands separators, the comma is the decimal point.
+    Comma,
+}
+
+/// How crossed bars (`high < low`, or open/close outside `[low, high]`) are repaired.
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum FixCrossed {
+    /// Trust open and close: put high/low in order and widen them to cover open and close.
+    Swap,
+    /// Trust high and low: put them in order and clamp open and close into the range.
+    Clamp,
+    /// Drop the bar.
+    Drop,
+}
+
+/// Notation used when printing volumes.
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum VolumeFormat {
+    /// Raw integer: `1320000000`.
+    Plain,
+    /// Thousands separators: `1,320,000,000`.
+    Grouped,
+    /// SI suffix: `1.32G`.
+    Si,
+}
+
+/// Which timestamp a resampled bar carries.
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum BarTimestamp {
+    /// Start of the bucket (`ts - ts % tf`, or the session's first bar for daily bars).
+    BucketStart,
+    /// Timestamp of the first source bar in the bucket.
+    FirstTrade,
+    /// Timestamp of the last source bar in the bucket.
+    LastTrade,
+}
+
+/// Which bucket an intraday bar exactly on a bucket boundary belongs to.
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum BucketEdge {
+    /// Left-closed `[start, start + tf)`: 09:05:00 opens the 09:05 bucket.
+    Left,
+    /// Right-closed `(start, start + tf]`: 09:05:00 closes the 09:00 bucket.
+    Right,
+}
+
+/// What resampling does with a NaN high or low in the source bars.
+#[derive(Debug, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum NanPolicy {
+    /// The bucket's high (low) becomes NaN, so the bad value stays visible.
+    Propagate,
+    /// The NaN is left out of the bucket's max (min); the skipped values are counted in a warning.
+    Skip,
+    /// Resampling fails at the first bar with a NaN high or low.
+    Error,
+}
+
+/// Time order of the bars written to a `.bin`.
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum BarOrder {
+    /// Oldest bar first.
+    Asc,
+    /// Newest bar first.
+    Desc,
+}
+
+/// How local times that are ambiguous or nonexistent around DST transitions resolve.
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum DstPolicy {
+    /// Ambiguous times take the earlier instant; nonexistent times the end of the gap.
+    Earliest,
+    /// Ambiguous times take the later instant; nonexistent times the end of the gap.
+    Latest,
+    /// Both fail the conversion.
+    Error,
+}
+
+/// Time bucket used to split one input into multiple output files.
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum SplitMode {
+    Daily,
+    Monthly,
+    Yearly,
+}
+
+/// Subcommands that replace the default CSV conversion run.
+#[derive(Debug)]
+pub enum Command {
+    /// Merge several .bin files into one with a rebuilt index.
+    Merge {
+        inputs: Vec<std::path::PathBuf>,
+        output: std::path::PathBuf,
+        storage_format: StorageFormat,
+    },
+    /// Convert, read back and resample a built-in dataset, printing PASS/FAIL.
+    SelfTest,
+    /// Print a .idx file as pretty JSON.
+    IndexDump {
+        path: std::path::PathBuf,
+        full: bool,
+    },
+    /// Write the embedded .fbs schemas into a directory.
+    EmitSchema {
+        output: std::path::PathBuf,
+    },
+    /// Pack existing .bin/.idx pairs into .ohlcv bundles.
+    Bundle {
+        inputs: Vec<std::path::PathBuf>,
+        remove_originals: bool,
+    },
+    /// Compare the bars of two .bin files.
+    Diff {
+        a: std::path::PathBuf,
+        b: std::path::PathBuf,
+        epsilon: f64,
+    },
+}
+
 #[derive(Debug)]
 pub struct Args {
+    pub command: Option<Command>,
     pub input: std::path::PathBuf,
     pub output: std::path::PathBuf,
     pub threads: Option<usize>,
     pub check: bool,
     pub resample: Option<String>,
     pub storage_format: StorageFormat,
+    pub report_memory: bool,
+    pub calendar: Option<std::path::PathBuf>,
+    pub split: Option<SplitMode>,
+    pub bar_timestamp: BarTimestamp,
+    pub schema_version: u32,
+    pub input_format: InputFormat,
+    pub show_count: bool,
+    pub report_staleness: bool,
+    pub now: Option<u64>,
+    pub target_bars: Option<usize>,
+    pub empty_field: EmptyField,
+    pub table: bool,
+    pub max_file_size: Option<u64>,
+    pub io_retries: u32,
+    pub track_source_lines: bool,
+    pub strict_resample: bool,
+    pub volume_format: VolumeFormat,
+    pub no_volume: bool,
+    pub timings: bool,
+    pub symbol: Option<String>,
+    pub round_decimals: Option<u32>,
+    pub since_last: bool,
+    pub timezone: chrono_tz::Tz,
+    pub dst_policy: DstPolicy,
+    pub session: Option<crate::calendar::SessionWindow>,
+    pub with_true_range: bool,
+    pub timestamp_offset: i64,
+    pub compact_index: bool,
+    pub quote_char: u8,
+    pub escape_char: Option<u8>,
+    pub no_quoting: bool,
+    pub delimiter: u8,
+    pub decimal_separator: DecimalSeparator,
+    pub self_verify: bool,
+    pub verify_output: bool,
+    pub bucket_edge: BucketEdge,
+    pub concat_directory: bool,
+    pub resample_all: bool,
+    pub timeframes: Vec<String>,
+    pub no_index: bool,
+    pub log_level: Option<String>,
+    pub plain: bool,
+    pub flag_volume_spikes: Option<u64>,
+    pub order: BarOrder,
+    pub symbol_from_filename: bool,
+    pub symbol_regex: Option<regex::Regex>,
+    pub encoding: &'static encoding_rs::Encoding,
+    pub adjustments: Option<std::path::PathBuf>,
+    pub soa_segment_size: Option<usize>,
+    pub fix_crossed: Option<FixCrossed>,
+    pub output_suffix: String,
+    pub bucket_anchor: u64,
+    pub daily_report: bool,
+    pub io_buffer_size: usize,
+    pub precompute_tf_positions: bool,
+    pub sparkline: bool,
+    pub drop_partial_bar: bool,
+    pub stdout_csv: bool,
+    pub nan_policy: NanPolicy,
+    pub unique_timestamps: bool,
+    pub dir_mode: Option<u32>,
+    pub input_kind: InputKind,
+    pub collect_errors: Option<usize>,
+    pub errors_csv: bool,
+    pub bundle: bool,
+    pub list_skipped: usize,
+    pub resample_anchor_to_session: bool,
+    pub grid_mark_missing: bool,
+    pub cache_resample: bool,
 }
 
 /// Command-line arguments parser using Clap.
@@ -32,6 +286,106 @@ impl Args {
             .version("0.1.0")
             .author("AndyDar")
             .about("Convert CSV/TXT files to flatbuffer")
+            .subcommand_negates_reqs(true)
+            .subcommand(
+                clap::Command::new("merge")
+                    .about("Merge multiple .bin files (AOS or SOA) into one .bin with a rebuilt .idx")
+                    .arg(
+                        clap::Arg::new("inputs")
+                            .help("Input .bin files to merge; they must not overlap in time")
+                            .required(true)
+                            .num_args(1..),
+                    )
+                    .arg(
+                        clap::Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .help("Path of the merged .bin file")
+                            .required(true)
+                            .num_args(1),
+                    )
+                    .arg(
+                        clap::Arg::new("storage_format")
+                            .short('s')
+                            .long("storage_format")
+                            .help("Storage format of the merged file")
+                            .value_parser(clap::value_parser!(StorageFormat))
+                            .default_value("soa")
+                            .required(false)
+                    )
+            )
+            .subcommand(
+                clap::Command::new("selftest")
+                    .about("Convert a built-in dataset in both formats, read it back, resample it and check the results")
+            )
+            .subcommand(
+                clap::Command::new("index-dump")
+                    .about("Print a .idx file as pretty JSON for debugging")
+                    .arg(
+                        clap::Arg::new("path")
+                            .help("The .idx file, or the .bin file next to it")
+                            .required(true)
+                            .num_args(1),
+                    )
+                    .arg(
+                        clap::Arg::new("full")
+                            .long("full")
+                            .help("Print every index entry instead of the first and last few")
+                            .action(clap::ArgAction::SetTrue),
+                    )
+            )
+            .subcommand(
+                clap::Command::new("emit-schema")
+                    .about("Write the ohlcv.fbs / ohlcv_soa.fbs schemas this binary was built against, for codegen in other languages")
+                    .arg(
+                        clap::Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .help("Directory to write the .fbs files into")
+                            .required(true)
+                            .num_args(1),
+                    )
+            )
+            .subcommand(
+                clap::Command::new("bundle")
+                    .about("Pack existing .bin files and the .idx next to each into single .ohlcv bundles")
+                    .arg(
+                        clap::Arg::new("inputs")
+                            .help("The .bin files to pack; each bundle is written next to its .bin")
+                            .required(true)
+                            .num_args(1..),
+                    )
+                    .arg(
+                        clap::Arg::new("remove_originals")
+                            .long("remove-originals")
+                            .help("Delete each .bin and .idx once its bundle is written")
+                            .action(clap::ArgAction::SetTrue),
+                    )
+            )
+            .subcommand(
+                clap::Command::new("diff")
+                    .about("Compare two .bin files (AOS or SOA) bar by bar; exits non-zero if they differ")
+                    .arg(
+                        clap::Arg::new("a")
+                            .help("First .bin file")
+                            .required(true)
+                            .num_args(1),
+                    )
+                    .arg(
+                        clap::Arg::new("b")
+                            .help("Second .bin file")
+                            .required(true)
+                            .num_args(1),
+                    )
+                    .arg(
+                        clap::Arg::new("epsilon")
+                            .long("epsilon")
+                            .help("Largest absolute price difference still counted as equal")
+                            .value_parser(clap::builder::ValueParser::new(parse_epsilon))
+                            .default_value("0")
+                            .num_args(1),
+                    )
+            )
             .arg(
                 clap::Arg::new("input")
                     .short('i')
@@ -52,7 +406,7 @@ impl Args {
                 clap::Arg::new("threads")
                     .short('t')
                     .long("threads")
-                    .help("Number of threads to use (default: all available)")
+                    .help("Number of threads to use (default: $CSV2FB_THREADS, else all available)")
                     .num_args(1)
                     .value_parser(clap::builder::ValueParser::new(parse_usize_positive)),
             )
@@ -68,11 +422,11 @@ impl Args {
                 clap::Arg::new("resample")
                     .short('r')
                     .long("resample")
-                    .help("Resample data to specified timeframe. Available: 1min, 2min, 3min, 4min, 5min, 1d")
-                    .value_parser(["1min", "2min", "3min", "4min", "5min", "1d"])
+                    .help("Resample data to specified timeframe. Available: 1min, 2min, 3min, 4min, 5min, 90min (restarting at every UTC day's first bar), 1d (one bar per UTC day), vol:N for volume bars closing every N volume, or count:N for bars of N source bars")
+                    .value_parser(parse_resample)
                     .required(false)
+                    .required_if_eq("input_kind", "ticks")
                     .num_args(1)
-                    .requires("check")
             )
             .arg(
                 clap::Arg::new("storage_format")
@@ -83,19 +437,712 @@ impl Args {
                     .default_value("soa")
                     .required(false)
             )
+            .arg(
+                clap::Arg::new("report_memory")
+                    .long("report-memory")
+                    .help("Print peak resident memory usage after conversion")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("calendar")
+                    .long("calendar")
+                    .help("Trading calendar file (one YYYY-MM-DD date per line) used to report missing trading days")
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("split")
+                    .long("split")
+                    .help("Split each input into one .bin/.idx per time bucket and write a manifest")
+                    .value_parser(clap::value_parser!(SplitMode))
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("bar_timestamp")
+                    .long("bar-timestamp")
+                    .help("Timestamp carried by resampled bars")
+                    .value_parser(clap::value_parser!(BarTimestamp))
+                    .default_value("bucket-start")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("bucket_edge")
+                    .long("bucket-edge")
+                    .help("Whether a bar exactly on a bucket boundary starts the next bucket (left) or ends the previous one (right)")
+                    .value_parser(clap::value_parser!(BucketEdge))
+                    .default_value("left")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("bucket_anchor")
+                    .long("bucket-anchor")
+                    .help("Shift intraday bucket boundaries by this many seconds, e.g. 120 puts 5min buckets at :02, :07, ...")
+                    .value_parser(clap::value_parser!(u64))
+                    .default_value("0")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("schema_version")
+                    .long("schema-version")
+                    .help("FlatBuffer schema version to write (default: latest supported)")
+                    .value_parser(clap::value_parser!(u32).range(1..=crate::utils::SCHEMA_VERSION as i64))
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("input_format")
+                    .long("input-format")
+                    .help("Format of the input files: csv (*.txt), ndjson (*.ndjson) or tar-gz (*.tar.gz of CSV files, each entry converted to its own .bin)")
+                    .value_parser(clap::value_parser!(InputFormat))
+                    .default_value("csv")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("show_count")
+                    .long("show-count")
+                    .help("Show how many source bars were aggregated into each resampled bar")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("resampling")
+            )
+            .arg(
+                clap::Arg::new("report_staleness")
+                    .long("report-staleness")
+                    .help("Report how old the latest bar of each converted file is")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("now")
+                    .long("now")
+                    .help("Override the current time (Unix seconds) used for staleness reports")
+                    .value_parser(clap::value_parser!(u64))
+                    .required(false)
+                    .num_args(1)
+                    .requires("report_staleness")
+            )
+            .arg(
+                clap::Arg::new("target_bars")
+                    .long("target-bars")
+                    .help("Resample to about N bars, choosing the timeframe automatically")
+                    .value_parser(clap::builder::ValueParser::new(parse_usize_positive))
+                    .required(false)
+                    .num_args(1)
+                    .requires("check")
+            )
+            .arg(
+                clap::Arg::new("empty_field")
+                    .long("empty-field")
+                    .help("How empty numeric CSV fields are handled: default (volume 0, prices previous close) or error")
+                    .value_parser(clap::value_parser!(EmptyField))
+                    .default_value("default")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("table")
+                    .long("table")
+                    .help("Print bars as an aligned table with a header row")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("check")
+            )
+            .arg(
+                clap::Arg::new("sparkline")
+                    .long("sparkline")
+                    .help("Also print the closes of the printed bars as a Unicode block sparkline")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("check")
+            )
+            .arg(
+                clap::Arg::new("max_file_size")
+                    .long("max-file-size")
+                    .help("Skip input files larger than this size: bytes or 512KB, 100MB, 2GB (1KB = 1024 bytes)")
+                    .value_parser(clap::builder::ValueParser::new(parse_byte_size))
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("io_retries")
+                    .long("io-retries")
+                    .help("Retry transient I/O errors (EIO, ESTALE, timeouts) up to N times with backoff when reading")
+                    .value_parser(clap::value_parser!(u32))
+                    .default_value("0")
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("track_source_lines")
+                    .long("track-source-lines")
+                    .help("Store the input line number of every bar in the .idx (source_line_index)")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("strict_resample")
+                    .long("strict-resample")
+                    .help("Fail if resampled bars are out of order or duplicated (e.g. malformed .idx)")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("resampling")
+            )
+            .arg(
+                clap::Arg::new("volume_format")
+                    .long("volume-format")
+                    .help("How printed volumes are formatted: plain, grouped (1,320,000) or si (1.32M)")
+                    .value_parser(clap::value_parser!(VolumeFormat))
+                    .default_value("plain")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("no_volume")
+                    .long("no-volume")
+                    .help("Volumeless data (e.g. indices): <VOL> is optional and no volume is stored")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("timings")
+                    .long("timings")
+                    .help("Print a per-phase timing breakdown (parse, index, build, write) for each file")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("symbol")
+                    .long("symbol")
+                    .help("Instrument the converted files represent (e.g. AAPL), stored in the .idx")
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("encoding")
+                    .long("encoding")
+                    .help("Encoding of the input files (e.g. windows-1251, latin1), transcoded to UTF-8 while reading")
+                    .value_parser(parse_encoding)
+                    .default_value("utf-8")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("symbol_from_filename")
+                    .long("symbol-from-filename")
+                    .help("Store each file's name stem (AAPL.csv -> AAPL) as its symbol in the .idx")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with_all(["symbol", "concat_directory"])
+            )
+            .arg(
+                clap::Arg::new("symbol_regex")
+                    .long("symbol-regex")
+                    .help("Regex extracting the symbol from the file stem: the `symbol` group, else group 1, else the whole match")
+                    .value_parser(parse_regex)
+                    .required(false)
+                    .num_args(1)
+                    .requires("symbol_from_filename")
+            )
+            .arg(
+                clap::Arg::new("round_decimals")
+                    .long("round-decimals")
+                    .help("Round printed prices to N decimals after resampling (the .bin keeps raw values)")
+                    .value_parser(clap::value_parser!(u32).range(0..=15))
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("since_last")
+                    .long("since-last")
+                    .help("Convert only rows newer than the existing output's .idx into a .delta.<fmt>.bin file")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with("split")
+            )
+            .arg(
+                clap::Arg::new("timezone")
+                    .long("timezone")
+                    .help("IANA time zone of the input timestamps (e.g. America/New_York)")
+                    .value_parser(|s: &str| s.parse::<chrono_tz::Tz>().map_err(|e| e.to_string()))
+                    .default_value("UTC")
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("dst_policy")
+                    .long("dst-policy")
+                    .help("How ambiguous / nonexistent local times around DST transitions resolve")
+                    .value_parser(clap::value_parser!(DstPolicy))
+                    .default_value("error")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("session")
+                    .long("session")
+                    .help("Keep only bars inside this local trading window, e.g. 09:30-16:00 (may cross midnight)")
+                    .value_parser(|s: &str| crate::calendar::SessionWindow::parse(s).map_err(|e| e.to_string()))
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("with_true_range")
+                    .long("with-true-range")
+                    .help("Annotate resampled bars with their true range (uses the previous bar's close)")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("resampling")
+            )
+            .arg(
+                clap::Arg::new("timestamp_offset")
+                    .long("timestamp-offset")
+                    .help("Shift printed timestamps by seconds or ±HH:MM (display only, the .bin stays UTC)")
+                    .value_parser(clap::builder::ValueParser::new(parse_timestamp_offset))
+                    .allow_hyphen_values(true)
+                    .default_value("0")
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("quote_char")
+                    .long("quote-char")
+                    .help("Quote character of CSV fields")
+                    .value_parser(clap::builder::ValueParser::new(parse_ascii_char))
+                    .default_value("\"")
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("escape_char")
+                    .long("escape-char")
+                    .help("Escape character for quotes inside quoted CSV fields, e.g. \\ (default: quotes are doubled)")
+                    .value_parser(clap::builder::ValueParser::new(parse_ascii_char))
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("no_quoting")
+                    .long("no-quoting")
+                    .help("Treat quote characters in CSV input as ordinary data")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("delimiter")
+                    .long("delimiter")
+                    .help("Field delimiter of CSV input, e.g. ; or tab")
+                    .value_parser(clap::builder::ValueParser::new(parse_delimiter))
+                    .default_value(",")
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("decimal_separator")
+                    .long("decimal-separator")
+                    .help("Decimal separator of numeric CSV fields; comma reads 1.234,56 as 1234.56 and needs a --delimiter other than ,")
+                    .value_parser(clap::value_parser!(DecimalSeparator))
+                    .default_value("point")
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("concat_directory")
+                    .long("concat-directory")
+                    .help("Concatenate per-day input files (dated in their names) into one .bin/.idx, reporting gaps between days")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with_all(["split", "since_last"])
+            )
+            .arg(
+                clap::Arg::new("self_verify")
+                    .long("self-verify")
+                    .help("Also build the other storage format in memory and fail unless both read back identically (slow)")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("verify_output")
+                    .long("verify-output")
+                    .help("Parse each FlatBuffer back from memory before writing it and fail the file if it doesn't load")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("compact_index")
+                    .long("compact-index")
+                    .help("Write .idx files with a delta/varint-encoded time index (much smaller for regular data)")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("flag_volume_spikes")
+                    .long("flag-volume-spikes")
+                    .help("Report bars whose volume exceeds N times the median of the preceding 100 bars (data is not modified)")
+                    .value_parser(clap::value_parser!(u64).range(1..))
+                    .required(false)
+                    .num_args(1)
+                    .conflicts_with("no_volume")
+            )
+            .arg(
+                clap::Arg::new("order")
+                    .long("order")
+                    .help("Time order of the bars written to .bin files (indices stay in ascending time)")
+                    .value_parser(clap::value_parser!(BarOrder))
+                    .default_value("asc")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("log_level")
+                    .long("log-level")
+                    .help("Log level filter (overrides RUST_LOG, default: info)")
+                    .value_parser(["error", "warn", "info", "debug", "trace"])
+                    .required(false)
+                    .num_args(1)
+                    .global(true)
+            )
+            .arg(
+                clap::Arg::new("plain")
+                    .long("plain")
+                    .help("Print bare log messages to stdout (interactive output) instead of structured lines to stderr")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .global(true)
+            )
+            .arg(
+                clap::Arg::new("no_index")
+                    .long("no-index")
+                    .help("Write only the .bin file: skip the timeframe boundaries and saving the .idx (raw reads still work, resampling needs the index)")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with_all(["compact_index", "track_source_lines", "precompute_tf_positions", "resample", "target_bars"])
+            )
+            .arg(
+                clap::Arg::new("resample_all")
+                    .long("resample-all")
+                    .help("Read each .bin once and resample it to every timeframe in --timeframes in a single pass")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("check")
+                    .requires("timeframes")
+            )
+            .arg(
+                clap::Arg::new("timeframes")
+                    .long("timeframes")
+                    .help("Comma-separated timeframes, e.g. 5min,1d: the only ones indexed in the .idx timeframe_index, and the ones --resample-all resamples to")
+                    .value_parser(["1min", "2min", "3min", "4min", "5min", "90min", "1d"])
+                    .value_delimiter(',')
+                    .required(false)
+                    .num_args(1..)
+            )
+            .arg(
+                clap::Arg::new("adjustments")
+                    .long("adjustments")
+                    .help("Corporate actions file (YYYY-MM-DD,factor per line) used to store a split/dividend adjusted close with every bar")
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("soa_segment_size")
+                    .long("soa-segment-size")
+                    .help("Write SOA output as self-contained segments of at most N bars, bounding the builder's memory (offsets are stored in the .idx)")
+                    .value_parser(clap::builder::ValueParser::new(parse_usize_positive))
+                    .required(false)
+                    .num_args(1)
+                    .conflicts_with("no_index")
+            )
+            .arg(
+                clap::Arg::new("fix_crossed")
+                    .long("fix-crossed")
+                    .help("Repair crossed bars (high < low, or open/close outside [low, high]): swap, clamp or drop")
+                    .value_parser(clap::value_parser!(FixCrossed))
+                    .required(false)
+                    .num_args(1)
+            )
+            .arg(
+                clap::Arg::new("output_suffix")
+                    .long("output-suffix")
+                    .help("Final extension of .bin files, e.g. fb writes data.aos.fb (pass the same value when reading)")
+                    .value_parser(clap::builder::ValueParser::new(parse_output_suffix))
+                    .default_value(DEFAULT_OUTPUT_SUFFIX)
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("daily_report")
+                    .long("daily-report")
+                    .help("With -c, write per-day statistics (bars, high/low and their times, volume, range) to a .daily-report.csv next to each .bin")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("io_buffer_size")
+                    .long("io-buffer-size")
+                    .help("Read/write buffer capacity for input and segmented output, e.g. 64K or 1M (default 256K)")
+                    .value_parser(clap::builder::ValueParser::new(parse_io_buffer_size))
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("precompute_tf_positions")
+                    .long("precompute-tf-positions")
+                    .help("Also store where every timeframe_index bucket starts in the .idx, so resampled bars can be looked up without a scan (larger index)")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+            )
+            .arg(
+                clap::Arg::new("drop_partial_bar")
+                    .long("drop-partial-bar")
+                    .help("Leave out the final resampled bar (or day) when the data ends before its bucket does")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with("target_bars")
+            )
+            .arg(
+                clap::Arg::new("stdout_csv")
+                    .long("stdout-csv")
+                    .help("Instead of writing .bin/.idx files, convert the one input file, resample it to --resample in memory and write the bars to stdout as CSV (logging drops to warnings)")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("resample")
+                    .conflicts_with("plain")
+            )
+            .arg(
+                clap::Arg::new("nan_policy")
+                    .long("nan-policy")
+                    .help("NaN highs/lows when resampling: propagate (the bar shows NaN), skip (leave them out, with a warning) or error")
+                    .value_parser(clap::value_parser!(NanPolicy))
+                    .default_value("propagate")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("unique_timestamps")
+                    .long("unique-timestamps")
+                    .help("Show how many distinct source timestamps each resampled bar covers (equals the bar count for 1-minute data)")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("resample_output")
+            )
+            .arg(
+                clap::Arg::new("dir_mode")
+                    .long("dir-mode")
+                    .help("Octal permissions (e.g. 0750) for the output directories created, instead of leaving them to the umask; ignored on Windows")
+                    .value_parser(parse_dir_mode)
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("input_kind")
+                    .long("input-kind")
+                    .help("What the input rows are: bars (OHLCV) or ticks (<DATE>,<TIME>,<PRICE>,<VOL>), aggregated into bars at --resample, which is then required")
+                    .value_parser(clap::value_parser!(InputKind))
+                    .default_value("bars")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("collect_errors")
+                    .long("collect-errors")
+                    .help("Skip rows that fail to parse and report them all at the end, failing only past N bad rows per file (default N: 1000)")
+                    .value_parser(clap::builder::ValueParser::new(parse_usize_positive))
+                    .num_args(0..=1)
+                    .default_missing_value("1000")
+                    .required(false)
+            )
+            .arg(
+                clap::Arg::new("errors_csv")
+                    .long("errors-csv")
+                    .help("With --collect-errors, also write the skipped rows (line,reason) to a .errors.csv next to each .bin")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("collect_errors")
+            )
+            .arg(
+                clap::Arg::new("bundle")
+                    .long("bundle")
+                    .help("Write each output as a single .ohlcv file holding both the .bin data and its .idx, so they can't get separated")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with_all(["split", "since_last", "no_index", "stdout_csv"])
+            )
+            .arg(
+                clap::Arg::new("resample_anchor_to_session")
+                    .long("resample-anchor-to-session")
+                    .help("With -c and an intraday -r, restart buckets at each day's first bar instead of aligning them to the clock, so no bucket spans the overnight gap")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires_all(["check", "resample"])
+                    .conflicts_with_all(["target_bars", "resample_all", "unique_timestamps", "drop_partial_bar"])
+            )
+            .arg(
+                clap::Arg::new("grid_mark_missing")
+                    .long("grid-mark-missing")
+                    .help("With -c and -r, also write every timeframe_index boundary of the timeframe to a .grid-<tf>.csv, with a present column and NaN prices where there are no bars")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires_all(["check", "resample"])
+            )
+            .arg(
+                clap::Arg::new("cache_resample")
+                    .long("cache-resample")
+                    .help("With -c and -r, keep the resampled bars of each file in a <file>.<tf>.cache.bin and reuse them until the file changes")
+                    .required(false)
+                    .action(clap::ArgAction::SetTrue)
+                    .requires_all(["check", "resample"])
+                    .conflicts_with_all(["target_bars", "resample_all"])
+            )
+            .arg(
+                clap::Arg::new("list_skipped")
+                    .long("list-skipped")
+                    .help("With -c, name at most this many skipped files in the summary printed at the end (0 prints only the count)")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("20")
+                    .required(false)
+                    .requires("check")
+            )
+            .group(
+                clap::ArgGroup::new("resample_output")
+                    .args(["check", "stdout_csv"])
+                    .multiple(false)
+            )
+            .group(
+                clap::ArgGroup::new("resampling")
+                    .args(["resample", "target_bars", "resample_all"])
+                    .multiple(false)
+            )
             .get_matches();
 
+        let command = match matches.subcommand() {
+            Some(("merge", sub)) => Some(Command::Merge {
+                inputs: sub.get_many::<String>("inputs").unwrap().map(std::path::PathBuf::from).collect(),
+                output: std::path::PathBuf::from(sub.get_one::<String>("output").unwrap()),
+                storage_format: sub.get_one::<StorageFormat>("storage_format").cloned().unwrap(),
+            }),
+            Some(("selftest", _)) => Some(Command::SelfTest),
+            Some(("index-dump", sub)) => Some(Command::IndexDump {
+                path: std::path::PathBuf::from(sub.get_one::<String>("path").unwrap()),
+                full: sub.get_flag("full"),
+            }),
+            Some(("emit-schema", sub)) => Some(Command::EmitSchema {
+                output: std::path::PathBuf::from(sub.get_one::<String>("output").unwrap()),
+            }),
+            Some(("bundle", sub)) => Some(Command::Bundle {
+                inputs: sub.get_many::<String>("inputs").unwrap().map(std::path::PathBuf::from).collect(),
+                remove_originals: sub.get_flag("remove_originals"),
+            }),
+            Some(("diff", sub)) => Some(Command::Diff {
+                a: std::path::PathBuf::from(sub.get_one::<String>("a").unwrap()),
+                b: std::path::PathBuf::from(sub.get_one::<String>("b").unwrap()),
+                epsilon: *sub.get_one::<f64>("epsilon").unwrap(),
+            }),
+            _ => None,
+        };
+
         Args {
-            input: std::path::PathBuf::from(matches.get_one::<String>("input").unwrap()),
-            output: std::path::PathBuf::from(matches.get_one::<String>("output").unwrap()),
+            command,
+            input: matches.get_one::<String>("input").map(std::path::PathBuf::from).unwrap_or_default(),
+            output: matches.get_one::<String>("output").map(std::path::PathBuf::from).unwrap_or_default(),
             threads: matches.get_one::<usize>("threads").cloned(),
             check: matches.get_flag("check"),
             resample: matches.get_one::<String>("resample").cloned(),
             storage_format: matches.get_one::<StorageFormat>("storage_format").cloned().unwrap(),
+            report_memory: matches.get_flag("report_memory"),
+            calendar: matches.get_one::<String>("calendar").map(std::path::PathBuf::from),
+            split: matches.get_one::<SplitMode>("split").cloned(),
+            bar_timestamp: matches.get_one::<BarTimestamp>("bar_timestamp").cloned().unwrap(),
+            schema_version: matches.get_one::<u32>("schema_version").cloned().unwrap_or(crate::utils::SCHEMA_VERSION),
+            input_format: matches.get_one::<InputFormat>("input_format").cloned().unwrap(),
+            show_count: matches.get_flag("show_count"),
+            report_staleness: matches.get_flag("report_staleness"),
+            now: matches.get_one::<u64>("now").cloned(),
+            target_bars: matches.get_one::<usize>("target_bars").cloned(),
+            empty_field: matches.get_one::<EmptyField>("empty_field").cloned().unwrap(),
+            table: matches.get_flag("table"),
+            max_file_size: matches.get_one::<u64>("max_file_size").cloned(),
+            io_retries: matches.get_one::<u32>("io_retries").cloned().unwrap(),
+            track_source_lines: matches.get_flag("track_source_lines"),
+            strict_resample: matches.get_flag("strict_resample"),
+            volume_format: matches.get_one::<VolumeFormat>("volume_format").cloned().unwrap(),
+            no_volume: matches.get_flag("no_volume"),
+            timings: matches.get_flag("timings"),
+            symbol: matches.get_one::<String>("symbol").cloned(),
+            round_decimals: matches.get_one::<u32>("round_decimals").cloned(),
+            since_last: matches.get_flag("since_last"),
+            timezone: matches.get_one::<chrono_tz::Tz>("timezone").cloned().unwrap(),
+            dst_policy: matches.get_one::<DstPolicy>("dst_policy").cloned().unwrap(),
+            session: matches.get_one::<crate::calendar::SessionWindow>("session").cloned(),
+            with_true_range: matches.get_flag("with_true_range"),
+            timestamp_offset: matches.get_one::<i64>("timestamp_offset").cloned().unwrap(),
+            compact_index: matches.get_flag("compact_index"),
+            quote_char: matches.get_one::<u8>("quote_char").cloned().unwrap(),
+            escape_char: matches.get_one::<u8>("escape_char").cloned(),
+            no_quoting: matches.get_flag("no_quoting"),
+            delimiter: matches.get_one::<u8>("delimiter").cloned().unwrap(),
+            decimal_separator: matches.get_one::<DecimalSeparator>("decimal_separator").cloned().unwrap(),
+            self_verify: matches.get_flag("self_verify"),
+            verify_output: matches.get_flag("verify_output"),
+            bucket_edge: matches.get_one::<BucketEdge>("bucket_edge").cloned().unwrap(),
+            concat_directory: matches.get_flag("concat_directory"),
+            resample_all: matches.get_flag("resample_all"),
+            timeframes: matches.get_many::<String>("timeframes").map(|tfs| tfs.cloned().collect()).unwrap_or_default(),
+            no_index: matches.get_flag("no_index"),
+            log_level: matches.get_one::<String>("log_level").cloned(),
+            plain: matches.get_flag("plain"),
+            flag_volume_spikes: matches.get_one::<u64>("flag_volume_spikes").cloned(),
+            order: matches.get_one::<BarOrder>("order").cloned().unwrap(),
+            symbol_from_filename: matches.get_flag("symbol_from_filename"),
+            symbol_regex: matches.get_one::<regex::Regex>("symbol_regex").cloned(),
+            encoding: matches.get_one::<&'static encoding_rs::Encoding>("encoding").copied().unwrap(),
+            adjustments: matches.get_one::<String>("adjustments").map(std::path::PathBuf::from),
+            soa_segment_size: matches.get_one::<usize>("soa_segment_size").cloned(),
+            fix_crossed: matches.get_one::<FixCrossed>("fix_crossed").cloned(),
+            output_suffix: matches.get_one::<String>("output_suffix").cloned().unwrap_or_else(|| DEFAULT_OUTPUT_SUFFIX.to_string()),
+            bucket_anchor: *matches.get_one::<u64>("bucket_anchor").unwrap(),
+            daily_report: matches.get_flag("daily_report"),
+            io_buffer_size: matches.get_one::<usize>("io_buffer_size").copied().unwrap_or(crate::file_processing::DEFAULT_IO_BUFFER_SIZE),
+            precompute_tf_positions: matches.get_flag("precompute_tf_positions"),
+            sparkline: matches.get_flag("sparkline"),
+            drop_partial_bar: matches.get_flag("drop_partial_bar"),
+            stdout_csv: matches.get_flag("stdout_csv"),
+            nan_policy: matches.get_one::<NanPolicy>("nan_policy").cloned().unwrap(),
+            unique_timestamps: matches.get_flag("unique_timestamps"),
+            dir_mode: matches.get_one::<u32>("dir_mode").copied(),
+            input_kind: matches.get_one::<InputKind>("input_kind").cloned().unwrap(),
+            collect_errors: matches.get_one::<usize>("collect_errors").copied(),
+            errors_csv: matches.get_flag("errors_csv"),
+            bundle: matches.get_flag("bundle"),
+            list_skipped: *matches.get_one::<usize>("list_skipped").unwrap(),
+            resample_anchor_to_session: matches.get_flag("resample_anchor_to_session"),
+            grid_mark_missing: matches.get_flag("grid_mark_missing"),
+            cache_resample: matches.get_flag("cache_resample"),
         }
     }
 }
 
+/// Parses a float comparison tolerance, which must be finite and not negative.
+fn parse_epsilon(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(epsilon) if epsilon.is_finite() && epsilon >= 0.0 => Ok(epsilon),
+        Ok(_) => Err("Must be a finite number >= 0".to_string()),
+        Err(e) => Err(format!("Not a valid number: {}", e)),
+    }
+}
+
+/// Parses a `--resample` value: a supported timeframe, or `vol:N` volume / `count:N` count bars.
+fn parse_resample(s: &str) -> Result<String, String> {
+    const TIMEFRAMES: [&str; 7] = ["1min", "2min", "3min", "4min", "5min", "90min", "1d"];
+    if TIMEFRAMES.contains(&s) || crate::resample::BarThreshold::parse(s).is_some() {
+        Ok(s.to_string())
+    } else if s.starts_with("vol:") || s.starts_with("count:") {
+        Err("Volume and count bars need a positive threshold, e.g. vol:10000 or count:500".to_string())
+    } else {
+        Err(format!("Must be one of {}, vol:N or count:N", TIMEFRAMES.join(", ")))
+    }
+}
+
+/// Parses a `--dir-mode` like `0750` or `755`: octal permission bits up to `7777`.
+fn parse_dir_mode(s: &str) -> Result<u32, String> {
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        Ok(_) => Err("Must be at most 7777".to_string()),
+        Err(e) => Err(format!("Not a valid octal mode: {}", e)),
+    }
+}
+
 /// Validates that the number of threads is a positive integer.
 ///
 /// # Arguments
@@ -110,3 +1157,207 @@ fn parse_usize_positive(s: &str) -> Result<usize, String> {
         Err(e) => Err(format!("Not a valid number: {}", e)),
     }
 }
+
+/// Parses an `--io-buffer-size` like `parse_byte_size`, requiring a positive size that
+/// fits in memory.
+///
+/// # Arguments
+/// * `s` - The size, e.g. `64K` or `1M`.
+///
+/// # Returns
+/// * `Result<usize, String>` - The size in bytes or an error.
+fn parse_io_buffer_size(s: &str) -> Result<usize, String> {
+    match parse_byte_size(s)? {
+        0 => Err("Buffer size must be positive".to_string()),
+        size => usize::try_from(size).map_err(|_| format!("Buffer size is too large: {}", s)),
+    }
+}
+
+/// Validates an `--output-suffix` extension.
+///
+/// A leading dot is stripped. The extension must be a single path component without
+/// dots, and can't be `idx`, `aos` or `soa`, which would clash with the index file or
+/// the storage format part of the name.
+///
+/// # Arguments
+/// * `s` - The extension, e.g. `fb` or `.fb`.
+///
+/// # Returns
+/// * `Result<String, String>` - The extension without a leading dot or an error.
+fn parse_output_suffix(s: &str) -> Result<String, String> {
+    let suffix = s.trim().trim_start_matches('.');
+    if suffix.is_empty() {
+        return Err("Output suffix must not be empty".to_string());
+    }
+    if suffix.contains(['.', '/', '\\']) {
+        return Err(format!("Output suffix must be a single extension without dots or slashes: {}", s));
+    }
+    if ["idx", "aos", "soa"].iter().any(|reserved| suffix.eq_ignore_ascii_case(reserved)) {
+        return Err(format!("Output suffix '{}' is reserved", suffix));
+    }
+    Ok(suffix.to_string())
+}
+
+/// Compiles a `--symbol-regex` pattern.
+///
+/// # Arguments
+/// * `s` - The regular expression.
+///
+/// # Returns
+/// * `Result<regex::Regex, String>` - The compiled regex or the compile error.
+fn parse_regex(s: &str) -> Result<regex::Regex, String> {
+    regex::Regex::new(s).map_err(|e| e.to_string())
+}
+
+/// Looks up an `--encoding` label as defined by the WHATWG Encoding Standard.
+///
+/// Labels are case-insensitive and include aliases, e.g. `cp1251`, `latin1`, `iso-8859-1`.
+///
+/// # Arguments
+/// * `s` - The encoding label.
+///
+/// # Returns
+/// * `Result<&'static encoding_rs::Encoding, String>` - The encoding or an error for an unknown label.
+fn parse_encoding(s: &str) -> Result<&'static encoding_rs::Encoding, String> {
+    encoding_rs::Encoding::for_label(s.trim().as_bytes()).ok_or_else(|| format!("Unknown encoding: {}", s))
+}
+
+/// Environment variable supplying the thread count when `--threads` isn't given.
+pub const THREADS_ENV_VAR: &str = "CSV2FB_THREADS";
+
+/// Reads the default thread count from `THREADS_ENV_VAR`, used when `--threads` isn't given.
+///
+/// Invalid values (validated like `--threads`) are reported and ignored, so a bad
+/// deployment setting falls back to rayon's default instead of aborting the run.
+/// Called from `main` once logging is initialized, so the warning isn't lost.
+///
+/// # Returns
+/// * `Option<usize>` - The thread count, or `None` if the variable is unset or invalid.
+pub fn threads_from_env() -> Option<usize> {
+    let value = std::env::var(THREADS_ENV_VAR).ok()?;
+    match parse_usize_positive(value.trim()) {
+        Ok(n) => Some(n),
+        Err(e) => {
+            tracing::warn!("⚠️ Ignoring {}={:?}: {}", THREADS_ENV_VAR, value, e);
+            None
+        }
+    }
+}
+
+/// Parses a file size given in bytes or with a binary unit suffix.
+///
+/// Accepts `1048576`, `512KB`, `100MB`, `2GB`, `1TB` (also `K`, `M`, `G`, `T`, `B`),
+/// case-insensitive, where 1KB = 1024 bytes.
+///
+/// # Arguments
+/// * `s` - String representation of the size.
+///
+/// # Returns
+/// * `Result<u64, String>` - Size in bytes or error message.
+fn parse_byte_size(s: &str) -> Result<u64, String> {
+    let s = s.trim().to_ascii_uppercase();
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(digits_end);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Not a valid size: {}", s))?;
+    let multiplier: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        other => return Err(format!("Unknown size unit: {}", other)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Size is too large: {}", s))
+}
+
+/// Parses a display offset given in seconds (`-18000`) or as `±HH:MM` (`+03:00`).
+///
+/// # Arguments
+/// * `s` - String representation of the offset.
+///
+/// # Returns
+/// * `Result<i64, String>` - Offset in seconds or error message.
+fn parse_timestamp_offset(s: &str) -> Result<i64, String> {
+    let s = s.trim();
+    if let Ok(seconds) = s.parse::<i64>() {
+        return Ok(seconds);
+    }
+    let (sign, rest) = match s.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (hours, minutes) = rest
+        .split_once(':')
+        .ok_or_else(|| format!("Not a valid offset: {} (use seconds or ±HH:MM)", s))?;
+    let digits = |part: &str| -> Result<i64, String> {
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("Not a valid offset: {}", s));
+        }
+        part.parse().map_err(|_| format!("Offset is too large: {}", s))
+    };
+    let (hours, minutes) = (digits(hours)?, digits(minutes)?);
+    if minutes >= 60 {
+        return Err(format!("Not a valid offset: {}", s));
+    }
+    hours
+        .checked_mul(3600)
+        .and_then(|seconds| seconds.checked_add(minutes * 60))
+        .map(|seconds| sign * seconds)
+        .ok_or_else(|| format!("Offset is too large: {}", s))
+}
+
+/// Parses a single ASCII character used as a CSV quote or escape (e.g. `'` or `\`).
+///
+/// # Arguments
+/// * `s` - String representation of the character.
+///
+/// # Returns
+/// * `Result<u8, String>` - The character as a byte or error message.
+fn parse_ascii_char(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [byte] if byte.is_ascii() => Ok(*byte),
+        _ => Err(format!("Expected a single ASCII character, got {:?}", s)),
+    }
+}
+
+/// Parses the CSV field delimiter: a single ASCII character, or `tab` / `\t` for a tab.
+///
+/// # Arguments
+/// * `s` - String representation of the delimiter.
+///
+/// # Returns
+/// * `Result<u8, String>` - The delimiter as a byte or error message.
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "tab" | "\\t" => Ok(b'\t'),
+        _ => parse_ascii_char(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_offset_accepts_seconds_and_signed_hours_and_minutes() {
+        assert_eq!(parse_timestamp_offset("-18000"), Ok(-18000));
+        assert_eq!(parse_timestamp_offset("+03:00"), Ok(10800));
+        assert_eq!(parse_timestamp_offset("03:30"), Ok(12600));
+        assert_eq!(parse_timestamp_offset("-05:45"), Ok(-20700));
+        assert_eq!(parse_timestamp_offset("-00:30"), Ok(-1800));
+    }
+
+    #[test]
+    fn timestamp_offset_rejects_out_of_range_and_malformed_input() {
+        assert!(parse_timestamp_offset("+03:60").is_err());
+        assert!(parse_timestamp_offset("9999999999999999:00").is_err());
+        assert!(parse_timestamp_offset("99999999999999999999:00").is_err());
+        for malformed in ["", "+", "+3h", "+-3:00", "++03:00", "+03:", ":30", "+03:+5", "+03:00:00", "a:b"] {
+            assert!(parse_timestamp_offset(malformed).is_err(), "{:?}", malformed);
+        }
+    }
+}
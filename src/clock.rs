@@ -0,0 +1,67 @@
+/// Source of the current wall-clock time.
+///
+/// Time-relative logic (e.g. "how stale is the latest bar") takes a `Clock` instead
+/// of calling `chrono::Utc::now()` directly, so tests and reproducible runs can pin "now".
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// Current Unix timestamp in seconds (UTC).
+    fn now(&self) -> u64;
+}
+
+/// Production clock backed by the system time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        chrono::Utc::now().timestamp() as u64
+    }
+}
+
+/// Clock frozen at a fixed Unix timestamp, used for `--now` and in tests.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Returns how many seconds `timestamp` lags behind the clock's current time.
+///
+/// Timestamps in the future yield 0 rather than underflowing.
+///
+/// # Arguments
+/// * `clock` - Source of the current time.
+/// * `timestamp` - Unix timestamp in seconds (e.g. the latest bar).
+///
+/// # Returns
+/// * `u64` - Staleness in seconds.
+pub fn staleness_secs(clock: &dyn Clock, timestamp: u64) -> u64 {
+    clock.now().saturating_sub(timestamp)
+}
+
+/// Formats a duration in seconds as `Nd HHh MMm`.
+pub fn format_staleness(secs: u64) -> String {
+    format!("{}d {:02}h {:02}m", secs / 86400, (secs % 86400) / 3600, (secs % 3600) / 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staleness_is_measured_against_the_injected_clock() {
+        let clock = FixedClock(1_700_000_000);
+
+        assert_eq!(staleness_secs(&clock, 1_700_000_000 - 90_061), 90_061);
+        assert_eq!(format_staleness(90_061), "1d 01h 01m");
+    }
+
+    #[test]
+    fn future_timestamps_are_not_stale() {
+        let clock = FixedClock(1_700_000_000);
+
+        assert_eq!(staleness_secs(&clock, 1_700_000_100), 0);
+    }
+}
@@ -1,5 +1,15 @@
 use crate::cli;
+use crate::bar_source::{BarSource, SoaColumns};
+use crate::clock;
 use crate::index;
+use crate::bundle;
+use crate::utils;
+use crate::calendar;
+use crate::adjustments;
+use crate::file_processing;
+use crate::resample;
+use crate::timezone;
+use crate::read_flatbuffers;
 use crate::ohlcv_generated;
 use crate::ohlcv_soa_generated;
 
@@ -7,6 +17,8 @@ use crate::ohlcv_soa_generated;
 /// 
 /// This struct maps the columns of the input CSV file using serde attributes.
 /// The expected CSV format is: <DATE>,<TIME>,<OPEN>,<HIGH>,<LOW>,<CLOSE>,<VOL>
+/// Numeric columns may be empty (`,,`); they are resolved according to `cli::EmptyField`.
+/// The `<VOL>` column may be missing entirely for volumeless data converted with `--no-volume`.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct CsvRecord {
     #[serde(rename = "<DATE>")]
@@ -14,22 +26,71 @@ pub struct CsvRecord {
     #[serde(rename = "<TIME>")]
     time: String,
     #[serde(rename = "<OPEN>")]
-    open: f64,
+    open: Option<f64>,
     #[serde(rename = "<HIGH>")]
-    high: f64,
+    high: Option<f64>,
     #[serde(rename = "<LOW>")]
-    low: f64,
+    low: Option<f64>,
     #[serde(rename = "<CLOSE>")]
-    close: f64,
-    #[serde(rename = "<VOL>")]
-    vol: u64,
+    close: Option<f64>,
+    #[serde(rename = "<VOL>", default)]
+    vol: Option<u64>,
+}
+
+/// Represents a single record from NDJSON input.
+///
+/// Each input line is one JSON object with short field names:
+/// `{"t":"20240115 093000","o":100.0,"h":101.0,"l":99.5,"c":100.5,"v":1200}`
+/// where `t` uses the same `YYYYMMDD HHMMSS` layout as the CSV `<DATE>`/`<TIME>` columns.
+#[derive(Debug, serde::Deserialize)]
+pub struct JsonRecord {
+    t: String,
+    o: f64,
+    h: f64,
+    l: f64,
+    c: f64,
+    #[serde(default)]
+    v: Option<u64>,
+}
+
+/// A raw trade read with `--input-kind ticks`: no OHLC, just the traded price and size.
+///
+/// Ticks are aggregated into bars on ingest (`ticks_to_bars`), where every tick's price is
+/// its own open, high, low and close.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickRecord {
+    pub timestamp: u64,
+    pub price: f64,
+    pub volume: u64,
+}
+
+/// One row of tick CSV input: `<DATE>,<TIME>,<PRICE>,<VOL>`.
+#[derive(Debug, serde::Deserialize)]
+struct CsvTickRow {
+    #[serde(rename = "<DATE>")]
+    date: String,
+    #[serde(rename = "<TIME>")]
+    time: String,
+    #[serde(rename = "<PRICE>")]
+    price: Option<f64>,
+    #[serde(rename = "<VOL>", default)]
+    vol: Option<u64>,
+}
+
+/// One line of tick NDJSON input: `{"t":"20240115 093000","p":100.25,"v":3}`.
+#[derive(Debug, serde::Deserialize)]
+struct JsonTickRow {
+    t: String,
+    p: f64,
+    #[serde(default)]
+    v: Option<u64>,
 }
 
 /// Intermediate processed record with timestamp.
 /// 
 /// This struct holds OHLCV data after parsing the datetime string into a Unix timestamp.
 /// It's used to accumulate raw data before FlatBuffer creation, facilitating both AOS and SOA processing.
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct ProcessedRecord {
     timestamp: u64,
     open: f64,
@@ -37,6 +98,25 @@ pub struct ProcessedRecord {
     low: f64,
     close: f64,
     vol: u64,
+    /// Input line the record was read from, if known.
+    source_line: Option<u64>,
+    /// Split/dividend adjusted close, computed with `--adjustments`.
+    adj_close: Option<f64>,
+}
+
+impl From<&resample::OHLCVBar> for ProcessedRecord {
+    fn from(bar: &resample::OHLCVBar) -> Self {
+        ProcessedRecord {
+            timestamp: bar.timestamp,
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            vol: bar.volume,
+            source_line: None,
+            adj_close: None,
+        }
+    }
 }
 
 /// Contains index data generated during the conversion from CSV to FlatBuffer format.
@@ -50,17 +130,402 @@ pub struct ProcessedRecord {
 /// # Fields
 ///
 /// * `time_index` - Maps timestamps to their positions (indices) within the FlatBuffer vector.
-///                   Enables fast seeking to specific points in time.
+///   Enables fast seeking to specific points in time.
 /// * `daily_index` - Provides start and end indices for each trading day.
-///                    Useful for quickly accessing data for a particular day without scanning the whole file.
+///   Useful for quickly accessing data for a particular day without scanning the whole file.
 /// * `timeframe_index` - Precomputed lists of timestamps for common resampling intervals (e.g., "1m", "5m").
-///                       Facilitates rapid aggregation of data into larger timeframes.
+///   Facilitates rapid aggregation of data into larger timeframes.
+/// * `segment_offsets` - Byte offset of every SOA segment in the `.bin`, when it was written
+///   in segments (`--soa-segment-size`).
+#[derive(Debug)]
 pub struct ProcessedData {
     pub time_index: Vec<index::TimeIndexEntry>,
     pub daily_index: Vec<index::DailyIndexEntry>,
-    pub timeframe_index: std::collections::HashMap<String, Vec<u64>>,
+    pub timeframe_index: std::collections::BTreeMap<String, Vec<u64>>,
+    pub segment_offsets: Option<Vec<u64>>,
+}
+
+/// Options controlling a single CSV to FlatBuffer conversion.
+///
+/// Built once from the command-line arguments in `main` and shared (read-only)
+/// across all rayon workers in `progress::process_files`.
+#[derive(Debug, Clone)]
+pub struct ConversionOptions {
+    pub input_format: cli::InputFormat,
+    pub storage_format: cli::StorageFormat,
+    pub calendar: Option<calendar::TradingCalendar>,
+    /// Corporate actions used to store an adjusted close with every bar.
+    pub adjustments: Option<adjustments::Adjustments>,
+    pub split: Option<cli::SplitMode>,
+    pub schema_version: u32,
+    pub empty_field: cli::EmptyField,
+    /// Inputs larger than this many bytes are refused before being opened.
+    pub max_file_size: Option<u64>,
+    /// Store each bar's input line number in the index (`source_line_index`).
+    pub track_source_lines: bool,
+    /// Volumeless data: `<VOL>` is optional and ignored, and no volume is stored.
+    pub no_volume: bool,
+    /// Print a per-phase timing breakdown for each converted file.
+    pub timings: bool,
+    /// Instrument stored in every written `.idx` (`FullIndex::symbol`).
+    pub symbol: Option<String>,
+    /// Derive each file's symbol from its name instead (`progress::process_files`).
+    pub symbol_from_filename: Option<SymbolFromFilename>,
+    /// Convert only rows newer than the existing output's `.idx` into a `.delta` file.
+    pub since_last: bool,
+    /// Time zone of the input timestamps and DST resolution policy.
+    pub timezone: timezone::InputTimezone,
+    /// Keep only bars whose local time of day falls inside this window.
+    pub session: Option<calendar::SessionWindow>,
+    /// Write `.idx` files in the compact varint layout (`index::CompactFullIndex`).
+    pub compact_index: bool,
+    /// Delimiter, quote and escape characters of CSV input.
+    pub csv_quoting: CsvQuoting,
+    /// Decimal separator of numeric CSV fields.
+    pub decimal_separator: cli::DecimalSeparator,
+    /// Encoding of the input files, transcoded to UTF-8 while reading; `None` reads UTF-8 as is.
+    pub encoding: Option<&'static encoding_rs::Encoding>,
+    /// Cross-check every buffer against the other storage format before writing it.
+    pub self_verify: bool,
+    /// Parse every buffer back before writing it (`verify_output_buffer`).
+    pub verify_output: bool,
+    /// Write only the `.bin`: no `.idx`, and no `timeframe_index` is generated.
+    pub no_index: bool,
+    /// Report bars whose volume exceeds this multiple of the rolling median (`find_volume_spikes`).
+    pub volume_spike_multiple: Option<u64>,
+    /// Time order of the stored bars (see `save_flatbuffer`).
+    pub order: cli::BarOrder,
+    /// Write SOA output as segments of at most this many bars (see `save_flatbuffer`).
+    pub soa_segment_size: Option<usize>,
+    /// Repair or drop crossed bars instead of storing them as they are (`fix_crossed_bars`).
+    pub fix_crossed: Option<cli::FixCrossed>,
+    /// Final extension of output files (`--output-suffix`), `bin` by default.
+    pub output_suffix: String,
+    /// Capacity of the input read buffer and the segment write buffer (`--io-buffer-size`).
+    pub io_buffer_size: usize,
+    /// Store the `time_index` position of every timeframe boundary (`FullIndex::timeframe_positions`).
+    pub precompute_tf_positions: bool,
+    /// When set, the staleness of each file's latest bar is reported against this clock.
+    pub staleness_clock: Option<std::sync::Arc<dyn clock::Clock>>,
+    /// The input rows are ticks, aggregated into these bars on ingest (`--input-kind ticks`).
+    pub tick_bars: Option<TickBars>,
+    /// Keys of the `timeframe_index` entries to build (`--timeframes`, see `index_timeframe_keys`).
+    pub index_timeframes: Vec<String>,
+    /// Skip up to this many bad rows per file instead of failing at the first (`BadRows`).
+    pub collect_errors: Option<usize>,
+    /// Also write the bad rows to a `.errors.csv` next to the output (`--errors-csv`).
+    pub errors_csv: bool,
+    /// Pack each `.bin` and its `.idx` into a single `.ohlcv` file (`bundle::bundle_files`).
+    pub bundle: bool,
+    /// Set from another thread to stop the conversion with `Cancelled` (see `check_cancelled`).
+    pub cancel: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
+}
+
+impl Default for ConversionOptions {
+    /// Every option as if no optional flag was given, with the CLI's default `soa` storage.
+    fn default() -> Self {
+        ConversionOptions {
+            input_format: cli::InputFormat::Csv,
+            storage_format: cli::StorageFormat::Soa,
+            calendar: None,
+            adjustments: None,
+            split: None,
+            schema_version: utils::SCHEMA_VERSION,
+            empty_field: cli::EmptyField::Error,
+            max_file_size: None,
+            track_source_lines: false,
+            no_volume: false,
+            timings: false,
+            symbol: None,
+            symbol_from_filename: None,
+            since_last: false,
+            timezone: timezone::InputTimezone::default(),
+            session: None,
+            compact_index: false,
+            csv_quoting: CsvQuoting::default(),
+            decimal_separator: cli::DecimalSeparator::Point,
+            encoding: None,
+            self_verify: false,
+            verify_output: false,
+            no_index: false,
+            volume_spike_multiple: None,
+            order: cli::BarOrder::Asc,
+            soa_segment_size: None,
+            fix_crossed: None,
+            output_suffix: cli::DEFAULT_OUTPUT_SUFFIX.to_string(),
+            io_buffer_size: file_processing::DEFAULT_IO_BUFFER_SIZE,
+            precompute_tf_positions: false,
+            staleness_clock: None,
+            tick_bars: None,
+            index_timeframes: index_timeframe_keys(&[]),
+            collect_errors: None,
+            errors_csv: false,
+            bundle: false,
+            cancel: None,
+        }
+    }
+}
+
+impl ConversionOptions {
+    /// The `timeframe_index` entries to build, or `None` with `--no-index`.
+    fn indexed_timeframes(&self) -> Option<&[String]> {
+        (!self.no_index).then_some(&self.index_timeframes[..])
+    }
+
+    /// File name suffix of the outputs, e.g. `.aos.bin`, or `.aos.fb` with `--output-suffix fb`.
+    pub fn output_file_suffix(&self) -> String {
+        self.storage_format.file_suffix_with(&self.output_suffix)
+    }
+}
+
+/// The bars ticks are aggregated into on ingest (`--input-kind ticks`), taken from the
+/// `--resample` target and the bucketing flags.
+#[derive(Debug, Clone)]
+pub struct TickBars {
+    pub timeframe: resample::Timeframe,
+    pub bar_timestamp: cli::BarTimestamp,
+    pub bucket_edge: cli::BucketEdge,
+    pub bucket_anchor: u64,
+}
+
+/// Derives a file's symbol from its name (`--symbol-from-filename`).
+///
+/// Without a pattern the whole stem is the symbol (`AAPL.csv` → `AAPL`). With one, the
+/// symbol is its `symbol` group, else its first group, else the whole match, so
+/// `^([A-Z]+)_` turns `ES_20240115.txt` into `ES`.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolFromFilename {
+    pub pattern: Option<regex::Regex>,
+}
+
+impl SymbolFromFilename {
+    /// Returns the symbol encoded in `file_stem`.
+    ///
+    /// # Errors
+    /// * If the pattern doesn't match `file_stem`, or the stem is empty.
+    pub fn extract(&self, file_stem: &str) -> anyhow::Result<String> {
+        let symbol = match &self.pattern {
+            None => file_stem,
+            Some(pattern) => {
+                let captures = pattern
+                    .captures(file_stem)
+                    .ok_or_else(|| anyhow::anyhow!("Symbol pattern {} doesn't match file name {}", pattern, file_stem))?;
+                captures
+                    .name("symbol")
+                    .or_else(|| captures.get(1))
+                    .or_else(|| captures.get(0))
+                    .map_or("", |m| m.as_str())
+            }
+        };
+        if symbol.is_empty() {
+            return Err(anyhow::anyhow!("No symbol in file name {}", file_stem));
+        }
+        anyhow::Ok(symbol.to_string())
+    }
+}
+
+/// How CSV fields are delimited and quoted.
+///
+/// The default is standard CSV: fields are separated by `,`, may be wrapped in `"` and a
+/// quote inside a quoted field is written twice (`""`).
+#[derive(Debug, Clone)]
+pub struct CsvQuoting {
+    /// Quote character.
+    pub quote: u8,
+    /// Escape character for quotes inside quoted fields (e.g. `\"`), in addition to `""`.
+    pub escape: Option<u8>,
+    /// When false, quote characters are ordinary data.
+    pub quoting: bool,
+    /// Field delimiter.
+    pub delimiter: u8,
+}
+
+impl Default for CsvQuoting {
+    fn default() -> Self {
+        CsvQuoting { quote: b'"', escape: None, quoting: true, delimiter: b',' }
+    }
+}
+
+impl CsvQuoting {
+    /// Returns a header-aware CSV reader builder configured with these settings.
+    pub fn reader_builder(&self) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder
+            .has_headers(true)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .quoting(self.quoting);
+        builder
+    }
+}
+
+/// Summary of a single file conversion returned by `convert_csv_to_flatbuffer`.
+///
+/// # Fields
+///
+/// * `bars` - Number of OHLCV bars written to the `.bin` file.
+/// * `missing_days` - Trading days from the calendar (if provided) absent from the data.
+/// * `last_timestamp` - Timestamp of the latest bar, if any.
+/// * `timings` - Time spent in each conversion phase.
+/// * `skipped` - Input rows dropped before indexing.
+/// * `volume_spikes` - Bars flagged by `find_volume_spikes` (only with `volume_spike_multiple`).
+#[derive(Debug, Clone, Default)]
+pub struct ConversionStats {
+    pub bars: usize,
+    pub missing_days: Vec<chrono::NaiveDate>,
+    pub last_timestamp: Option<u64>,
+    pub timings: ConversionTimings,
+    pub skipped: SkippedRows,
+    pub volume_spikes: Vec<VolumeSpike>,
+}
+
+/// Input rows dropped while parsing, by reason, and rows repaired on the way.
+#[derive(Debug, Clone, Default)]
+pub struct SkippedRows {
+    /// Rows at or before the last converted bar (`--since-last`).
+    pub already_converted: usize,
+    /// Rows outside the trading session (`--session`).
+    pub outside_session: usize,
+    /// Crossed bars dropped (`--fix-crossed drop`).
+    pub crossed: usize,
+    /// Crossed bars repaired and kept (`--fix-crossed swap|clamp`); not dropped.
+    pub repaired_crossed: usize,
+    /// Rows that failed to parse, skipped with `--collect-errors`.
+    pub bad_rows: Vec<BadRow>,
+}
+
+/// An input row left out with `--collect-errors`, and why.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct BadRow {
+    /// Input line of the row.
+    pub line: u64,
+    /// The error that would have failed the conversion.
+    pub reason: String,
+}
+
+/// How many input rows are parsed between two checks of `ConversionOptions::cancel`.
+pub const CANCEL_CHECK_ROWS: usize = 10_000;
+
+/// Error returned when a conversion is stopped through `ConversionOptions::cancel`.
+///
+/// Find it with `error.downcast_ref::<Cancelled>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Conversion cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Fails with `Cancelled` once `cancel` is set.
+///
+/// The row loops call it every `CANCEL_CHECK_ROWS` rows and `convert_records` once more
+/// before writing anything, so a cancelled conversion stops within that many rows of
+/// parsing and never leaves output behind: nothing is written before the last check, and
+/// what is written afterwards goes through `file_processing::write_atomic`.
+///
+/// # Errors
+/// * `Cancelled`, if `cancel` is set.
+fn check_cancelled(cancel: Option<&std::sync::atomic::AtomicBool>) -> anyhow::Result<()> {
+    match cancel {
+        Some(cancel) if cancel.load(std::sync::atomic::Ordering::Relaxed) => Err(Cancelled.into()),
+        _ => anyhow::Ok(()),
+    }
+}
+
+/// Collects bad input rows with `--collect-errors` instead of failing at the first.
+///
+/// Without a cap (the default) every error is returned as is, so parsing stays fail-fast.
+/// With one, rows that don't parse are recorded and skipped; the parsers push only good
+/// rows, so positions in the indices stay contiguous. One more bad row than the cap
+/// fails the conversion, since the input is then likely in the wrong format altogether.
+#[derive(Debug, Clone, Default)]
+pub struct BadRows {
+    cap: Option<usize>,
+    rows: Vec<BadRow>,
+}
+
+impl BadRows {
+    /// Creates a collector keeping up to `cap` bad rows; `None` fails at the first.
+    pub fn new(cap: Option<usize>) -> Self {
+        BadRows { cap, rows: Vec::new() }
+    }
+
+    /// Passes through the parsed value of row `line`, or records its error.
+    ///
+    /// # Returns
+    /// * `anyhow::Result<Option<T>>` - The value, `None` if the error was recorded and the
+    ///   row is to be skipped, or the error when not collecting or past the cap.
+    fn check<T>(&mut self, line: usize, result: anyhow::Result<T>) -> anyhow::Result<Option<T>> {
+        let error = match result {
+            Ok(value) => return anyhow::Ok(Some(value)),
+            Err(error) => error,
+        };
+        match self.cap {
+            Some(cap) if self.rows.len() < cap => {
+                self.rows.push(BadRow { line: line as u64, reason: error.to_string() });
+                anyhow::Ok(None)
+            }
+            Some(cap) => Err(anyhow::anyhow!("More than {} bad rows (--collect-errors); at line {}: {}", cap, line, error)),
+            None => Err(error),
+        }
+    }
+
+    /// The rows recorded so far, in input order.
+    pub fn into_rows(self) -> Vec<BadRow> {
+        self.rows
+    }
+}
+
+/// A bar whose volume is far above the recent median, reported by `find_volume_spikes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeSpike {
+    /// Timestamp of the bar.
+    pub timestamp: u64,
+    /// Input line the bar was read from, if known.
+    pub source_line: Option<u64>,
+    /// Volume of the bar.
+    pub volume: u64,
+    /// Median volume of the preceding window.
+    pub median: u64,
+}
+
+/// Wall-clock time spent in each phase of a conversion.
+///
+/// With `--split` every phase is summed over all buckets.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionTimings {
+    /// Reading and parsing the input file.
+    pub parse: std::time::Duration,
+    /// Building the time, daily and timeframe indices.
+    pub build_index: std::time::Duration,
+    /// Serializing records into the FlatBuffer.
+    pub build_flatbuffer: std::time::Duration,
+    /// Writing the `.bin` file.
+    pub write_flatbuffer: std::time::Duration,
+    /// Serializing and writing the `.idx` file.
+    pub write_index: std::time::Duration,
 }
 
+/// Number of fields a vtable describes whose last field has the slot `last`: slots are
+/// 2 bytes each and follow the vtable's 4-byte header.
+const fn vtable_fields(last: flatbuffers::VOffsetT) -> usize {
+    (last as usize - 4) / 2 + 1
+}
+
+/// Bytes one bar takes in an SOA buffer: one 8-byte value in every column, `adj_closes`
+/// included.
+const SOA_BYTES_PER_BAR: usize = vtable_fields(ohlcv_soa_generated::OHLCVSOA::VT_ADJ_CLOSES) * 8;
+/// Upper bound on the bytes one bar takes in an AOS buffer: an `OHLCV` table with all its
+/// 8-byte fields and its soffset, padded to 8, plus its 4-byte offset in `items`.
+const AOS_BYTES_PER_BAR: usize = (4 + vtable_fields(ohlcv_generated::OHLCV::VT_ADJ_CLOSE) * 8).next_multiple_of(8) + 4;
+/// Room for vtables, root tables and vector length prefixes.
+const BUFFER_OVERHEAD: usize = 1024;
+
 // --- SOA Builder Implementation ---
 // The SOABuilder struct and its implementation handle the creation of FlatBuffer data
 // in the Structure of Arrays (SOA) format.
@@ -69,6 +534,11 @@ pub struct ProcessedData {
 /// 
 /// This struct accumulates OHLCV data into separate vectors for each field
 /// before finalizing the FlatBuffer binary representation.
+///
+/// `create_vector` on a scalar slice already copies the slice straight into the buffer
+/// (flatbuffers 25 has no separate `create_vector_direct`), so the columns are written
+/// with it. What does cost time on large files is the builder growing: every doubling
+/// copies everything built so far. `with_capacity` therefore sizes the buffer upfront.
 struct SOABuilder<'a> {
     builder: flatbuffers::FlatBufferBuilder<'a>,
     timestamps: Vec<u64>,
@@ -77,39 +547,47 @@ struct SOABuilder<'a> {
     lows: Vec<f64>,
     closes: Vec<f64>,
     volumes: Vec<u64>,
+    adj_closes: Vec<f64>,
 }
 
 impl<'a> SOABuilder<'a> {
-    /// Creates a new `SOABuilder` with an initial buffer capacity.
-    pub fn new() -> Self {
+    /// Creates a new `SOABuilder` sized for `bars` records, so neither the columns
+    /// nor the FlatBuffer have to grow while building.
+    pub fn with_capacity(bars: usize) -> Self {
         Self {
-            builder: flatbuffers::FlatBufferBuilder::with_capacity(1024 * 1024),
-            timestamps: Vec::new(),
-            opens: Vec::new(),
-            highs: Vec::new(),
-            lows: Vec::new(),
-            closes: Vec::new(),
-            volumes: Vec::new(),
+            builder: flatbuffers::FlatBufferBuilder::with_capacity(bars * SOA_BYTES_PER_BAR + BUFFER_OVERHEAD),
+            timestamps: Vec::with_capacity(bars),
+            opens: Vec::with_capacity(bars),
+            highs: Vec::with_capacity(bars),
+            lows: Vec::with_capacity(bars),
+            closes: Vec::with_capacity(bars),
+            volumes: Vec::with_capacity(bars),
+            adj_closes: Vec::new(),
         }
     }
 
     /// Adds a single OHLCV record to the builder's internal vectors.
-    pub fn add_ohlcv(&mut self, timestamp: u64, open: f64, high: f64, low: f64, close: f64, volume: u64) {
-        self.timestamps.push(timestamp);
-        self.opens.push(open);
-        self.highs.push(high);
-        self.lows.push(low);
-        self.closes.push(close);
-        self.volumes.push(volume);
+    ///
+    /// `adj_close` must be set on every record or on none.
+    pub fn add_ohlcv(&mut self, record: &ProcessedRecord) {
+        self.timestamps.push(record.timestamp);
+        self.opens.push(record.open);
+        self.highs.push(record.high);
+        self.lows.push(record.low);
+        self.closes.push(record.close);
+        self.volumes.push(record.vol);
+        if let Some(adj_close) = record.adj_close {
+            self.adj_closes.push(adj_close);
+        }
     }
 
     /// Finalizes the FlatBuffer data by creating the SOA structure and returning the binary vector.
     /// 
     /// This method takes ownership of `self`, constructs the FlatBuffer objects for the SOA layout,
-    /// and returns the final binary representation.
-    pub fn finish_buffer(self) -> Vec<u8> {
+    /// stamps the root table with `schema_version` and returns the final binary representation.
+    pub fn finish_buffer(self, schema_version: u32, has_volume: bool) -> Vec<u8> {
         // Destructure `self` to get access to the builder and the accumulated vectors
-        let Self { mut builder, timestamps, opens, highs, lows, closes, volumes } = self;
+        let Self { mut builder, timestamps, opens, highs, lows, closes, volumes, adj_closes } = self;
 
         // Create FlatBuffer vectors from the accumulated data
         let timestamps_vec = builder.create_vector(&timestamps);
@@ -117,7 +595,10 @@ impl<'a> SOABuilder<'a> {
         let highs_vec = builder.create_vector(&highs);
         let lows_vec = builder.create_vector(&lows);
         let closes_vec = builder.create_vector(&closes);
-        let volumes_vec = builder.create_vector(&volumes);
+        // Volumeless files omit the vector entirely; readers treat missing volumes as 0.
+        let volumes_vec = has_volume.then(|| builder.create_vector(&volumes));
+        // Adjusted closes are only written with --adjustments.
+        let adj_closes_vec = (!adj_closes.is_empty()).then(|| builder.create_vector(&adj_closes));
 
         // Build the OHLCVSOa object containing the separate vectors
         let ohlcv_soa = {
@@ -127,7 +608,12 @@ impl<'a> SOABuilder<'a> {
             ohlcv_soa_builder.add_highs(highs_vec);
             ohlcv_soa_builder.add_lows(lows_vec);
             ohlcv_soa_builder.add_closes(closes_vec);
-            ohlcv_soa_builder.add_volumes(volumes_vec);
+            if let Some(volumes_vec) = volumes_vec {
+                ohlcv_soa_builder.add_volumes(volumes_vec);
+            }
+            if let Some(adj_closes_vec) = adj_closes_vec {
+                ohlcv_soa_builder.add_adj_closes(adj_closes_vec);
+            }
             ohlcv_soa_builder.finish()
         };
 
@@ -135,6 +621,8 @@ impl<'a> SOABuilder<'a> {
         let ohlcv_list_soa = {
             let mut list_builder = ohlcv_soa_generated::OHLCVListSOABuilder::new(&mut builder);
             list_builder.add_data(ohlcv_soa);
+            list_builder.add_version(schema_version);
+            list_builder.add_has_volume(has_volume);
             list_builder.finish()
         };
 
@@ -145,304 +633,2691 @@ impl<'a> SOABuilder<'a> {
 
 // --- /SOA Builder Implementation ---
 
-/// Processes CSV records, accumulates raw data, and builds index structures.
+/// Processes CSV records and accumulates raw data.
 ///
-/// This function reads OHLCV records from a CSV reader, parses datetime strings
-/// into Unix timestamps, and populates index collections (time, daily, timeframe).
-/// Crucially, it accumulates the raw OHLCV data into a `Vec<ProcessedRecord>`,
-/// which is then used by `save_flatbuffer` to create either AOS or SOA FlatBuffers.
+/// This function reads OHLCV records from a CSV reader and parses datetime strings
+/// into Unix timestamps. It accumulates the raw OHLCV data into a `Vec<ProcessedRecord>`,
+/// which is then used by `save_flatbuffer` to create either AOS or SOA FlatBuffers
+/// and by `build_index` to create the index structures.
 ///
-/// The `timeframe_index` is generated to include ALL possible timeframe boundaries
-/// within the data's time range, ensuring no gaps for resampling purposes, even if
-/// some boundaries have no corresponding raw data.
+/// Empty numeric fields are handled according to `empty_field`:
+/// - `EmptyField::Default` - an empty `<VOL>` becomes 0 and an empty price becomes
+///   the previous record's close (an error if there is no previous record).
+/// - `EmptyField::Error` - any empty numeric field is an error.
+///
+/// With `no_volume` the `<VOL>` column is optional and ignored (volume is always 0);
+/// otherwise a header without `<VOL>` is an error.
+///
+/// With `session` rows outside the trading hours are dropped, and with `since_last` rows
+/// at or before that timestamp are skipped (see `is_new_row`).
+///
+/// Rows that don't deserialize, whose datetime doesn't parse or whose empty fields can't be
+/// resolved go through `bad_rows`: an error unless it collects them (`--collect-errors`).
 ///
 /// # Arguments
 /// * `reader` - CSV reader for input data.
-/// * `time_index` - Output vector to store timestamp-to-index mappings.
-/// * `daily_index` - Output vector to store daily OHLCV ranges.
-/// * `tf_index_map` - Output map to store timeframe-specific timestamps.
 /// * `raw_data` - Output vector to store raw ProcessedRecord data for FlatBuffer creation.
+/// * `options` - Supplies the decimal separator (see `deserialize_rows`), the empty-field
+///   policy, `no_volume`, the time zone of `<DATE>`/`<TIME>`, the session to keep and the
+///   cancel flag, checked every `CANCEL_CHECK_ROWS` rows (see `check_cancelled`).
+/// * `since_last` - Timestamp of the last bar already converted, if any.
+/// * `bad_rows` - Decides whether a bad row fails the file or is skipped.
 ///
 /// # Returns
-/// * `anyhow::Result<()>` - Success or an error if processing fails.
+/// * `anyhow::Result<SkippedRows>` - Counts of dropped rows, or an error if processing fails.
 ///
 /// # Errors
-/// * If datetime parsing fails.
+/// * `Cancelled`, once `options.cancel` is set.
+/// * If datetime parsing fails or a local time can't be resolved (see `InputTimezone::to_timestamp`).
 /// * If CSV deserialization fails.
+/// * If a numeric field is empty and can't be resolved (the line number is reported).
+/// * If an already converted row follows a new one.
 fn process_csv_records<R: std::io::Read>(
     reader: &mut csv::Reader<R>,
-    time_index: &mut Vec<index::TimeIndexEntry>,
-    daily_index: &mut Vec<index::DailyIndexEntry>,
-    tf_index_map: &mut std::collections::HashMap<String, Vec<u64>>,
-    raw_data: &mut Vec<ProcessedRecord>
-) -> anyhow::Result<()> {
-    let mut index_in_vector = 0u64;
-    let mut current_day = None::<String>;
-    let mut day_start_index = 0u64;
-    let supported_timeframes = vec![
-        ("1m", 60),
-        ("2m", 120),
-        ("3m", 180),
-        ("4m", 240),
-        ("5m", 300),
-        ("1d", 86400),
-    ];
-
-    // --- Collect raw data and basic indices first ---
-    let mut all_timestamps = Vec::new(); // Collect all timestamps for min/max calculation
-
-    for result in reader.deserialize::<CsvRecord>() {
-        let record: CsvRecord = result?;
-        let date_str = &record.date;
-        let time_str = &record.time;
-        let dt_str = format!("{} {}", date_str, time_str);
-        let dt = chrono::NaiveDateTime::parse_from_str(&dt_str, "%Y%m%d %H%M%S")
-        .map_err(|e| anyhow::anyhow!("Failed to parse datetime: {}", e))?;
-        let timestamp = dt.and_utc().timestamp() as u64;
+    raw_data: &mut Vec<ProcessedRecord>,
+    options: &ConversionOptions,
+    since_last: Option<u64>,
+    bad_rows: &mut BadRows,
+) -> anyhow::Result<SkippedRows> {
+    let mut skipped = SkippedRows::default();
+    check_csv_header(reader.headers()?, &REQUIRED_CSV_COLUMNS, options.no_volume)?;
+    // Reused for every row rather than formatting a new string each time.
+    let mut dt_str = String::with_capacity("YYYYMMDD HHMMSS".len());
+    for (record_no, result) in deserialize_rows::<R, CsvRecord>(reader, &options.decimal_separator)?.enumerate() {
+        if record_no % CANCEL_CHECK_ROWS == 0 {
+            check_cancelled(options.cancel.as_deref())?;
+        }
+        // Record 0 sits on line 2, right below the header.
+        let line = record_no + 2;
+        let Some(record) = bad_rows.check(line, result)? else {
+            continue;
+        };
+        dt_str.clear();
+        dt_str.push_str(&record.date);
+        dt_str.push(' ');
+        dt_str.push_str(&record.time);
+        let Some(timestamp) = bad_rows.check(line, parse_datetime(&dt_str, &options.timezone, options.session.as_ref()))? else {
+            continue;
+        };
+        let Some(timestamp) = timestamp else {
+            skipped.outside_session += 1;
+            continue;
+        };
+        if !is_new_row(timestamp, since_last, raw_data, line)? {
+            skipped.already_converted += 1;
+            continue;
+        }
+
+        let previous_close = raw_data.last().map(|r| r.close);
+        let values = resolve_csv_values(&record, line, &options.empty_field, options.no_volume, previous_close);
+        let Some((open, high, low, close, vol)) = bad_rows.check(line, values)? else {
+            continue;
+        };
 
         let processed_record = ProcessedRecord {
             timestamp,
-            open: record.open,
-            high: record.high,
-            low: record.low,
-            close: record.close,
-            vol: record.vol,
+            open,
+            high,
+            low,
+            close,
+            vol,
+            source_line: Some(line as u64),
+            adj_close: None,
         };
         raw_data.push(processed_record);
-        all_timestamps.push(timestamp);
-
-        // index by time
-        time_index.push(index::TimeIndexEntry {
-            timestamp,
-            index: index_in_vector,
-        });
-
-        //index by day
-        let date_key = dt.format("%Y-%m-%d").to_string();
-        if let Some(ref d) = current_day {
-            if d != &date_key {
-                if let Some(day) = current_day.take() {
-                    let entry = index::DailyIndexEntry {
-                        date: day,
-                        start_index: day_start_index,
-                        end_index: index_in_vector - 1,
-                    };
-                    daily_index.push(entry);
-                }
-                day_start_index = index_in_vector;
-                current_day = Some(date_key.clone());
-            }
-        } else {
-            current_day = Some(date_key.clone());
-            day_start_index = index_in_vector;
-        }
-        index_in_vector += 1;
-    }
-
-    // last day
-    if let Some(day) = current_day.take() {
-        daily_index.push(index::DailyIndexEntry { 
-            date: day,
-            start_index: day_start_index,
-            end_index: index_in_vector - 1,
-        });
-    }
-
-    // --- Generate comprehensive timeframe indices ---
-    if !all_timestamps.is_empty() {
-        let min_ts = *all_timestamps.iter().min().unwrap();
-        let max_ts = *all_timestamps.iter().max().unwrap();
-
-        for (tf_name, tf_sec) in &supported_timeframes {
-            let start_boundary = (min_ts / tf_sec) * tf_sec; // First boundary >= min_ts
-            let end_boundary = (max_ts / tf_sec) * tf_sec;   // Last boundary <= max_ts
-
-            let mut timeframe_timestamps = Vec::new();
-            let mut current_boundary = start_boundary;
-
-            // Populate all boundaries within the range
-            while current_boundary <= end_boundary {
-                timeframe_timestamps.push(current_boundary);
-                current_boundary += tf_sec;
-            }
-
-            tf_index_map.insert(tf_name.to_string(), timeframe_timestamps);
-        }
     }
 
-    anyhow::Ok(())
+    anyhow::Ok(skipped)
 }
 
-/// Converts CSV data to a FlatBuffer binary file (.bin) in AOS or SOA format and generates index data.
+/// Deserializes the rows of `reader` into `T`, rewriting numeric fields with a decimal comma
+/// first.
 ///
-/// This function orchestrates the conversion process based on the specified `storage_format`:
-/// 1. Opens and reads the input CSV file.
-/// 2. Initializes index collections.
-/// 3. Calls `process_csv_records` to accumulate raw data and populate indices.
-/// 4. Based on `storage_format`, creates the FlatBuffer data (either AOS or SOA).
-/// 5. Writes the binary FlatBuffer data to the output file.
-/// 6. Packages the generated index data for later use.
+/// With `DecimalSeparator::Comma` every field except `<DATE>` and `<TIME>` goes through
+/// `decimal_comma_to_point`, so `1.234,56` reaches serde as `1234.56`. The CSV reader has
+/// already split the row on its delimiter by then, so a decimal comma can only be told apart
+/// from a `,` delimiter if the field is quoted.
 ///
 /// # Arguments
-/// * `input_dir_path` - Path to the input CSV file.
-/// * `output_path` - Path for the output .bin file.
-/// * `storage_format` - The desired FlatBuffer storage format (AOS or SOA).
+/// * `reader` - CSV reader for input data.
+/// * `decimal_separator` - Decimal separator of the numeric fields.
 ///
 /// # Returns
-/// * `anyhow::Result<ProcessedData>` - The generated index data or an error.
+/// * `anyhow::Result<Box<dyn Iterator<Item = anyhow::Result<T>>>>` - The rows in file order.
 ///
 /// # Errors
-/// * If file I/O fails.
-/// * If `process_csv_records` fails.
-fn save_flatbuffer<P: AsRef<std::path::Path>>(
-    input_dir_path: P,
-    output_path: P,
-    storage_format: cli::StorageFormat,
-) -> anyhow::Result<ProcessedData> {
-    let input_file = std::fs::File::open(input_dir_path)?;
-    let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input_file);  
-    
-    let mut time_index: Vec<index::TimeIndexEntry> = Vec::new();
-    let mut daily_index: Vec<index::DailyIndexEntry> = Vec::new();
-    let mut tf_index_map: std::collections::HashMap<String, Vec<u64>> = std::collections::HashMap::new();
-    let mut raw_data = Vec::new();
-
-    // Accumulate raw data and indices
-    process_csv_records(
-        &mut reader,
-        &mut time_index,
-        &mut daily_index,
-        &mut tf_index_map,
-        &mut raw_data,
-    )?;
-
-    // --- Create FlatBuffer Data based on Storage Format ---
-    let flatbuffer_data = match storage_format {
-        cli::StorageFormat::Aos => {
-            // --- AOS Logic ---
-            let mut builder = flatbuffers::FlatBufferBuilder::with_capacity(1024 * 1024);
-            let mut ohlcv_offsets = Vec::with_capacity(raw_data.len());
-            for record in &raw_data {
-                let ohlcv_args = ohlcv_generated::OHLCVArgs {
-                    timestamp: record.timestamp,
-                    open: record.open,
-                    high: record.high,
-                    low: record.low,
-                    close: record.close,
-                    volume: record.vol,
-                };
-                let ohlcv = ohlcv_generated::OHLCV::create(&mut builder, &ohlcv_args);
-                ohlcv_offsets.push(ohlcv);
-            }
-
-            let items = builder.create_vector(&ohlcv_offsets);
-            let ohlcv_list = {
-                let mut list_builder = ohlcv_generated::OHLCVListBuilder::new(&mut builder);
-                list_builder.add_items(items);
-                list_builder.finish()
-            };
-            builder.finish(ohlcv_list, None);
-            builder.finished_data().to_vec()
+/// * If the header can't be read.
+fn deserialize_rows<'r, R: std::io::Read, T: serde::de::DeserializeOwned + 'r>(
+    reader: &'r mut csv::Reader<R>,
+    decimal_separator: &cli::DecimalSeparator,
+) -> anyhow::Result<Box<dyn Iterator<Item = anyhow::Result<T>> + 'r>> {
+    match decimal_separator {
+        cli::DecimalSeparator::Point => {
+            anyhow::Ok(Box::new(reader.deserialize::<T>().map(|result| result.map_err(anyhow::Error::from))))
         }
-        cli::StorageFormat::Soa => {
-            // --- SOA Logic ---
-            let mut soa_builder = SOABuilder::new();
-            for record in &raw_data {
-                soa_builder.add_ohlcv(
-                    record.timestamp,
-                    record.open,
-                    record.high,
-                    record.low,
-                    record.close,
-                    record.vol
-                );
-            }
-            soa_builder.finish_buffer()
+        cli::DecimalSeparator::Comma => {
+            let headers = reader.headers()?.clone();
+            let numeric: Vec<bool> = headers.iter().map(|name| name != "<DATE>" && name != "<TIME>").collect();
+            anyhow::Ok(Box::new(reader.records().map(move |result| {
+                let record = result?;
+                let mut normalized: csv::StringRecord = record
+                    .iter()
+                    .zip(numeric.iter().chain(std::iter::repeat(&true)))
+                    .map(|(field, &numeric)| if numeric { decimal_comma_to_point(field) } else { field.to_string() })
+                    .collect();
+                normalized.set_position(record.position().cloned());
+                anyhow::Ok(normalized.deserialize(Some(&headers))?)
+            })))
         }
-    };
-
-    // --- /Create FlatBuffer Data ---
+    }
+}
 
-    // Write the generated FlatBuffer binary data to the output file
-    std::fs::write(output_path.as_ref(), flatbuffer_data)?;
+/// Rewrites a number written with a decimal comma to the `.` notation serde parses:
+/// points (thousands separators) are dropped and the comma becomes the decimal point,
+/// e.g. `1.234,56` becomes `1234.56` and `1.234` becomes `1234`.
+fn decimal_comma_to_point(field: &str) -> String {
+    field.chars().filter(|&c| c != '.').map(|c| if c == ',' { '.' } else { c }).collect()
+}
 
-    // Package the generated index data
-    let processed_data = ProcessedData{
-        time_index: time_index,
-        daily_index: daily_index,
-        timeframe_index: tf_index_map,
+/// Resolves the prices and volume of a CSV row, filling empty fields according to
+/// `empty_field` (see `process_csv_records`).
+///
+/// # Returns
+/// * `anyhow::Result<(f64, f64, f64, f64, u64)>` - Open, high, low, close and volume, or an
+///   error naming the first empty field that can't be resolved.
+fn resolve_csv_values(
+    record: &CsvRecord,
+    line: usize,
+    empty_field: &cli::EmptyField,
+    no_volume: bool,
+    previous_close: Option<f64>,
+) -> anyhow::Result<(f64, f64, f64, f64, u64)> {
+    let price = |value: Option<f64>, column: &str| -> anyhow::Result<f64> {
+        match (value, empty_field, previous_close) {
+            (Some(v), _, _) => anyhow::Ok(v),
+            (None, cli::EmptyField::Default, Some(close)) => anyhow::Ok(close),
+            (None, cli::EmptyField::Default, None) => Err(anyhow::anyhow!(
+                "Empty {} at line {} and no previous close to fill it from", column, line
+            )),
+            (None, cli::EmptyField::Error, _) => Err(anyhow::anyhow!("Empty {} at line {}", column, line)),
+        }
     };
-
-    anyhow::Ok(processed_data)
+    let vol = match (record.vol, empty_field) {
+        _ if no_volume => 0,
+        (Some(v), _) => v,
+        (None, cli::EmptyField::Default) => 0,
+        (None, cli::EmptyField::Error) => return Err(anyhow::anyhow!("Empty <VOL> at line {}", line)),
+    };
+    anyhow::Ok((
+        price(record.open, "<OPEN>")?,
+        price(record.high, "<HIGH>")?,
+        price(record.low, "<LOW>")?,
+        price(record.close, "<CLOSE>")?,
+        vol,
+    ))
 }
 
-/// Serializes and saves index data to a companion .idx file.
-///
-/// This function takes the generated time, daily, and timeframe indices,
-/// packages them into a `FullIndex` struct, serializes it using `bincode`,
-/// and writes it to a file with the same name as the output but with a `.idx` extension.
+/// Columns every CSV input must have, in the order of the expected header.
+const REQUIRED_CSV_COLUMNS: [&str; 6] = ["<DATE>", "<TIME>", "<OPEN>", "<HIGH>", "<LOW>", "<CLOSE>"];
+
+/// Columns every tick CSV input (`--input-kind ticks`) must have.
+const REQUIRED_TICK_COLUMNS: [&str; 3] = ["<DATE>", "<TIME>", "<PRICE>"];
+
+/// Checks the CSV header row before any record is read.
 ///
-/// The .idx file enables fast random access and resampling without loading the full dataset.
+/// A wrong header would otherwise only surface as a deserialization error on the first
+/// record, so this fails fast with every missing column and the headers actually found.
+/// `<VOL>` is required unless `no_volume` is set; extra columns are allowed.
 ///
 /// # Arguments
-/// * `time_index` - Vector of timestamp-to-index mappings.
-/// * `daily_index` - Vector of daily OHLCV range mappings.
-/// * `timeframe_index` - Map of timeframe names to lists of timestamps.
-/// * `output_path` - Path to the main .bin file (used to derive .idx path).
+/// * `headers` - The header row.
+/// * `required` - Columns besides `<VOL>` (`REQUIRED_CSV_COLUMNS` or `REQUIRED_TICK_COLUMNS`).
+/// * `no_volume` - Whether the input is volumeless.
 ///
 /// # Returns
-/// * `anyhow::Result<()>` - Success or an error if writing fails.
-///
-/// # Errors
-/// * If serialization or file I/O fails.
-fn save_index<P: AsRef<std::path::Path>>(
-    time_index: &[index::TimeIndexEntry],
-    daily_index: &[index::DailyIndexEntry],
-    timeframe_index: &std::collections::HashMap<String, Vec<u64>>,
-    output_path: P,
-) -> anyhow::Result<()> {
-    let idx_path = std::path::Path::new(output_path.as_ref()).with_extension("idx");  
-    let full_index = index::FullIndex {
-        time_index: time_index.to_vec(),
-        daily_index: daily_index.to_vec(),
-        timeframe_index: timeframe_index.clone(),
-    };
-
-    let data = bincode::serialize(&full_index)?;
-    std::fs::write(idx_path, data)?;
+/// * `anyhow::Result<()>` - Success, or an error like
+///   `Missing column <OPEN>; found headers [<DATE>, <TIME>, ...]`.
+fn check_csv_header(headers: &csv::StringRecord, required: &[&str], no_volume: bool) -> anyhow::Result<()> {
+    let volume = if no_volume { None } else { Some("<VOL>") };
+    let missing: Vec<&str> = required
+        .iter()
+        .copied()
+        .chain(volume)
+        .filter(|column| !headers.iter().any(|h| h == *column))
+        .collect();
+    if missing.is_empty() {
+        return anyhow::Ok(());
+    }
 
-    anyhow::Ok(())
+    let found: Vec<&str> = headers.iter().collect();
+    let hint = if missing.contains(&"<VOL>") { " (use --no-volume for volumeless data)" } else { "" };
+    Err(anyhow::anyhow!(
+        "Missing column{} {}; found headers [{}]{}",
+        if missing.len() == 1 { "" } else { "s" },
+        missing.join(", "),
+        found.join(", "),
+        hint
+    ))
 }
 
-/// Public entry point to convert a CSV file to FlatBuffer format with indexing.
+/// Processes NDJSON records and accumulates raw data.
 ///
-/// This function provides a high-level interface for the conversion process.
-/// It delegates to `save_flatbuffer` for the core logic (reading CSV, creating FlatBuffer based on format)
-/// and `save_index` for persisting the generated indices. It's designed to be called from `main.rs`
-/// or other modules needing to trigger the conversion.
+/// The NDJSON counterpart of `process_csv_records`: every non-empty line is parsed
+/// as a `JsonRecord` and pushed to `raw_data` as a `ProcessedRecord`, so indexing and
+/// FlatBuffer building downstream are identical for both input formats.
 ///
 /// # Arguments
-/// * `input_dir_path` - Path to the input CSV file.
-/// * `output_path` - Path for the output .bin file (e.g., filename.aos.bin or filename.soa.bin).
-/// * `storage_format` - The desired FlatBuffer storage format (AOS or SOA).
+/// * `reader` - Buffered reader over the NDJSON input.
+/// * `raw_data` - Output vector to store raw ProcessedRecord data for FlatBuffer creation.
+/// * `options` - Supplies `no_volume` (`v` is then optional and ignored), the time zone of
+///   `t`, the session to keep and the cancel flag, checked every `CANCEL_CHECK_ROWS` lines.
+/// * `since_last` - Timestamp of the last bar already converted, if any.
+/// * `bad_rows` - Decides whether a bad line fails the file or is skipped.
 ///
 /// # Returns
-/// * `anyhow::Result<()>` - Success or an error if conversion or saving fails.
+/// * `anyhow::Result<SkippedRows>` - Counts of dropped rows, or an error if processing fails.
 ///
 /// # Errors
-/// * Propagates errors from `save_flatbuffer` or `save_index`.
-pub fn convert_csv_to_flatbuffer<P: AsRef<std::path::Path>>(input_dir_path: P, output_path: P, storage_format: cli::StorageFormat) -> anyhow::Result<()> {
-    let processed_data = save_flatbuffer(
-        input_dir_path.as_ref(),
-        output_path.as_ref(),
-        storage_format.clone(),
-    )?;
-    save_index(
-        &processed_data.time_index,
-        &processed_data.daily_index,
-        &processed_data.timeframe_index,
-        output_path.as_ref(),
-    )?;
+/// * `Cancelled`, once `options.cancel` is set.
+/// * If a line isn't a valid `JsonRecord` (the line number is reported).
+/// * If `v` is missing and `no_volume` isn't set.
+/// * If datetime parsing fails or a local time can't be resolved.
+/// * If an already converted row follows a new one.
+fn process_ndjson_records<R: std::io::BufRead>(
+    reader: R,
+    raw_data: &mut Vec<ProcessedRecord>,
+    options: &ConversionOptions,
+    since_last: Option<u64>,
+    bad_rows: &mut BadRows,
+) -> anyhow::Result<SkippedRows> {
+    let mut skipped = SkippedRows::default();
+    for (line_no, line) in reader.lines().enumerate() {
+        if line_no % CANCEL_CHECK_ROWS == 0 {
+            check_cancelled(options.cancel.as_deref())?;
+        }
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str::<JsonRecord>(&line)
+            .map_err(|e| anyhow::anyhow!("Invalid NDJSON record at line {}: {}", line_no + 1, e));
+        let Some(record) = bad_rows.check(line_no + 1, record)? else {
+            continue;
+        };
+        let Some(timestamp) = bad_rows.check(line_no + 1, parse_datetime(&record.t, &options.timezone, options.session.as_ref()))? else {
+            continue;
+        };
+        let Some(timestamp) = timestamp else {
+            skipped.outside_session += 1;
+            continue;
+        };
+        if !is_new_row(timestamp, since_last, raw_data, line_no + 1)? {
+            skipped.already_converted += 1;
+            continue;
+        }
+        let vol = match record.v {
+            _ if options.no_volume => Ok(0),
+            Some(v) => Ok(v),
+            None => Err(anyhow::anyhow!(
+                "Missing \"v\" at line {}; use --no-volume for volumeless data", line_no + 1
+            )),
+        };
+        let Some(vol) = bad_rows.check(line_no + 1, vol)? else {
+            continue;
+        };
 
-    anyhow::Ok(())
+        raw_data.push(ProcessedRecord {
+            timestamp,
+            open: record.o,
+            high: record.h,
+            low: record.l,
+            close: record.c,
+            vol,
+            source_line: Some(line_no as u64 + 1),
+            adj_close: None,
+        });
+    }
+
+    anyhow::Ok(skipped)
+}
+
+/// Parses tick CSV input (`<DATE>,<TIME>,<PRICE>,<VOL>`) into ticks.
+///
+/// The tick counterpart of `process_csv_records`. An empty `<PRICE>` takes the previous
+/// tick's price with `EmptyField::Default`.
+///
+/// # Arguments
+/// * `reader` - CSV reader for input data.
+/// * `options` - Supplies the decimal separator (see `deserialize_rows`), the empty-field
+///   policy, `no_volume`, the time zone of `<DATE>`/`<TIME>`, the session to keep and the
+///   cancel flag, checked every `CANCEL_CHECK_ROWS` rows (see `check_cancelled`).
+/// * `bad_rows` - Decides whether a bad row fails the file or is skipped.
+///
+/// # Returns
+/// * `anyhow::Result<(Vec<TickRecord>, SkippedRows)>` - Ticks in file order and counts of
+///   dropped rows.
+///
+/// # Errors
+/// * `Cancelled`, once `options.cancel` is set.
+/// * If the header lacks a column, a row can't be deserialized or its datetime parsed.
+/// * If a numeric field is empty and can't be resolved (the line number is reported).
+fn parse_csv_ticks<R: std::io::Read>(
+    reader: &mut csv::Reader<R>,
+    options: &ConversionOptions,
+    bad_rows: &mut BadRows,
+) -> anyhow::Result<(Vec<TickRecord>, SkippedRows)> {
+    let mut ticks: Vec<TickRecord> = Vec::new();
+    let mut skipped = SkippedRows::default();
+    check_csv_header(reader.headers()?, &REQUIRED_TICK_COLUMNS, options.no_volume)?;
+    for (record_no, result) in deserialize_rows::<R, CsvTickRow>(reader, &options.decimal_separator)?.enumerate() {
+        if record_no % CANCEL_CHECK_ROWS == 0 {
+            check_cancelled(options.cancel.as_deref())?;
+        }
+        let line = record_no + 2;
+        let Some(row) = bad_rows.check(line, result)? else {
+            continue;
+        };
+        let timestamp = parse_datetime(&format!("{} {}", row.date, row.time), &options.timezone, options.session.as_ref());
+        let Some(timestamp) = bad_rows.check(line, timestamp)? else {
+            continue;
+        };
+        let Some(timestamp) = timestamp else {
+            skipped.outside_session += 1;
+            continue;
+        };
+        let price = match (row.price, &options.empty_field, ticks.last()) {
+            (Some(price), _, _) => Ok(price),
+            (None, cli::EmptyField::Default, Some(previous)) => Ok(previous.price),
+            (None, cli::EmptyField::Default, None) => {
+                Err(anyhow::anyhow!("Empty <PRICE> at line {} and no previous price to fill it from", line))
+            }
+            (None, cli::EmptyField::Error, _) => Err(anyhow::anyhow!("Empty <PRICE> at line {}", line)),
+        };
+        let volume = match (row.vol, &options.empty_field) {
+            _ if options.no_volume => Ok(0),
+            (Some(v), _) => Ok(v),
+            (None, cli::EmptyField::Default) => Ok(0),
+            (None, cli::EmptyField::Error) => Err(anyhow::anyhow!("Empty <VOL> at line {}", line)),
+        };
+        let Some((price, volume)) = bad_rows.check(line, price.and_then(|price| volume.map(|volume| (price, volume))))? else {
+            continue;
+        };
+        ticks.push(TickRecord { timestamp, price, volume });
+    }
+
+    anyhow::Ok((ticks, skipped))
+}
+
+/// Parses tick NDJSON input (`{"t":"20240115 093000","p":100.25,"v":3}`) into ticks.
+///
+/// The tick counterpart of `process_ndjson_records`; blank lines are ignored.
+///
+/// # Arguments
+/// * `reader` - Buffered reader over the NDJSON input.
+/// * `options` - Supplies `no_volume` (`v` is then optional and ignored), the time zone of
+///   `t`, the session to keep and the cancel flag, checked every `CANCEL_CHECK_ROWS` lines.
+/// * `bad_rows` - Decides whether a bad line fails the file or is skipped.
+///
+/// # Returns
+/// * `anyhow::Result<(Vec<TickRecord>, SkippedRows)>` - Ticks in file order and counts of
+///   dropped rows.
+///
+/// # Errors
+/// * `Cancelled`, once `options.cancel` is set.
+/// * If a line isn't a valid tick (the line number is reported) or `v` is missing without
+///   `no_volume`.
+fn parse_ndjson_ticks<R: std::io::BufRead>(
+    reader: R,
+    options: &ConversionOptions,
+    bad_rows: &mut BadRows,
+) -> anyhow::Result<(Vec<TickRecord>, SkippedRows)> {
+    let mut ticks = Vec::new();
+    let mut skipped = SkippedRows::default();
+    for (line_no, line) in reader.lines().enumerate() {
+        if line_no % CANCEL_CHECK_ROWS == 0 {
+            check_cancelled(options.cancel.as_deref())?;
+        }
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row = serde_json::from_str::<JsonTickRow>(&line)
+            .map_err(|e| anyhow::anyhow!("Invalid NDJSON tick at line {}: {}", line_no + 1, e));
+        let Some(row) = bad_rows.check(line_no + 1, row)? else {
+            continue;
+        };
+        let Some(timestamp) = bad_rows.check(line_no + 1, parse_datetime(&row.t, &options.timezone, options.session.as_ref()))? else {
+            continue;
+        };
+        let Some(timestamp) = timestamp else {
+            skipped.outside_session += 1;
+            continue;
+        };
+        let volume = match row.v {
+            _ if options.no_volume => Ok(0),
+            Some(v) => Ok(v),
+            None => Err(anyhow::anyhow!(
+                "Missing \"v\" at line {}; use --no-volume for volumeless data", line_no + 1
+            )),
+        };
+        let Some(volume) = bad_rows.check(line_no + 1, volume)? else {
+            continue;
+        };
+        ticks.push(TickRecord { timestamp, price: row.p, volume });
+    }
+
+    anyhow::Ok((ticks, skipped))
+}
+
+/// Aggregates ticks, in time order, into OHLCV bars (`--input-kind ticks`).
+///
+/// Each tick is a one-trade bar whose open, high, low and close are its price, resampled
+/// with `resample::resample_many`, so the bars are bucketed exactly like `--resample` on
+/// read. A bar's `count` is its number of ticks.
+///
+/// # Arguments
+/// * `ticks` - Ticks in time order.
+/// * `tick_bars` - Timeframe and bucketing of the bars.
+///
+/// # Returns
+/// * `Vec<resample::OHLCVBar>` - One bar per non-empty bucket.
+pub fn ticks_to_bars(ticks: &[TickRecord], tick_bars: &TickBars) -> Vec<resample::OHLCVBar> {
+    let bars = ticks.iter().map(|tick| resample::OHLCVBar {
+        timestamp: tick.timestamp,
+        open: tick.price,
+        high: tick.price,
+        low: tick.price,
+        close: tick.price,
+        volume: tick.volume,
+        count: 1,
+    });
+    resample::resample_many(bars, &[tick_bars.timeframe], &tick_bars.bar_timestamp, &tick_bars.bucket_edge, tick_bars.bucket_anchor)
+        .pop()
+        .unwrap_or_default()
+}
+
+/// Decides whether a row belongs to the new tail converted by `--since-last`.
+///
+/// Rows at or before `since_last` were already converted and are skipped. Once a newer
+/// row has been kept, every following row must be newer too, otherwise the input isn't
+/// cumulative and the delta would silently miss data.
+///
+/// # Arguments
+/// * `timestamp` - Timestamp of the row.
+/// * `since_last` - Timestamp of the last bar already converted; `None` keeps every row.
+/// * `raw_data` - Rows kept so far.
+/// * `line` - Input line of the row, for error messages.
+///
+/// # Returns
+/// * `anyhow::Result<bool>` - Whether to keep the row, or an error for an out-of-order row.
+fn is_new_row(timestamp: u64, since_last: Option<u64>, raw_data: &[ProcessedRecord], line: usize) -> anyhow::Result<bool> {
+    match since_last {
+        Some(last) if timestamp <= last => {
+            if !raw_data.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Row at line {} is not newer than the last converted bar ({}) but follows newer rows",
+                    line,
+                    last
+                ));
+            }
+            anyhow::Ok(false)
+        }
+        _ => anyhow::Ok(true),
+    }
+}
+
+/// Parses a `YYYYMMDD HHMMSS` local datetime string into a Unix timestamp (UTC seconds).
+///
+/// Returns `None` if the local time of day falls outside `session`. The check uses the
+/// time as written, so the window is in the input's time zone.
+fn parse_datetime(
+    dt_str: &str,
+    timezone: &timezone::InputTimezone,
+    session: Option<&calendar::SessionWindow>,
+) -> anyhow::Result<Option<u64>> {
+    let dt = chrono::NaiveDateTime::parse_from_str(dt_str, "%Y%m%d %H%M%S")
+        .map_err(|e| anyhow::anyhow!("Failed to parse datetime: {}", e))?;
+    if session.is_some_and(|session| !session.contains(dt.time())) {
+        return anyhow::Ok(None);
+    }
+    timezone.to_timestamp(dt).map(Some)
+}
+
+/// Builds the time, daily and timeframe indices for a slice of records.
+///
+/// Positions in the resulting indices are relative to the start of `records`,
+/// so each slice written to its own `.bin` gets a self-contained index.
+///
+/// The `timeframe_index` is generated to include ALL possible timeframe boundaries
+/// within the data's time range, ensuring no gaps for resampling purposes, even if
+/// some boundaries have no corresponding raw data.
+///
+/// # Arguments
+/// * `records` - Records in the order they are stored in the FlatBuffer.
+///
+/// # Returns
+/// * `anyhow::Result<ProcessedData>` - The generated index data or an error.
+///
+/// # Errors
+/// * If a timestamp can't be converted to a date.
+/// * If a date appears in non-contiguous ranges (the input isn't sorted by time), since its
+///   `daily_index` entry couldn't cover all of its bars.
+pub fn build_index(records: &[ProcessedRecord]) -> anyhow::Result<ProcessedData> {
+    index_records(records, Some(&index_timeframe_keys(&[])))
+}
+
+/// Every `timeframe_index` entry a conversion can build: its key and bucket size in seconds.
+const INDEX_TIMEFRAMES: [(&str, u64); 6] = [
+    ("1m", 60),
+    ("2m", 120),
+    ("3m", 180),
+    ("4m", 240),
+    ("5m", 300),
+    ("1d", 86400),
+];
+
+/// Returns the `timeframe_index` keys of `--timeframes` names, e.g. `5min` → `5m`.
+///
+/// An empty `timeframes` selects every key. Timeframes without an index entry (`90min`)
+/// select none, so `--timeframes 90min` builds no `timeframe_index` at all.
+///
+/// # Arguments
+/// * `timeframes` - Timeframe names as accepted by `resample::Timeframe::parse`.
+///
+/// # Returns
+/// * `Vec<String>` - The selected keys, in `INDEX_TIMEFRAMES` order.
+pub fn index_timeframe_keys(timeframes: &[String]) -> Vec<String> {
+    let seconds: Vec<u64> = timeframes
+        .iter()
+        .filter_map(|tf| match resample::Timeframe::parse(tf) {
+            Ok(resample::Timeframe::Intraday(timeframe_sec)) => Some(timeframe_sec),
+            Ok(resample::Timeframe::Daily) => Some(86400),
+            Err(_) => None,
+        })
+        .collect();
+    INDEX_TIMEFRAMES
+        .iter()
+        .filter(|(_, tf_sec)| timeframes.is_empty() || seconds.contains(tf_sec))
+        .map(|(tf_name, _)| tf_name.to_string())
+        .collect()
+}
+
+/// Builds the indices of `records` like `build_index`, with only the `timeframes` entries of
+/// `timeframe_index` (keys of `INDEX_TIMEFRAMES`), or none if it is `None`.
+///
+/// With `--no-index` no `.idx` is written, but the daily index is still needed for the
+/// calendar check, so only the timeframe boundaries are skipped.
+fn index_records(records: &[ProcessedRecord], timeframes: Option<&[String]>) -> anyhow::Result<ProcessedData> {
+    let mut time_index: Vec<index::TimeIndexEntry> = Vec::with_capacity(records.len());
+    let mut daily_index: Vec<index::DailyIndexEntry> = Vec::new();
+    let mut tf_index_map: std::collections::BTreeMap<String, Vec<u64>> = std::collections::BTreeMap::new();
+    let mut current_day = None::<String>;
+    let mut seen_days: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let mut day_start_index = 0u64;
+
+    for (position, record) in records.iter().enumerate() {
+        let index_in_vector = position as u64;
+
+        // index by time
+        time_index.push(index::TimeIndexEntry {
+            timestamp: record.timestamp,
+            index: index_in_vector,
+        });
+
+        //index by day
+        let date_key = utils::format_date(record.timestamp)?;
+        if let Some(ref d) = current_day {
+            if d != &date_key {
+                if let Some(day) = current_day.take() {
+                    let entry = index::DailyIndexEntry {
+                        date: day,
+                        start_index: day_start_index,
+                        end_index: index_in_vector - 1,
+                    };
+                    daily_index.push(entry);
+                }
+                // A day is one `start_index..=end_index` range, so a date can't reopen later.
+                if !seen_days.insert(date_key.clone()) {
+                    return Err(anyhow::anyhow!(
+                        "Date {} appears in non-contiguous ranges (again at {}); input must be sorted by time",
+                        date_key, record.timestamp
+                    ));
+                }
+                day_start_index = index_in_vector;
+                current_day = Some(date_key.clone());
+            }
+        } else {
+            seen_days.insert(date_key.clone());
+            current_day = Some(date_key.clone());
+            day_start_index = index_in_vector;
+        }
+    }
+
+    // last day
+    if let Some(day) = current_day.take() {
+        daily_index.push(index::DailyIndexEntry {
+            date: day,
+            start_index: day_start_index,
+            end_index: records.len() as u64 - 1,
+        });
+    }
+
+    // --- Generate comprehensive timeframe indices ---
+    if let Some(timeframes) = timeframes.filter(|_| !records.is_empty()) {
+        let min_ts = records.iter().map(|r| r.timestamp).min().unwrap();
+        let max_ts = records.iter().map(|r| r.timestamp).max().unwrap();
+
+        for (tf_name, tf_sec) in INDEX_TIMEFRAMES.iter().filter(|(tf_name, _)| timeframes.iter().any(|tf| tf == tf_name)) {
+            let start_boundary = (min_ts / tf_sec) * tf_sec; // First boundary >= min_ts
+            let end_boundary = (max_ts / tf_sec) * tf_sec;   // Last boundary <= max_ts
+
+            let mut timeframe_timestamps = Vec::new();
+            let mut current_boundary = start_boundary;
+
+            // Populate all boundaries within the range
+            while current_boundary <= end_boundary {
+                timeframe_timestamps.push(current_boundary);
+                current_boundary += tf_sec;
+            }
+
+            tf_index_map.insert(tf_name.to_string(), timeframe_timestamps);
+        }
+    }
+
+    anyhow::Ok(ProcessedData {
+        time_index,
+        daily_index,
+        timeframe_index: tf_index_map,
+        segment_offsets: None,
+    })
+}
+
+/// Reads and parses an input file (CSV or NDJSON) into processed records.
+///
+/// With `options.tick_bars` the file holds ticks, which are aggregated into bars first
+/// (`ticks_to_bars`); everything after sees only the bars.
+///
+/// With `options.fix_crossed` crossed bars are repaired or dropped (`fix_crossed_bars`), and
+/// with `options.adjustments` every record then gets its adjusted close (`apply_adjustments`).
+/// Both run after parsing, so they apply to CSV and NDJSON alike.
+///
+/// # Arguments
+/// * `input_path` - Path to the input file.
+/// * `options` - Conversion options (input format, CSV quoting, empty field and volume handling).
+/// * `since_last` - Timestamp of the last bar already converted; older rows are skipped.
+///
+/// # Returns
+/// * `anyhow::Result<(Vec<ProcessedRecord>, SkippedRows)>` - Parsed records in file order and
+///   counts of dropped rows.
+///
+/// # Errors
+/// * If file I/O fails.
+/// * If `process_csv_records` or `process_ndjson_records` fails.
+fn read_input_file<P: AsRef<std::path::Path>>(
+    input_path: P,
+    options: &ConversionOptions,
+    since_last: Option<u64>,
+) -> anyhow::Result<(Vec<ProcessedRecord>, SkippedRows)> {
+    let file = std::fs::File::open(input_path.as_ref()).map_err(|e| file_processing::io_error(e, "open input", &input_path))?;
+    let size_hint = file.metadata().ok().map(|metadata| metadata.len());
+    read_input(file, size_hint, options, since_last)
+}
+
+/// Bytes per CSV row assumed by `estimated_rows`.
+///
+/// Typical rows (`20240912,100000,90300.25,90340.50,90250.75,90321.00,516`) run 40 to 60
+/// bytes, so this errs towards reserving too much: a few spare records cost less than
+/// the reallocations and copies of growing past the reservation.
+const ESTIMATED_ROW_BYTES: u64 = 40;
+
+/// Bytes per NDJSON row assumed by `estimated_rows`. The keys and quoting add about 25
+/// bytes to a CSV row (`{"t":"20240912 100000","o":90300.25,...,"v":516}` runs 70 to 85
+/// bytes), with the same margin as `ESTIMATED_ROW_BYTES`.
+const ESTIMATED_NDJSON_ROW_BYTES: u64 = 65;
+
+/// Estimates the rows of an input of `input_bytes`, to reserve the record vector up front.
+fn estimated_rows(input_bytes: u64, input_format: &cli::InputFormat) -> usize {
+    let row_bytes = match input_format {
+        cli::InputFormat::Csv | cli::InputFormat::TarGz => ESTIMATED_ROW_BYTES,
+        cli::InputFormat::Ndjson => ESTIMATED_NDJSON_ROW_BYTES,
+    };
+    usize::try_from(input_bytes / row_bytes).unwrap_or(usize::MAX)
+}
+
+/// Parses input from any reader into processed records, as `read_input_file` does for a
+/// file. Tarball entries (`--input-format tar-gz`) are streamed through here as CSV.
+///
+/// With `size_hint`, the size of the input in bytes, the records are reserved up front
+/// (`estimated_rows`), so the hot loop pushes without reallocating on large files. Input
+/// filtered by `since_last` or `options.session` isn't reserved: only part of its rows are
+/// kept, often a small tail, and the estimate would reserve for all of them.
+///
+/// # Errors
+/// * If reading fails.
+/// * If `process_csv_records` or `process_ndjson_records` fails.
+fn read_input<R: std::io::Read>(
+    file: R,
+    size_hint: Option<u64>,
+    options: &ConversionOptions,
+    since_last: Option<u64>,
+) -> anyhow::Result<(Vec<ProcessedRecord>, SkippedRows)> {
+    let input: Box<dyn std::io::BufRead + '_> = match options.encoding {
+        Some(encoding) => Box::new(std::io::BufReader::with_capacity(
+            options.io_buffer_size,
+            file_processing::TranscodingReader::new(file, encoding),
+        )),
+        None => {
+            let mut input = std::io::BufReader::with_capacity(options.io_buffer_size, file);
+            skip_utf8_bom(&mut input)?;
+            Box::new(input)
+        }
+    };
+    // Ticks are aggregated into far fewer bars, so only unfiltered bar input is reserved.
+    let mut raw_data = match (&options.tick_bars, size_hint) {
+        (None, Some(size)) if since_last.is_none() && options.session.is_none() => {
+            Vec::with_capacity(estimated_rows(size, &options.input_format))
+        }
+        _ => Vec::new(),
+    };
+    let mut bad_rows = BadRows::new(options.collect_errors);
+
+    let mut skipped = match (&options.tick_bars, &options.input_format) {
+        (Some(tick_bars), input_format) => {
+            let (ticks, skipped) = match input_format {
+                cli::InputFormat::Csv | cli::InputFormat::TarGz => {
+                    let mut reader = options.csv_quoting.reader_builder().from_reader(input);
+                    parse_csv_ticks(&mut reader, options, &mut bad_rows)?
+                }
+                cli::InputFormat::Ndjson => {
+                    parse_ndjson_ticks(input, options, &mut bad_rows)?
+                }
+            };
+            raw_data = ticks_to_bars(&ticks, tick_bars).iter().map(ProcessedRecord::from).collect();
+            tracing::info!("🧮 Aggregated {} ticks into {} bars", ticks.len(), raw_data.len());
+            skipped
+        }
+        (None, cli::InputFormat::Csv | cli::InputFormat::TarGz) => {
+            let mut reader = options.csv_quoting.reader_builder().from_reader(input);
+            process_csv_records(&mut reader, &mut raw_data, options, since_last, &mut bad_rows)?
+        }
+        (None, cli::InputFormat::Ndjson) => {
+            process_ndjson_records(input, &mut raw_data, options, since_last, &mut bad_rows)?
+        }
+    };
+    skipped.bad_rows = bad_rows.into_rows();
+    if let Some(fix_crossed) = &options.fix_crossed {
+        let (repaired, dropped) = fix_crossed_bars(&mut raw_data, fix_crossed);
+        skipped.repaired_crossed += repaired;
+        skipped.crossed += dropped;
+    }
+    if let Some(adjustments) = &options.adjustments {
+        apply_adjustments(&mut raw_data, adjustments)?;
+    }
+
+    anyhow::Ok((raw_data, skipped))
+}
+
+/// Converts one input file to resampled bars in memory, without writing a `.bin` or
+/// `.idx` (`--stdout-csv`).
+///
+/// The file is parsed exactly as for a conversion (`read_input_file`), then the records are
+/// resampled with `resample::resample_many`, so rows must be in time order as they must
+/// for the index.
+///
+/// # Arguments
+/// * `input_path` - The CSV / NDJSON file.
+/// * `options` - Conversion options; only the parsing ones apply.
+/// * `timeframe` - Timeframe to resample to.
+/// * `bar_timestamp` - Which timestamp each bar carries (bucket start, first or last source bar).
+/// * `bucket_edge` - Whether a bar exactly on a bucket boundary starts a bucket (left) or ends one (right).
+/// * `bucket_anchor` - Offset in seconds of intraday bucket boundaries (0 aligns them to the epoch).
+/// * `unique_timestamps` - Also count the distinct source timestamps of every bar
+///   (`--unique-timestamps`, see `resample::unique_timestamps`).
+///
+/// # Returns
+/// * `anyhow::Result<(Vec<OHLCVBar>, Option<Vec<u64>>)>` - The bars and, if asked for, their
+///   distinct timestamp counts (see `write_bars_csv`).
+///
+/// # Errors
+/// * If the input can't be read or parsed.
+pub fn resample_input_file<P: AsRef<std::path::Path>>(
+    input_path: P,
+    options: &ConversionOptions,
+    timeframe: resample::Timeframe,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+    unique_timestamps: bool,
+) -> anyhow::Result<(Vec<resample::OHLCVBar>, Option<Vec<u64>>)> {
+    if let Some(max_size) = options.max_file_size {
+        file_processing::check_file_size(input_path.as_ref(), max_size)?;
+    }
+    let (raw_data, skipped) = read_input_file(input_path.as_ref(), options, None)?;
+    if let Some(first) = skipped.bad_rows.first() {
+        tracing::warn!(
+            "⚠️ Skipped {} bad row(s), the first at line {}: {}",
+            skipped.bad_rows.len(),
+            first.line,
+            first.reason
+        );
+    }
+    let bars = resample_records(&raw_data, timeframe, bar_timestamp, bucket_edge, bucket_anchor);
+    let unique = unique_timestamps
+        .then(|| resample::unique_timestamps(raw_data.iter().map(|r| r.timestamp), timeframe, bucket_edge, bucket_anchor));
+    anyhow::Ok((bars, unique))
+}
+
+/// Resamples parsed records, in time order, to one timeframe.
+fn resample_records(
+    records: &[ProcessedRecord],
+    timeframe: resample::Timeframe,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> Vec<resample::OHLCVBar> {
+    let bars = records.iter().map(|r| resample::OHLCVBar {
+        timestamp: r.timestamp,
+        open: r.open,
+        high: r.high,
+        low: r.low,
+        close: r.close,
+        volume: r.vol,
+        count: 1,
+    });
+    resample::resample_many(bars, &[timeframe], bar_timestamp, bucket_edge, bucket_anchor)
+        .pop()
+        .unwrap_or_default()
+}
+
+/// Writes bars as CSV: a `timestamp,open,high,low,close,volume` header, then one row per
+/// bar with the Unix timestamp in seconds.
+///
+/// With `unique_timestamps` (aligned with `bars`), a `unique_timestamps` column follows
+/// the volume.
+///
+/// `out` is flushed before returning, so a closed pipe surfaces as an error here.
+pub fn write_bars_csv<W: std::io::Write>(bars: &[resample::OHLCVBar], unique_timestamps: Option<&[u64]>, out: W) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    let mut header = vec!["timestamp", "open", "high", "low", "close", "volume"];
+    if unique_timestamps.is_some() {
+        header.push("unique_timestamps");
+    }
+    writer.write_record(&header)?;
+    for (i, bar) in bars.iter().enumerate() {
+        let mut row = vec![
+            bar.timestamp.to_string(),
+            bar.open.to_string(),
+            bar.high.to_string(),
+            bar.low.to_string(),
+            bar.close.to_string(),
+            bar.volume.to_string(),
+        ];
+        if let Some(unique) = unique_timestamps {
+            row.push(unique.get(i).map(u64::to_string).unwrap_or_default());
+        }
+        writer.write_record(&row)?;
+    }
+    writer.flush().map_err(|e| anyhow::anyhow!("Failed to write CSV: {}", e))
+}
+
+/// Whether a bar's prices are inconsistent: `high < low`, or open or close outside `[low, high]`.
+///
+/// Bars with a NaN price are never considered crossed, since no ordering applies to them.
+fn is_crossed(record: &ProcessedRecord) -> bool {
+    let prices = [record.open, record.high, record.low, record.close];
+    if prices.iter().any(|p| p.is_nan()) {
+        return false;
+    }
+    record.high < record.low
+        || [record.open, record.close].iter().any(|&p| p < record.low || p > record.high)
+}
+
+/// Repairs or drops crossed bars (see `is_crossed`) according to `fix_crossed`.
+///
+/// * `Swap` - high and low become the highest and lowest of the four prices, so a swapped
+///   high/low is put back in order and open/close are kept as they are.
+/// * `Clamp` - high and low are put in order and open/close are clamped into `[low, high]`.
+/// * `Drop` - the bar is removed.
+///
+/// Consistent bars are left untouched.
+///
+/// # Arguments
+/// * `records` - Parsed records, repaired in place.
+/// * `fix_crossed` - Repair mode.
+///
+/// # Returns
+/// * `(usize, usize)` - Number of bars repaired and number of bars dropped.
+fn fix_crossed_bars(records: &mut Vec<ProcessedRecord>, fix_crossed: &cli::FixCrossed) -> (usize, usize) {
+    let before = records.len();
+    let mut repaired = 0;
+    records.retain_mut(|record| {
+        if !is_crossed(record) {
+            return true;
+        }
+        match fix_crossed {
+            cli::FixCrossed::Swap => {
+                let prices = [record.open, record.high, record.low, record.close];
+                record.high = prices.iter().copied().fold(f64::MIN, f64::max);
+                record.low = prices.iter().copied().fold(f64::MAX, f64::min);
+            }
+            cli::FixCrossed::Clamp => {
+                let (low, high) = if record.low <= record.high { (record.low, record.high) } else { (record.high, record.low) };
+                record.low = low;
+                record.high = high;
+                record.open = record.open.clamp(low, high);
+                record.close = record.close.clamp(low, high);
+            }
+            cli::FixCrossed::Drop => return false,
+        }
+        repaired += 1;
+        true
+    });
+    (repaired, before - records.len())
+}
+
+/// Stores the split/dividend adjusted close of every record (`--adjustments`).
+///
+/// See `adjustments::Adjustments` for the convention. Every record gets an `adj_close`,
+/// including those after the latest corporate action (where it equals the close), so the
+/// column is either complete or absent.
+///
+/// # Errors
+/// * If a timestamp can't be converted to a date.
+fn apply_adjustments(records: &mut [ProcessedRecord], adjustments: &adjustments::Adjustments) -> anyhow::Result<()> {
+    for record in records {
+        record.adj_close = Some(adjustments.adjusted_close(record.timestamp, record.close)?);
+    }
+    anyhow::Ok(())
+}
+
+/// UTF-8 byte-order mark written at the start of files by Excel and other Windows tools.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Skips a leading UTF-8 byte-order mark, if there is one.
+///
+/// Without this the first CSV header reads as `\u{feff}<DATE>` and matches no field.
+///
+/// # Arguments
+/// * `reader` - Input positioned at the start of the file.
+///
+/// # Returns
+/// * `std::io::Result<()>` - Success or an error if reading fails.
+fn skip_utf8_bom<R: std::io::BufRead>(reader: &mut R) -> std::io::Result<()> {
+    if reader.fill_buf()?.starts_with(UTF8_BOM) {
+        reader.consume(UTF8_BOM.len());
+    }
+    Ok(())
+}
+
+/// Returns the timestamp of the latest bar recorded in the `.idx` next to `output_path`.
+///
+/// # Arguments
+/// * `output_path` - Path to an existing .bin file.
+///
+/// # Returns
+/// * `anyhow::Result<u64>` - The latest timestamp.
+///
+/// # Errors
+/// * If the `.idx` is missing or unreadable, or indexes no bars.
+fn last_converted_timestamp(output_path: &std::path::Path) -> anyhow::Result<u64> {
+    let idx_path = output_path.with_extension("idx");
+    let full_index = utils::load_full_index(&idx_path)
+        .map_err(|e| anyhow::anyhow!("--since-last needs an existing index at {}: {}", idx_path.display(), e))?;
+    full_index
+        .time_index
+        .iter()
+        .map(|e| e.timestamp)
+        .max()
+        .ok_or_else(|| anyhow::anyhow!("--since-last: {} indexes no bars", idx_path.display()))
+}
+
+/// Builds the FlatBuffer binary representation of `records` in AOS or SOA format.
+///
+/// # Arguments
+/// * `records` - Records to serialize, in storage order.
+/// * `storage_format` - The desired FlatBuffer storage format (AOS or SOA).
+/// * `schema_version` - Schema version stamped into the root table.
+/// * `has_volume` - False for volumeless data: volumes are not stored and the root table's
+///   `has_volume` flag is cleared.
+///
+/// # Returns
+/// * `Vec<u8>` - The finished FlatBuffer bytes.
+pub fn build_flatbuffer(records: &[ProcessedRecord], storage_format: cli::StorageFormat, schema_version: u32, has_volume: bool) -> Vec<u8> {
+    build_flatbuffer_from(records.iter(), storage_format, schema_version, has_volume)
+}
+
+/// Like `build_flatbuffer`, but serializes the records an iterator yields, so records can
+/// be written in another order (`in_storage_order`) without copying them first.
+fn build_flatbuffer_from<'a>(
+    records: impl ExactSizeIterator<Item = &'a ProcessedRecord>,
+    storage_format: cli::StorageFormat,
+    schema_version: u32,
+    has_volume: bool,
+) -> Vec<u8> {
+    match storage_format {
+        cli::StorageFormat::Aos => {
+            // --- AOS Logic ---
+            let mut builder = flatbuffers::FlatBufferBuilder::with_capacity(records.len() * AOS_BYTES_PER_BAR + BUFFER_OVERHEAD);
+            let mut ohlcv_offsets = Vec::with_capacity(records.len());
+            for record in records {
+                let ohlcv_args = ohlcv_generated::OHLCVArgs {
+                    timestamp: record.timestamp,
+                    open: record.open,
+                    high: record.high,
+                    low: record.low,
+                    close: record.close,
+                    // A zero volume equals the field default, so FlatBuffers doesn't store it.
+                    volume: if has_volume { record.vol } else { 0 },
+                    adj_close: record.adj_close,
+                };
+                let ohlcv = ohlcv_generated::OHLCV::create(&mut builder, &ohlcv_args);
+                ohlcv_offsets.push(ohlcv);
+            }
+
+            let items = builder.create_vector(&ohlcv_offsets);
+            let ohlcv_list = {
+                let mut list_builder = ohlcv_generated::OHLCVListBuilder::new(&mut builder);
+                list_builder.add_items(items);
+                list_builder.add_version(schema_version);
+                list_builder.add_has_volume(has_volume);
+                list_builder.finish()
+            };
+            builder.finish(ohlcv_list, None);
+            builder.finished_data().to_vec()
+        }
+        cli::StorageFormat::Soa => {
+            // --- SOA Logic ---
+            let mut soa_builder = SOABuilder::with_capacity(records.len());
+            for record in records {
+                soa_builder.add_ohlcv(record);
+            }
+            soa_builder.finish_buffer(schema_version, has_volume)
+        }
+    }
+}
+
+/// Yields the records at `positions` of the storage order `order` gives `records` (sorted
+/// ascending): themselves, or mirrored for newest-first output. Nothing is copied.
+fn in_storage_order<'a>(
+    records: &'a [ProcessedRecord],
+    order: &cli::BarOrder,
+    positions: std::ops::Range<usize>,
+) -> impl ExactSizeIterator<Item = &'a ProcessedRecord> + Clone + 'a {
+    let descending = matches!(order, cli::BarOrder::Desc);
+    let last = records.len().saturating_sub(1);
+    positions.map(move |position| &records[if descending { last - position } else { position }])
+}
+
+/// Writes records to a FlatBuffer binary file (.bin) in AOS or SOA format and generates index data.
+///
+/// This function orchestrates the conversion of already-parsed records:
+/// 1. Based on `storage_format`, creates the FlatBuffer data (either AOS or SOA).
+/// 2. Writes the binary FlatBuffer data to the output file.
+/// 3. Builds the index data for later use.
+///
+/// With `segment_size` the SOA output is written as consecutive segments of at most that
+/// many bars, each a self-contained `OHLCVListSOA`, built and written one at a time so the
+/// builder never holds more than one segment. Their byte offsets are returned in
+/// `ProcessedData::segment_offsets` for the `.idx`; every index position still counts bars
+/// from the start of the file.
+///
+/// # Arguments
+/// * `records` - Parsed records to write.
+/// * `output_path` - Path for the output .bin file.
+/// * `options` - Supplies the storage format, the schema version stamped into the root
+///   table, whether volumes are stored (see `build_flatbuffer`), the `timeframe_index`
+///   entries to generate (see `ConversionOptions::indexed_timeframes`), the order, the
+///   segment size and the capacity of the write buffer segments go through. A single
+///   buffer is written with one `write_all`, which a `BufWriter` would pass straight
+///   through. With `self_verify` the buffer is checked against the other format
+///   (`verify_against_other_format`), and with `verify_output` parsed back
+///   (`verify_output_buffer`), before it is written; the time spent counts as building.
+/// * `timings` - Accumulates the time spent building, writing and indexing.
+///
+/// # Returns
+/// * `anyhow::Result<ProcessedData>` - The generated index data or an error.
+///
+/// # Errors
+/// * If `options.soa_segment_size` is set for AOS output.
+/// * If file I/O fails.
+/// * If `self_verify` is set and the two formats disagree; nothing is written then.
+/// * If `verify_output` is set and a buffer doesn't parse back; nothing is written then.
+/// * If `build_index` fails.
+fn save_flatbuffer<P: AsRef<std::path::Path>>(
+    records: &[ProcessedRecord],
+    output_path: P,
+    options: &ConversionOptions,
+    timings: &mut ConversionTimings,
+) -> anyhow::Result<ProcessedData> {
+    let (storage_format, schema_version, has_volume) = (&options.storage_format, options.schema_version, !options.no_volume);
+    let segment_offsets = match (storage_format, options.soa_segment_size) {
+        (_, None) => {
+            let start = std::time::Instant::now();
+            let stored = in_storage_order(records, &options.order, 0..records.len());
+            let flatbuffer_data = build_flatbuffer_from(stored.clone(), storage_format.clone(), schema_version, has_volume);
+            if options.self_verify {
+                verify_against_other_format(stored, &flatbuffer_data, storage_format, schema_version, has_volume)?;
+            }
+            if options.verify_output {
+                verify_output_buffer(&flatbuffer_data, storage_format, records.len())?;
+            }
+            timings.build_flatbuffer += start.elapsed();
+
+            // Write the generated FlatBuffer binary data to the output file
+            let start = std::time::Instant::now();
+            file_processing::write_atomic(output_path.as_ref(), &flatbuffer_data)?;
+            timings.write_flatbuffer += start.elapsed();
+            None
+        }
+        (cli::StorageFormat::Soa, Some(segment_size)) => {
+            // An empty file still gets one (empty) segment, so it reads like any other.
+            let segments: Vec<std::ops::Range<usize>> = (0..records.len().max(1))
+                .step_by(segment_size)
+                .map(|start| start..(start + segment_size).min(records.len()))
+                .collect();
+            let mut offsets = Vec::with_capacity(segments.len());
+            file_processing::write_atomic_with(output_path.as_ref(), |file| {
+                let mut file = std::io::BufWriter::with_capacity(options.io_buffer_size, file);
+                let mut written = 0u64;
+                for positions in segments {
+                    let start = std::time::Instant::now();
+                    let segment = in_storage_order(records, &options.order, positions);
+                    let segment_data = build_flatbuffer_from(segment.clone(), cli::StorageFormat::Soa, schema_version, has_volume);
+                    if options.self_verify {
+                        verify_against_other_format(segment.clone(), &segment_data, &cli::StorageFormat::Soa, schema_version, has_volume)?;
+                    }
+                    if options.verify_output {
+                        verify_output_buffer(&segment_data, &cli::StorageFormat::Soa, segment.len())?;
+                    }
+                    timings.build_flatbuffer += start.elapsed();
+
+                    // Finished buffers are padded to their largest alignment (8 bytes), so
+                    // every segment starts aligned.
+                    let start = std::time::Instant::now();
+                    std::io::Write::write_all(&mut file, &segment_data)
+                        .map_err(|e| file_processing::io_error(e, "write", output_path.as_ref()))?;
+                    timings.write_flatbuffer += start.elapsed();
+                    offsets.push(written);
+                    written += segment_data.len() as u64;
+                }
+                let start = std::time::Instant::now();
+                std::io::Write::flush(&mut file).map_err(|e| file_processing::io_error(e, "write", output_path.as_ref()))?;
+                timings.write_flatbuffer += start.elapsed();
+                anyhow::Ok(())
+            })?;
+            Some(offsets)
+        }
+        (cli::StorageFormat::Aos, Some(_)) => {
+            return Err(anyhow::anyhow!("--soa-segment-size only applies to SOA output"));
+        }
+    };
+
+    let start = std::time::Instant::now();
+    let mut processed_data = index_records(records, options.indexed_timeframes())?;
+    if let cli::BarOrder::Desc = options.order {
+        point_into_descending_storage(&mut processed_data, records.len());
+    }
+    processed_data.segment_offsets = segment_offsets;
+    timings.build_index += start.elapsed();
+    anyhow::Ok(processed_data)
+}
+
+/// Remaps indices built over ascending records to storage written newest-first.
+///
+/// The indices themselves stay in ascending time, so seeking and resampling through them
+/// work unchanged: only the stored positions are mirrored. Every `daily_index` entry keeps
+/// the positions of the day's first and last bar in time, so `start_index > end_index`.
+fn point_into_descending_storage(processed_data: &mut ProcessedData, len: usize) {
+    let mirror = |position: u64| len as u64 - 1 - position;
+    for entry in &mut processed_data.time_index {
+        entry.index = mirror(entry.index);
+    }
+    for entry in &mut processed_data.daily_index {
+        entry.start_index = mirror(entry.start_index);
+        entry.end_index = mirror(entry.end_index);
+    }
+}
+
+/// Builds `records` in the other storage format and checks that both buffers read back
+/// bar-for-bar identical.
+///
+/// This guards the AOS and SOA builders against drifting apart (e.g. a column written in
+/// the wrong order). It doubles the build cost, so it only runs with `--self-verify`.
+///
+/// # Arguments
+/// * `records` - Records `written` was built from.
+/// * `written` - The buffer about to be written, in `storage_format`.
+/// * `storage_format` - Layout of `written`.
+/// * `schema_version` - Schema version stamped into the root table.
+/// * `has_volume` - Whether volumes are stored (see `build_flatbuffer`).
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success if every bar matches.
+///
+/// # Errors
+/// * If either buffer can't be read back, the bar counts differ, or a bar differs; the
+///   first mismatching bar is named.
+fn verify_against_other_format<'a>(
+    records: impl ExactSizeIterator<Item = &'a ProcessedRecord>,
+    written: &[u8],
+    storage_format: &cli::StorageFormat,
+    schema_version: u32,
+    has_volume: bool,
+) -> anyhow::Result<()> {
+    let other_format = match storage_format {
+        cli::StorageFormat::Aos => cli::StorageFormat::Soa,
+        cli::StorageFormat::Soa => cli::StorageFormat::Aos,
+    };
+    let other = build_flatbuffer_from(records, other_format.clone(), schema_version, has_volume);
+    let written_bars = read_flatbuffers::iter_bars(written, storage_format)?;
+    let other_bars = read_flatbuffers::iter_bars(&other, &other_format)?;
+
+    if written_bars.len() != other_bars.len() {
+        return Err(anyhow::anyhow!(
+            "Self-verify failed: {:?} has {} bars but {:?} has {}",
+            storage_format,
+            written_bars.len(),
+            other_format,
+            other_bars.len()
+        ));
+    }
+    let fields = |b: &resample::OHLCVBar| (b.timestamp, b.open, b.high, b.low, b.close, b.volume);
+    for (position, (a, b)) in written_bars.zip(other_bars).enumerate() {
+        if fields(&a) != fields(&b) {
+            return Err(anyhow::anyhow!(
+                "Self-verify failed: bar {} differs between {:?} {:?} and {:?} {:?}",
+                position,
+                storage_format,
+                a,
+                other_format,
+                b
+            ));
+        }
+    }
+    anyhow::Ok(())
+}
+
+/// Parses a just-built buffer the way a reader would and checks it holds `bars` bars.
+///
+/// This runs on the in-memory buffer before it is written (`--verify-output`), so a `.bin`
+/// that wouldn't load is never written next to a valid `.idx`. Unlike `--self-verify` it
+/// doesn't build anything, so it's cheap enough to leave on.
+///
+/// # Arguments
+/// * `written` - The buffer about to be written, in `storage_format`.
+/// * `storage_format` - Layout of `written`.
+/// * `bars` - Number of records `written` was built from.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success if the buffer parses and holds `bars` bars.
+///
+/// # Errors
+/// * If the root table fails FlatBuffer verification or has an unsupported schema version.
+/// * If the SOA columns have mismatched lengths or the bar count differs from `bars`.
+fn verify_output_buffer(written: &[u8], storage_format: &cli::StorageFormat, bars: usize) -> anyhow::Result<()> {
+    let parsed = match storage_format {
+        cli::StorageFormat::Aos => {
+            let ohlcv_list = ohlcv_generated::root_as_ohlcvlist(written)
+                .map_err(|e| anyhow::anyhow!("Output verification failed: buffer doesn't parse as OHLCVList: {}", e))?;
+            utils::check_schema_version(ohlcv_list.version())?;
+            ohlcv_list.items().map_or(0, |items| items.len())
+        }
+        cli::StorageFormat::Soa => {
+            let ohlcv_list_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(written)
+                .map_err(|e| anyhow::anyhow!("Output verification failed: buffer doesn't parse as OHLCVListSOA: {}", e))?;
+            utils::check_schema_version(ohlcv_list_soa.version())?;
+            match ohlcv_list_soa.data() {
+                Some(data_soa) => SoaColumns::new(data_soa)
+                    .map_err(|e| anyhow::anyhow!("Output verification failed: {}", e))?
+                    .len(),
+                None => 0,
+            }
+        }
+    };
+    if parsed != bars {
+        return Err(anyhow::anyhow!(
+            "Output verification failed: {:?} buffer holds {} bars but {} were written",
+            storage_format,
+            parsed,
+            bars
+        ));
+    }
+    anyhow::Ok(())
+}
+
+/// Writes already-materialized bars to a `.bin` file and its companion `.idx`.
+///
+/// Used by paths that don't start from CSV, such as `merge`. The bars are written in
+/// ascending order with every other conversion option at its default, and the indices
+/// are rebuilt from scratch for `bars`.
+///
+/// # Arguments
+/// * `bars` - Bars in storage order.
+/// * `output_path` - Path for the output .bin file.
+/// * `storage_format` - The desired FlatBuffer storage format (AOS or SOA).
+/// * `has_volume` - Whether to store volumes (`false` writes a file like `--no-volume`).
+/// * `symbol` - Instrument to record in the `.idx`, like `--symbol`.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success or an error if writing fails.
+pub fn write_bars<P: AsRef<std::path::Path>>(
+    bars: &[resample::OHLCVBar],
+    output_path: P,
+    storage_format: cli::StorageFormat,
+    has_volume: bool,
+    symbol: Option<String>,
+) -> anyhow::Result<()> {
+    let records: Vec<ProcessedRecord> = bars.iter().map(ProcessedRecord::from).collect();
+    let options = ConversionOptions { storage_format, no_volume: !has_volume, symbol, ..ConversionOptions::default() };
+    let mut timings = ConversionTimings::default();
+    let processed_data = save_flatbuffer(&records, output_path.as_ref(), &options, &mut timings)?;
+    save_index(&processed_data, &records, &options, output_path.as_ref())?;
+
+    anyhow::Ok(())
+}
+
+/// Splits records into contiguous buckets according to `split_mode`.
+///
+/// Bucket keys come from the bar's UTC calendar date (`2023-01-15`, `2023-01` or `2023`),
+/// so a bar exactly on a boundary (e.g. `2023-02-01 00:00:00`) always opens the later bucket.
+///
+/// # Arguments
+/// * `records` - Records in storage order.
+/// * `split_mode` - Bucket granularity.
+///
+/// # Returns
+/// * `anyhow::Result<Vec<(String, std::ops::Range<usize>)>>` - Bucket keys with their record ranges.
+///
+/// # Errors
+/// * If a bucket appears in non-contiguous ranges (the input isn't sorted by time).
+fn split_records(
+    records: &[ProcessedRecord],
+    split_mode: &cli::SplitMode,
+) -> anyhow::Result<Vec<(String, std::ops::Range<usize>)>> {
+    let format = match split_mode {
+        cli::SplitMode::Daily => "%Y-%m-%d",
+        cli::SplitMode::Monthly => "%Y-%m",
+        cli::SplitMode::Yearly => "%Y",
+    };
+
+    let mut buckets: Vec<(String, std::ops::Range<usize>)> = Vec::new();
+    let mut seen_keys: std::collections::HashSet<String> = std::collections::HashSet::new();
+    for (position, record) in records.iter().enumerate() {
+        let key = chrono::DateTime::from_timestamp(record.timestamp as i64, 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid timestamp: {}", record.timestamp))?
+            .format(format)
+            .to_string();
+
+        match buckets.last_mut() {
+            Some((last_key, range)) if *last_key == key => range.end = position + 1,
+            _ => {
+                if !seen_keys.insert(key.clone()) {
+                    return Err(anyhow::anyhow!("Bucket {} appears in non-contiguous ranges; input must be sorted by time", key));
+                }
+                buckets.push((key, position..position + 1));
+            }
+        }
+    }
+
+    anyhow::Ok(buckets)
+}
+
+/// Derives the output path of a split bucket, e.g. `data.aos.bin` → `data.2023-01.aos.bin`.
+fn bucket_output_path(output_path: &std::path::Path, bucket: &str, suffix: &str) -> std::path::PathBuf {
+    let file_name = output_path.file_name().and_then(|n| n.to_str()).unwrap_or("output");
+    let split_name = match file_name.strip_suffix(suffix) {
+        Some(stem) => format!("{}.{}{}", stem, bucket, suffix),
+        None => format!("{}.{}{}", file_name, bucket, suffix),
+    };
+    output_path.with_file_name(split_name)
+}
+
+/// A single row of the split manifest written next to split outputs.
+#[derive(Debug, serde::Serialize)]
+struct SplitManifestEntry {
+    bucket: String,
+    file: String,
+    bars: usize,
+    first_timestamp: u64,
+    last_timestamp: u64,
+}
+
+/// Serializes and saves index data to a companion .idx file.
+///
+/// This function takes the generated time, daily, and timeframe indices,
+/// packages them into a `FullIndex` struct, serializes it using `bincode` behind an
+/// `index::INDEX_MAGIC` header and version byte, and writes it to a file with the same name as the output but with a `.idx` extension.
+///
+/// The .idx file enables fast random access and resampling without loading the full dataset.
+///
+/// # Arguments
+/// * `processed_data` - The time, daily and timeframe indices, and the SOA segment offsets
+///   if the `.bin` was written in segments.
+/// * `records` - The records written, for `source_line_index` (see `source_line_index`).
+/// * `options` - Supplies the symbol, whether `timeframe_positions` are precomputed and
+///   whether the compact layout is written (`index::COMPACT_INDEX_MAGIC` header, varint
+///   `time_index`).
+/// * `output_path` - Path to the main .bin file (used to derive .idx path).
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success or an error if writing fails.
+///
+/// # Errors
+/// * If serialization or file I/O fails.
+fn save_index<P: AsRef<std::path::Path>>(
+    processed_data: &ProcessedData,
+    records: &[ProcessedRecord],
+    options: &ConversionOptions,
+    output_path: P,
+) -> anyhow::Result<()> {
+    let idx_path = std::path::Path::new(output_path.as_ref()).with_extension("idx");  
+    let full_index = index::FullIndex {
+        time_index: processed_data.time_index.clone(),
+        daily_index: processed_data.daily_index.clone(),
+        timeframe_index: processed_data.timeframe_index.clone(),
+        source_line_index: source_line_index(records, options),
+        symbol: options.symbol.clone(),
+        segment_offsets: processed_data.segment_offsets.clone(),
+        timeframe_positions: timeframe_positions(processed_data, options),
+    };
+
+    let data = if options.compact_index {
+        let mut data = index::COMPACT_INDEX_MAGIC.to_vec();
+        data.push(index::COMPACT_INDEX_VERSION);
+        data.extend(bincode::serialize(&index::CompactFullIndex::from(&full_index))?);
+        data
+    } else {
+        let mut data = index::INDEX_MAGIC.to_vec();
+        data.push(index::INDEX_VERSION);
+        data.extend(bincode::serialize(&full_index)?);
+        data
+    };
+    file_processing::write_atomic(idx_path, &data)?;
+
+    anyhow::Ok(())
+}
+
+/// Number of preceding bars whose median volume a bar is compared against.
+const VOLUME_SPIKE_WINDOW: usize = 100;
+
+/// Bars needed in the window before spikes are reported, so the first bars of a file
+/// aren't judged against a median of one or two values.
+const VOLUME_SPIKE_MIN_SAMPLES: usize = 20;
+
+/// Finds bars whose volume exceeds `multiple` times the median of the preceding bars.
+///
+/// The median is taken over the last `VOLUME_SPIKE_WINDOW` bars, kept in a bounded sorted
+/// window, so memory stays constant however large the file is. Checks start once
+/// `VOLUME_SPIKE_MIN_SAMPLES` bars have been seen, and are skipped while the median is 0
+/// (a multiple of nothing isn't meaningful). Spikes stay in the window, so a lasting
+/// change in activity stops being flagged once it becomes the norm. Records are only read.
+///
+/// # Arguments
+/// * `records` - Records in storage order.
+/// * `multiple` - How many times the median a volume must exceed to be flagged.
+///
+/// # Returns
+/// * `Vec<VolumeSpike>` - Flagged bars in storage order.
+pub fn find_volume_spikes(records: &[ProcessedRecord], multiple: u64) -> Vec<VolumeSpike> {
+    let mut recent = std::collections::VecDeque::with_capacity(VOLUME_SPIKE_WINDOW);
+    let mut sorted: Vec<u64> = Vec::with_capacity(VOLUME_SPIKE_WINDOW);
+    let mut spikes = Vec::new();
+
+    for record in records {
+        if sorted.len() >= VOLUME_SPIKE_MIN_SAMPLES {
+            let median = sorted[sorted.len() / 2];
+            if median > 0 && record.vol > median.saturating_mul(multiple) {
+                spikes.push(VolumeSpike {
+                    timestamp: record.timestamp,
+                    source_line: record.source_line,
+                    volume: record.vol,
+                    median,
+                });
+            }
+        }
+
+        if recent.len() == VOLUME_SPIKE_WINDOW
+            && let Some(oldest) = recent.pop_front()
+            && let Ok(pos) = sorted.binary_search(&oldest)
+        {
+            sorted.remove(pos);
+        }
+        recent.push_back(record.vol);
+        let pos = sorted.partition_point(|&v| v < record.vol);
+        sorted.insert(pos, record.vol);
+    }
+
+    spikes
+}
+
+/// Computes `FullIndex::timeframe_positions` of `processed_data` with `--precompute-tf-positions`.
+///
+/// `time_index` is in ascending time for either `--order`, so the positions are too.
+fn timeframe_positions(processed_data: &ProcessedData, options: &ConversionOptions) -> Option<std::collections::BTreeMap<String, Vec<u64>>> {
+    options
+        .precompute_tf_positions
+        .then(|| index::timeframe_positions(&processed_data.time_index, &processed_data.timeframe_index))
+}
+
+/// Collects the input line numbers of `records` for `FullIndex::source_line_index`.
+///
+/// Lines are listed in storage order, so they're reversed for `--order desc`.
+/// Returns `None` unless tracking is enabled and every record knows its line.
+fn source_line_index(records: &[ProcessedRecord], options: &ConversionOptions) -> Option<Vec<u64>> {
+    if !options.track_source_lines {
+        return None;
+    }
+    let mut lines: Vec<u64> = records.iter().map(|r| r.source_line).collect::<Option<_>>()?;
+    if let cli::BarOrder::Desc = options.order {
+        lines.reverse();
+    }
+    Some(lines)
+}
+
+/// Public entry point to convert a CSV file to FlatBuffer format with indexing.
+///
+/// This function provides a high-level interface for the conversion process.
+/// It reads the input with `read_input_file`, then delegates to `save_flatbuffer` for creating
+/// the FlatBuffer (based on format) and `save_index` for persisting the generated indices.
+/// It's designed to be called from `main.rs` or other modules needing to trigger the conversion.
+///
+/// When `options.split` is set, one `.bin`/`.idx` pair with self-contained indices is written
+/// per time bucket (e.g. `data.2023-01.aos.bin`), together with a `data.aos.manifest.csv`
+/// listing every split.
+///
+/// When `options.since_last` is set, only rows newer than the latest bar in `output_path`'s
+/// existing `.idx` are converted, into `data.delta.aos.bin`; nothing is written if there are none.
+///
+/// With `options.bundle` the `.bin` and `.idx` are packed into a `data.aos.ohlcv` bundle
+/// once written, and removed (`bundle::bundle_files`).
+///
+/// # Arguments
+/// * `input_dir_path` - Path to the input CSV file.
+/// * `output_path` - Path for the output .bin file (e.g., filename.aos.bin or filename.soa.bin).
+/// * `options` - Conversion options (storage format, trading calendar, split mode).
+///
+/// # Returns
+/// * `anyhow::Result<ConversionStats>` - Summary of the conversion or an error if conversion or saving fails.
+///
+/// # Errors
+/// * If the input is larger than `options.max_file_size`.
+/// * With `options.since_last`, if the existing `.idx` can't be read.
+/// * Propagates errors from `read_input_file`, `save_flatbuffer` or `save_index`.
+/// * If the daily index contains a date that can't be compared against the calendar.
+pub fn convert_csv_to_flatbuffer<P: AsRef<std::path::Path>>(input_dir_path: P, output_path: P, options: &ConversionOptions) -> anyhow::Result<ConversionStats> {
+    if let Some(max_size) = options.max_file_size {
+        file_processing::check_file_size(input_dir_path.as_ref(), max_size)?;
+    }
+    let since_last = if options.since_last {
+        Some(last_converted_timestamp(output_path.as_ref())?)
+    } else {
+        None
+    };
+    let start = std::time::Instant::now();
+    let (raw_data, skipped) = read_input_file(input_dir_path.as_ref(), options, since_last)?;
+    convert_records(raw_data, skipped, start.elapsed(), output_path.as_ref(), since_last, options)
+}
+
+/// Writes parsed records as `convert_csv_to_flatbuffer` does, once the input is read.
+///
+/// # Arguments
+/// * `raw_data` - Records parsed from the input, in file order.
+/// * `skipped` - Rows dropped while parsing.
+/// * `parse` - Time spent parsing, reported in the timings.
+/// * `output_path` - Path for the output .bin file.
+/// * `since_last` - Timestamp the existing output ends at, with `options.since_last`.
+/// * `options` - Conversion options.
+///
+/// # Errors
+/// * See `convert_csv_to_flatbuffer`.
+fn convert_records(
+    raw_data: Vec<ProcessedRecord>,
+    skipped: SkippedRows,
+    parse: std::time::Duration,
+    output_path: &std::path::Path,
+    since_last: Option<u64>,
+    options: &ConversionOptions,
+) -> anyhow::Result<ConversionStats> {
+    // The row loops check `cancel` only every `CANCEL_CHECK_ROWS` rows; this catches the
+    // rest before anything is written.
+    check_cancelled(options.cancel.as_deref())?;
+    let mut timings = ConversionTimings { parse, ..Default::default() };
+    if options.errors_csv {
+        save_bad_rows(&skipped.bad_rows, output_path)?;
+    }
+    let mut daily_index: Vec<index::DailyIndexEntry> = Vec::new();
+
+    // The new tail goes next to the existing output, e.g. `data.delta.aos.bin`.
+    let output_path = match since_last {
+        Some(_) if raw_data.is_empty() => {
+            return anyhow::Ok(ConversionStats { timings, skipped, ..Default::default() });
+        }
+        Some(_) => bucket_output_path(output_path, "delta", &options.output_file_suffix()),
+        None => output_path.to_path_buf(),
+    };
+
+    match &options.split {
+        None => {
+            let processed_data = save_flatbuffer(&raw_data, &output_path, options, &mut timings)?;
+            if !options.no_index {
+                let start = std::time::Instant::now();
+                save_index(&processed_data, &raw_data, options, &output_path)?;
+                timings.write_index += start.elapsed();
+                if options.bundle {
+                    let start = std::time::Instant::now();
+                    bundle::bundle_files(&output_path, true)?;
+                    timings.write_index += start.elapsed();
+                }
+            }
+            daily_index = processed_data.daily_index;
+        }
+        Some(split_mode) => {
+            let mut manifest = Vec::new();
+            for (bucket, range) in split_records(&raw_data, split_mode)? {
+                let records = &raw_data[range];
+                let bucket_path = bucket_output_path(&output_path, &bucket, &options.output_file_suffix());
+                let processed_data = save_flatbuffer(records, &bucket_path, options, &mut timings)?;
+                if !options.no_index {
+                    let start = std::time::Instant::now();
+                    save_index(&processed_data, records, options, &bucket_path)?;
+                    timings.write_index += start.elapsed();
+                }
+                daily_index.extend(processed_data.daily_index);
+
+                manifest.push(SplitManifestEntry {
+                    bucket,
+                    file: bucket_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+                    bars: records.len(),
+                    first_timestamp: records.first().map(|r| r.timestamp).unwrap_or_default(),
+                    last_timestamp: records.last().map(|r| r.timestamp).unwrap_or_default(),
+                });
+            }
+
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for entry in &manifest {
+                writer.serialize(entry)?;
+            }
+            let data = writer.into_inner().map_err(|e| anyhow::anyhow!("Failed to write manifest: {}", e))?;
+            file_processing::write_atomic(output_path.with_extension("manifest.csv"), &data)?;
+        }
+    }
+
+    let missing_days = match &options.calendar {
+        Some(calendar) => calendar.missing_days(&daily_index)?,
+        None => Vec::new(),
+    };
+
+    anyhow::Ok(ConversionStats {
+        bars: raw_data.len(),
+        missing_days,
+        last_timestamp: raw_data.iter().map(|r| r.timestamp).max(),
+        timings,
+        skipped,
+        volume_spikes: options.volume_spike_multiple.map_or_else(Vec::new, |m| find_volume_spikes(&raw_data, m)),
+    })
+}
+
+/// Extensions of the tarball entries converted by `convert_tarball`; others are skipped.
+const TARBALL_CSV_EXTENSIONS: [&str; 2] = ["txt", "csv"];
+
+/// Converts every CSV entry of a gzipped tarball (`--input-format tar-gz`) to its own `.bin`.
+///
+/// The tarball is streamed: it is decompressed as it is read and never extracted, and each
+/// entry goes straight through the CSV parser (`read_input`). Entries are converted one at a
+/// time, so memory holds the parsed records and FlatBuffer of a single entry, as when
+/// converting a file. Outputs are named after the entry paths: `2024-01/ES_20240115.txt`
+/// becomes `<output_dir>/2024-01/ES_20240115.aos.bin`, creating the subdirectory. Entries
+/// that aren't regular files ending in `.txt` or `.csv` are skipped.
+///
+/// An entry that fails (a bad row, an entry path escaping `output_dir`, a name yielding no
+/// symbol with `options.symbol_from_filename`) is reported through `on_entry` and the next
+/// entry is converted; only an unreadable tarball stops the conversion.
+///
+/// # Arguments
+/// * `tarball_path` - The `.tar.gz` file.
+/// * `output_dir` - Output directory the entry paths are resolved against.
+/// * `options` - Conversion options shared by every entry.
+/// * `on_entry` - Called after each CSV entry with its path in the tarball and the result.
+///
+/// # Returns
+/// * `anyhow::Result<usize>` - The number of CSV entries, converted or not.
+///
+/// # Errors
+/// * If the tarball can't be opened, or its gzip or tar stream is corrupt.
+pub fn convert_tarball<F>(
+    tarball_path: &std::path::Path,
+    output_dir: &std::path::Path,
+    options: &ConversionOptions,
+    mut on_entry: F,
+) -> anyhow::Result<usize>
+where
+    F: FnMut(&str, anyhow::Result<ConversionStats>),
+{
+    let file = std::fs::File::open(tarball_path).map_err(|e| file_processing::io_error(e, "open input", tarball_path))?;
+    let gzip = flate2::read::GzDecoder::new(std::io::BufReader::with_capacity(options.io_buffer_size, file));
+    let mut archive = tar::Archive::new(gzip);
+    let read_error = |e: std::io::Error| file_processing::io_error(e, "read tarball", tarball_path);
+
+    let mut entries = 0;
+    for entry in archive.entries().map_err(read_error)? {
+        let entry = entry.map_err(read_error)?;
+        let entry_path = entry.path().map_err(read_error)?.into_owned();
+        let is_csv = entry_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| TARBALL_CSV_EXTENSIONS.contains(&ext));
+        if !entry.header().entry_type().is_file() || !is_csv {
+            tracing::debug!("Skipping tarball entry {}", entry_path.display());
+            continue;
+        }
+        entries += 1;
+        let result = convert_tarball_entry(entry, &entry_path, output_dir, options);
+        on_entry(&entry_path.to_string_lossy(), result);
+    }
+    anyhow::Ok(entries)
+}
+
+/// Converts one CSV entry of `convert_tarball`, reading it to the end.
+fn convert_tarball_entry<R: std::io::Read>(
+    entry: tar::Entry<'_, R>,
+    entry_path: &std::path::Path,
+    output_dir: &std::path::Path,
+    options: &ConversionOptions,
+) -> anyhow::Result<ConversionStats> {
+    // Only plain names are joined to the output directory, so no entry writes outside it.
+    if !entry_path.components().all(|c| matches!(c, std::path::Component::Normal(_))) {
+        return Err(anyhow::anyhow!("Entry path leaves the output directory"));
+    }
+    if let Some(max_size) = options.max_file_size {
+        let size = entry.header().size()?;
+        if size > max_size {
+            return Err(anyhow::anyhow!(
+                "Skipped: entry is {} bytes, larger than --max-file-size ({} bytes)",
+                size,
+                max_size
+            ));
+        }
+    }
+    let file_stem = entry_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("output");
+    let entry_options;
+    let options = match &options.symbol_from_filename {
+        Some(symbol_from_filename) => {
+            entry_options = ConversionOptions { symbol: Some(symbol_from_filename.extract(file_stem)?), ..options.clone() };
+            &entry_options
+        }
+        None => options,
+    };
+
+    let output_path = output_dir.join(entry_path).with_file_name(format!("{}{}", file_stem, options.output_file_suffix()));
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| file_processing::io_error(e, "create directory", parent))?;
+    }
+    let since_last = if options.since_last {
+        Some(last_converted_timestamp(&output_path)?)
+    } else {
+        None
+    };
+    let start = std::time::Instant::now();
+    let size_hint = entry.header().size().ok();
+    let (raw_data, skipped) = read_input(entry, size_hint, options, since_last)?;
+    convert_records(raw_data, skipped, start.elapsed(), &output_path, since_last, options)
+}
+
+/// Writes the rows skipped with `--collect-errors` to a `.errors.csv` next to `output_path`,
+/// e.g. `data.aos.errors.csv`, with one `line,reason` row each.
+///
+/// The file is written even when there are no bad rows, so a stale report from an earlier
+/// run never outlives the data it describes.
+///
+/// # Errors
+/// * If the CSV can't be serialized or written.
+fn save_bad_rows(bad_rows: &[BadRow], output_path: &std::path::Path) -> anyhow::Result<()> {
+    // The header is written by hand so that an empty report still has one.
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+    writer.write_record(["line", "reason"])?;
+    for row in bad_rows {
+        writer.serialize(row)?;
+    }
+    let data = writer.into_inner().map_err(|e| anyhow::anyhow!("Failed to write error report: {}", e))?;
+    file_processing::write_atomic(output_path.with_extension("errors.csv"), &data)
+}
+
+/// Expected trading days missing between two consecutive per-day files (`concat_directory`).
+#[derive(Debug, Clone)]
+pub struct DayGap {
+    /// Date of the earlier file.
+    pub after: chrono::NaiveDate,
+    /// Date of the later file.
+    pub before: chrono::NaiveDate,
+    /// Trading days in between: calendar days with `options.calendar`, otherwise weekdays.
+    pub missing: Vec<chrono::NaiveDate>,
+}
+
+/// Summary of `concat_directory`.
+#[derive(Debug, Clone, Default)]
+pub struct ConcatStats {
+    /// Number of input files concatenated.
+    pub files: usize,
+    /// The single `.bin` written, or `.ohlcv` with `options.bundle`.
+    pub output_path: std::path::PathBuf,
+    /// Conversion summary of the concatenated series.
+    pub stats: ConversionStats,
+    /// Suspicious gaps between consecutive files.
+    pub gaps: Vec<DayGap>,
+}
+
+/// Extracts the date embedded in a per-day file name (`ES_20240115.txt`, `2024-01-15.txt`).
+///
+/// The first run of exactly eight digits is read as `YYYYMMDD`; failing that, the first
+/// `YYYY-MM-DD` that isn't part of a longer run of digits.
+fn embedded_date(file_name: &str) -> Option<chrono::NaiveDate> {
+    static DASHED_DATE: std::sync::LazyLock<regex::Regex> =
+        std::sync::LazyLock::new(|| regex::Regex::new(r"(?:^|\D)(\d{4}-\d{2}-\d{2})(?:\D|$)").unwrap());
+
+    let compact = file_name
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|run| run.len() == 8)
+        .find_map(|run| chrono::NaiveDate::parse_from_str(run, "%Y%m%d").ok());
+    compact.or_else(|| {
+        DASHED_DATE
+            .captures_iter(file_name)
+            .find_map(|captures| chrono::NaiveDate::parse_from_str(&captures[1], "%Y-%m-%d").ok())
+    })
+}
+
+/// Converts a directory of per-day files into one continuous `.bin` + `.idx`.
+///
+/// Files are ordered by the date embedded in their names (see `embedded_date`), parsed in
+/// parallel and concatenated in that order. Every file must start after the previous one
+/// ends. Between consecutive files, expected trading days without a file are reported as
+/// `DayGap`s: calendar days when `options.calendar` is set, otherwise weekdays. The output
+/// is named after the input directory, e.g. `ES/` → `ES.aos.bin`.
+///
+/// `options.split` and `options.since_last` don't apply and are ignored.
+///
+/// # Arguments
+/// * `input_dir` - Directory of per-day input files.
+/// * `output_dir` - Directory for the concatenated `.bin` / `.idx`.
+/// * `options` - Conversion options.
+///
+/// # Returns
+/// * `anyhow::Result<ConcatStats>` - The written path, conversion summary and gaps.
+///
+/// # Errors
+/// * If there are no input files, a file name has no date, or two files have the same date.
+/// * If a file's bars don't start after the previous file's last bar.
+/// * Propagates errors from `read_input_file`, `save_flatbuffer` or `save_index`.
+pub fn concat_directory<P: AsRef<std::path::Path>>(input_dir: P, output_dir: P, options: &ConversionOptions) -> anyhow::Result<ConcatStats> {
+    use rayon::prelude::*;
+
+    let mut files = Vec::new();
+    for path in file_processing::get_list_files_in_dir(&input_dir, Some(options.input_format.file_extension()))? {
+        let name = std::path::Path::new(&path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let date = embedded_date(&name)
+            .ok_or_else(|| anyhow::anyhow!("No date (YYYYMMDD or YYYY-MM-DD) in file name {}", path))?;
+        files.push((date, path));
+    }
+    if files.is_empty() {
+        return Err(anyhow::anyhow!("No input files in {}", input_dir.as_ref().display()));
+    }
+    files.sort();
+    if let Some(pair) = files.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(anyhow::anyhow!("Files {} and {} have the same date {}", pair[0].1, pair[1].1, pair[0].0));
+    }
+
+    let mut timings = ConversionTimings::default();
+    let start = std::time::Instant::now();
+    let parsed = files
+        .par_iter()
+        .map(|(_, path)| {
+            if let Some(max_size) = options.max_file_size {
+                file_processing::check_file_size(path, max_size)?;
+            }
+            read_input_file(path, options, None).map_err(|e| anyhow::anyhow!("{}: {}", path, e))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    timings.parse = start.elapsed();
+
+    let mut records: Vec<ProcessedRecord> = Vec::with_capacity(parsed.iter().map(|(r, _)| r.len()).sum());
+    let mut skipped = SkippedRows::default();
+    for ((_, path), (file_records, file_skipped)) in files.iter().zip(parsed) {
+        if let (Some(last), Some(first)) = (records.last(), file_records.first())
+            && first.timestamp <= last.timestamp
+        {
+            return Err(anyhow::anyhow!(
+                "{} starts at {}, not after the previous file's last bar at {}",
+                path,
+                first.timestamp,
+                last.timestamp
+            ));
+        }
+        records.extend(file_records);
+        skipped.outside_session += file_skipped.outside_session;
+        skipped.crossed += file_skipped.crossed;
+        skipped.repaired_crossed += file_skipped.repaired_crossed;
+        skipped.bad_rows.extend(file_skipped.bad_rows.into_iter().map(|row| BadRow {
+            reason: format!("{}: {}", path, row.reason),
+            ..row
+        }));
+    }
+
+    let gaps = files
+        .windows(2)
+        .map(|pair| {
+            let (after, before) = (pair[0].0, pair[1].0);
+            let missing = match &options.calendar {
+                Some(calendar) => calendar.trading_days_between(after, before),
+                None => calendar::weekdays_between(after, before),
+            };
+            DayGap { after, before, missing }
+        })
+        .filter(|gap| !gap.missing.is_empty())
+        .collect();
+
+    let output_dir = output_dir.as_ref();
+    std::fs::create_dir_all(output_dir).map_err(|e| file_processing::io_error(e, "create directory", output_dir))?;
+    let stem = input_dir.as_ref().file_name().and_then(|n| n.to_str()).unwrap_or("output");
+    let output_path = output_dir.join(format!("{}{}", stem, options.output_file_suffix()));
+    if options.errors_csv {
+        save_bad_rows(&skipped.bad_rows, &output_path)?;
+    }
+    let processed_data = save_flatbuffer(&records, &output_path, options, &mut timings)?;
+    if !options.no_index {
+        let start = std::time::Instant::now();
+        save_index(&processed_data, &records, options, &output_path)?;
+        timings.write_index += start.elapsed();
+    }
+    let output_path = if options.bundle {
+        let start = std::time::Instant::now();
+        let bundle_path = bundle::bundle_files(&output_path, true)?;
+        timings.write_index += start.elapsed();
+        bundle_path
+    } else {
+        output_path
+    };
+
+    let missing_days = match &options.calendar {
+        Some(calendar) => calendar.missing_days(&processed_data.daily_index)?,
+        None => Vec::new(),
+    };
+
+    anyhow::Ok(ConcatStats {
+        files: files.len(),
+        output_path,
+        stats: ConversionStats {
+            bars: records.len(),
+            missing_days,
+            last_timestamp: records.last().map(|r| r.timestamp),
+            timings,
+            skipped,
+            volume_spikes: options.volume_spike_multiple.map_or_else(Vec::new, |m| find_volume_spikes(&records, m)),
+        },
+        gaps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "<DATE>,<TIME>,<OPEN>,<HIGH>,<LOW>,<CLOSE>,<VOL>\n";
+
+    fn parse(body: &str, empty_field: cli::EmptyField) -> anyhow::Result<Vec<ProcessedRecord>> {
+        let input = format!("{}{}", HEADER, body);
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        let mut raw_data = Vec::new();
+        process_csv_records(&mut reader, &mut raw_data, &ConversionOptions { empty_field, ..ConversionOptions::default() }, None, &mut BadRows::default())?;
+        anyhow::Ok(raw_data)
+    }
+
+    #[test]
+    fn empty_volume_defaults_to_zero() {
+        let records = parse("20240115,093000,100.0,101.0,99.0,100.5,\n", cli::EmptyField::Default).unwrap();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].vol, 0);
+    }
+
+    #[test]
+    fn empty_price_defaults_to_previous_close() {
+        let body = "20240115,093000,100.0,101.0,99.0,100.5,10\n20240115,093100,,,,,\n";
+        let records = parse(body, cli::EmptyField::Default).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].open, 100.5);
+        assert_eq!(records[1].high, 100.5);
+        assert_eq!(records[1].low, 100.5);
+        assert_eq!(records[1].close, 100.5);
+        assert_eq!(records[1].vol, 0);
+    }
+
+    #[test]
+    fn empty_price_without_previous_close_is_an_error() {
+        assert!(parse("20240115,093000,,101.0,99.0,100.5,10\n", cli::EmptyField::Default).is_err());
+    }
+
+    #[test]
+    fn empty_fields_are_errors_in_error_mode() {
+        assert!(parse("20240115,093000,100.0,101.0,99.0,100.5,\n", cli::EmptyField::Error).is_err());
+        assert!(parse("20240115,093000,100.0,,99.0,100.5,10\n", cli::EmptyField::Error).is_err());
+    }
+
+    #[test]
+    fn missing_volume_column_requires_no_volume() {
+        let input = "<DATE>,<TIME>,<OPEN>,<HIGH>,<LOW>,<CLOSE>\n20240115,093000,100.0,101.0,99.0,100.5\n";
+        let mut raw_data = Vec::new();
+
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        assert!(process_csv_records(&mut reader, &mut raw_data, &ConversionOptions { empty_field: cli::EmptyField::Default, ..ConversionOptions::default() }, None, &mut BadRows::default()).is_err());
+
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        process_csv_records(&mut reader, &mut raw_data, &ConversionOptions { empty_field: cli::EmptyField::Default, no_volume: true, ..ConversionOptions::default() }, None, &mut BadRows::default()).unwrap();
+        assert_eq!(raw_data.len(), 1);
+        assert_eq!(raw_data[0].vol, 0);
+    }
+
+    #[test]
+    fn wrong_header_fails_before_any_record_is_read() {
+        let input = "<date>,<TIME>,<OPEN>,<HIGH>,<CLOSE>,<VOL>\n20240115,093000,100.0,101.0,100.5,10\n";
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        let mut raw_data = Vec::new();
+
+        let err = process_csv_records(&mut reader, &mut raw_data, &ConversionOptions { empty_field: cli::EmptyField::Default, ..ConversionOptions::default() }, None, &mut BadRows::default())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Missing columns <DATE>, <LOW>; found headers [<date>, <TIME>, <OPEN>, <HIGH>, <CLOSE>, <VOL>]");
+        assert!(raw_data.is_empty());
+    }
+
+    #[test]
+    fn collect_errors_skips_bad_rows_up_to_the_cap() {
+        let input = format!(
+            "{}{}{}{}{}",
+            HEADER,
+            "20240115,093000,100.0,101.0,99.0,100.5,10\n",
+            "20240115,093100,abc,101.0,99.0,100.5,10\n",
+            "20240115,25:00:00,100.0,101.0,99.0,100.5,10\n",
+            "20240115,093300,100.0,101.0,99.0,100.5,10\n",
+        );
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        let mut raw_data = Vec::new();
+        let mut bad_rows = BadRows::new(Some(2));
+        process_csv_records(&mut reader, &mut raw_data, &ConversionOptions::default(), None, &mut bad_rows).unwrap();
+
+        assert_eq!(raw_data.iter().map(|r| r.source_line).collect::<Vec<_>>(), vec![Some(2), Some(5)]);
+        assert_eq!(bad_rows.into_rows().iter().map(|r| r.line).collect::<Vec<_>>(), vec![3, 4]);
+
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        let mut raw_data = Vec::new();
+        let err = process_csv_records(&mut reader, &mut raw_data, &ConversionOptions::default(), None, &mut BadRows::new(Some(1)))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("More than 1 bad rows (--collect-errors); at line 4"), "{}", err);
+
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        assert!(process_csv_records(&mut reader, &mut Vec::new(), &ConversionOptions::default(), None, &mut BadRows::default()).is_err());
+    }
+
+    #[test]
+    fn volumeless_files_are_flagged_in_the_root_table() {
+        let input = "<DATE>,<TIME>,<OPEN>,<HIGH>,<LOW>,<CLOSE>\n20240115,093000,100.0,101.0,99.0,100.5\n";
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        let mut raw_data = Vec::new();
+        process_csv_records(&mut reader, &mut raw_data, &ConversionOptions { empty_field: cli::EmptyField::Default, no_volume: true, ..ConversionOptions::default() }, None, &mut BadRows::default()).unwrap();
+
+        let aos = build_flatbuffer(&raw_data, cli::StorageFormat::Aos, utils::SCHEMA_VERSION, false);
+        assert!(!ohlcv_generated::root_as_ohlcvlist(&aos).unwrap().has_volume());
+
+        let soa = build_flatbuffer(&raw_data, cli::StorageFormat::Soa, utils::SCHEMA_VERSION, false);
+        let list_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&soa).unwrap();
+        assert!(!list_soa.has_volume());
+        assert!(list_soa.data().unwrap().volumes().is_none());
+    }
+
+    #[test]
+    fn adjusted_closes_are_cumulative_backward_from_ex_dates() {
+        let body = "20240115,093000,100.0,101.0,99.0,100.0,10\n20240116,093000,50.0,51.0,49.0,50.0,10\n20240117,093000,49.0,50.0,48.0,49.0,10\n";
+        let mut records = parse(body, cli::EmptyField::Error).unwrap();
+        // 2-for-1 split on the 16th, 2% dividend on the 17th.
+        let adjustments = adjustments::Adjustments::parse("# ex-date,factor\n2024-01-16,0.5\n2024-01-17,0.98\n").unwrap();
+        apply_adjustments(&mut records, &adjustments).unwrap();
+
+        let aos = build_flatbuffer(&records, cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&aos).unwrap().items().unwrap();
+        let aos_adj: Vec<f64> = items.iter().map(|item| item.adj_close().unwrap()).collect();
+        assert_eq!(aos_adj, vec![100.0 * 0.5 * 0.98, 50.0 * 0.98, 49.0]);
+
+        let soa = build_flatbuffer(&records, cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let data_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&soa).unwrap().data().unwrap();
+        assert_eq!(data_soa.adj_closes().unwrap().iter().collect::<Vec<f64>>(), aos_adj);
+
+        let plain = build_flatbuffer(&parse(body, cli::EmptyField::Error).unwrap(), cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        assert!(ohlcv_soa_generated::root_as_ohlcvlist_soa(&plain).unwrap().data().unwrap().adj_closes().is_none());
+    }
+
+    #[test]
+    fn presized_capacity_covers_bars_with_every_field() {
+        let records: Vec<ProcessedRecord> = (0..1000u64)
+            .map(|i| ProcessedRecord { adj_close: Some(1.0), ..ProcessedRecord::from(&resample::OHLCVBar { timestamp: 1705312800 + i * 60, open: 1.0, high: 2.0, low: 0.5, close: 1.5, volume: 10, count: 1 }) })
+            .collect();
+
+        assert_eq!((AOS_BYTES_PER_BAR, SOA_BYTES_PER_BAR), (68, 56));
+        for (format, bytes_per_bar) in [(cli::StorageFormat::Aos, AOS_BYTES_PER_BAR), (cli::StorageFormat::Soa, SOA_BYTES_PER_BAR)] {
+            let buf = build_flatbuffer(&records, format, utils::SCHEMA_VERSION, true);
+            assert!(buf.len() <= records.len() * bytes_per_bar + BUFFER_OVERHEAD, "{} bytes", buf.len());
+        }
+    }
+
+    /// A bar with high and low swapped and a close above the real high, next to a clean bar.
+    const CROSSED_BODY: &str = "20240115,093000,100.0,99.0,101.0,101.5,10\n20240115,093100,100.0,101.0,99.0,100.5,10\n";
+
+    fn prices(record: &ProcessedRecord) -> (f64, f64, f64, f64) {
+        (record.open, record.high, record.low, record.close)
+    }
+
+    #[test]
+    fn fix_crossed_swap_widens_the_range_to_open_and_close() {
+        let mut records = parse(CROSSED_BODY, cli::EmptyField::Error).unwrap();
+        assert_eq!(fix_crossed_bars(&mut records, &cli::FixCrossed::Swap), (1, 0));
+        assert_eq!(prices(&records[0]), (100.0, 101.5, 99.0, 101.5));
+        assert_eq!(prices(&records[1]), (100.0, 101.0, 99.0, 100.5));
+    }
+
+    #[test]
+    fn fix_crossed_clamp_moves_open_and_close_into_the_range() {
+        let mut records = parse(CROSSED_BODY, cli::EmptyField::Error).unwrap();
+        assert_eq!(fix_crossed_bars(&mut records, &cli::FixCrossed::Clamp), (1, 0));
+        assert_eq!(prices(&records[0]), (100.0, 101.0, 99.0, 101.0));
+        assert_eq!(prices(&records[1]), (100.0, 101.0, 99.0, 100.5));
+    }
+
+    #[test]
+    fn fix_crossed_drop_removes_the_bar() {
+        let mut records = parse(CROSSED_BODY, cli::EmptyField::Error).unwrap();
+        assert_eq!(fix_crossed_bars(&mut records, &cli::FixCrossed::Drop), (0, 1));
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].timestamp, 1705311060);
+    }
+
+    #[test]
+    fn since_last_skips_rows_already_converted() {
+        let input = format!(
+            "{}{}",
+            HEADER,
+            "20240115,093000,1,1,1,1,1\n20240115,093100,2,2,2,2,2\n20240115,093200,3,3,3,3,3\n"
+        );
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        let mut raw_data = Vec::new();
+
+        let skipped = process_csv_records(&mut reader, &mut raw_data, &ConversionOptions { empty_field: cli::EmptyField::Default, ..ConversionOptions::default() }, Some(1705311060), &mut BadRows::default()).unwrap();
+
+        assert_eq!(skipped.already_converted, 2);
+        assert_eq!(raw_data.len(), 1);
+        assert_eq!(raw_data[0].timestamp, 1705311120);
+    }
+
+    #[test]
+    fn since_last_rejects_old_rows_after_new_ones() {
+        let input = format!(
+            "{}{}",
+            HEADER,
+            "20240115,093200,3,3,3,3,3\n20240115,093000,1,1,1,1,1\n"
+        );
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        let mut raw_data = Vec::new();
+
+        assert!(process_csv_records(&mut reader, &mut raw_data, &ConversionOptions { empty_field: cli::EmptyField::Default, ..ConversionOptions::default() }, Some(1705311060), &mut BadRows::default()).is_err());
+    }
+
+    #[test]
+    fn session_filter_drops_bars_outside_regular_hours() {
+        let body = "20240115,092900,1,1,1,1,1\n20240115,093000,2,2,2,2,2\n20240115,155900,3,3,3,3,3\n20240115,160000,4,4,4,4,4\n";
+        let input = format!("{}{}", HEADER, body);
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        let mut raw_data = Vec::new();
+        let session = calendar::SessionWindow::parse("09:30-16:00").unwrap();
+
+        let skipped = process_csv_records(&mut reader, &mut raw_data, &ConversionOptions { empty_field: cli::EmptyField::Default, session: Some(session), ..ConversionOptions::default() }, None, &mut BadRows::default()).unwrap();
+
+        assert_eq!(skipped.outside_session, 2);
+        assert_eq!(raw_data.iter().map(|r| r.open).collect::<Vec<_>>(), vec![2.0, 3.0]);
+    }
+
+    #[test]
+    fn session_filter_handles_windows_crossing_midnight() {
+        let session = calendar::SessionWindow::parse("18:00-17:00").unwrap();
+        let time = |s: &str| chrono::NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+
+        assert!(session.contains(time("18:00")));
+        assert!(session.contains(time("23:59")));
+        assert!(session.contains(time("00:00")));
+        assert!(session.contains(time("16:59")));
+        assert!(!session.contains(time("17:00")));
+        assert!(!session.contains(time("17:30")));
+    }
+
+    #[test]
+    fn read_metadata_matches_plain_and_compact_indices() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_metadata_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let records = parse("20240115,100000,1,2,0.5,1.5,10\n20240115,100100,1.5,2,1,1.75,5\n20240116,100000,2,3,1,2.5,7\n", cli::EmptyField::Error).unwrap();
+        let processed = build_index(&records).unwrap();
+
+        for (name, compact) in [("plain.aos.bin", false), ("compact.aos.bin", true)] {
+            let path = dir.join(name);
+            save_index(&processed, &records, &ConversionOptions { compact_index: compact, ..ConversionOptions::default() }, &path).unwrap();
+
+            let metadata = utils::read_metadata(&path).unwrap();
+            assert_eq!(metadata, utils::Metadata { bars: 3, first_timestamp: Some(1705312800), last_timestamp: Some(1705399200) });
+            assert_eq!(utils::load_full_index(path.with_extension("idx")).unwrap().time_index.len(), 3);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn index_is_decoded_by_its_header_version() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_index_version_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let records = parse("20240115,100000,1,2,0.5,1.5,10\n20240116,100000,2,3,1,2.5,7\n", cli::EmptyField::Error).unwrap();
+        let processed = build_index(&records).unwrap();
+        let path = dir.join("versioned.aos.bin");
+        save_index(&processed, &records, &ConversionOptions { symbol: Some("ES".into()), ..ConversionOptions::default() }, &path).unwrap();
+        let data = std::fs::read(path.with_extension("idx")).unwrap();
+
+        assert_eq!(&data[..5], b"FIDX\x01");
+        assert_eq!(utils::decode_full_index(&data).unwrap().symbol.as_deref(), Some("ES"));
+
+        // Written before the header existed: the three original indices, bare.
+        let legacy = bincode::serialize(&(&processed.time_index, &processed.daily_index, &processed.timeframe_index)).unwrap();
+        let decoded = utils::decode_full_index(&legacy).unwrap();
+        assert_eq!((decoded.time_index.len(), decoded.symbol), (2, None));
+        std::fs::write(path.with_extension("idx"), &legacy).unwrap();
+        assert_eq!(utils::read_metadata(&path).unwrap().last_timestamp, Some(1705399200));
+
+        let mut newer = data.clone();
+        newer[4] = index::INDEX_VERSION + 1;
+        let err = utils::decode_full_index(&newer).unwrap_err();
+        assert!(err.to_string().contains("Unsupported index version"), "{}", err);
+        std::fs::write(path.with_extension("idx"), &newer).unwrap();
+        assert!(utils::read_metadata(&path).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn index_without_timeframes_keeps_time_and_daily_indices() {
+        let records = parse("20240115,100000,1,2,0.5,1.5,10\n20240116,100000,2,3,1,2.5,7\n", cli::EmptyField::Error).unwrap();
+
+        let processed = index_records(&records, None).unwrap();
+
+        assert!(processed.timeframe_index.is_empty());
+        assert_eq!(processed.time_index.len(), 2);
+        assert_eq!(processed.daily_index.len(), 2);
+        assert_eq!(build_index(&records).unwrap().timeframe_index.len(), 6);
+    }
+
+    #[test]
+    fn reduced_timeframes_shrink_the_index() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_index_timeframes_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let records = parse("20240115,100000,1,2,0.5,1.5,10\n20240116,100000,2,3,1,2.5,7\n", cli::EmptyField::Error).unwrap();
+        let daily_only = index_timeframe_keys(&["1d".to_string()]);
+        let idx_size = |name: &str, timeframes: &[String]| {
+            let processed = index_records(&records, Some(timeframes)).unwrap();
+            let path = dir.join(name);
+            save_index(&processed, &records, &ConversionOptions::default(), &path).unwrap();
+            (processed.timeframe_index.keys().cloned().collect::<Vec<_>>(), std::fs::metadata(path.with_extension("idx")).unwrap().len())
+        };
+
+        let (full_keys, full_size) = idx_size("full.aos.bin", &index_timeframe_keys(&[]));
+        let (daily_keys, daily_size) = idx_size("daily.aos.bin", &daily_only);
+
+        assert_eq!(full_keys.len(), 6);
+        assert_eq!(daily_keys, vec!["1d".to_string()]);
+        assert_eq!(index_timeframe_keys(&["5min".to_string(), "90min".to_string(), "1d".to_string()]), vec!["5m", "1d"]);
+        // One day of 1-minute boundaries alone is 1441 u64s.
+        assert!(daily_size * 10 < full_size, "{} vs {}", daily_size, full_size);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn interleaved_dates_are_rejected_instead_of_split_across_entries() {
+        let records = parse(
+            "20240115,100000,1,2,0.5,1.5,10\n20240116,100000,2,3,1,2.5,7\n20240115,150000,1.5,2,1,1.75,5\n",
+            cli::EmptyField::Error,
+        )
+        .unwrap();
+
+        let err = build_index(&records).unwrap_err().to_string();
+        assert!(err.contains("Date 2024-01-15 appears in non-contiguous ranges"), "{}", err);
+        assert!(index_records(&records, None).is_err());
+    }
+
+    #[test]
+    fn volume_spikes_are_flagged_against_the_rolling_median() {
+        let mut input = String::new();
+        for minute in 0..30 {
+            let vol = match minute {
+                5 => 90_000,  // too early: fewer than VOLUME_SPIKE_MIN_SAMPLES bars before it
+                25 => 50_000, // 500x the median of 100
+                26 => 9_000,  // only 90x
+                _ => 100,
+            };
+            input.push_str(&format!("20240115,10{:02}00,1,2,0.5,1.5,{}\n", minute, vol));
+        }
+        let records = parse(&input, cli::EmptyField::Error).unwrap();
+
+        let spikes = find_volume_spikes(&records, 100);
+
+        assert_eq!(spikes, vec![VolumeSpike { timestamp: 1705312800 + 25 * 60, source_line: Some(27), volume: 50_000, median: 100 }]);
+        assert!(find_volume_spikes(&records, 1000).is_empty());
+    }
+
+    #[test]
+    fn ticks_are_aggregated_into_bars() {
+        let input = "<DATE>,<TIME>,<PRICE>,<VOL>\n20240115,093000,100,1\n20240115,093020,101,2\n20240115,093105,99,3\n20240115,093500,102,1\n";
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        let (ticks, _) = parse_csv_ticks(&mut reader, &ConversionOptions::default(), &mut BadRows::default()).unwrap();
+        let tick_bars = TickBars {
+            timeframe: resample::Timeframe::Intraday(300),
+            bar_timestamp: cli::BarTimestamp::BucketStart,
+            bucket_edge: cli::BucketEdge::Left,
+            bucket_anchor: 0,
+        };
+
+        let bars = ticks_to_bars(&ticks, &tick_bars);
+
+        assert_eq!(ticks[1], TickRecord { timestamp: 1705311020, price: 101.0, volume: 2 });
+        assert_eq!(bars.len(), 2);
+        assert_eq!((bars[0].timestamp, bars[0].open, bars[0].high, bars[0].low, bars[0].close), (1705311000, 100.0, 101.0, 99.0, 99.0));
+        assert_eq!((bars[0].volume, bars[0].count), (6, 3));
+        assert_eq!((bars[1].timestamp, bars[1].open, bars[1].close), (1705311300, 102.0, 102.0));
+    }
+
+    #[test]
+    fn resampled_records_are_written_as_csv() {
+        let records = parse("20240115,100000,1,2,0.5,1.5,10\n20240115,100100,1.5,2.5,1,1.75,5\n20240115,100500,2,3,1.5,2.5,7\n", cli::EmptyField::Error).unwrap();
+        let bars = resample_records(&records, resample::Timeframe::Intraday(300), &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0);
+        let mut out = Vec::new();
+
+        write_bars_csv(&bars, None, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "timestamp,open,high,low,close,volume\n1705312800,1,2.5,0.5,1.75,15\n1705313100,2,3,1.5,2.5,7\n"
+        );
+    }
+
+    #[test]
+    fn descending_order_keeps_indices_in_ascending_time() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_desc_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("desc.aos.bin");
+        let records = parse("20240115,100000,1,2,0.5,1.5,10\n20240115,100100,1.5,2,1,1.75,5\n20240116,100000,2,3,1,2.5,7\n", cli::EmptyField::Error).unwrap();
+        let mut timings = ConversionTimings::default();
+
+        let processed = save_flatbuffer(&records, &path, &ConversionOptions { storage_format: cli::StorageFormat::Aos, order: cli::BarOrder::Desc, ..ConversionOptions::default() }, &mut timings).unwrap();
+
+        let buf = std::fs::read(&path).unwrap();
+        let stored: Vec<u64> = read_flatbuffers::iter_bars(&buf, &cli::StorageFormat::Aos).unwrap().map(|b| b.timestamp).collect();
+        assert_eq!(stored, vec![1705399200, 1705312860, 1705312800]);
+        assert_eq!(processed.time_index.iter().map(|e| (e.timestamp, e.index)).collect::<Vec<_>>(),
+                   vec![(1705312800, 2), (1705312860, 1), (1705399200, 0)]);
+        assert_eq!(read_flatbuffers::load_bars(&path).unwrap().iter().map(|b| b.timestamp).collect::<Vec<_>>(),
+                   vec![1705312800, 1705312860, 1705399200]);
+
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+        let daily = resample::resample_daily_aos(&items, &processed.daily_index, &cli::BarTimestamp::BucketStart).unwrap();
+        assert_eq!(daily.iter().map(|b| (b.timestamp, b.open, b.close, b.count)).collect::<Vec<_>>(),
+                   vec![(1705312800, 1.0, 1.75, 2), (1705399200, 2.0, 2.5, 1)]);
+
+        // Segments are cut from the newest-first order too.
+        let segmented = dir.join("desc.soa.bin");
+        let options = ConversionOptions { order: cli::BarOrder::Desc, soa_segment_size: Some(2), self_verify: true, verify_output: true, ..ConversionOptions::default() };
+        let processed = save_flatbuffer(&records, &segmented, &options, &mut timings).unwrap();
+        let buf = std::fs::read(&segmented).unwrap();
+        let segments = read_flatbuffers::split_segments(&buf, processed.segment_offsets.as_deref()).unwrap();
+        let stored: Vec<u64> = read_flatbuffers::iter_segments(&segments, &cli::StorageFormat::Soa).unwrap().map(|b| b.timestamp).collect();
+        assert_eq!((segments.len(), stored), (2, vec![1705399200, 1705312860, 1705312800]));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn soa_segments_read_back_as_one_file() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_segments_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("segmented.soa.bin");
+        let body: String = (0..5).map(|minute| format!("20240115,10{:02}00,1,2,0.5,1.5,{}\n", minute, minute + 1)).collect();
+        let records = parse(&body, cli::EmptyField::Error).unwrap();
+        let mut timings = ConversionTimings::default();
+
+        let processed = save_flatbuffer(&records, &path, &ConversionOptions { storage_format: cli::StorageFormat::Soa, self_verify: true, verify_output: true, soa_segment_size: Some(2), ..ConversionOptions::default() }, &mut timings).unwrap();
+        save_index(&processed, &records, &ConversionOptions::default(), &path).unwrap();
+
+        let offsets = utils::load_full_index(path.with_extension("idx")).unwrap().segment_offsets.unwrap();
+        assert_eq!(offsets.len(), 3);
+        let buf = std::fs::read(&path).unwrap();
+        let segments = read_flatbuffers::split_segments(&buf, Some(&offsets)).unwrap();
+        assert_eq!(segments.iter().map(|s| read_flatbuffers::iter_bars(s, &cli::StorageFormat::Soa).unwrap().len()).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert_eq!(read_flatbuffers::load_bars(&path).unwrap().iter().map(|b| b.volume).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert!(save_flatbuffer(&records, dir.join("segmented.aos.bin"), &ConversionOptions { storage_format: cli::StorageFormat::Aos, soa_segment_size: Some(2), ..ConversionOptions::default() }, &mut timings).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn segmented_soa_bundle_reads_like_the_pair_it_replaces() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_bundle_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bundled.soa.bin");
+        let body: String = (0..5).map(|minute| format!("20240115,10{:02}00,1,2,0.5,1.5,{}\n", minute, minute + 1)).collect();
+        let records = parse(&body, cli::EmptyField::Error).unwrap();
+        let mut timings = ConversionTimings::default();
+        let processed = save_flatbuffer(&records, &path, &ConversionOptions { storage_format: cli::StorageFormat::Soa, soa_segment_size: Some(2), ..ConversionOptions::default() }, &mut timings).unwrap();
+        save_index(&processed, &records, &ConversionOptions { compact_index: true, ..ConversionOptions::default() }, &path).unwrap();
+        let volumes = |path: &std::path::Path| read_flatbuffers::load_bars(path).unwrap().iter().map(|b| (b.timestamp, b.volume)).collect::<Vec<_>>();
+        let expected = volumes(&path);
+
+        let bundle_path = bundle::bundle_files(&path, true).unwrap();
+
+        assert_eq!(bundle_path, dir.join("bundled.soa.ohlcv"));
+        assert!(!path.exists() && !path.with_extension("idx").exists());
+        assert_eq!(volumes(&bundle_path), expected);
+        let data = std::fs::read(&bundle_path).unwrap();
+        let bundle = bundle::Bundle::parse(&data).unwrap().unwrap();
+        assert_eq!(bundle.full_index().unwrap().time_index.len(), 5);
+        assert!(bundle::bundle_files(&bundle_path, false).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tarball_csv_entries_are_converted_after_their_paths() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_tarball_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tarball_path = dir.join("2024-01.tar.gz");
+        let good = format!("{}20240115,100000,1,2,0.5,1.5,10\n20240115,100100,1.5,2,1,1.75,5\n", HEADER);
+        let bad = format!("{}20240116,100000,1,2,0.5,,10\n", HEADER);
+        let gzip = flate2::write::GzEncoder::new(std::fs::File::create(&tarball_path).unwrap(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(gzip);
+        for (name, data) in [("2024-01/ES_20240115.txt", good.as_str()), ("2024-01/ES_20240116.csv", bad.as_str()), ("README.md", "notes")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let out_dir = dir.join("out");
+        let options = ConversionOptions { storage_format: cli::StorageFormat::Aos, ..ConversionOptions::default() };
+        let mut results = Vec::new();
+        let entries = convert_tarball(&tarball_path, &out_dir, &options, |entry, result| results.push((entry.to_string(), result.map(|stats| stats.bars)))).unwrap();
+
+        assert_eq!(entries, 2);
+        assert_eq!(results[0].0, "2024-01/ES_20240115.txt");
+        assert_eq!(results[0].1.as_ref().unwrap(), &2);
+        assert_eq!(results[1].0, "2024-01/ES_20240116.csv");
+        assert!(results[1].1.is_err());
+        let output_path = out_dir.join("2024-01").join("ES_20240115.aos.bin");
+        assert_eq!(read_flatbuffers::load_bars(&output_path).unwrap().len(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_conversion_writes_no_output() {
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let input = format!("{}20240115,100000,1,2,0.5,1.5,10\n", HEADER);
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        let err = process_csv_records(&mut reader, &mut Vec::new(), &ConversionOptions { cancel: Some(cancel.clone()), ..ConversionOptions::default() }, None, &mut BadRows::default()).unwrap_err();
+        assert_eq!(err.downcast_ref::<Cancelled>(), Some(&Cancelled));
+
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_cancel_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input_path = dir.join("ES.txt");
+        std::fs::write(&input_path, &input).unwrap();
+        let output_path = dir.join("ES.aos.bin");
+        let options = ConversionOptions { storage_format: cli::StorageFormat::Aos, cancel: Some(cancel.clone()), ..ConversionOptions::default() };
+
+        let err = convert_csv_to_flatbuffer(&input_path, &output_path, &options).unwrap_err();
+        assert!(err.downcast_ref::<Cancelled>().is_some(), "{}", err);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        cancel.store(false, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(convert_csv_to_flatbuffer(&input_path, &output_path, &options).unwrap().bars, 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn symbol_is_taken_from_the_file_stem() {
+        let whole_stem = SymbolFromFilename::default();
+        assert_eq!(whole_stem.extract("AAPL").unwrap(), "AAPL");
+
+        let first_group = SymbolFromFilename { pattern: Some(regex::Regex::new("^([A-Z]+)_").unwrap()) };
+        assert_eq!(first_group.extract("ES_20240115").unwrap(), "ES");
+        assert!(first_group.extract("es_20240115").is_err());
+
+        let named = SymbolFromFilename { pattern: Some(regex::Regex::new(r"(?P<date>\d{8})-(?P<symbol>\w+)").unwrap()) };
+        assert_eq!(named.extract("20240115-MSFT").unwrap(), "MSFT");
+    }
+
+    fn parse_quoted(input: &str, csv_quoting: CsvQuoting) -> anyhow::Result<Vec<ProcessedRecord>> {
+        let mut reader = csv_quoting.reader_builder().from_reader(input.as_bytes());
+        let mut raw_data = Vec::new();
+        process_csv_records(&mut reader, &mut raw_data, &ConversionOptions::default(), None, &mut BadRows::default())?;
+        anyhow::Ok(raw_data)
+    }
+
+    #[test]
+    fn quoted_field_with_a_comma_is_one_field() {
+        let input = "<DATE>,<TIME>,<OPEN>,<HIGH>,<LOW>,<CLOSE>,<VOL>,<NOTE>\n\
+                     20240115,100000,1,2,0.5,1.5,10,\"halted, then resumed\"\n";
+
+        let records = parse_quoted(input, CsvQuoting::default()).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].vol, 10);
+
+        // Without quoting the comma splits the note into an extra column.
+        assert!(parse_quoted(input, CsvQuoting { quoting: false, ..CsvQuoting::default() }).is_err());
+    }
+
+    #[test]
+    fn escape_char_and_custom_quote_are_honoured() {
+        let escaped = "<DATE>,<TIME>,<OPEN>,<HIGH>,<LOW>,<CLOSE>,<VOL>,<NOTE>\n\
+                       20240115,100000,1,2,0.5,1.5,10,\"say \\\"hi\\\", then go\"\n";
+        let escape = CsvQuoting { escape: Some(b'\\'), ..CsvQuoting::default() };
+        assert_eq!(parse_quoted(escaped, escape).unwrap().len(), 1);
+
+        let single_quoted = "<DATE>,<TIME>,<OPEN>,<HIGH>,<LOW>,<CLOSE>,<VOL>,<NOTE>\n\
+                             20240115,100000,1,2,0.5,1.5,10,'a, b'\n";
+        let single = CsvQuoting { quote: b'\'', ..CsvQuoting::default() };
+        assert_eq!(parse_quoted(single_quoted, single).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn decimal_comma_numbers_are_read_with_a_semicolon_delimiter() {
+        let input = "<DATE>;<TIME>;<OPEN>;<HIGH>;<LOW>;<CLOSE>;<VOL>\n\
+                     20240115;100000;1.234,56;1.240,5;1.230;1.235,25;1.500\n\
+                     20240115;100100;1.235,25;1.236;1.234,75;1.235,5;\n";
+        let csv_quoting = CsvQuoting { delimiter: b';', ..CsvQuoting::default() };
+        let mut reader = csv_quoting.reader_builder().from_reader(input.as_bytes());
+        let mut raw_data = Vec::new();
+        process_csv_records(&mut reader, &mut raw_data, &ConversionOptions { decimal_separator: cli::DecimalSeparator::Comma, empty_field: cli::EmptyField::Default, ..ConversionOptions::default() }, None, &mut BadRows::default()).unwrap();
+
+        let rows: Vec<_> = raw_data.iter().map(|r| (r.timestamp, r.open, r.high, r.low, r.close, r.vol)).collect();
+        assert_eq!(
+            rows,
+            vec![
+                (1705312800, 1234.56, 1240.5, 1230.0, 1235.25, 1500),
+                (1705312860, 1235.25, 1236.0, 1234.75, 1235.5, 0),
+            ]
+        );
+
+        // Read with a decimal point, `1.234,56` is not a number.
+        let mut reader = csv_quoting.reader_builder().from_reader(input.as_bytes());
+        assert!(process_csv_records(&mut reader, &mut Vec::new(), &ConversionOptions { empty_field: cli::EmptyField::Default, ..ConversionOptions::default() }, None, &mut BadRows::default()).is_err());
+    }
+
+    #[test]
+    fn self_verify_names_the_first_mismatching_bar() {
+        let records = parse("20240115,100000,1,2,0.5,1.5,10\n20240115,100100,1.5,2,1,1.75,5\n", cli::EmptyField::Error).unwrap();
+        let aos = build_flatbuffer(&records, cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        assert!(verify_against_other_format(records.iter(), &aos, &cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true).is_ok());
+
+        let drifted = parse("20240115,100000,1,2,0.5,1.5,10\n20240115,100100,1.5,2,1,1.8,5\n", cli::EmptyField::Error).unwrap();
+        let soa = build_flatbuffer(&drifted, cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let err = verify_against_other_format(records.iter(), &soa, &cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true).unwrap_err();
+        assert!(err.to_string().contains("bar 1 differs"), "{}", err);
+    }
+
+    #[test]
+    fn verify_output_rejects_truncated_and_miscounted_buffers() {
+        let records = parse("20240115,100000,1,2,0.5,1.5,10\n20240115,100100,1.5,2,1,1.75,5\n", cli::EmptyField::Error).unwrap();
+        for format in [cli::StorageFormat::Aos, cli::StorageFormat::Soa] {
+            let buf = build_flatbuffer(&records, format.clone(), utils::SCHEMA_VERSION, true);
+            assert!(verify_output_buffer(&buf, &format, records.len()).is_ok());
+            assert!(verify_output_buffer(&buf[..buf.len() / 2], &format, records.len()).is_err());
+            let err = verify_output_buffer(&buf, &format, records.len() + 1).unwrap_err();
+            assert!(err.to_string().contains("holds 2 bars"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn estimated_reservation_is_filled_without_reallocating() {
+        let body: String = (0..100)
+            .map(|minute| format!("20240115,{:02}{:02}00,90300.25,90340.50,90250.75,90321.00,516\n", 10 + minute / 60, minute % 60))
+            .collect();
+        let input = format!("{}{}", HEADER, body);
+        let mut raw_data = Vec::with_capacity(estimated_rows(input.len() as u64, &cli::InputFormat::Csv));
+        let reserved = raw_data.as_ptr();
+
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input.as_bytes());
+        process_csv_records(&mut reader, &mut raw_data, &ConversionOptions::default(), None, &mut BadRows::default()).unwrap();
+
+        assert_eq!(raw_data.len(), 100);
+        assert_eq!(raw_data.as_ptr(), reserved);
+        let fields = |records: &[ProcessedRecord]| records.iter().map(|r| (r.timestamp, r.open, r.close, r.vol)).collect::<Vec<_>>();
+        assert_eq!(fields(&raw_data), fields(&parse(&body, cli::EmptyField::Error).unwrap()));
+
+        // With `since_last` only the tail is kept, so nothing is reserved for the other rows.
+        let since_last = Some(raw_data[97].timestamp);
+        let (tail, _) = read_input(input.as_bytes(), Some(input.len() as u64), &ConversionOptions::default(), since_last).unwrap();
+        assert_eq!(tail.len(), 2);
+        assert!(tail.capacity() < 10, "{}", tail.capacity());
+    }
+
+    #[test]
+    fn leading_bom_is_skipped() {
+        let input = format!("\u{feff}{}20240115,100000,1,2,0.5,1.5,10\n", HEADER);
+        let mut bytes = input.as_bytes();
+        skip_utf8_bom(&mut bytes).unwrap();
+
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(bytes);
+        let mut raw_data = Vec::new();
+        process_csv_records(&mut reader, &mut raw_data, &ConversionOptions::default(), None, &mut BadRows::default()).unwrap();
+        assert_eq!(raw_data.len(), 1);
+        assert_eq!(raw_data[0].vol, 10);
+
+        // Input without a BOM is left untouched.
+        let mut plain = HEADER.as_bytes();
+        skip_utf8_bom(&mut plain).unwrap();
+        assert_eq!(plain, HEADER.as_bytes());
+    }
+
+    #[test]
+    fn embedded_date_reads_compact_and_dashed_names() {
+        let date = |y, m, d| chrono::NaiveDate::from_ymd_opt(y, m, d);
+
+        assert_eq!(embedded_date("ES_20240115.txt"), date(2024, 1, 15));
+        assert_eq!(embedded_date("ES-2024-01-16-rth.txt"), date(2024, 1, 16));
+        assert_eq!(embedded_date("ES_202401151000.txt"), None);
+        assert_eq!(embedded_date("run-12024-01-16.txt"), None);
+        assert_eq!(embedded_date("prices.txt"), None);
+    }
+
+    #[test]
+    fn concat_directory_orders_dashed_names_and_creates_the_output_dir() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_concat_{}", std::process::id()));
+        let input_dir = dir.join("ES");
+        std::fs::create_dir_all(&input_dir).unwrap();
+        std::fs::write(input_dir.join("ES-2024-01-16-rth.txt"), format!("{}20240116,100000,1,2,0.5,1.5,10\n", HEADER)).unwrap();
+        std::fs::write(input_dir.join("ES-2024-01-12-rth.txt"), format!("{}20240112,100000,1,2,0.5,1.5,10\n", HEADER)).unwrap();
+        let output_dir = dir.join("out").join("nested");
+
+        let concat = concat_directory(&input_dir, &output_dir, &ConversionOptions { storage_format: cli::StorageFormat::Aos, ..ConversionOptions::default() }).unwrap();
+
+        assert_eq!(concat.files, 2);
+        assert_eq!(concat.output_path, output_dir.join("ES.aos.bin"));
+        assert!(concat.output_path.exists());
+        // Monday the 15th has no file.
+        assert_eq!(concat.gaps.len(), 1);
+        assert_eq!(concat.gaps[0].missing, vec![chrono::NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
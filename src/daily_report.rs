@@ -0,0 +1,195 @@
+use crate::cli;
+use crate::index;
+use crate::resample;
+use crate::file_processing;
+use crate::read_flatbuffers;
+
+/// Statistics of one trading day: one row of a `.daily-report.csv` (`--daily-report`).
+///
+/// Times of day are UTC `HH:MM:SS`, like the `daily_index` dates. When the high or low is
+/// reached by several bars, the earliest one is reported.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DayStats {
+    pub date: String,
+    pub bars: usize,
+    pub open: f64,
+    pub high: f64,
+    pub high_time: String,
+    pub low: f64,
+    pub low_time: String,
+    pub close: f64,
+    pub volume: u64,
+    /// Intraday range, `high - low`.
+    pub range: f64,
+}
+
+/// Computes the statistics of every day in `daily_index`.
+///
+/// Each day's bars are read through the AOS/SOA accessors of `read_flatbuffers` from the
+/// slice its `daily_index` entry delimits, in either storage order (`--order desc` files
+/// have `start_index > end_index`). A day with a single bar is that bar's own OHLCV, with
+/// the same high and low time.
+///
+/// # Arguments
+/// * `segments` - The mapped .bin file, split into its segments (a single one unless
+///   written with `--soa-segment-size`).
+/// * `storage_format` - Layout of `segments`.
+/// * `daily_index` - The file's daily index.
+///
+/// # Returns
+/// * `anyhow::Result<Vec<DayStats>>` - One row per day, in `daily_index` order.
+///
+/// # Errors
+/// * If the file can't be parsed.
+/// * If a `daily_index` entry points past the end of the file.
+pub fn daily_report(
+    segments: &[&[u8]],
+    storage_format: &cli::StorageFormat,
+    daily_index: &[index::DailyIndexEntry],
+) -> anyhow::Result<Vec<DayStats>> {
+    // Segmented files have no O(1) positional access across segments, so they're collected.
+    let (bars, collected) = match segments {
+        [buf] => (Some(read_flatbuffers::iter_bars(buf, storage_format)?), None),
+        _ => (None, Some(read_flatbuffers::iter_segments(segments, storage_format)?.collect::<Vec<_>>())),
+    };
+
+    daily_index
+        .iter()
+        .map(|entry| {
+            let low = entry.start_index.min(entry.end_index) as usize;
+            let count = entry.start_index.abs_diff(entry.end_index) as usize + 1;
+            let day: Vec<resample::OHLCVBar> = match (&bars, &collected) {
+                (Some(bars), _) => bars.clone().skip(low).take(count).collect(),
+                (None, Some(collected)) => collected.iter().skip(low).take(count).copied().collect(),
+                (None, None) => Vec::new(),
+            };
+            if day.len() != count {
+                return Err(anyhow::anyhow!("Daily index entry for {} points past the end of the file", entry.date));
+            }
+            day_stats(&entry.date, &day)
+        })
+        .collect()
+}
+
+/// Computes the statistics of one day from its bars, given in any order.
+fn day_stats(date: &str, day: &[resample::OHLCVBar]) -> anyhow::Result<DayStats> {
+    let first = day.iter().min_by_key(|b| b.timestamp).ok_or_else(|| anyhow::anyhow!("Day {} has no bars", date))?;
+    let last = day.iter().max_by_key(|b| b.timestamp).unwrap_or(first);
+    let mut high = first;
+    let mut low = first;
+    for bar in day {
+        if bar.high > high.high || (bar.high == high.high && bar.timestamp < high.timestamp) {
+            high = bar;
+        }
+        if bar.low < low.low || (bar.low == low.low && bar.timestamp < low.timestamp) {
+            low = bar;
+        }
+    }
+
+    anyhow::Ok(DayStats {
+        date: date.to_string(),
+        bars: day.len(),
+        open: first.open,
+        high: high.high,
+        high_time: format_time_of_day(high.timestamp)?,
+        low: low.low,
+        low_time: format_time_of_day(low.timestamp)?,
+        close: last.close,
+        volume: day.iter().map(|b| b.volume).sum(),
+        range: high.high - low.low,
+    })
+}
+
+/// Formats the UTC time of day of a Unix timestamp as `HH:MM:SS`.
+fn format_time_of_day(ts: u64) -> anyhow::Result<String> {
+    let dt = chrono::DateTime::from_timestamp(ts as i64, 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid timestamp: {}", ts))?;
+    anyhow::Ok(dt.format("%H:%M:%S").to_string())
+}
+
+/// Writes the daily report of a .bin file next to it, e.g. `data.aos.daily-report.csv`.
+///
+/// # Arguments
+/// * `path` - Path to the .bin file.
+/// * `segments` - The mapped file, split into its segments.
+/// * `storage_format` - Layout of `segments`.
+/// * `daily_index` - The file's daily index.
+///
+/// # Returns
+/// * `anyhow::Result<(std::path::PathBuf, usize)>` - Path of the report and number of days.
+///
+/// # Errors
+/// * If the report can't be computed (see `daily_report`) or written.
+pub fn write_daily_report(
+    path: &std::path::Path,
+    segments: &[&[u8]],
+    storage_format: &cli::StorageFormat,
+    daily_index: &[index::DailyIndexEntry],
+) -> anyhow::Result<(std::path::PathBuf, usize)> {
+    let report = daily_report(segments, storage_format, daily_index)?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for day in &report {
+        writer.serialize(day)?;
+    }
+    let data = writer.into_inner().map_err(|e| anyhow::anyhow!("Failed to write daily report: {}", e))?;
+    let report_path = path.with_extension("daily-report.csv");
+    file_processing::write_atomic(&report_path, &data)?;
+
+    anyhow::Ok((report_path, report.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use crate::csv_processor;
+
+    fn bar(timestamp: u64, high: f64, low: f64, volume: u64) -> resample::OHLCVBar {
+        resample::OHLCVBar { timestamp, open: low + 0.5, high, low, close: high - 0.5, volume, count: 1 }
+    }
+
+    #[test]
+    fn daily_report_summarizes_each_day_in_either_order() {
+        // 2024-01-15 09:30, 09:31, 09:32 and a single bar on 2024-01-16 09:30.
+        let bars = [
+            bar(1705311000, 101.0, 99.0, 10),
+            bar(1705311060, 103.0, 100.0, 20),
+            bar(1705311120, 103.0, 98.0, 30),
+            bar(1705397400, 105.0, 104.0, 5),
+        ];
+        let records: Vec<csv_processor::ProcessedRecord> = bars.iter().map(csv_processor::ProcessedRecord::from).collect();
+        let index = csv_processor::build_index(&records).unwrap();
+
+        for storage_format in [cli::StorageFormat::Aos, cli::StorageFormat::Soa] {
+            let buf = csv_processor::build_flatbuffer(&records, storage_format.clone(), utils::SCHEMA_VERSION, true);
+            let report = daily_report(&[&buf], &storage_format, &index.daily_index).unwrap();
+
+            assert_eq!(report.len(), 2);
+            let day = &report[0];
+            assert_eq!((day.date.as_str(), day.bars, day.volume), ("2024-01-15", 3, 60));
+            assert_eq!((day.open, day.close), (99.5, 102.5));
+            assert_eq!((day.high, day.high_time.as_str()), (103.0, "09:31:00"));
+            assert_eq!((day.low, day.low_time.as_str()), (98.0, "09:32:00"));
+            assert_eq!(day.range, 5.0);
+
+            let single = &report[1];
+            assert_eq!((single.bars, single.range), (1, 1.0));
+            assert_eq!(single.high_time, single.low_time);
+
+            // Newest-first storage with the daily index pointing backwards gives the same rows.
+            let reversed: Vec<csv_processor::ProcessedRecord> = records.iter().rev().cloned().collect();
+            let desc_buf = csv_processor::build_flatbuffer(&reversed, storage_format.clone(), utils::SCHEMA_VERSION, true);
+            let desc_index: Vec<index::DailyIndexEntry> = index
+                .daily_index
+                .iter()
+                .map(|e| index::DailyIndexEntry {
+                    date: e.date.clone(),
+                    start_index: 3 - e.start_index,
+                    end_index: 3 - e.end_index,
+                })
+                .collect();
+            assert_eq!(daily_report(&[&desc_buf], &storage_format, &desc_index).unwrap(), report);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::resample;
+use crate::read_flatbuffers;
+
+/// Outcome of comparing two sequences of bars position by position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarDiff {
+    /// Number of bars on each side.
+    pub lengths: (usize, usize),
+    /// First position whose bars differ, or that only the longer side has.
+    pub first_mismatch: Option<usize>,
+    /// Positions whose bars differ, counting every bar only one side has.
+    pub mismatches: usize,
+}
+
+impl BarDiff {
+    /// Whether both sides hold the same bars.
+    pub fn is_identical(&self) -> bool {
+        self.first_mismatch.is_none()
+    }
+}
+
+/// Whether two bars match: same timestamp and volume, prices within `epsilon`.
+///
+/// NaN matches NaN, so a NaN carried through unchanged by both sides is not a difference.
+fn bars_match(a: &resample::OHLCVBar, b: &resample::OHLCVBar, epsilon: f64) -> bool {
+    let price_matches = |x: f64, y: f64| (x.is_nan() && y.is_nan()) || (x - y).abs() <= epsilon;
+    a.timestamp == b.timestamp
+        && a.volume == b.volume
+        && price_matches(a.open, b.open)
+        && price_matches(a.high, b.high)
+        && price_matches(a.low, b.low)
+        && price_matches(a.close, b.close)
+}
+
+/// Compares two sequences of bars position by position.
+///
+/// # Arguments
+/// * `a`, `b` - The bars to compare, in time order.
+/// * `epsilon` - Largest absolute difference at which two prices still match.
+///
+/// # Returns
+/// * `BarDiff` - Lengths, first differing position and number of differing positions.
+pub fn diff_bars(a: &[resample::OHLCVBar], b: &[resample::OHLCVBar], epsilon: f64) -> BarDiff {
+    let mut first_mismatch = None;
+    let mut mismatches = 0;
+    for (i, (bar_a, bar_b)) in a.iter().zip(b).enumerate() {
+        if !bars_match(bar_a, bar_b, epsilon) {
+            first_mismatch.get_or_insert(i);
+            mismatches += 1;
+        }
+    }
+    let common = a.len().min(b.len());
+    if a.len() != b.len() {
+        first_mismatch.get_or_insert(common);
+        mismatches += a.len().max(b.len()) - common;
+    }
+    BarDiff { lengths: (a.len(), b.len()), first_mismatch, mismatches }
+}
+
+/// Reads two .bin files (AOS or SOA, in any combination) and compares their bars.
+///
+/// Both files are loaded in ascending time with `read_flatbuffers::load_bars`, so a file
+/// written with `--order desc` compares equal to its ascending twin.
+///
+/// # Arguments
+/// * `a`, `b` - Paths of the .bin files.
+/// * `epsilon` - Largest absolute difference at which two prices still match.
+///
+/// # Returns
+/// * `anyhow::Result<(BarDiff, Vec<OHLCVBar>, Vec<OHLCVBar>)>` - The comparison and the bars
+///   of both files, for reporting the first difference.
+///
+/// # Errors
+/// * If either file can't be read (see `load_bars`).
+pub fn diff_files(
+    a: &std::path::Path,
+    b: &std::path::Path,
+    epsilon: f64,
+) -> anyhow::Result<(BarDiff, Vec<resample::OHLCVBar>, Vec<resample::OHLCVBar>)> {
+    let bars_a = read_flatbuffers::load_bars(a)?;
+    let bars_b = read_flatbuffers::load_bars(b)?;
+    let diff = diff_bars(&bars_a, &bars_b, epsilon);
+    anyhow::Ok((diff, bars_a, bars_b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(timestamp: u64, close: f64) -> resample::OHLCVBar {
+        resample::OHLCVBar { timestamp, open: 1.0, high: 2.0, low: 0.5, close, volume: 10, count: 1 }
+    }
+
+    #[test]
+    fn diff_reports_first_mismatch_count_and_extra_bars() {
+        let a = [bar(60, 1.5), bar(120, 1.5), bar(180, 1.5), bar(240, 1.5)];
+        let b = [bar(60, 1.5 + 1e-12), bar(120, 1.75), bar(180, 1.5)];
+
+        let exact = diff_bars(&a, &b, 0.0);
+        let tolerant = diff_bars(&a, &b, 1e-9);
+
+        assert_eq!(exact, BarDiff { lengths: (4, 3), first_mismatch: Some(0), mismatches: 3 });
+        assert_eq!(tolerant, BarDiff { lengths: (4, 3), first_mismatch: Some(1), mismatches: 2 });
+        assert!(diff_bars(&a, &a, 0.0).is_identical());
+        assert_eq!(diff_bars(&a[..1], &a, 0.0).first_mismatch, Some(1));
+    }
+}
@@ -9,24 +9,17 @@ pub fn get_list_files_in_dir<P: AsRef<std::path::Path>>(dir_path: P, ext_filter:
     let mut files: Vec<String> = Vec::new();
 
     let dir_read_result: Result<std::fs::ReadDir, std::io::Error> = std::fs::read_dir(dir_path.as_ref());
-    let dir_entries = match dir_read_result {
-        Ok(entries) => entries,
-        Err(e) => return Err(e.into()),
-    };
+    let dir_entries = dir_read_result?;
 
     for entry in dir_entries {
-        let dir_entry = match entry {
-            Ok(entry) => entry,
-            Err(e) => return Err(e.into()),
-        };
+        let dir_entry = entry?;
         let path = dir_entry.path();
 
-        if path.is_file() {
-            if let Some(filter) = ext_filter {
-                if path.extension().map(|e| e == filter).unwrap_or(false) {
-                    files.push(path.to_string_lossy().into_owned());
-                }
-            }
+        if path.is_file()
+            && let Some(filter) = ext_filter
+            && path.extension().map(|e| e == filter).unwrap_or(false)
+        {
+            files.push(path.to_string_lossy().into_owned());
         }
     }
     Ok(files)
@@ -43,30 +36,93 @@ pub fn check_path<P: AsRef<std::path::Path>> (path_argument: P) -> anyhow::Resul
     let metadata_path_in = std::fs::metadata(&path_argument)?;
 
     if !metadata_path_in.is_dir() {
-        return Err(anyhow::anyhow!("Provide diretcory, not file!"));
+        Err(anyhow::anyhow!(
+            "Input path {} is a file, expected a directory",
+            path_argument.as_ref().display()
+        ))
     } else { Ok(()) }
 }
 
+/// Checks that `path` can be used as an output directory.
+///
+/// A missing path is fine (it will be created), but an existing regular file is
+/// rejected up front instead of failing later with an obscure `read_dir` error.
+///
+/// # Arguments
+/// * `path` - Intended output directory.
+///
+/// # Returns
+/// * `Result<()>` - Success if the path is a directory or doesn't exist yet.
+pub fn check_output_dir<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if path.exists() && !path.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Output path {} exists and is a file, expected a directory",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that `path` can be used as an output file.
+///
+/// # Arguments
+/// * `path` - Intended output file.
+///
+/// # Returns
+/// * `Result<()>` - Success if the path is a file or doesn't exist yet.
+pub fn check_output_file<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Output path {} exists and is a directory, expected a file",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that a file is not larger than `max_size` bytes.
+///
+/// Only the file metadata is read, so the check is cheap even for huge files.
+///
+/// # Arguments
+/// * `path` - File to check.
+/// * `max_size` - Maximum allowed size in bytes.
+///
+/// # Returns
+/// * `Result<()>` - Success if the file fits, error if it is too large or can't be stat'ed.
+pub fn check_file_size<P: AsRef<std::path::Path>>(path: P, max_size: u64) -> anyhow::Result<()> {
+    let size = std::fs::metadata(path.as_ref())?.len();
+    if size > max_size {
+        return Err(anyhow::anyhow!(
+            "Skipped: file is {} bytes, larger than --max-file-size ({} bytes)",
+            size,
+            max_size
+        ));
+    }
+    Ok(())
+}
+
 /// Ensures parent directory exists; creates it if necessary.
 ///
 /// Also ensures the target output directory exists and is clean.
 ///
 /// # Arguments
 /// * `out_dir_path` - Output directory path.
+/// * `dir_mode` - Permission bits for the directories created (`--dir-mode`, Unix only; see
+///   `create_dir_chain`). `None` leaves them to the umask.
 ///
 /// # Returns
-/// * `Result<()>` - Success or error if creation fails.
-pub fn ensure_parent_dir_exist<P: AsRef<std::path::Path>>(out_dir_path: P) -> anyhow::Result<()> {
+/// * `Result<()>` - Success or error if creation fails or `out_dir_path` is an existing file.
+pub fn ensure_parent_dir_exist<P: AsRef<std::path::Path>>(out_dir_path: P, dir_mode: Option<u32>) -> anyhow::Result<()> {
     let out_dir = out_dir_path.as_ref();
-    let parent_dir = match std::path::Path::new(out_dir_path.as_ref()).parent() {
-        Some(dir) => dir.to_path_buf(),
-        None => return Err(anyhow::anyhow!("Pls input correct path of output dir of Parquet files")),
-    };
-    if !parent_dir.exists() {
-        std::fs::create_dir_all(parent_dir)?;
+    check_output_dir(out_dir)?;
+    if out_dir.parent().is_none() {
+        return Err(anyhow::anyhow!("Pls input correct path of output dir of Parquet files"));
     }
     if !out_dir.exists() {
-        std::fs::create_dir_all(out_dir)?;
+        create_dir_chain(out_dir, dir_mode)?;
     } else {
         for entry in std::fs::read_dir(out_dir)? {
             let path = entry?.path();
@@ -78,3 +134,340 @@ pub fn ensure_parent_dir_exist<P: AsRef<std::path::Path>>(out_dir_path: P) -> an
 
     Ok(())
 }
+
+/// Creates `dir` and every missing parent of it.
+///
+/// With `dir_mode` (Unix), each directory is created by a single `mkdir` with that mode,
+/// then `chmod`ed to exactly it, since `mkdir` applies the umask. The umask can only clear
+/// bits, so no directory of the chain is ever more permissive than asked for, and a
+/// directory another process creates concurrently is accepted as is. Directories that
+/// already existed keep their permissions. On other platforms `dir_mode` is ignored.
+///
+/// # Arguments
+/// * `dir` - Directory to create.
+/// * `dir_mode` - Permission bits, e.g. `0o750`; `None` leaves them to the umask.
+///
+/// # Returns
+/// * `Result<()>` - Success or an error naming the directory that couldn't be created.
+fn create_dir_chain(dir: &std::path::Path, dir_mode: Option<u32>) -> anyhow::Result<()> {
+    let missing: Vec<&std::path::Path> = dir
+        .ancestors()
+        .take_while(|d| !d.as_os_str().is_empty() && !d.exists())
+        .collect();
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    if let Some(mode) = dir_mode {
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, mode);
+    }
+    builder.create(dir).map_err(|e| io_error(e, "create directory", dir))?;
+
+    #[cfg(unix)]
+    if let Some(mode) = dir_mode {
+        use std::os::unix::fs::PermissionsExt;
+        for created in missing.iter().rev() {
+            std::fs::set_permissions(created, std::fs::Permissions::from_mode(mode))
+                .map_err(|e| io_error(e, "set permissions of", created))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (dir_mode, missing);
+    Ok(())
+}
+
+/// Default capacity of the input read buffer and the segment write buffer (`--io-buffer-size`).
+///
+/// Larger than `BufReader`'s 8 KiB default so big inputs are read in fewer system calls,
+/// which matters most on spinning disks and network filesystems.
+pub const DEFAULT_IO_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Wraps an I/O error with the action and path it failed on.
+///
+/// The message reads e.g. `Failed to open input 'data/AAPL.csv': permission denied (os error 13)`,
+/// which identifies the file in a batch of hundreds. The `std::io::Error` stays the
+/// source of the returned error, so `retry_io` can still recognise transient failures.
+///
+/// # Arguments
+/// * `error` - The I/O error.
+/// * `action` - What was being done, e.g. `open input` or `write`.
+/// * `path` - The file the action was on.
+///
+/// # Returns
+/// * `anyhow::Error` - The error with its context attached.
+pub fn io_error<P: AsRef<std::path::Path>>(error: std::io::Error, action: &str, path: P) -> anyhow::Error {
+    let reason = match error.raw_os_error() {
+        Some(code) => format!("{} (os error {})", error.kind(), code),
+        None => error.to_string(),
+    };
+    let message = format!("Failed to {} '{}': {}", action, path.as_ref().display(), reason);
+    anyhow::Error::new(error).context(message)
+}
+
+/// Writes `data` to `path` atomically.
+///
+/// The bytes go to a `<file name>.tmp` sibling first, are flushed to disk, and the
+/// sibling is then renamed over `path`. Readers therefore see either the previous file
+/// or the complete new one, never a truncated write, even if the process is killed or
+/// the machine loses power mid-way. A failed write leaves no `.tmp` file behind.
+///
+/// # Arguments
+/// * `path` - Destination file path.
+/// * `data` - Bytes to write.
+///
+/// # Returns
+/// * `Result<()>` - Success or error if writing, syncing or renaming fails.
+pub fn write_atomic<P: AsRef<std::path::Path>>(path: P, data: &[u8]) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    write_atomic_with(path, |file| {
+        std::io::Write::write_all(file, data).map_err(|e| io_error(e, "write", path))
+    })
+}
+
+/// Writes a file atomically like `write_atomic`, with the contents produced by `write`.
+///
+/// Used when the contents are built piece by piece (e.g. SOA segments) and shouldn't
+/// be held in memory all at once. If `write` fails, the `.tmp` file is removed and
+/// `path` is left untouched.
+///
+/// # Arguments
+/// * `path` - Destination file path.
+/// * `write` - Writes the contents to the temporary file.
+///
+/// # Returns
+/// * `Result<()>` - Success or error if `write`, syncing or renaming fails.
+pub fn write_atomic_with<P, F>(path: P, write: F) -> anyhow::Result<()>
+where
+    P: AsRef<std::path::Path>,
+    F: FnOnce(&mut std::fs::File) -> anyhow::Result<()>,
+{
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Invalid output path: {}", path.display()))?;
+    let mut tmp_name = file_name.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let written = std::fs::File::create(&tmp_path).map_err(|e| io_error(e, "create", &tmp_path)).and_then(|mut file| {
+        write(&mut file)?;
+        // Without this, a power loss right after the rename can leave an empty file under the final name.
+        file.sync_all().map_err(|e| io_error(e, "sync", &tmp_path))?;
+        anyhow::Ok(())
+    });
+    if let Err(e) = written.and_then(|_| std::fs::rename(&tmp_path, path).map_err(|e| io_error(e, "rename into place", path))) {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Base delay before the first retry in `retry_io`; doubled after each failed attempt.
+const RETRY_BASE_DELAY_MS: u64 = 100;
+
+/// Returns true if `error` is an I/O error worth retrying.
+///
+/// Transient kinds are interruptions and timeouts plus the raw `EIO` / `ESTALE` errors
+/// that network filesystems such as NFS report while recovering. Parse failures and
+/// missing files are not transient.
+fn is_transient_io_error(error: &anyhow::Error) -> bool {
+    const EIO: i32 = 5;
+    const ESTALE: i32 = 116;
+
+    match error.downcast_ref::<std::io::Error>() {
+        Some(io_error) => {
+            matches!(
+                io_error.kind(),
+                std::io::ErrorKind::Interrupted | std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ) || matches!(io_error.raw_os_error(), Some(EIO) | Some(ESTALE))
+        }
+        None => false,
+    }
+}
+
+/// Runs `op`, retrying up to `retries` times with exponential backoff on transient I/O errors.
+///
+/// The first retry waits `RETRY_BASE_DELAY_MS`, and every further retry doubles the delay.
+/// Non-transient errors (see `is_transient_io_error`) are returned immediately, so
+/// `retries == 0` keeps plain fail-fast behaviour.
+///
+/// # Arguments
+/// * `retries` - Maximum number of retries after the first attempt.
+/// * `description` - What is being attempted, used in the retry log message.
+/// * `op` - The fallible I/O operation.
+///
+/// # Returns
+/// * `Result<T>` - The first successful result, or the last error.
+pub fn retry_io<T, F: FnMut() -> anyhow::Result<T>>(retries: u32, description: &str, mut op: F) -> anyhow::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < retries && is_transient_io_error(&e) => {
+                let delay_ms = RETRY_BASE_DELAY_MS << attempt.min(16);
+                attempt += 1;
+                tracing::warn!(
+                    "🔁 Transient I/O error on {} ({}), retry {}/{} in {} ms",
+                    description, e, attempt, retries, delay_ms
+                );
+                std::thread::sleep(std::time::Duration::from_millis(delay_ms));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Size of the raw and decoded buffers of `TranscodingReader`.
+const TRANSCODE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Reader that decodes `inner` from a legacy encoding to UTF-8 as it is read.
+///
+/// Decoding is streamed through fixed-size buffers, so large inputs aren't loaded whole.
+/// Bytes that are invalid in the source encoding become U+FFFD, and a BOM matching the
+/// encoding is dropped.
+pub struct TranscodingReader<R> {
+    inner: R,
+    decoder: encoding_rs::Decoder,
+    raw: Vec<u8>,
+    raw_pos: usize,
+    raw_len: usize,
+    decoded: Vec<u8>,
+    decoded_pos: usize,
+    decoded_len: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl<R: std::io::Read> TranscodingReader<R> {
+    /// Wraps `inner`, whose bytes are in `encoding`.
+    pub fn new(inner: R, encoding: &'static encoding_rs::Encoding) -> Self {
+        TranscodingReader {
+            inner,
+            decoder: encoding.new_decoder_with_bom_removal(),
+            raw: vec![0; TRANSCODE_BUFFER_SIZE],
+            raw_pos: 0,
+            raw_len: 0,
+            decoded: vec![0; TRANSCODE_BUFFER_SIZE],
+            decoded_pos: 0,
+            decoded_len: 0,
+            eof: false,
+            done: false,
+        }
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for TranscodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if self.decoded_pos < self.decoded_len {
+                let n = buf.len().min(self.decoded_len - self.decoded_pos);
+                buf[..n].copy_from_slice(&self.decoded[self.decoded_pos..self.decoded_pos + n]);
+                self.decoded_pos += n;
+                return Ok(n);
+            }
+            if self.done {
+                return Ok(0);
+            }
+            if self.raw_pos == self.raw_len && !self.eof {
+                self.raw_len = self.inner.read(&mut self.raw)?;
+                self.raw_pos = 0;
+                self.eof = self.raw_len == 0;
+            }
+            let (result, read, written, _) =
+                self.decoder.decode_to_utf8(&self.raw[self.raw_pos..self.raw_len], &mut self.decoded, self.eof);
+            self.raw_pos += read;
+            self.decoded_pos = 0;
+            self.decoded_len = written;
+            self.done = self.eof && result == encoding_rs::CoderResult::InputEmpty;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn existing_file_is_rejected_as_output_dir() {
+        let dir = scratch_dir("file_as_dir");
+        let file = dir.join("out");
+        std::fs::write(&file, b"not a directory").unwrap();
+
+        let err = ensure_parent_dir_exist(&file, None).unwrap_err().to_string();
+        assert!(err.contains("exists and is a file, expected a directory"), "{}", err);
+        assert!(check_output_dir(&dir).is_ok());
+        assert!(check_output_dir(dir.join("missing")).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dir_mode_applies_to_every_created_directory() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = scratch_dir("dir_mode");
+        let out = dir.join("a").join("b").join("out");
+        let mode = |p: &std::path::Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o7777;
+        let existing_mode = mode(&dir);
+
+        ensure_parent_dir_exist(&out, Some(0o750)).unwrap();
+
+        assert_eq!(mode(&dir.join("a")), 0o750);
+        assert_eq!(mode(&dir.join("a").join("b")), 0o750);
+        assert_eq!(mode(&out), 0o750);
+        assert_eq!(mode(&dir), existing_mode);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn existing_dir_is_rejected_as_output_file() {
+        let dir = scratch_dir("dir_as_file");
+
+        let err = check_output_file(&dir).unwrap_err().to_string();
+        assert!(err.contains("exists and is a directory, expected a file"), "{}", err);
+        assert!(check_output_file(dir.join("merged.aos.bin")).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn transcoding_reader_decodes_legacy_encodings() {
+        use std::io::Read;
+
+        // "<DATE>;Цена" in Windows-1251.
+        let cp1251 = b"<DATE>;\xd6\xe5\xed\xe0";
+        let mut decoded = String::new();
+        TranscodingReader::new(&cp1251[..], encoding_rs::WINDOWS_1251).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, "<DATE>;Цена");
+
+        // Longer than one buffer, so decoding spans several refills.
+        let latin1: Vec<u8> = std::iter::repeat_n(b"caf\xe9,".as_slice(), TRANSCODE_BUFFER_SIZE).flatten().copied().collect();
+        let mut decoded = String::new();
+        TranscodingReader::new(latin1.as_slice(), encoding_rs::WINDOWS_1252).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, "café,".repeat(TRANSCODE_BUFFER_SIZE));
+    }
+
+    #[test]
+    fn io_errors_name_the_file_and_stay_retryable() {
+        let dir = scratch_dir("io_error");
+        let missing = dir.join("AAPL.csv");
+
+        let err = std::fs::File::open(&missing).map_err(|e| io_error(e, "open input", &missing)).unwrap_err();
+        let message = err.to_string();
+        assert!(message.starts_with(&format!("Failed to open input '{}': ", missing.display())), "{}", message);
+        assert!(message.contains("not found"), "{}", message);
+
+        let timed_out = io_error(std::io::Error::from(std::io::ErrorKind::TimedOut), "open", &missing);
+        assert!(is_transient_io_error(&timed_out));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
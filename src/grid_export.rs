@@ -0,0 +1,200 @@
+use crate::cli;
+use crate::index;
+use crate::resample;
+use crate::csv_processor;
+use crate::file_processing;
+use crate::read_flatbuffers;
+
+/// One boundary of a fixed timeframe grid: one row of a `.grid-<tf>.csv` (`--grid-mark-missing`).
+///
+/// Every boundary gets a row, whether or not it has bars. A boundary without bars has
+/// `present` false, NaN prices and a zero volume and count, so it can't be mistaken for
+/// a real (or forward-filled) bar.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GridBar {
+    /// The boundary, i.e. the start of the bucket.
+    pub timestamp: u64,
+    /// Whether any bar falls into the bucket.
+    pub present: bool,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+    /// Number of source bars aggregated into the bucket.
+    pub count: u64,
+}
+
+impl GridBar {
+    /// The row of a boundary with no bars.
+    fn missing(timestamp: u64) -> Self {
+        GridBar {
+            timestamp,
+            present: false,
+            open: f64::NAN,
+            high: f64::NAN,
+            low: f64::NAN,
+            close: f64::NAN,
+            volume: 0,
+            count: 0,
+        }
+    }
+}
+
+impl From<resample::OHLCVBar> for GridBar {
+    fn from(bar: resample::OHLCVBar) -> Self {
+        GridBar {
+            timestamp: bar.timestamp,
+            present: true,
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            volume: bar.volume,
+            count: bar.count,
+        }
+    }
+}
+
+/// Aggregates `bars` onto the complete boundary list of one `timeframe_index` entry.
+///
+/// Buckets are aligned like the boundaries themselves (`ts / tf * tf`, UTC midnight for
+/// `1d`), so every bucket lines up with exactly one boundary; `bar_timestamp`,
+/// `--bucket-edge` and `--bucket-anchor` don't apply.
+///
+/// # Arguments
+/// * `bars` - The stored bars, in any order.
+/// * `boundaries` - The timeframe's `timeframe_index` boundaries, ascending.
+/// * `timeframe_sec` - Bucket size of the boundaries.
+///
+/// # Returns
+/// * `Vec<GridBar>` - One row per boundary, in boundary order.
+pub fn grid_bars(bars: &[resample::OHLCVBar], boundaries: &[u64], timeframe_sec: u64) -> Vec<GridBar> {
+    let mut ordered = bars.to_vec();
+    ordered.sort_by_key(|bar| bar.timestamp);
+
+    let mut buckets: Vec<(u64, resample::OhlcvAggregate)> = Vec::new();
+    for bar in &ordered {
+        let start = bar.timestamp / timeframe_sec * timeframe_sec;
+        match buckets.last_mut() {
+            Some((last, aggregate)) if *last == start => aggregate.add(bar),
+            _ => {
+                let mut aggregate = resample::OhlcvAggregate::default();
+                aggregate.add(bar);
+                buckets.push((start, aggregate));
+            }
+        }
+    }
+
+    let mut buckets = buckets.into_iter().peekable();
+    boundaries
+        .iter()
+        .map(|&boundary| {
+            // Buckets before the grid (not possible for the file's own index) are dropped.
+            while buckets.next_if(|(start, _)| *start < boundary).is_some() {}
+            match buckets.next_if(|(start, _)| *start == boundary) {
+                Some((start, aggregate)) => aggregate
+                    .finish(start, &cli::BarTimestamp::BucketStart)
+                    .map_or_else(|| GridBar::missing(boundary), GridBar::from),
+                None => GridBar::missing(boundary),
+            }
+        })
+        .collect()
+}
+
+/// Builds the grid of a .bin file for `timeframe` from its `.idx`.
+///
+/// # Arguments
+/// * `segments` - The mapped .bin file, split into its segments.
+/// * `storage_format` - Layout of `segments`.
+/// * `full_index` - The file's index, whose `timeframe_index` holds the boundaries.
+/// * `timeframe` - Timeframe name as given to `--resample`, e.g. `5min`.
+///
+/// # Returns
+/// * `anyhow::Result<Vec<GridBar>>` - One row per boundary.
+///
+/// # Errors
+/// * If `timeframe` has no `timeframe_index` entry (e.g. `90min`), or the `.idx` was written
+///   without it (`--timeframes`).
+/// * If the file can't be parsed.
+pub fn grid(
+    segments: &[&[u8]],
+    storage_format: &cli::StorageFormat,
+    full_index: &index::FullIndex,
+    timeframe: &str,
+) -> anyhow::Result<Vec<GridBar>> {
+    let timeframe_sec = match resample::Timeframe::parse(timeframe)? {
+        resample::Timeframe::Intraday(timeframe_sec) => timeframe_sec,
+        resample::Timeframe::Daily => 86400,
+    };
+    let key = csv_processor::index_timeframe_keys(&[timeframe.to_string()])
+        .pop()
+        .ok_or_else(|| anyhow::anyhow!("--grid-mark-missing: {} has no timeframe_index entry to take the grid from", timeframe))?;
+    let boundaries = full_index.timeframe_index.get(&key).ok_or_else(|| {
+        anyhow::anyhow!("--grid-mark-missing: the .idx has no {} timeframe_index; convert with {} in --timeframes", key, timeframe)
+    })?;
+    let bars: Vec<resample::OHLCVBar> = read_flatbuffers::iter_segments(segments, storage_format)?.collect();
+    anyhow::Ok(grid_bars(&bars, boundaries, timeframe_sec))
+}
+
+/// Writes the grid of a .bin file next to it, e.g. `data.aos.grid-5min.csv`.
+///
+/// # Arguments
+/// * `path` - Path to the .bin file.
+/// * `segments` - The mapped file, split into its segments.
+/// * `storage_format` - Layout of `segments`.
+/// * `full_index` - The file's index.
+/// * `timeframe` - Timeframe name, e.g. `5min`.
+///
+/// # Returns
+/// * `anyhow::Result<(std::path::PathBuf, usize, usize)>` - Path of the grid, its number of
+///   rows and how many of them are missing.
+///
+/// # Errors
+/// * If the grid can't be computed (see `grid`) or written.
+pub fn write_grid(
+    path: &std::path::Path,
+    segments: &[&[u8]],
+    storage_format: &cli::StorageFormat,
+    full_index: &index::FullIndex,
+    timeframe: &str,
+) -> anyhow::Result<(std::path::PathBuf, usize, usize)> {
+    let rows = grid(segments, storage_format, full_index, timeframe)?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in &rows {
+        writer.serialize(row)?;
+    }
+    let data = writer.into_inner().map_err(|e| anyhow::anyhow!("Failed to write grid: {}", e))?;
+    let grid_path = path.with_extension(format!("grid-{}.csv", timeframe));
+    file_processing::write_atomic(&grid_path, &data)?;
+
+    let missing = rows.iter().filter(|row| !row.present).count();
+    anyhow::Ok((grid_path, rows.len(), missing))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(timestamp: u64, close: f64, volume: u64) -> resample::OHLCVBar {
+        resample::OHLCVBar { timestamp, open: close - 0.5, high: close + 1.0, low: close - 1.0, close, volume, count: 1 }
+    }
+
+    #[test]
+    fn every_boundary_gets_a_row_and_gaps_are_marked() {
+        // 10:00 and 10:01, nothing in 10:05, then 10:12, given newest-first.
+        let bars = [bar(1705313520, 3.0, 30), bar(1705312860, 2.0, 20), bar(1705312800, 1.0, 10)];
+        let boundaries = [1705312800, 1705313100, 1705313400];
+
+        let rows = grid_bars(&bars, &boundaries, 300);
+
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().zip(boundaries).all(|(row, boundary)| row.timestamp == boundary));
+        assert_eq!((rows[0].present, rows[0].open, rows[0].close, rows[0].volume, rows[0].count), (true, 0.5, 2.0, 30, 2));
+        assert!(!rows[1].present);
+        assert!(rows[1].open.is_nan() && rows[1].close.is_nan());
+        assert_eq!((rows[1].volume, rows[1].count), (0, 0));
+        assert_eq!((rows[2].present, rows[2].close, rows[2].volume), (true, 3.0, 30));
+    }
+}
@@ -14,9 +14,355 @@ pub struct DailyIndexEntry {
 }
 
 /// Full index structure saved as .idx file.
+///
+/// `timeframe_index` has a handful of fixed keys and is only read whole (nothing looks
+/// keys up on a hot path), so a `BTreeMap` is kept over an enum-keyed struct: bincode
+/// encodes it exactly like the `HashMap` older files were written with, but in sorted key
+/// order, so converting the same input twice yields byte-identical .idx files.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FullIndex {
     pub time_index: Vec<TimeIndexEntry>,
     pub daily_index: Vec<DailyIndexEntry>,
-    pub timeframe_index: std::collections::HashMap<String, Vec<u64>>,       // "3m" → [timestamp1, timestamp2...]
+    pub timeframe_index: std::collections::BTreeMap<String, Vec<u64>>,       // "3m" → [timestamp1, timestamp2...]
+    pub source_line_index: Option<Vec<u64>>,    // bar position → input line (only with --track-source-lines)
+    pub symbol: Option<String>,                 // instrument the file represents (only with --symbol)
+    pub segment_offsets: Option<Vec<u64>>,      // byte offset of every SOA segment in the .bin (only with --soa-segment-size)
+    pub timeframe_positions: Option<std::collections::BTreeMap<String, Vec<u64>>>, // "3m" → time_index position of each boundary (only with --precompute-tf-positions)
+}
+
+impl FullIndex {
+    /// Returns the first `time_index` entry at or after `timestamp`.
+    ///
+    /// Timestamps are stored in whole seconds, so sub-second ticks can share one. All of
+    /// them are kept as separate entries, and this always lands on the first of such a
+    /// run rather than an arbitrary one. `None` if every bar is older than `timestamp`.
+    pub fn seek_timestamp(&self, timestamp: u64) -> Option<&TimeIndexEntry> {
+        self.time_index.get(self.time_index.partition_point(|e| e.timestamp < timestamp))
+    }
+
+    /// Returns the `time_index` entries with `start <= timestamp <= end`.
+    ///
+    /// Every bar sharing a boundary timestamp is included, so a query for a single
+    /// second (`start == end`) returns all of its bars. Empty if `start > end`.
+    pub fn query_range(&self, start: u64, end: u64) -> &[TimeIndexEntry] {
+        let from = self.time_index.partition_point(|e| e.timestamp < start);
+        let to = self.time_index.partition_point(|e| e.timestamp <= end);
+        self.time_index.get(from..to).unwrap_or_default()
+    }
+
+    /// Returns the input line that produced the bar at `timestamp`.
+    ///
+    /// If several bars share `timestamp`, this is the line of the first one.
+    /// `None` if the bar doesn't exist or source lines weren't tracked for this file.
+    pub fn source_line_for(&self, timestamp: u64) -> Option<u64> {
+        let lines = self.source_line_index.as_ref()?;
+        let entry = self.query_range(timestamp, timestamp).first()?;
+        lines.get(entry.index as usize).copied()
+    }
+
+    /// Returns the start of the `n`-th bucket of `timeframe` and its `time_index` entries.
+    ///
+    /// Buckets are the `timeframe_index` boundaries, and `timeframe_positions` holds the
+    /// `time_index` position of the first bar at or after each of them, so bucket `n` is
+    /// the entries between its position and the next one, found without a scan. A boundary
+    /// with no bars gets an empty slice. `None` if `n` is past the last boundary or the
+    /// positions weren't precomputed for `timeframe` (e.g. "5m").
+    pub fn timeframe_bucket(&self, timeframe: &str, n: usize) -> Option<(u64, &[TimeIndexEntry])> {
+        let boundaries = self.timeframe_index.get(timeframe)?;
+        let positions = self.timeframe_positions.as_ref()?.get(timeframe)?;
+        let start = *boundaries.get(n)?;
+        let from = *positions.get(n)? as usize;
+        let to = positions.get(n + 1).map_or(self.time_index.len(), |&p| p as usize);
+        Some((start, self.time_index.get(from..to)?))
+    }
+}
+
+/// Computes `FullIndex::timeframe_positions` for an index's `time_index` and `timeframe_index`.
+///
+/// Each boundary maps to the position in `time_index` (which is in ascending time, whatever
+/// the storage order) of the first bar at or after it.
+pub fn timeframe_positions(
+    time_index: &[TimeIndexEntry],
+    timeframe_index: &std::collections::BTreeMap<String, Vec<u64>>,
+) -> std::collections::BTreeMap<String, Vec<u64>> {
+    timeframe_index
+        .iter()
+        .map(|(timeframe, boundaries)| {
+            let positions = boundaries
+                .iter()
+                .map(|&boundary| time_index.partition_point(|e| e.timestamp < boundary) as u64)
+                .collect();
+            (timeframe.clone(), positions)
+        })
+        .collect()
+}
+
+/// Layout of .idx files written before they had an `INDEX_MAGIC` header.
+///
+/// bincode isn't self-describing, so these files can't be read as `FullIndex` directly.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct LegacyFullIndex {
+    pub time_index: Vec<TimeIndexEntry>,
+    pub daily_index: Vec<DailyIndexEntry>,
+    pub timeframe_index: std::collections::BTreeMap<String, Vec<u64>>,
+}
+
+impl From<LegacyFullIndex> for FullIndex {
+    fn from(legacy: LegacyFullIndex) -> Self {
+        FullIndex {
+            time_index: legacy.time_index,
+            daily_index: legacy.daily_index,
+            timeframe_index: legacy.timeframe_index,
+            source_line_index: None,
+            symbol: None,
+            segment_offsets: None,
+            timeframe_positions: None,
+        }
+    }
+}
+
+/// Magic bytes that open a plain .idx file, followed by an `INDEX_VERSION` byte.
+///
+/// Files from before the header are bare bincode (`LegacyFullIndex`); they start with the
+/// little-endian `u64` length of `time_index`, which can only spell these bytes (or
+/// `COMPACT_INDEX_MAGIC`) past 1.4 billion bars.
+pub const INDEX_MAGIC: &[u8; 4] = b"FIDX";
+
+/// Version of the plain layout, stored in the byte right after `INDEX_MAGIC`.
+pub const INDEX_VERSION: u8 = 1;
+
+/// Magic bytes that open a compact .idx file (`--compact-index`).
+pub const COMPACT_INDEX_MAGIC: &[u8; 4] = b"CIDX";
+
+/// Version of the compact layout, stored in the byte right after `COMPACT_INDEX_MAGIC`.
+///
+/// Version 2 added `segment_offsets` and version 3 `timeframe_positions`; older files are
+/// still read (`CompactFullIndexV1`, `CompactFullIndexV2`).
+pub const COMPACT_INDEX_VERSION: u8 = 3;
+
+/// Layout of compact .idx files after the header.
+///
+/// Same as `FullIndex`, except `time_index` is packed by `encode_time_index`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CompactFullIndex {
+    pub time_index: Vec<u8>,
+    pub daily_index: Vec<DailyIndexEntry>,
+    pub timeframe_index: std::collections::BTreeMap<String, Vec<u64>>,
+    pub source_line_index: Option<Vec<u64>>,
+    pub symbol: Option<String>,
+    pub segment_offsets: Option<Vec<u64>>,
+    pub timeframe_positions: Option<std::collections::BTreeMap<String, Vec<u64>>>,
+}
+
+/// Layout of version 2 compact .idx files, written before `timeframe_positions` existed.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct CompactFullIndexV2 {
+    pub time_index: Vec<u8>,
+    pub daily_index: Vec<DailyIndexEntry>,
+    pub timeframe_index: std::collections::BTreeMap<String, Vec<u64>>,
+    pub source_line_index: Option<Vec<u64>>,
+    pub symbol: Option<String>,
+    pub segment_offsets: Option<Vec<u64>>,
+}
+
+impl From<CompactFullIndexV2> for CompactFullIndex {
+    fn from(v2: CompactFullIndexV2) -> Self {
+        CompactFullIndex {
+            time_index: v2.time_index,
+            daily_index: v2.daily_index,
+            timeframe_index: v2.timeframe_index,
+            source_line_index: v2.source_line_index,
+            symbol: v2.symbol,
+            segment_offsets: v2.segment_offsets,
+            timeframe_positions: None,
+        }
+    }
+}
+
+/// Layout of version 1 compact .idx files, written before `segment_offsets` existed.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct CompactFullIndexV1 {
+    pub time_index: Vec<u8>,
+    pub daily_index: Vec<DailyIndexEntry>,
+    pub timeframe_index: std::collections::BTreeMap<String, Vec<u64>>,
+    pub source_line_index: Option<Vec<u64>>,
+    pub symbol: Option<String>,
+}
+
+impl From<CompactFullIndexV1> for CompactFullIndex {
+    fn from(v1: CompactFullIndexV1) -> Self {
+        CompactFullIndex {
+            time_index: v1.time_index,
+            daily_index: v1.daily_index,
+            timeframe_index: v1.timeframe_index,
+            source_line_index: v1.source_line_index,
+            symbol: v1.symbol,
+            segment_offsets: None,
+            timeframe_positions: None,
+        }
+    }
+}
+
+impl From<&FullIndex> for CompactFullIndex {
+    fn from(full: &FullIndex) -> Self {
+        CompactFullIndex {
+            time_index: encode_time_index(&full.time_index),
+            daily_index: full.daily_index.clone(),
+            timeframe_index: full.timeframe_index.clone(),
+            source_line_index: full.source_line_index.clone(),
+            symbol: full.symbol.clone(),
+            segment_offsets: full.segment_offsets.clone(),
+            timeframe_positions: full.timeframe_positions.clone(),
+        }
+    }
+}
+
+impl TryFrom<CompactFullIndex> for FullIndex {
+    type Error = anyhow::Error;
+
+    fn try_from(compact: CompactFullIndex) -> anyhow::Result<Self> {
+        anyhow::Ok(FullIndex {
+            time_index: decode_time_index(&compact.time_index)?,
+            daily_index: compact.daily_index,
+            timeframe_index: compact.timeframe_index,
+            source_line_index: compact.source_line_index,
+            symbol: compact.symbol,
+            segment_offsets: compact.segment_offsets,
+            timeframe_positions: compact.timeframe_positions,
+        })
+    }
+}
+
+/// Packs `time_index` as LEB128 varints: for every entry, the timestamp's delta from the
+/// previous entry followed by the bar index.
+///
+/// Regular-cadence data then costs about 1 byte per delta and 1-4 bytes per index instead
+/// of 16 bytes per entry. Deltas wrap, so unsorted input still round-trips (just larger).
+pub fn encode_time_index(time_index: &[TimeIndexEntry]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(time_index.len() * 4);
+    let mut previous = 0u64;
+    for entry in time_index {
+        write_varint(&mut out, entry.timestamp.wrapping_sub(previous));
+        write_varint(&mut out, entry.index);
+        previous = entry.timestamp;
+    }
+    out
+}
+
+/// Reverses `encode_time_index`.
+///
+/// # Errors
+/// * If the bytes end in the middle of an entry or a varint overflows `u64`.
+pub fn decode_time_index(bytes: &[u8]) -> anyhow::Result<Vec<TimeIndexEntry>> {
+    let mut time_index = Vec::new();
+    let mut pos = 0;
+    let mut previous = 0u64;
+    while pos < bytes.len() {
+        let timestamp = previous.wrapping_add(read_varint(bytes, &mut pos)?);
+        let index = read_varint(bytes, &mut pos)?;
+        time_index.push(TimeIndexEntry { timestamp, index });
+        previous = timestamp;
+    }
+    anyhow::Ok(time_index)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> anyhow::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes
+            .get(*pos)
+            .ok_or_else(|| anyhow::anyhow!("Truncated compact time index at byte {}", *pos))?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return anyhow::Ok(value);
+        }
+    }
+    Err(anyhow::anyhow!("Varint longer than 64 bits in compact time index at byte {}", *pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two ticks at 10:01:00 and three at 10:02:00, as written for sub-second input.
+    fn index_with_duplicates() -> FullIndex {
+        let timestamps = [1705312800, 1705312860, 1705312860, 1705312920, 1705312920, 1705312920, 1705312980];
+        FullIndex {
+            time_index: timestamps
+                .iter()
+                .enumerate()
+                .map(|(i, &timestamp)| TimeIndexEntry { timestamp, index: i as u64 })
+                .collect(),
+            daily_index: Vec::new(),
+            timeframe_index: std::collections::BTreeMap::new(),
+            source_line_index: Some((2..9).collect()),
+            symbol: None,
+            segment_offsets: None,
+            timeframe_positions: None,
+        }
+    }
+
+    fn positions(entries: &[TimeIndexEntry]) -> Vec<u64> {
+        entries.iter().map(|e| e.index).collect()
+    }
+
+    #[test]
+    fn query_range_returns_every_bar_of_a_duplicated_second() {
+        let index = index_with_duplicates();
+
+        assert_eq!(positions(index.query_range(1705312920, 1705312920)), vec![3, 4, 5]);
+        assert_eq!(positions(index.query_range(1705312860, 1705312920)), vec![1, 2, 3, 4, 5]);
+        assert_eq!(positions(index.query_range(1705312861, 1705312919)), Vec::<u64>::new());
+        assert_eq!(positions(index.query_range(1705312980, 1705312800)), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn seek_timestamp_lands_on_the_first_duplicate() {
+        let index = index_with_duplicates();
+
+        assert_eq!(index.seek_timestamp(1705312920).map(|e| e.index), Some(3));
+        assert_eq!(index.seek_timestamp(1705312861).map(|e| e.index), Some(3));
+        assert_eq!(index.seek_timestamp(1705312981).map(|e| e.index), None);
+        assert_eq!(index.source_line_for(1705312860), Some(3));
+    }
+
+    #[test]
+    fn compact_time_index_round_trips() {
+        let mut time_index = index_with_duplicates().time_index;
+        time_index.push(TimeIndexEntry { timestamp: 1705312000, index: u64::MAX });
+
+        let encoded = encode_time_index(&time_index);
+        let decoded = decode_time_index(&encoded).unwrap();
+
+        assert_eq!(decoded.iter().map(|e| (e.timestamp, e.index)).collect::<Vec<_>>(),
+                   time_index.iter().map(|e| (e.timestamp, e.index)).collect::<Vec<_>>());
+        assert!(decode_time_index(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn compact_time_index_is_small_for_regular_cadence() {
+        let time_index: Vec<TimeIndexEntry> = (0..1000)
+            .map(|i| TimeIndexEntry { timestamp: 1705312800 + i * 60, index: i })
+            .collect();
+
+        // 5 bytes for the first timestamp, then 1 byte per delta and at most 2 per index.
+        assert!(encode_time_index(&time_index).len() <= 5 + 1000 * 3);
+    }
+
+    #[test]
+    fn timeframe_index_encoding_matches_the_former_hash_map() {
+        let sorted = std::collections::BTreeMap::from([("5m".to_string(), vec![1705312800u64, 1705313100])]);
+        let hashed: std::collections::HashMap<String, Vec<u64>> = sorted.clone().into_iter().collect();
+
+        assert_eq!(bincode::serialize(&sorted).unwrap(), bincode::serialize(&hashed).unwrap());
+    }
 }
@@ -0,0 +1,123 @@
+use crate::index;
+use crate::bundle;
+use crate::utils;
+
+/// Entries kept at each end of a previewed array.
+const PREVIEW_EDGE: usize = 5;
+
+/// Prints the `.idx` belonging to `path` as pretty JSON on stdout.
+///
+/// `path` may be the `.idx` itself, the `.bin` (or `--output-suffix` file) next to it, or a
+/// `.ohlcv` bundle, whose index section is printed. Unless `full` is set, the
+/// per-bar arrays (`time_index`, every `timeframe_index` and `timeframe_positions` entry
+/// and `source_line_index`)
+/// are cut down to their first and last `PREVIEW_EDGE` entries, with a marker string
+/// saying how many were left out. `daily_index` has one entry per day and is always
+/// printed in full.
+///
+/// # Arguments
+/// * `path` - Path to a `.idx`, `.bin` or `.ohlcv` file.
+/// * `full` - Print every entry instead of a head/tail preview.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success or an error if the index can't be read.
+pub fn dump(path: &std::path::Path, full: bool) -> anyhow::Result<()> {
+    let full_index = if path.extension().is_some_and(|ext| ext == bundle::BUNDLE_EXTENSION) {
+        let data = std::fs::read(path)?;
+        bundle::Bundle::parse(&data)?
+            .ok_or_else(|| anyhow::anyhow!("{} is not a bundle", path.display()))?
+            .full_index()
+            .map_err(|e| anyhow::anyhow!("Failed to load index of bundle {}: {}", path.display(), e))?
+    } else {
+        let idx_path = if path.extension().is_some_and(|ext| ext != "idx") {
+            path.with_extension("idx")
+        } else {
+            path.to_path_buf()
+        };
+        utils::load_full_index(&idx_path)
+            .map_err(|e| anyhow::anyhow!("Failed to load index {}: {}", idx_path.display(), e))?
+    };
+
+    println!("{}", serde_json::to_string_pretty(&index_to_json(&full_index, full)?)?);
+    anyhow::Ok(())
+}
+
+/// Serializes `full_index` to JSON, previewing the per-bar arrays unless `full` is set.
+fn index_to_json(full_index: &index::FullIndex, full: bool) -> anyhow::Result<serde_json::Value> {
+    let mut value = serde_json::to_value(full_index)?;
+    if full {
+        return anyhow::Ok(value);
+    }
+
+    if let Some(time_index) = value.get_mut("time_index") {
+        preview(time_index);
+    }
+    if let Some(source_lines) = value.get_mut("source_line_index") {
+        preview(source_lines);
+    }
+    for key in ["timeframe_index", "timeframe_positions"] {
+        if let Some(serde_json::Value::Object(timeframes)) = value.get_mut(key) {
+            timeframes.values_mut().for_each(preview);
+        }
+    }
+    anyhow::Ok(value)
+}
+
+/// Replaces the middle of a long JSON array with a single "... N more ..." marker.
+fn preview(value: &mut serde_json::Value) {
+    if let serde_json::Value::Array(items) = value
+        && items.len() > 2 * PREVIEW_EDGE
+    {
+        let omitted = items.len() - 2 * PREVIEW_EDGE;
+        items.splice(
+            PREVIEW_EDGE..PREVIEW_EDGE + omitted,
+            [serde_json::Value::String(format!("... {} more ...", omitted))],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_index(bars: u64) -> index::FullIndex {
+        index::FullIndex {
+            time_index: (0..bars).map(|i| index::TimeIndexEntry { timestamp: 1705312800 + i * 60, index: i }).collect(),
+            daily_index: vec![index::DailyIndexEntry {
+                date: "2024-01-15".to_string(),
+                start_index: 0,
+                end_index: bars - 1,
+            }],
+            timeframe_index: std::collections::BTreeMap::from([(
+                "1min".to_string(),
+                (0..bars).map(|i| 1705312800 + i * 60).collect(),
+            )]),
+            source_line_index: None,
+            symbol: Some("ES".to_string()),
+            segment_offsets: None,
+            timeframe_positions: None,
+        }
+    }
+
+    #[test]
+    fn long_arrays_are_previewed_unless_full() {
+        let index = sample_index(100);
+
+        let preview = index_to_json(&index, false).unwrap();
+        let time_index = preview["time_index"].as_array().unwrap();
+        assert_eq!(time_index.len(), 2 * PREVIEW_EDGE + 1);
+        assert_eq!(time_index[PREVIEW_EDGE], "... 90 more ...");
+        assert_eq!(time_index[2 * PREVIEW_EDGE]["index"], 99);
+        assert_eq!(preview["timeframe_index"]["1min"].as_array().unwrap().len(), 2 * PREVIEW_EDGE + 1);
+        assert_eq!(preview["symbol"], "ES");
+
+        let full = index_to_json(&index, true).unwrap();
+        assert_eq!(full["time_index"].as_array().unwrap().len(), 100);
+    }
+
+    #[test]
+    fn short_arrays_are_printed_whole() {
+        let preview = index_to_json(&sample_index(2 * PREVIEW_EDGE as u64), false).unwrap();
+        assert_eq!(preview["time_index"].as_array().unwrap().len(), 2 * PREVIEW_EDGE);
+    }
+}
@@ -0,0 +1,45 @@
+//! Conversion of OHLCV market data (CSV / NDJSON) into FlatBuffers files with
+//! companion indices, plus the reading, resampling and merging built on top of them.
+//!
+//! The `csv-to-flatbuffer` binary is a thin command-line front end over these modules;
+//! services can call `csv_processor::convert_csv_to_flatbuffer` directly, or the
+//! `async_api` wrapper with the `async` feature enabled.
+
+#[allow(dead_code)]
+#[allow(unused_imports)]
+#[allow(clippy::all)]
+#[allow(unsafe_op_in_unsafe_fn)]
+pub mod ohlcv_generated;
+
+#[allow(dead_code)]
+#[allow(unused_imports)]
+#[allow(clippy::all)]
+#[allow(unsafe_op_in_unsafe_fn)]
+pub mod ohlcv_soa_generated;
+
+pub mod cli;
+pub mod utils;
+pub mod index;
+pub mod bundle;
+pub mod clock;
+pub mod logging;
+pub mod merge;
+pub mod diff;
+pub mod index_dump;
+pub mod schema;
+pub mod calendar;
+pub mod adjustments;
+pub mod daily_report;
+pub mod grid_export;
+pub mod bar_source;
+pub mod resample;
+pub mod resample_cache;
+pub mod timezone;
+pub mod progress;
+pub mod selftest;
+pub mod csv_processor;
+pub mod file_processing;
+pub mod read_flatbuffers;
+
+#[cfg(feature = "async")]
+pub mod async_api;
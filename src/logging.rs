@@ -0,0 +1,37 @@
+/// Installs the global `tracing` subscriber for the command-line tool.
+///
+/// The level filter comes from `level` (`--log-level`) if given, otherwise from
+/// `RUST_LOG`, defaulting to `info`. By default events are written to stderr with
+/// timestamp, level and target. With `plain` (`--plain`) only the message is written,
+/// to stdout, which reproduces the tool's interactive output.
+///
+/// Library callers embedding the converter install their own subscriber instead; every
+/// module logs through the `tracing` macros and never prints directly, except the bar
+/// and JSON output that is the tool's actual result.
+///
+/// # Arguments
+/// * `level` - Level filter directive (e.g. `debug`), overriding `RUST_LOG`.
+/// * `plain` - Print bare messages to stdout instead of structured lines to stderr.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success or an error if a subscriber is already installed or
+///   the filter is invalid.
+pub fn init(level: Option<&str>, plain: bool) -> anyhow::Result<()> {
+    let filter = match level {
+        Some(level) => tracing_subscriber::EnvFilter::try_new(level)?,
+        None => tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+    };
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let result = if plain {
+        builder
+            .without_time()
+            .with_level(false)
+            .with_target(false)
+            .with_writer(std::io::stdout)
+            .try_init()
+    } else {
+        builder.with_writer(std::io::stderr).try_init()
+    };
+    result.map_err(|e| anyhow::anyhow!("Failed to initialize logging: {}", e))
+}
@@ -1,24 +1,23 @@
-#[allow(dead_code)]
-#[allow(unused_imports)]
-#[allow(clippy::all)]
-#[allow(unsafe_op_in_unsafe_fn)]
-mod ohlcv_generated;
-
-#[allow(dead_code)]
-#[allow(unused_imports)]
-#[allow(clippy::all)]
-#[allow(unsafe_op_in_unsafe_fn)]
-mod ohlcv_soa_generated;
-
-// Minimal imports required for the main logic
-mod cli;
-mod utils;
-mod index;
-mod resample;
-mod progress;
-mod csv_processor;
-mod file_processing;
-mod read_flatbuffers;
+use csv_to_flatbuffer::{
+    adjustments,
+    bundle,
+    calendar,
+    cli,
+    clock,
+    csv_processor,
+    diff,
+    file_processing,
+    index_dump,
+    logging,
+    merge,
+    progress,
+    read_flatbuffers,
+    resample,
+    schema,
+    selftest,
+    timezone,
+    utils,
+};
 
 /// Main entry point of the application.
 ///
@@ -35,54 +34,289 @@ mod read_flatbuffers;
 fn main() -> anyhow::Result<()> {
     let total_start = std::time::Instant::now();
     let args = cli::Args::parse();
-    println!("Start conversion...");
+    // --stdout-csv keeps stdout for the CSV; only warnings and errors go to stderr.
+    let log_level = args.log_level.as_deref().or(args.stdout_csv.then_some("warn"));
+    logging::init(log_level, args.plain)?;
+    if let Some(command) = &args.command {
+        return run_command(command);
+    }
+    tracing::info!("Start conversion...");
+    let ticks = args.input_kind == cli::InputKind::Ticks;
+    if args.resample.is_some() && !(args.check || args.stdout_csv || ticks) {
+        return Err(anyhow::anyhow!("--resample needs --check or --stdout-csv, or --input-kind ticks"));
+    }
+    if args.resample.as_deref().and_then(resample::BarThreshold::parse).is_some() && (ticks || args.stdout_csv) {
+        return Err(anyhow::anyhow!("Volume and count bars (--resample vol:N / count:N) are only supported with --check"));
+    }
+    if matches!(args.input_format, cli::InputFormat::TarGz) && (args.stdout_csv || args.concat_directory) {
+        return Err(anyhow::anyhow!("--input-format tar-gz is not supported with --stdout-csv or --concat-directory"));
+    }
+    if matches!(args.decimal_separator, cli::DecimalSeparator::Comma) && args.delimiter == b',' {
+        return Err(anyhow::anyhow!("--decimal-separator comma needs a --delimiter other than ',', e.g. --delimiter ';'"));
+    }
+    if ticks && args.since_last {
+        return Err(anyhow::anyhow!("--since-last is not supported with --input-kind ticks"));
+    }
+    let threads = args.threads.or_else(cli::threads_from_env);
 
-    file_processing::check_path(&args.input)?;
-    file_processing::ensure_parent_dir_exist(&args.output)?;
+    if !args.stdout_csv {
+        file_processing::check_path(&args.input)?;
+        file_processing::ensure_parent_dir_exist(&args.output, args.dir_mode)?;
+    }
 
-    let effective_threads = match args.threads {
+    let effective_threads = match threads {
         Some(n) if n > 0 => {
             let max_threads = num_cpus::get();
             if n > max_threads {
-                println!("⚠️ Warning: Limiting thread count to {} (max available)", max_threads);
+                tracing::warn!("⚠️ Warning: Limiting thread count to {} (max available)", max_threads);
                 max_threads
             } else { n }
         }
         Some(_) => return Err(anyhow::anyhow!("Number of threads must be a positive integer")),
-        None => {
-            let default_threads = rayon::current_num_threads();
-            default_threads
-        }
+        None => rayon::current_num_threads(),
     };
-    println!("🚀 Using {} thread(s)", effective_threads);
+    tracing::info!("🚀 Using {} thread(s)", effective_threads);
 
-    if let Some(n) = args.threads {
+    let options = csv_processor::ConversionOptions {
+        input_format: args.input_format.clone(),
+        storage_format: args.storage_format.clone(),
+        calendar: match &args.calendar {
+            Some(path) => Some(calendar::TradingCalendar::load(path)?),
+            None => None,
+        },
+        adjustments: match &args.adjustments {
+            Some(path) => Some(adjustments::Adjustments::load(path)?),
+            None => None,
+        },
+        split: args.split.clone(),
+        schema_version: args.schema_version,
+        empty_field: args.empty_field.clone(),
+        max_file_size: args.max_file_size,
+        track_source_lines: args.track_source_lines,
+        no_volume: args.no_volume,
+        timings: args.timings,
+        symbol: args.symbol.clone(),
+        symbol_from_filename: args.symbol_from_filename.then(|| csv_processor::SymbolFromFilename {
+            pattern: args.symbol_regex.clone(),
+        }),
+        since_last: args.since_last,
+        timezone: timezone::InputTimezone { tz: args.timezone, dst_policy: args.dst_policy.clone() },
+        session: args.session,
+        compact_index: args.compact_index,
+        csv_quoting: csv_processor::CsvQuoting {
+            quote: args.quote_char,
+            escape: args.escape_char,
+            quoting: !args.no_quoting,
+            delimiter: args.delimiter,
+        },
+        decimal_separator: args.decimal_separator.clone(),
+        encoding: Some(args.encoding).filter(|&encoding| encoding != encoding_rs::UTF_8),
+        self_verify: args.self_verify,
+        verify_output: args.verify_output,
+        no_index: args.no_index,
+        volume_spike_multiple: args.flag_volume_spikes,
+        order: args.order.clone(),
+        soa_segment_size: args.soa_segment_size,
+        fix_crossed: args.fix_crossed.clone(),
+        output_suffix: args.output_suffix.clone(),
+        io_buffer_size: args.io_buffer_size,
+        precompute_tf_positions: args.precompute_tf_positions,
+        staleness_clock: if args.report_staleness {
+            let clock: std::sync::Arc<dyn clock::Clock> = match args.now {
+                Some(now) => std::sync::Arc::new(clock::FixedClock(now)),
+                None => std::sync::Arc::new(clock::SystemClock),
+            };
+            Some(clock)
+        } else {
+            None
+        },
+        tick_bars: match &args.resample {
+            Some(tf) if ticks => Some(csv_processor::TickBars {
+                timeframe: resample::Timeframe::parse(tf)?,
+                bar_timestamp: args.bar_timestamp.clone(),
+                bucket_edge: args.bucket_edge.clone(),
+                bucket_anchor: args.bucket_anchor,
+            }),
+            _ => None,
+        },
+        index_timeframes: csv_processor::index_timeframe_keys(&args.timeframes),
+        collect_errors: args.collect_errors,
+        errors_csv: args.errors_csv,
+        bundle: args.bundle,
+        cancel: None,
+    };
+
+    if args.stdout_csv {
+        let input_path = stdout_csv_input(&args.input, &args.input_format)?;
+        let timeframe = resample::Timeframe::parse(args.resample.as_deref().unwrap_or_default())?;
+        let (bars, unique) = csv_processor::resample_input_file(
+            &input_path,
+            &options,
+            timeframe,
+            &args.bar_timestamp,
+            &args.bucket_edge,
+            args.bucket_anchor,
+            args.unique_timestamps,
+        )?;
+        csv_processor::write_bars_csv(&bars, unique.as_deref(), std::io::stdout().lock())?;
+        return Ok(());
+    }
+
+    let convert = || if args.concat_directory {
+        progress::concat_files(&args.input, &args.output, &options)
+    } else if let cli::InputFormat::TarGz = args.input_format {
+        progress::process_tarballs(&args.input, &args.output, &options)
+    } else {
+        progress::process_files(&args.input, &args.output, &options)
+    };
+    if threads.is_some() {
+        let file_count = file_processing::get_list_files_in_dir(&args.input, Some(args.input_format.file_extension()))?.len();
+        let n = utils::cap_threads_to_work(effective_threads, file_count, "conversion");
         let local_pool = utils::configure_thread_pool(n)?;
-        local_pool.install(|| progress::process_files(&args.input, &args.output, args.storage_format))?;
+        local_pool.install(convert)?;
     } else {
-        progress::process_files(&args.input, &args.output, args.storage_format)?;
+        convert()?;
     }
 
     let duration = total_start.elapsed();
-    println!(
+    tracing::info!(
         "✅ Conversion completed in {:?} seconds",
         duration.as_secs_f64()
     );
+    if args.report_memory {
+        match utils::peak_rss_kb() {
+            Some(kb) => tracing::info!("📊 Peak memory usage: {:.1} MB", kb as f64 / 1024.0),
+            None => tracing::warn!("⚠️ Peak memory usage is not available on this platform"),
+        }
+    }
 
     if args.check {
-        println!("Start reading...");
+        tracing::info!("Start reading...");
         let start = std::time::Instant::now();
+        let read_options = read_flatbuffers::ReadOptions {
+            resample: args.resample.clone(),
+            bar_timestamp: args.bar_timestamp.clone(),
+            show_count: args.show_count,
+            target_bars: args.target_bars,
+            table: args.table,
+            sparkline: args.sparkline,
+            io_retries: args.io_retries,
+            strict_resample: args.strict_resample,
+            volume_format: args.volume_format.clone(),
+            round_decimals: args.round_decimals,
+            with_true_range: args.with_true_range,
+            timestamp_offset: args.timestamp_offset,
+            bucket_edge: args.bucket_edge.clone(),
+            bucket_anchor: args.bucket_anchor,
+            resample_all: args.resample_all.then(|| args.timeframes.clone()),
+            output_suffix: args.output_suffix.clone(),
+            daily_report: args.daily_report,
+            drop_partial_bar: args.drop_partial_bar,
+            nan_policy: args.nan_policy.clone(),
+            unique_timestamps: args.unique_timestamps,
+            list_skipped: args.list_skipped,
+            anchor_to_session: args.resample_anchor_to_session,
+            grid_mark_missing: args.grid_mark_missing,
+            cache_resample: args.cache_resample,
+        };
 
-        if let Some(n) = args.threads {
+        if threads.is_some() {
+            let file_count = file_processing::get_list_files_in_dir(&args.output, Some(&args.output_suffix))?.len();
+            let n = utils::cap_threads_to_work(effective_threads, file_count, "reading");
             let local_pool = utils::configure_thread_pool(n)?;
-            local_pool.install(||  read_flatbuffers::read_flatbuffers(args.output, args.resample))?;
+            local_pool.install(||  read_flatbuffers::read_flatbuffers(&args.output, &read_options))?;
         } else {
-            read_flatbuffers::read_flatbuffers(args.output, args.resample)?;
+            read_flatbuffers::read_flatbuffers(&args.output, &read_options)?;
         }
-        println!(
+        tracing::info!(
             "✅ Reading files complete in {:?} seconds", 
             start.elapsed().as_secs_f64()
         );
     }
     Ok(())
 }
+
+/// Returns the one input file of `--stdout-csv`: `input` itself if it is a file, else the
+/// only input file in the `input` directory.
+///
+/// # Arguments
+///
+/// * `input` - The `-i` path.
+/// * `input_format` - Input format, whose extension selects files in a directory.
+///
+/// # Returns
+///
+/// * `anyhow::Result<std::path::PathBuf>` - The input file, or an error if a directory holds
+///   no input file or more than one.
+fn stdout_csv_input(input: &std::path::Path, input_format: &cli::InputFormat) -> anyhow::Result<std::path::PathBuf> {
+    if input.is_file() {
+        return Ok(input.to_path_buf());
+    }
+    let files = file_processing::get_list_files_in_dir(input, Some(input_format.file_extension()))?;
+    match files.as_slice() {
+        [file] => Ok(std::path::PathBuf::from(file)),
+        _ => Err(anyhow::anyhow!(
+            "--stdout-csv needs exactly one input file, found {} in {}",
+            files.len(),
+            input.display()
+        )),
+    }
+}
+
+/// Runs a subcommand instead of the default conversion workflow.
+///
+/// # Arguments
+///
+/// * `command` - The parsed subcommand.
+///
+/// # Returns
+///
+/// * `anyhow::Result<()>` - Success or an error if the subcommand fails.
+fn run_command(command: &cli::Command) -> anyhow::Result<()> {
+    match command {
+        cli::Command::Merge { inputs, output, storage_format } => {
+            let start = std::time::Instant::now();
+            let (output_path, bars) = merge::merge_files(inputs, output, storage_format.clone())?;
+            tracing::info!(
+                "✅ Merged {} file(s) into {} ({} bars) in {:?} seconds",
+                inputs.len(),
+                output_path.display(),
+                bars,
+                start.elapsed().as_secs_f64()
+            );
+        }
+        cli::Command::SelfTest => selftest::run()?,
+        cli::Command::IndexDump { path, full } => index_dump::dump(path, *full)?,
+        cli::Command::EmitSchema { output } => {
+            for path in schema::emit_schema(output)? {
+                tracing::info!("📐 Wrote schema {}", path.display());
+            }
+        }
+        cli::Command::Bundle { inputs, remove_originals } => {
+            for input in inputs {
+                let path = bundle::bundle_files(input, *remove_originals)?;
+                tracing::info!("📦 Bundled {} into {}", input.display(), path.display());
+            }
+        }
+        cli::Command::Diff { a, b, epsilon } => {
+            let (report, bars_a, bars_b) = diff::diff_files(a, b, *epsilon)?;
+            let Some(first) = report.first_mismatch else {
+                tracing::info!("✅ {} and {} are identical ({} bars)", a.display(), b.display(), report.lengths.0);
+                return Ok(());
+            };
+            if report.lengths.0 != report.lengths.1 {
+                tracing::warn!("⚠️ {} has {} bars, {} has {}", a.display(), report.lengths.0, b.display(), report.lengths.1);
+            }
+            tracing::info!("🔍 First difference at bar {}:", first);
+            tracing::info!("   a: {:?}", bars_a.get(first));
+            tracing::info!("   b: {:?}", bars_b.get(first));
+            return Err(anyhow::anyhow!(
+                "{} of {} bar position(s) differ (first at {})",
+                report.mismatches,
+                report.lengths.0.max(report.lengths.1),
+                first
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use crate::cli;
+use crate::file_processing;
+use crate::resample;
+use crate::csv_processor;
+use crate::read_flatbuffers;
+
+/// Merges several .bin files into a single FlatBuffer file with a rebuilt index.
+///
+/// Each input is read in full (AOS and SOA inputs can be mixed), the inputs are
+/// ordered by their first timestamp and their bars are concatenated. Inputs must
+/// not overlap in time: every file has to start strictly after the previous one ends.
+///
+/// The merged file stores volumes and records a symbol in its `.idx` like its inputs, so
+/// the inputs must agree: all of them store volumes or none does (`--no-volume`), and
+/// those with a symbol share it.
+///
+/// The output file name always ends with the suffix of `storage_format`
+/// (`.aos.bin` / `.soa.bin`) so it can be read back by `read_flatbuffers`;
+/// the suffix is appended if missing.
+///
+/// # Arguments
+/// * `inputs` - Paths of the .bin files to merge.
+/// * `output` - Path of the merged .bin file.
+/// * `storage_format` - Storage format of the merged file.
+///
+/// # Returns
+/// * `anyhow::Result<(std::path::PathBuf, usize)>` - The written path and the number of merged bars.
+///
+/// # Errors
+/// * If any input can't be read.
+/// * If two inputs overlap in time.
+/// * If some inputs store volumes and others don't, or two inputs have different symbols.
+/// * If the output path is an existing directory.
+pub fn merge_files(
+    inputs: &[std::path::PathBuf],
+    output: &std::path::Path,
+    storage_format: cli::StorageFormat,
+) -> anyhow::Result<(std::path::PathBuf, usize)> {
+    let suffix = storage_format.file_suffix();
+    let output_path = match output.file_name().and_then(|n| n.to_str()) {
+        Some(name) if name.ends_with(suffix) => output.to_path_buf(),
+        Some(name) => output.with_file_name(format!("{}{}", name, suffix)),
+        None => return Err(anyhow::anyhow!("Invalid merge output path: {}", output.display())),
+    };
+
+    file_processing::check_output_file(&output_path)?;
+
+    let mut loaded: Vec<(String, Vec<resample::OHLCVBar>)> = Vec::with_capacity(inputs.len());
+    let mut has_volume: Option<(String, bool)> = None;
+    let mut symbol: Option<(String, String)> = None;
+    for input in inputs {
+        let file = read_flatbuffers::load_file(input)?;
+        let name = input.display().to_string();
+        match &has_volume {
+            Some((first_name, first_has_volume)) if *first_has_volume != file.has_volume => {
+                let (with, without) = if file.has_volume { (&name, first_name) } else { (first_name, &name) };
+                return Err(anyhow::anyhow!("Inputs differ in volume: {} stores volumes but {} doesn't", with, without));
+            }
+            Some(_) => {}
+            None => has_volume = Some((name.clone(), file.has_volume)),
+        }
+        if let Some(file_symbol) = file.symbol {
+            match &symbol {
+                Some((first_name, first_symbol)) if *first_symbol != file_symbol => {
+                    return Err(anyhow::anyhow!(
+                        "Inputs are for different symbols: {} is {} but {} is {}",
+                        first_name,
+                        first_symbol,
+                        name,
+                        file_symbol
+                    ));
+                }
+                Some(_) => {}
+                None => symbol = Some((name.clone(), file_symbol)),
+            }
+        }
+        if !file.bars.is_empty() {
+            loaded.push((name, file.bars));
+        }
+    }
+    loaded.sort_by_key(|(_, bars)| bars[0].timestamp);
+
+    let total: usize = loaded.iter().map(|(_, bars)| bars.len()).sum();
+    let mut merged: Vec<resample::OHLCVBar> = Vec::with_capacity(total);
+    let mut previous: Option<(&str, u64)> = None;
+
+    for (name, bars) in &loaded {
+        let first_ts = bars[0].timestamp;
+        if let Some((prev_name, prev_last_ts)) = previous
+            && first_ts <= prev_last_ts
+        {
+            return Err(anyhow::anyhow!(
+                "Inputs overlap: {} starts at {} but {} ends at {}",
+                name,
+                first_ts,
+                prev_name,
+                prev_last_ts
+            ));
+        }
+        merged.extend_from_slice(bars);
+        previous = Some((name.as_str(), bars[bars.len() - 1].timestamp));
+    }
+
+    let has_volume = has_volume.is_none_or(|(_, has_volume)| has_volume);
+    csv_processor::write_bars(&merged, &output_path, storage_format, has_volume, symbol.map(|(_, symbol)| symbol))?;
+
+    anyhow::Ok((output_path, merged.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars(start: u64, n: u64, volume: u64) -> Vec<resample::OHLCVBar> {
+        (0..n).map(|i| resample::OHLCVBar { timestamp: start + i * 60, open: 1.0, high: 2.0, low: 0.5, close: 1.5, volume, count: 1 }).collect()
+    }
+
+    #[test]
+    fn merge_keeps_the_inputs_volume_and_symbol_and_rejects_mixed_ones() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_merge_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, start: u64, has_volume: bool, symbol: Option<&str>| {
+            let path = dir.join(name);
+            let volume = if has_volume { 7 } else { 0 };
+            csv_processor::write_bars(&bars(start, 3, volume), &path, cli::StorageFormat::Soa, has_volume, symbol.map(str::to_string)).unwrap();
+            path
+        };
+        let a = write("a.soa.bin", 1705312800, false, Some("ES"));
+        let b = write("b.soa.bin", 1705399200, false, None);
+        let c = write("c.soa.bin", 1705485600, true, Some("ES"));
+        let d = write("d.soa.bin", 1705485600, false, Some("NQ"));
+
+        let (merged, count) = merge_files(&[b.clone(), a.clone()], &dir.join("ab"), cli::StorageFormat::Soa).unwrap();
+        let file = read_flatbuffers::load_file(&merged).unwrap();
+        assert_eq!((count, file.has_volume, file.symbol.as_deref()), (6, false, Some("ES")));
+
+        let err = merge_files(&[a.clone(), c], &dir.join("ac"), cli::StorageFormat::Soa).unwrap_err().to_string();
+        assert!(err.contains("differ in volume"), "{}", err);
+        let err = merge_files(&[a, d], &dir.join("ad"), cli::StorageFormat::Soa).unwrap_err().to_string();
+        assert!(err.contains("different symbols"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -31,6 +31,7 @@ impl<'a> OHLCV<'a> {
   pub const VT_LOW: flatbuffers::VOffsetT = 10;
   pub const VT_CLOSE: flatbuffers::VOffsetT = 12;
   pub const VT_VOLUME: flatbuffers::VOffsetT = 14;
+  pub const VT_ADJ_CLOSE: flatbuffers::VOffsetT = 16;
 
   #[inline]
   pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -42,6 +43,7 @@ impl<'a> OHLCV<'a> {
     args: &'args OHLCVArgs
   ) -> flatbuffers::WIPOffset<OHLCV<'bldr>> {
     let mut builder = OHLCVBuilder::new(_fbb);
+    if let Some(x) = args.adj_close { builder.add_adj_close(x); }
     builder.add_volume(args.volume);
     builder.add_close(args.close);
     builder.add_low(args.low);
@@ -94,6 +96,13 @@ impl<'a> OHLCV<'a> {
     // which contains a valid value in this slot
     unsafe { self._tab.get::<u64>(OHLCV::VT_VOLUME, Some(0)).unwrap()}
   }
+  #[inline]
+  pub fn adj_close(&self) -> Option<f64> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<f64>(OHLCV::VT_ADJ_CLOSE, None)}
+  }
 }
 
 impl flatbuffers::Verifiable for OHLCV<'_> {
@@ -109,6 +118,7 @@ impl flatbuffers::Verifiable for OHLCV<'_> {
      .visit_field::<f64>("low", Self::VT_LOW, false)?
      .visit_field::<f64>("close", Self::VT_CLOSE, false)?
      .visit_field::<u64>("volume", Self::VT_VOLUME, false)?
+     .visit_field::<f64>("adj_close", Self::VT_ADJ_CLOSE, false)?
      .finish();
     Ok(())
   }
@@ -120,6 +130,7 @@ pub struct OHLCVArgs {
     pub low: f64,
     pub close: f64,
     pub volume: u64,
+    pub adj_close: Option<f64>,
 }
 impl<'a> Default for OHLCVArgs {
   #[inline]
@@ -131,6 +142,7 @@ impl<'a> Default for OHLCVArgs {
       low: 0.0,
       close: 0.0,
       volume: 0,
+      adj_close: None,
     }
   }
 }
@@ -165,6 +177,10 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> OHLCVBuilder<'a, 'b, A> {
     self.fbb_.push_slot::<u64>(OHLCV::VT_VOLUME, volume, 0);
   }
   #[inline]
+  pub fn add_adj_close(&mut self, adj_close: f64) {
+    self.fbb_.push_slot_always::<f64>(OHLCV::VT_ADJ_CLOSE, adj_close);
+  }
+  #[inline]
   pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> OHLCVBuilder<'a, 'b, A> {
     let start = _fbb.start_table();
     OHLCVBuilder {
@@ -188,6 +204,7 @@ impl core::fmt::Debug for OHLCV<'_> {
       ds.field("low", &self.low());
       ds.field("close", &self.close());
       ds.field("volume", &self.volume());
+      ds.field("adj_close", &self.adj_close());
       ds.finish()
   }
 }
@@ -208,6 +225,8 @@ impl<'a> flatbuffers::Follow<'a> for OHLCVList<'a> {
 
 impl<'a> OHLCVList<'a> {
   pub const VT_ITEMS: flatbuffers::VOffsetT = 4;
+  pub const VT_VERSION: flatbuffers::VOffsetT = 6;
+  pub const VT_HAS_VOLUME: flatbuffers::VOffsetT = 8;
 
   #[inline]
   pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -219,7 +238,9 @@ impl<'a> OHLCVList<'a> {
     args: &'args OHLCVListArgs<'args>
   ) -> flatbuffers::WIPOffset<OHLCVList<'bldr>> {
     let mut builder = OHLCVListBuilder::new(_fbb);
+    builder.add_version(args.version);
     if let Some(x) = args.items { builder.add_items(x); }
+    builder.add_has_volume(args.has_volume);
     builder.finish()
   }
 
@@ -231,6 +252,20 @@ impl<'a> OHLCVList<'a> {
     // which contains a valid value in this slot
     unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<OHLCV>>>>(OHLCVList::VT_ITEMS, None)}
   }
+  #[inline]
+  pub fn version(&self) -> u32 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u32>(OHLCVList::VT_VERSION, Some(1)).unwrap()}
+  }
+  #[inline]
+  pub fn has_volume(&self) -> bool {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<bool>(OHLCVList::VT_HAS_VOLUME, Some(true)).unwrap()}
+  }
 }
 
 impl flatbuffers::Verifiable for OHLCVList<'_> {
@@ -241,18 +276,24 @@ impl flatbuffers::Verifiable for OHLCVList<'_> {
     use self::flatbuffers::Verifiable;
     v.visit_table(pos)?
      .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<OHLCV>>>>("items", Self::VT_ITEMS, false)?
+     .visit_field::<u32>("version", Self::VT_VERSION, false)?
+     .visit_field::<bool>("has_volume", Self::VT_HAS_VOLUME, false)?
      .finish();
     Ok(())
   }
 }
 pub struct OHLCVListArgs<'a> {
     pub items: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<OHLCV<'a>>>>>,
+    pub version: u32,
+    pub has_volume: bool,
 }
 impl<'a> Default for OHLCVListArgs<'a> {
   #[inline]
   fn default() -> Self {
     OHLCVListArgs {
       items: None,
+      version: 1,
+      has_volume: true,
     }
   }
 }
@@ -267,6 +308,14 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> OHLCVListBuilder<'a, 'b, A> {
     self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(OHLCVList::VT_ITEMS, items);
   }
   #[inline]
+  pub fn add_version(&mut self, version: u32) {
+    self.fbb_.push_slot::<u32>(OHLCVList::VT_VERSION, version, 1);
+  }
+  #[inline]
+  pub fn add_has_volume(&mut self, has_volume: bool) {
+    self.fbb_.push_slot::<bool>(OHLCVList::VT_HAS_VOLUME, has_volume, true);
+  }
+  #[inline]
   pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> OHLCVListBuilder<'a, 'b, A> {
     let start = _fbb.start_table();
     OHLCVListBuilder {
@@ -285,6 +334,8 @@ impl core::fmt::Debug for OHLCVList<'_> {
   fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
     let mut ds = f.debug_struct("OHLCVList");
       ds.field("items", &self.items());
+      ds.field("version", &self.version());
+      ds.field("has_volume", &self.has_volume());
       ds.finish()
   }
 }
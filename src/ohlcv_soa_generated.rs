@@ -31,6 +31,7 @@ impl<'a> OHLCVSOA<'a> {
   pub const VT_LOWS: flatbuffers::VOffsetT = 10;
   pub const VT_CLOSES: flatbuffers::VOffsetT = 12;
   pub const VT_VOLUMES: flatbuffers::VOffsetT = 14;
+  pub const VT_ADJ_CLOSES: flatbuffers::VOffsetT = 16;
 
   #[inline]
   pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -42,6 +43,7 @@ impl<'a> OHLCVSOA<'a> {
     args: &'args OHLCVSOAArgs<'args>
   ) -> flatbuffers::WIPOffset<OHLCVSOA<'bldr>> {
     let mut builder = OHLCVSOABuilder::new(_fbb);
+    if let Some(x) = args.adj_closes { builder.add_adj_closes(x); }
     if let Some(x) = args.volumes { builder.add_volumes(x); }
     if let Some(x) = args.closes { builder.add_closes(x); }
     if let Some(x) = args.lows { builder.add_lows(x); }
@@ -94,6 +96,13 @@ impl<'a> OHLCVSOA<'a> {
     // which contains a valid value in this slot
     unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u64>>>(OHLCVSOA::VT_VOLUMES, None)}
   }
+  #[inline]
+  pub fn adj_closes(&self) -> Option<flatbuffers::Vector<'a, f64>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, f64>>>(OHLCVSOA::VT_ADJ_CLOSES, None)}
+  }
 }
 
 impl flatbuffers::Verifiable for OHLCVSOA<'_> {
@@ -109,6 +118,7 @@ impl flatbuffers::Verifiable for OHLCVSOA<'_> {
      .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, f64>>>("lows", Self::VT_LOWS, false)?
      .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, f64>>>("closes", Self::VT_CLOSES, false)?
      .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u64>>>("volumes", Self::VT_VOLUMES, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, f64>>>("adj_closes", Self::VT_ADJ_CLOSES, false)?
      .finish();
     Ok(())
   }
@@ -120,6 +130,7 @@ pub struct OHLCVSOAArgs<'a> {
     pub lows: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, f64>>>,
     pub closes: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, f64>>>,
     pub volumes: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u64>>>,
+    pub adj_closes: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, f64>>>,
 }
 impl<'a> Default for OHLCVSOAArgs<'a> {
   #[inline]
@@ -131,6 +142,7 @@ impl<'a> Default for OHLCVSOAArgs<'a> {
       lows: None,
       closes: None,
       volumes: None,
+      adj_closes: None,
     }
   }
 }
@@ -165,6 +177,10 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> OHLCVSOABuilder<'a, 'b, A> {
     self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(OHLCVSOA::VT_VOLUMES, volumes);
   }
   #[inline]
+  pub fn add_adj_closes(&mut self, adj_closes: flatbuffers::WIPOffset<flatbuffers::Vector<'b , f64>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(OHLCVSOA::VT_ADJ_CLOSES, adj_closes);
+  }
+  #[inline]
   pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> OHLCVSOABuilder<'a, 'b, A> {
     let start = _fbb.start_table();
     OHLCVSOABuilder {
@@ -188,6 +204,7 @@ impl core::fmt::Debug for OHLCVSOA<'_> {
       ds.field("lows", &self.lows());
       ds.field("closes", &self.closes());
       ds.field("volumes", &self.volumes());
+      ds.field("adj_closes", &self.adj_closes());
       ds.finish()
   }
 }
@@ -208,6 +225,8 @@ impl<'a> flatbuffers::Follow<'a> for OHLCVListSOA<'a> {
 
 impl<'a> OHLCVListSOA<'a> {
   pub const VT_DATA: flatbuffers::VOffsetT = 4;
+  pub const VT_VERSION: flatbuffers::VOffsetT = 6;
+  pub const VT_HAS_VOLUME: flatbuffers::VOffsetT = 8;
 
   #[inline]
   pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -219,7 +238,9 @@ impl<'a> OHLCVListSOA<'a> {
     args: &'args OHLCVListSOAArgs<'args>
   ) -> flatbuffers::WIPOffset<OHLCVListSOA<'bldr>> {
     let mut builder = OHLCVListSOABuilder::new(_fbb);
+    builder.add_version(args.version);
     if let Some(x) = args.data { builder.add_data(x); }
+    builder.add_has_volume(args.has_volume);
     builder.finish()
   }
 
@@ -231,6 +252,20 @@ impl<'a> OHLCVListSOA<'a> {
     // which contains a valid value in this slot
     unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<OHLCVSOA>>(OHLCVListSOA::VT_DATA, None)}
   }
+  #[inline]
+  pub fn version(&self) -> u32 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u32>(OHLCVListSOA::VT_VERSION, Some(1)).unwrap()}
+  }
+  #[inline]
+  pub fn has_volume(&self) -> bool {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<bool>(OHLCVListSOA::VT_HAS_VOLUME, Some(true)).unwrap()}
+  }
 }
 
 impl flatbuffers::Verifiable for OHLCVListSOA<'_> {
@@ -241,18 +276,24 @@ impl flatbuffers::Verifiable for OHLCVListSOA<'_> {
     use self::flatbuffers::Verifiable;
     v.visit_table(pos)?
      .visit_field::<flatbuffers::ForwardsUOffset<OHLCVSOA>>("data", Self::VT_DATA, false)?
+     .visit_field::<u32>("version", Self::VT_VERSION, false)?
+     .visit_field::<bool>("has_volume", Self::VT_HAS_VOLUME, false)?
      .finish();
     Ok(())
   }
 }
 pub struct OHLCVListSOAArgs<'a> {
     pub data: Option<flatbuffers::WIPOffset<OHLCVSOA<'a>>>,
+    pub version: u32,
+    pub has_volume: bool,
 }
 impl<'a> Default for OHLCVListSOAArgs<'a> {
   #[inline]
   fn default() -> Self {
     OHLCVListSOAArgs {
       data: None,
+      version: 1,
+      has_volume: true,
     }
   }
 }
@@ -267,6 +308,14 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> OHLCVListSOABuilder<'a, 'b, A>
     self.fbb_.push_slot_always::<flatbuffers::WIPOffset<OHLCVSOA>>(OHLCVListSOA::VT_DATA, data);
   }
   #[inline]
+  pub fn add_version(&mut self, version: u32) {
+    self.fbb_.push_slot::<u32>(OHLCVListSOA::VT_VERSION, version, 1);
+  }
+  #[inline]
+  pub fn add_has_volume(&mut self, has_volume: bool) {
+    self.fbb_.push_slot::<bool>(OHLCVListSOA::VT_HAS_VOLUME, has_volume, true);
+  }
+  #[inline]
   pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> OHLCVListSOABuilder<'a, 'b, A> {
     let start = _fbb.start_table();
     OHLCVListSOABuilder {
@@ -285,6 +334,8 @@ impl core::fmt::Debug for OHLCVListSOA<'_> {
   fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
     let mut ds = f.debug_struct("OHLCVListSOA");
       ds.field("data", &self.data());
+      ds.field("version", &self.version());
+      ds.field("has_volume", &self.has_volume());
       ds.finish()
   }
 }
@@ -1,4 +1,6 @@
 use crate::cli;
+use crate::clock;
+use crate::utils;
 use crate::csv_processor;
 use crate::file_processing;
 
@@ -6,60 +8,92 @@ use rayon::prelude::*;
 
 /// Processes each CSV file in parallel, converting to Parquet with progress tracking.
 ///
+/// With `options.symbol_from_filename` every file is converted with the symbol taken from
+/// its name; a file whose name yields no symbol is reported and skipped.
+///
+/// Progress is one aggregate bar weighted by input size: the byte sizes of all files are
+/// summed up front and each finished file advances a shared atomic counter by its size,
+/// so the ETA stays meaningful when file sizes vary widely.
+///
 /// # Arguments
 /// * `csv_path` - Input directory with CSV files.
 /// * `out_dir_path` - Output directory for Parquet files.
+/// * `options` - Conversion options shared by every file.
 ///
 /// # Returns
 /// * `Result<()>` - Success or error if any conversion fails.
-pub fn process_files<P: AsRef<std::path::Path> + std::marker::Sync>(csv_path: P, out_dir_path: P, storage_format: cli::StorageFormat) -> anyhow::Result<()> {
-    let files_list: Vec<String> = file_processing::get_list_files_in_dir(&csv_path, Some("txt"))?;
-    println!("📂 Found {} file(s) to convert", files_list.len());
+pub fn process_files<P: AsRef<std::path::Path> + std::marker::Sync>(csv_path: P, out_dir_path: P, options: &csv_processor::ConversionOptions) -> anyhow::Result<()> {
+    let files_list: Vec<String> = file_processing::get_list_files_in_dir(&csv_path, Some(options.input_format.file_extension()))?;
+    // Unreadable metadata counts as 0 bytes; the conversion itself reports the error.
+    let file_sizes: Vec<u64> = files_list
+        .iter()
+        .map(|file| std::fs::metadata(file).map(|m| m.len()).unwrap_or(0))
+        .collect();
+    let total_bytes: u64 = file_sizes.iter().sum();
+    tracing::info!("📂 Found {} file(s) to convert ({} bytes)", files_list.len(), total_bytes);
 
     let m = indicatif::MultiProgress::new();
-    let pb = m.add(indicatif::ProgressBar::new(files_list.len() as u64));
+    let pb = m.add(indicatif::ProgressBar::new(total_bytes));
     pb.set_style(indicatif::ProgressStyle::default_bar()
-        .template("[{wide_bar}] {pos}/{len} files converted ({percent}%))")?
+        .template("[{wide_bar}] {bytes}/{total_bytes} ({percent}%) ETA {eta} | {msg}")?
         .progress_chars("=>-"));
+    pb.set_message(format!("0/{} files converted", files_list.len()));
 
     let log_pb = m.add(indicatif::ProgressBar::new(4));
     log_pb.set_style(indicatif::ProgressStyle::default_spinner());
 
     let files_processed = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let bytes_processed = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+    let file_done = |size: u64| {
+        let bytes = bytes_processed.fetch_add(size, std::sync::atomic::Ordering::Relaxed) + size;
+        let files = files_processed.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+        pb.set_position(bytes);
+        pb.set_message(format!("{}/{} files converted", files, files_list.len()));
+    };
 
-    files_list.par_iter().for_each(|file| {
+    files_list.par_iter().zip(file_sizes.par_iter()).for_each(|(file, &file_size)| {
         let input_path = std::path::Path::new(&file);
         let file_stem = match input_path.file_stem() {
             Some(stem) => stem.to_str().unwrap_or("output"),
             None => "output",
         };
-        let output_file_name = match storage_format {
-            cli::StorageFormat::Aos => {
-                format!("{}.aos.bin", file_stem)
-            }
-            cli::StorageFormat::Soa => {
-                format!("{}.soa.bin", file_stem)
-            }
-        };
+        let output_file_name = format!("{}{}", file_stem, options.output_file_suffix());
         let output_path = out_dir_path.as_ref().join(output_file_name);
         let start = std::time::Instant::now();
 
-        match csv_processor::convert_csv_to_flatbuffer(input_path, &output_path, storage_format.clone()) {
-            Ok(_) => {
+        let file_options;
+        let options = match &options.symbol_from_filename {
+            Some(symbol_from_filename) => match symbol_from_filename.extract(file_stem) {
+                Ok(symbol) => {
+                    file_options = csv_processor::ConversionOptions { symbol: Some(symbol), ..options.clone() };
+                    &file_options
+                }
+                Err(e) => {
+                    m.suspend(|| tracing::error!("❌ Failed to convert file {}: {}", file, e));
+                    file_done(file_size);
+                    return;
+                }
+            },
+            None => options,
+        };
+
+        match csv_processor::convert_csv_to_flatbuffer(input_path, &output_path, options) {
+            Ok(stats) => {
                 let duration = start.elapsed();
-                m.println(format!(
-                    "✅ Converted '{}' in {:.2}s",
+                m.suspend(|| tracing::info!(
+                    "✅ Converted '{}' ({} bars) in {:.2}s",
                     file,
+                    stats.bars,
                     duration.as_secs_f64()
-                )).unwrap();
+                ));
+                report_conversion(&m, file, &stats, options);
             },
             Err(e) => {
-                m.println(format!("❌ Failed to convert file {}: {}", file, e)).unwrap();
+                m.suspend(|| tracing::error!("❌ Failed to convert file {}: {}", file, e));
             }
         }
 
-        pb.inc(1);
-        files_processed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        file_done(file_size);
     });
 
     pb.finish_with_message("✅ All files converted");
@@ -67,3 +101,237 @@ pub fn process_files<P: AsRef<std::path::Path> + std::marker::Sync>(csv_path: P,
     
     Ok(())
 }
+
+/// Converts every CSV entry of the tarballs in `csv_path` (`--input-format tar-gz`).
+///
+/// Tarballs are converted in parallel and the entries of each one in order, as they are
+/// streamed (`csv_processor::convert_tarball`). The number of entries isn't known before
+/// a tarball is read, so progress is a spinner counting converted entries.
+///
+/// # Arguments
+/// * `csv_path` - Input directory with `.tar.gz` files.
+/// * `out_dir_path` - Output directory the entry paths are resolved against.
+/// * `options` - Conversion options shared by every entry.
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error if the input directory can't be listed. Failed
+///   entries and unreadable tarballs are reported and don't stop the others.
+pub fn process_tarballs<P: AsRef<std::path::Path> + std::marker::Sync>(csv_path: P, out_dir_path: P, options: &csv_processor::ConversionOptions) -> anyhow::Result<()> {
+    let tarballs: Vec<String> = file_processing::get_list_files_in_dir(&csv_path, Some(options.input_format.file_extension()))?;
+    tracing::info!("📂 Found {} tarball(s) to convert", tarballs.len());
+
+    let m = indicatif::MultiProgress::new();
+    let pb = m.add(indicatif::ProgressBar::new_spinner());
+    pb.set_message("0 entries converted");
+    let entries_processed = std::sync::atomic::AtomicUsize::new(0);
+
+    tarballs.par_iter().for_each(|tarball| {
+        let result = csv_processor::convert_tarball(std::path::Path::new(tarball), out_dir_path.as_ref(), options, |entry, result| {
+            let file = format!("{}:{}", tarball, entry);
+            match result {
+                Ok(stats) => {
+                    m.suspend(|| tracing::info!("✅ Converted '{}' ({} bars)", file, stats.bars));
+                    report_conversion(&m, &file, &stats, options);
+                }
+                Err(e) => {
+                    m.suspend(|| tracing::error!("❌ Failed to convert file {}: {}", file, e));
+                }
+            }
+            let entries = entries_processed.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+            pb.set_message(format!("{} entries converted", entries));
+            pb.tick();
+        });
+        match result {
+            Ok(entries) => m.suspend(|| tracing::info!("📦 Read {} CSV entries from '{}'", entries, tarball)),
+            Err(e) => m.suspend(|| tracing::error!("❌ Failed to read tarball {}: {}", tarball, e)),
+        }
+    });
+
+    pb.finish_with_message("✅ All tarballs converted");
+    m.clear().unwrap();
+
+    Ok(())
+}
+
+/// Logs the per-file details of a successful conversion: rows skipped, crossed bars,
+/// missing days, volume spikes, timings and staleness, as enabled by `options`.
+fn report_conversion(m: &indicatif::MultiProgress, file: &str, stats: &csv_processor::ConversionStats, options: &csv_processor::ConversionOptions) {
+    if options.since_last {
+        m.suspend(|| tracing::info!(
+            "⏭️ '{}' skipped {} already converted row(s), converted {} new",
+            file,
+            stats.skipped.already_converted,
+            stats.bars
+        ));
+    }
+    if let Some(session) = &options.session {
+        m.suspend(|| tracing::info!(
+            "🕘 '{}' excluded {} bar(s) outside the {} session",
+            file,
+            stats.skipped.outside_session,
+            session
+        ));
+    }
+    if let Some(fix_crossed) = &options.fix_crossed {
+        m.suspend(|| tracing::info!(
+            "🩹 '{}' {}",
+            file,
+            describe_crossed(&stats.skipped, fix_crossed)
+        ));
+    }
+    if options.collect_errors.is_some() && !stats.skipped.bad_rows.is_empty() {
+        m.suspend(|| tracing::warn!(
+            "⚠️ '{}' skipped {} bad row(s): {}",
+            file,
+            stats.skipped.bad_rows.len(),
+            describe_bad_rows(&stats.skipped.bad_rows)
+        ));
+    }
+    if !stats.missing_days.is_empty() {
+        let days: Vec<String> = stats.missing_days.iter().map(|d| d.to_string()).collect();
+        m.suspend(|| tracing::warn!(
+            "⚠️ '{}' is missing {} trading day(s): {}",
+            file,
+            days.len(),
+            days.join(", ")
+        ));
+    }
+    if let Some(multiple) = options.volume_spike_multiple
+        && !stats.volume_spikes.is_empty()
+    {
+        m.suspend(|| tracing::warn!(
+            "⚠️ '{}' has {} volume spike(s) over {}x the rolling median: {}",
+            file,
+            stats.volume_spikes.len(),
+            multiple,
+            describe_volume_spikes(&stats.volume_spikes)
+        ));
+    }
+    if options.timings {
+        let t = &stats.timings;
+        m.suspend(|| tracing::info!(
+            "⏱️ '{}' parse {:.3}s | index {:.3}s | build {:.3}s | write .bin {:.3}s | write .idx {:.3}s",
+            file,
+            t.parse.as_secs_f64(),
+            t.build_index.as_secs_f64(),
+            t.build_flatbuffer.as_secs_f64(),
+            t.write_flatbuffer.as_secs_f64(),
+            t.write_index.as_secs_f64()
+        ));
+    }
+    if let (Some(clock), Some(last_ts)) = (&options.staleness_clock, stats.last_timestamp) {
+        let latest = utils::format_timestamp(last_ts).unwrap_or_else(|_| last_ts.to_string());
+        m.suspend(|| tracing::info!(
+            "🕒 '{}' latest bar {} is {} old",
+            file,
+            latest,
+            clock::format_staleness(clock::staleness_secs(clock.as_ref(), last_ts))
+        ));
+    }
+}
+
+/// Concatenates every per-day file in `csv_path` into a single output and reports gaps.
+///
+/// See `csv_processor::concat_directory`. Expected trading days with no file between two
+/// consecutive files are printed as warnings; they don't fail the conversion.
+///
+/// # Arguments
+/// * `csv_path` - Input directory with one file per trading day.
+/// * `out_dir_path` - Output directory for the concatenated file.
+/// * `options` - Conversion options.
+///
+/// # Returns
+/// * `Result<()>` - Success or error if the concatenation fails.
+pub fn concat_files<P: AsRef<std::path::Path>>(csv_path: P, out_dir_path: P, options: &csv_processor::ConversionOptions) -> anyhow::Result<()> {
+    let start = std::time::Instant::now();
+    let concat = csv_processor::concat_directory(csv_path, out_dir_path, options)?;
+
+    tracing::info!(
+        "✅ Concatenated {} file(s) into '{}' ({} bars) in {:.2}s",
+        concat.files,
+        concat.output_path.display(),
+        concat.stats.bars,
+        start.elapsed().as_secs_f64()
+    );
+    for gap in &concat.gaps {
+        let days: Vec<String> = gap.missing.iter().map(|d| d.to_string()).collect();
+        tracing::warn!(
+            "⚠️ Gap between {} and {}: no file for {} trading day(s): {}",
+            gap.after,
+            gap.before,
+            days.len(),
+            days.join(", ")
+        );
+    }
+    if let Some(fix_crossed) = &options.fix_crossed {
+        tracing::info!("🩹 {}", describe_crossed(&concat.stats.skipped, fix_crossed));
+    }
+    if options.collect_errors.is_some() && !concat.stats.skipped.bad_rows.is_empty() {
+        tracing::warn!(
+            "⚠️ Skipped {} bad row(s): {}",
+            concat.stats.skipped.bad_rows.len(),
+            describe_bad_rows(&concat.stats.skipped.bad_rows)
+        );
+    }
+    if let Some(multiple) = options.volume_spike_multiple
+        && !concat.stats.volume_spikes.is_empty()
+    {
+        tracing::warn!(
+            "⚠️ {} volume spike(s) over {}x the rolling median: {}",
+            concat.stats.volume_spikes.len(),
+            multiple,
+            describe_volume_spikes(&concat.stats.volume_spikes)
+        );
+    }
+    if !concat.stats.missing_days.is_empty() {
+        let days: Vec<String> = concat.stats.missing_days.iter().map(|d| d.to_string()).collect();
+        tracing::warn!("⚠️ Missing {} trading day(s) in the data: {}", days.len(), days.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Formats the crossed bars handled by `--fix-crossed`, e.g. `repaired 3 crossed bar(s) (clamp)`.
+fn describe_crossed(skipped: &csv_processor::SkippedRows, fix_crossed: &cli::FixCrossed) -> String {
+    match fix_crossed {
+        cli::FixCrossed::Drop => format!("dropped {} crossed bar(s)", skipped.crossed),
+        cli::FixCrossed::Swap => format!("repaired {} crossed bar(s) (swap)", skipped.repaired_crossed),
+        cli::FixCrossed::Clamp => format!("repaired {} crossed bar(s) (clamp)", skipped.repaired_crossed),
+    }
+}
+
+/// Spikes listed by `describe_volume_spikes` before the rest are only counted.
+const MAX_LISTED_SPIKES: usize = 10;
+
+/// Formats volume spikes for a report line, e.g. `2024-01-15 10:05:00 (line 7) 52000 vs median 400`.
+fn describe_volume_spikes(spikes: &[csv_processor::VolumeSpike]) -> String {
+    let mut listed: Vec<String> = spikes
+        .iter()
+        .take(MAX_LISTED_SPIKES)
+        .map(|spike| {
+            let when = utils::format_timestamp(spike.timestamp).unwrap_or_else(|_| spike.timestamp.to_string());
+            let line = spike.source_line.map(|l| format!(" (line {})", l)).unwrap_or_default();
+            format!("{}{} {} vs median {}", when, line, spike.volume, spike.median)
+        })
+        .collect();
+    if spikes.len() > MAX_LISTED_SPIKES {
+        listed.push(format!("and {} more", spikes.len() - MAX_LISTED_SPIKES));
+    }
+    listed.join(", ")
+}
+
+/// Bad rows listed by `describe_bad_rows` before the rest are only counted.
+const MAX_LISTED_BAD_ROWS: usize = 10;
+
+/// Formats rows skipped with `--collect-errors` for a report line, e.g. `line 7: Empty <OPEN> at line 7`.
+fn describe_bad_rows(bad_rows: &[csv_processor::BadRow]) -> String {
+    let mut listed: Vec<String> = bad_rows
+        .iter()
+        .take(MAX_LISTED_BAD_ROWS)
+        .map(|row| format!("line {}: {}", row.line, row.reason))
+        .collect();
+    if bad_rows.len() > MAX_LISTED_BAD_ROWS {
+        listed.push(format!("and {} more", bad_rows.len() - MAX_LISTED_BAD_ROWS));
+    }
+    listed.join("; ")
+}
@@ -1,172 +1,787 @@
+use crate::bar_source::{BarSource, NanSkipping, SoaColumns};
 use crate::cli;
+use crate::index;
+use crate::bundle;
+use crate::calendar;
 use crate::utils;
 use crate::resample;
+use crate::resample_cache;
+use crate::daily_report;
+use crate::grid_export;
+use crate::file_processing;
 use crate::ohlcv_generated;
 use crate::ohlcv_soa_generated;
 
 use rayon::prelude::*;
 
+/// Options controlling how .bin files are read and resampled.
+///
+/// Built once from the command-line arguments in `main` and shared (read-only)
+/// across all rayon workers in `read_flatbuffers`.
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    /// Optional timeframe: "1min", "2min", "3min", "4min", "5min", "90min", "1d".
+    pub resample: Option<String>,
+    /// Which timestamp resampled bars carry.
+    pub bar_timestamp: cli::BarTimestamp,
+    /// Print the number of aggregated source bars for resampled output.
+    pub show_count: bool,
+    /// Resample to about this many bars instead of a fixed timeframe.
+    pub target_bars: Option<usize>,
+    /// Print bars as an aligned table instead of one labelled line per bar.
+    pub table: bool,
+    /// Follow the printed bars with a sparkline of their closes (`utils::sparkline`).
+    pub sparkline: bool,
+    /// Retries for transient I/O errors when opening a .bin or loading its .idx.
+    pub io_retries: u32,
+    /// Fail if resampled bars are not strictly increasing in timestamp.
+    pub strict_resample: bool,
+    /// How volumes are printed.
+    pub volume_format: cli::VolumeFormat,
+    /// Round printed prices to this many decimals (output only, the `.bin` is untouched).
+    pub round_decimals: Option<u32>,
+    /// Annotate resampled bars with their true range.
+    pub with_true_range: bool,
+    /// Seconds added to printed timestamps (display only, the `.bin` stays UTC).
+    pub timestamp_offset: i64,
+    /// Bucket of intraday bars exactly on a boundary.
+    pub bucket_edge: cli::BucketEdge,
+    /// Offset in seconds of intraday bucket boundaries from the epoch (`--bucket-anchor`).
+    pub bucket_anchor: u64,
+    /// Resample to all of these timeframes in one pass over the file (`--resample-all`).
+    pub resample_all: Option<Vec<String>>,
+    /// Final extension of the .bin files to read (`--output-suffix`), `bin` by default.
+    pub output_suffix: String,
+    /// Write a `.daily-report.csv` with per-day statistics next to every file.
+    pub daily_report: bool,
+    /// Leave out the final resampled bar when the data ends part-way through its bucket.
+    pub drop_partial_bar: bool,
+    /// What resampling does with NaN highs and lows (`--nan-policy`).
+    pub nan_policy: cli::NanPolicy,
+    /// Annotate resampled bars with their number of distinct source timestamps.
+    pub unique_timestamps: bool,
+    /// Name at most this many skipped files in the end-of-run summary (`--list-skipped`).
+    pub list_skipped: usize,
+    /// Restart intraday buckets at every day's first bar (`resample::resample_by_session`).
+    pub anchor_to_session: bool,
+    /// Write the `--resample` timeframe's full grid, missing bars marked, to a `.grid-<tf>.csv`.
+    pub grid_mark_missing: bool,
+    /// Keep `-r` results in a `.cache.bin` next to each file and reuse them while fresh
+    /// (`resample_cache::load_or_compute`).
+    pub cache_resample: bool,
+}
+
+impl ReadOptions {
+    /// Whether reading needs the `.idx`: every resampling mode except `--resample-all`,
+    /// which scans the bars themselves, the daily report, which walks `daily_index`, and
+    /// the grid, whose boundaries come from `timeframe_index`.
+    fn needs_index(&self) -> bool {
+        self.daily_report || self.grid_mark_missing || self.target_bars.is_some() || self.resample.as_deref().is_some_and(|tf| tf != "1min")
+    }
+
+    /// Whether raw bars can be printed straight from the FlatBuffer, i.e. no option
+    /// needs them materialized as `OHLCVBar`s first.
+    fn prints_raw_directly(&self) -> bool {
+        !self.table && !self.sparkline && self.round_decimals.is_none() && self.timestamp_offset == 0
+    }
+
+    /// Whether only the first bars are printed as they are stored: no resampling mode is set
+    /// (`-r 1min` and unknown timeframes print the raw bars too).
+    fn prints_head_only(&self) -> bool {
+        self.resample_all.is_none()
+            && self.target_bars.is_none()
+            && self.threshold_bars().is_none()
+            && !self.resample.as_deref().is_some_and(|tf| ["2min", "3min", "4min", "5min", "90min", "1d"].contains(&tf))
+    }
+
+    /// The options besides the timeframe that change resampled bars, as the `params` of a
+    /// `resample_cache::CacheKey`.
+    fn resample_params(&self) -> String {
+        format!(
+            "bar_timestamp={:?} bucket_edge={:?} bucket_anchor={} nan_policy={:?} drop_partial_bar={} anchor_to_session={}",
+            self.bar_timestamp, self.bucket_edge, self.bucket_anchor, self.nan_policy, self.drop_partial_bar, self.anchor_to_session
+        )
+    }
+
+    /// The threshold of volume or count bars (`-r vol:N` / `-r count:N`), if those are asked for.
+    fn threshold_bars(&self) -> Option<resample::BarThreshold> {
+        self.resample.as_deref().and_then(resample::BarThreshold::parse)
+    }
+}
+
 /// Determines the storage format (AOS or SOA) based on the file name extension.
 /// 
-/// Checks if the file name ends with `.aos.bin` or `.soa.bin`.
+/// Checks if the file name ends with `.aos.<extension>` or `.soa.<extension>`, where
+/// `extension` is `bin` unless `--output-suffix` changed it.
 /// 
 /// # Arguments
 /// * `path` - The path to the FlatBuffer file (.bin).
+/// * `extension` - Final extension of FlatBuffer files, without the leading dot.
 /// 
 /// # Returns
 /// * `Some(StorageFormat)` if the format can be determined, `None` otherwise.
-fn determine_storage_format_from_path<P: AsRef<std::path::Path>>(path: P) -> Option<cli::StorageFormat> {
+fn determine_storage_format_from_path<P: AsRef<std::path::Path>>(path: P, extension: &str) -> Option<cli::StorageFormat> {
     let file_name = path.as_ref().file_name()?.to_str()?;
-    if file_name.ends_with(".aos.bin") {
+    if file_name.ends_with(&cli::StorageFormat::Aos.file_suffix_with(extension)) {
         Some(cli::StorageFormat::Aos)
-    } else if file_name.ends_with(".soa.bin") {
+    } else if file_name.ends_with(&cli::StorageFormat::Soa.file_suffix_with(extension)) {
         Some(cli::StorageFormat::Soa)
     } else {
         None
     }
 }
 
+/// Detects the storage format from the buffer itself, for files whose name doesn't say.
+///
+/// The buffer is verified as both root types, and the format is only reported if exactly
+/// one of them parses with its bar data present (`items` for AOS, `data.timestamps` for
+/// SOA), so an ambiguous buffer yields `None`.
+///
+/// # Arguments
+/// * `buf` - The FlatBuffer bytes (of the first segment, for segmented SOA files).
+///
+/// # Returns
+/// * `Some(StorageFormat)` if exactly one layout matches, `None` otherwise.
+pub fn detect_storage_format(buf: &[u8]) -> Option<cli::StorageFormat> {
+    let is_aos = ohlcv_generated::root_as_ohlcvlist(buf).is_ok_and(|list| list.items().is_some());
+    let is_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(buf).is_ok_and(|list| list.data().and_then(|d| d.timestamps()).is_some());
+    match (is_aos, is_soa) {
+        (true, false) => Some(cli::StorageFormat::Aos),
+        (false, true) => Some(cli::StorageFormat::Soa),
+        _ => None,
+    }
+}
+
+/// Determines the storage format from the file name, falling back to reading the file
+/// when the name doesn't end with a known suffix: the header of a `.ohlcv` bundle
+/// (`bundle::Bundle`), else the buffer itself (`detect_storage_format`).
+///
+/// # Arguments
+/// * `path` - The path to the FlatBuffer file.
+/// * `extension` - Final extension of FlatBuffer files, without the leading dot.
+///
+/// # Returns
+/// * `anyhow::Result<Option<StorageFormat>>` - The format, `None` if neither the name nor
+///   the contents identify it.
+///
+/// # Errors
+/// * If the file can't be opened or mapped.
+pub fn storage_format_of<P: AsRef<std::path::Path>>(path: P, extension: &str) -> anyhow::Result<Option<cli::StorageFormat>> {
+    if let Some(storage_format) = determine_storage_format_from_path(&path, extension) {
+        return anyhow::Ok(Some(storage_format));
+    }
+    let file = std::fs::File::open(&path).map_err(|e| file_processing::io_error(e, "open", &path))?;
+    let mmap = unsafe { memmap2::Mmap::map(&file) }.map_err(|e| file_processing::io_error(e, "map", &path))?;
+    if let Some(bundle) = bundle::Bundle::parse(&mmap)? {
+        return anyhow::Ok(Some(bundle.storage_format));
+    }
+    let storage_format = detect_storage_format(&mmap);
+    if let Some(storage_format) = &storage_format {
+        tracing::debug!("Detected {:?} layout from the contents of {}", storage_format, path.as_ref().display());
+    }
+    anyhow::Ok(storage_format)
+}
+
+/// `OHLCVList` as seen by `root_as_ohlcvlist_head`: `items` is verified as a vector of raw
+/// offsets, which only bounds-checks it instead of visiting every bar table.
+struct ShallowOHLCVList<'a>(flatbuffers::Table<'a>);
+
+impl<'a> flatbuffers::Follow<'a> for ShallowOHLCVList<'a> {
+    type Inner = ShallowOHLCVList<'a>;
+
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        ShallowOHLCVList(unsafe { flatbuffers::Table::new(buf, loc) })
+    }
+}
+
+impl flatbuffers::Verifiable for ShallowOHLCVList<'_> {
+    fn run_verifier(v: &mut flatbuffers::Verifier, pos: usize) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u32>>>("items", ohlcv_generated::OHLCVList::VT_ITEMS, false)?
+            .visit_field::<u32>("version", ohlcv_generated::OHLCVList::VT_VERSION, false)?
+            .visit_field::<bool>("has_volume", ohlcv_generated::OHLCVList::VT_HAS_VOLUME, false)?
+            .finish();
+        Ok(())
+    }
+}
+
+/// Parses an AOS buffer verifying only its root table and its first `n` bars.
+///
+/// `root_as_ohlcvlist` verifies every bar table, which faults in the whole file even when
+/// only the first bars are printed. Here `items` is bounds-checked as a whole (reading just
+/// its length) and each of the first `n` tables is verified on its own, so a raw read of a
+/// large mmapped file touches a few pages. SOA needs no such path: its columns are scalar
+/// vectors, which the verifier only bounds-checks.
+///
+/// # Safety
+/// Only `version()`, `has_volume()`, the length of `items()` and `items().get(i)` for `i < n`
+/// are verified. Reading any other bar of a corrupt buffer is undefined behaviour.
+///
+/// # Errors
+/// * If the root table or one of the first `n` bars doesn't verify.
+unsafe fn root_as_ohlcvlist_head(buf: &[u8], n: usize) -> anyhow::Result<ohlcv_generated::OHLCVList<'_>> {
+    // SAFETY: the caller reads no bar past the first `n`.
+    unsafe { root_as_ohlcvlist_bars(buf, |len| 0..n.min(len)) }
+}
+
+/// Parses an AOS buffer verifying only its root table and the bars in the range that
+/// `bars` returns for the length of `items` (not called if the list has no `items`).
+///
+/// # Safety
+/// Only `version()`, `has_volume()`, the length of `items()` and the bars in the returned
+/// range are verified. Reading any other bar of a corrupt buffer is undefined behaviour.
+///
+/// # Errors
+/// * If the root table or one of the bars in the range doesn't verify.
+unsafe fn root_as_ohlcvlist_bars(
+    buf: &[u8],
+    bars: impl FnOnce(usize) -> std::ops::Range<usize>,
+) -> anyhow::Result<ohlcv_generated::OHLCVList<'_>> {
+    let shallow = flatbuffers::root::<ShallowOHLCVList>(buf)
+        .map_err(|_| anyhow::anyhow!("Failed to parse root as OHLCVList"))?;
+    // SAFETY: `root` verified `items` as a vector of offsets.
+    let offsets = unsafe {
+        shallow.0.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<u32>>>(ohlcv_generated::OHLCVList::VT_ITEMS, None)
+    };
+    if let Some(offsets) = offsets {
+        let first = offsets.bytes().as_ptr() as usize - buf.as_ptr() as usize;
+        let verifier_options = flatbuffers::VerifierOptions::default();
+        let mut verifier = flatbuffers::Verifier::new(&verifier_options, buf);
+        let range = bars(offsets.len());
+        for i in range.start..range.end.min(offsets.len()) {
+            <flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV> as flatbuffers::Verifiable>::run_verifier(
+                &mut verifier,
+                first + i * flatbuffers::SIZE_UOFFSET,
+            )
+            .map_err(|_| anyhow::anyhow!("Failed to parse bar {} of OHLCVList", i))?;
+        }
+    }
+    // SAFETY: the root fields and the bars in the range are verified, and the caller reads
+    // nothing else.
+    anyhow::Ok(unsafe { ohlcv_generated::root_as_ohlcvlist_unchecked(buf) })
+}
+
+/// Lazy iterator over every bar of a FlatBuffer buffer, created by `iter_bars`.
+///
+/// AOS bars are read in place with `items.get(i)`; SOA bars are assembled from the
+/// six column vectors at the same position (`SoaColumns`). Nothing is materialized up front.
+#[derive(Clone)]
+pub enum BarIter<'a> {
+    Aos {
+        items: flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV<'a>>>,
+        pos: usize,
+    },
+    Soa {
+        columns: SoaColumns<'a>,
+        pos: usize,
+    },
+}
+
+impl Iterator for BarIter<'_> {
+    type Item = resample::OHLCVBar;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            BarIter::Aos { items, pos } => {
+                if *pos >= items.len() {
+                    return None;
+                }
+                let bar = resample::OHLCVBar::from(items.get(*pos));
+                *pos += 1;
+                Some(bar)
+            }
+            BarIter::Soa { columns, pos } => {
+                if *pos >= columns.len() {
+                    return None;
+                }
+                let bar = columns.bar(*pos);
+                *pos += 1;
+                Some(bar)
+            }
+        }
+    }
+
+    /// Jumps straight to the `n`-th remaining bar, so skipping ahead is O(1).
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        match self {
+            BarIter::Aos { pos, .. } | BarIter::Soa { pos, .. } => *pos = pos.saturating_add(n),
+        }
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = match self {
+            BarIter::Aos { items, pos } => items.len().saturating_sub(*pos),
+            BarIter::Soa { columns, pos } => columns.len().saturating_sub(*pos),
+        };
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for BarIter<'_> {}
+
+/// Iterates lazily over every bar of a FlatBuffer buffer (typically an mmap of a .bin file).
+///
+/// This is the building block for custom aggregations beyond the built-in resamplers:
+/// bars are yielded one at a time in storage order without collecting them into a `Vec`.
+///
+/// # Arguments
+/// * `buf` - The FlatBuffer bytes.
+/// * `storage_format` - Layout of `buf` (AOS or SOA).
+///
+/// # Returns
+/// * `anyhow::Result<BarIter>` - Iterator over the bars.
+///
+/// # Errors
+/// * If `buf` can't be parsed as the given format.
+/// * If the schema version isn't supported.
+/// * If the SOA columns have mismatched lengths (`SoaColumns::new`).
+pub fn iter_bars<'a>(buf: &'a [u8], storage_format: &cli::StorageFormat) -> anyhow::Result<BarIter<'a>> {
+    match storage_format {
+        cli::StorageFormat::Aos => {
+            let ohlcv_list = ohlcv_generated::root_as_ohlcvlist(buf)
+                .map_err(|_| anyhow::anyhow!("Failed to parse root as OHLCVList"))?;
+            utils::check_schema_version(ohlcv_list.version())?;
+            anyhow::Ok(BarIter::Aos { items: ohlcv_list.items().unwrap_or_default(), pos: 0 })
+        }
+        cli::StorageFormat::Soa => {
+            let ohlcv_list_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(buf)
+                .map_err(|_| anyhow::anyhow!("Failed to parse root as OHLCVListSOA (SOA)"))?;
+            utils::check_schema_version(ohlcv_list_soa.version())?;
+            let columns = match ohlcv_list_soa.data() {
+                Some(data_soa) => SoaColumns::new(data_soa)?,
+                None => SoaColumns::empty(),
+            };
+            anyhow::Ok(BarIter::Soa { columns, pos: 0 })
+        }
+    }
+}
+
+/// Splits a .bin into the SOA segments it was written in (`--soa-segment-size`).
+///
+/// Each segment is a self-contained `OHLCVListSOA` running from its offset to the next one
+/// (the last to the end of the file). A file without `segment_offsets` is one segment.
+///
+/// # Arguments
+/// * `buf` - The whole .bin file.
+/// * `segment_offsets` - Byte offsets of the segments, from `FullIndex::segment_offsets`.
+///
+/// # Returns
+/// * `anyhow::Result<Vec<&[u8]>>` - The segments in storage order.
+///
+/// # Errors
+/// * If an offset is out of order or past the end of `buf` (the `.idx` doesn't match the file).
+pub fn split_segments<'a>(buf: &'a [u8], segment_offsets: Option<&[u64]>) -> anyhow::Result<Vec<&'a [u8]>> {
+    let Some(offsets) = segment_offsets else {
+        return anyhow::Ok(vec![buf]);
+    };
+    let mut segments = Vec::with_capacity(offsets.len());
+    for (i, &start) in offsets.iter().enumerate() {
+        let end = offsets.get(i + 1).copied().unwrap_or(buf.len() as u64);
+        let segment = usize::try_from(start)
+            .ok()
+            .zip(usize::try_from(end).ok())
+            .and_then(|(start, end)| buf.get(start..end))
+            .filter(|segment| !segment.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Segment {} ({}..{}) doesn't fit the {}-byte file", i, start, end, buf.len()))?;
+        segments.push(segment);
+    }
+    anyhow::Ok(segments)
+}
+
+/// Iterates lazily over the bars of every segment in storage order, as if they were one buffer.
+///
+/// # Arguments
+/// * `segments` - Segments from `split_segments`.
+/// * `storage_format` - Layout of the segments.
+///
+/// # Returns
+/// * `anyhow::Result<...>` - Iterator over the bars of all segments.
+///
+/// # Errors
+/// * If a segment can't be parsed (see `iter_bars`).
+pub fn iter_segments<'a>(
+    segments: &[&'a [u8]],
+    storage_format: &cli::StorageFormat,
+) -> anyhow::Result<std::iter::Flatten<std::vec::IntoIter<BarIter<'a>>>> {
+    let iters = segments
+        .iter()
+        .map(|segment| iter_bars(segment, storage_format))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    anyhow::Ok(iters.into_iter().flatten())
+}
+
+/// Returns the `i`-th bar of a FlatBuffer buffer, without any index file.
+///
+/// This is O(1): an AOS buffer is parsed with only its root and bar `i` verified
+/// (`root_as_ohlcvlist_bars`), and an SOA buffer's columns are bounds-checked as wholes
+/// before position `i` of each is read. A corrupt bar elsewhere in the buffer doesn't
+/// stop bar `i` from being read.
+///
+/// # Arguments
+/// * `buf` - The FlatBuffer bytes (typically an mmap of a .bin file).
+/// * `storage_format` - Layout of `buf` (AOS or SOA).
+/// * `i` - Zero-based bar position in storage order.
+///
+/// # Returns
+/// * `anyhow::Result<Option<OHLCVBar>>` - The bar, or `None` if `i` is out of range.
+///
+/// # Errors
+/// * If `buf` can't be parsed as the given format.
+/// * If the schema version isn't supported.
+pub fn get_bar(buf: &[u8], storage_format: &cli::StorageFormat, i: usize) -> anyhow::Result<Option<resample::OHLCVBar>> {
+    bar_at(buf, storage_format, |len| (i < len).then_some(i))
+}
+
+/// Returns the bar at the position `position` picks from the number of bars, reading
+/// and verifying nothing else (see `get_bar`).
+fn bar_at(
+    buf: &[u8],
+    storage_format: &cli::StorageFormat,
+    position: impl FnOnce(usize) -> Option<usize>,
+) -> anyhow::Result<Option<resample::OHLCVBar>> {
+    match storage_format {
+        cli::StorageFormat::Aos => {
+            let mut picked = None;
+            // SAFETY: only the root fields and bar `picked` are read, and both are verified.
+            let ohlcv_list = unsafe {
+                root_as_ohlcvlist_bars(buf, |len| {
+                    picked = position(len);
+                    picked.map_or(0..0, |i| i..i + 1)
+                })?
+            };
+            utils::check_schema_version(ohlcv_list.version())?;
+            anyhow::Ok(picked.map(|i| resample::OHLCVBar::from(ohlcv_list.items().unwrap_or_default().get(i))))
+        }
+        cli::StorageFormat::Soa => {
+            let mut bars = iter_bars(buf, storage_format)?;
+            anyhow::Ok(position(bars.len()).and_then(|i| bars.nth(i)))
+        }
+    }
+}
+
+/// Streams the bars of a FlatBuffer buffer whose local time of day falls in a window.
+///
+/// Each bar's UTC timestamp is converted to `tz` (so DST shifts are followed day by day)
+/// and compared with the window, e.g. 10:00–11:00 New York time across every day for
+/// intraday seasonality studies. Bars are filtered lazily from `iter_bars`, so nothing
+/// is materialized.
+///
+/// Bounds follow `--session`: `start` is inclusive and `end` is exclusive, so
+/// 10:00–11:00 keeps the 10:00:00 bar and drops the 11:00:00 one. A window with
+/// `start > end` wraps midnight (22:00–02:00).
+///
+/// # Arguments
+/// * `buf` - The FlatBuffer bytes (typically an mmap of a .bin file).
+/// * `storage_format` - Layout of `buf` (AOS or SOA).
+/// * `start` - First local time of day kept.
+/// * `end` - Local time of day at which the window closes.
+/// * `tz` - Time zone the window is expressed in.
+///
+/// # Returns
+/// * `anyhow::Result<impl Iterator<Item = OHLCVBar>>` - The matching bars in storage order.
+///
+/// # Errors
+/// * If `start` and `end` are equal.
+/// * If `buf` can't be parsed as the given format, or its schema version isn't supported.
+pub fn query_time_of_day<'a>(
+    buf: &'a [u8],
+    storage_format: &cli::StorageFormat,
+    start: chrono::NaiveTime,
+    end: chrono::NaiveTime,
+    tz: chrono_tz::Tz,
+) -> anyhow::Result<impl Iterator<Item = resample::OHLCVBar> + 'a> {
+    if start == end {
+        return Err(anyhow::anyhow!("Invalid time-of-day window {}-{}: start and end are equal", start, end));
+    }
+    let window = calendar::SessionWindow { start, end };
+    let bars = iter_bars(buf, storage_format)?;
+    anyhow::Ok(bars.filter(move |bar| {
+        chrono::DateTime::from_timestamp(bar.timestamp as i64, 0)
+            .is_some_and(|dt| window.contains(dt.with_timezone(&tz).time()))
+    }))
+}
+
+/// The bars of a .bin file together with what the file records about them (`load_file`).
+#[derive(Debug, Clone)]
+pub struct LoadedFile {
+    /// All bars in ascending time.
+    pub bars: Vec<resample::OHLCVBar>,
+    /// Whether the file stores volumes; the bars of a `--no-volume` file read 0.
+    pub has_volume: bool,
+    /// Instrument recorded in the index (`FullIndex::symbol`), if any.
+    pub symbol: Option<String>,
+}
+
+/// Loads every bar of a .bin file into memory as `OHLCVBar`s.
+///
+/// The storage format is detected from the file name (`.aos.bin` / `.soa.bin`, with any
+/// final extension), or else from the contents, so AOS and SOA files can be mixed freely
+/// by callers such as `merge`. Segmented SOA
+/// files are read through the segment offsets in their `.idx`. `path` may also be a
+/// `.ohlcv` bundle, whose index is used instead.
+///
+/// # Arguments
+/// * `path` - Path to the .bin file or `.ohlcv` bundle.
+///
+/// # Returns
+/// * `anyhow::Result<Vec<OHLCVBar>>` - All bars in ascending time, also for files written
+///   newest-first (`--order desc`).
+///
+/// # Errors
+/// * If the format can't be determined from the file name or contents.
+/// * If the file can't be opened, mapped or parsed, or its `.idx` can't be read.
+pub fn load_bars<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Vec<resample::OHLCVBar>> {
+    anyhow::Ok(load_file(path)?.bars)
+}
+
+/// Like `load_bars`, but also returns whether the file stores volumes and the symbol in its
+/// index, for callers that write the bars out again (`merge`).
+///
+/// # Errors
+/// * The same as `load_bars`.
+pub fn load_file<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<LoadedFile> {
+    let extension = path.as_ref().extension().and_then(|ext| ext.to_str()).unwrap_or(cli::DEFAULT_OUTPUT_SUFFIX);
+    let storage_format = storage_format_of(&path, extension)?
+        .ok_or_else(|| anyhow::anyhow!("Unknown storage format for file {}", path.as_ref().display()))?;
+    let file = std::fs::File::open(&path).map_err(|e| file_processing::io_error(e, "open", &path))?;
+    let mmap = unsafe { memmap2::Mmap::map(&file) }.map_err(|e| file_processing::io_error(e, "map", &path))?;
+    let bundle = bundle::Bundle::parse(&mmap)?;
+    let idx_path = path.as_ref().with_extension("idx");
+    let full_index = match &bundle {
+        Some(bundle) => Some(bundle.full_index()?),
+        None if idx_path.exists() => Some(utils::load_full_index(&idx_path)?),
+        None => None,
+    };
+    let (segment_offsets, symbol) = full_index.map_or((None, None), |full_index| (full_index.segment_offsets, full_index.symbol));
+    let buf = bundle.as_ref().map_or(&mmap[..], |bundle| bundle.payload);
+    let segments = split_segments(buf, segment_offsets.as_deref())?;
+
+    let mut bars: Vec<resample::OHLCVBar> = iter_segments(&segments, &storage_format)?.collect();
+    if stored_newest_first(&segments, &storage_format)? {
+        bars.reverse();
+    }
+    let has_volume = match storage_format {
+        // SAFETY: only `has_volume()` is read, and the root is verified.
+        cli::StorageFormat::Aos => unsafe { root_as_ohlcvlist_head(segments[0], 0)? }.has_volume(),
+        cli::StorageFormat::Soa => ohlcv_soa_generated::root_as_ohlcvlist_soa(segments[0])
+            .map_err(|_| anyhow::anyhow!("Failed to parse root as OHLCVListSOA (SOA)"))?
+            .has_volume(),
+    };
+    anyhow::Ok(LoadedFile { bars, has_volume, symbol })
+}
+
+/// Whether the segments hold their bars newest-first (written with `--order desc`).
+///
+/// Only the first and last bar are read and verified (`bar_at`), so this is O(1).
+fn stored_newest_first(segments: &[&[u8]], storage_format: &cli::StorageFormat) -> anyhow::Result<bool> {
+    let (Some(first_segment), Some(last_segment)) = (segments.first(), segments.last()) else {
+        return anyhow::Ok(false);
+    };
+    let first = get_bar(first_segment, storage_format, 0)?;
+    let last = bar_at(last_segment, storage_format, |len| len.checked_sub(1))?;
+    anyhow::Ok(matches!((first, last), (Some(first), Some(last)) if first.timestamp > last.timestamp))
+}
+
 /// Reads .aos/.soa and .idx files, optionally resamples data,
 /// and prints first 5 bars in human-readable format.
 ///
 /// Uses mmap for zero-copy reading and supports multiple timeframes.
 /// This function now identifies the storage format (AOS/SOA) from the file name
-/// and processes the file accordingly.
+/// and processes the file accordingly. `.ohlcv` bundles (`--bundle`) are read as well.
 ///
 /// # Arguments
-/// * `output_dir_path` - Directory with .bin files.
-/// * `resample` - Optional timeframe: "1min", "2min", "3min", "4min", "5min", "1d".
+/// * `output_dir_path` - Directory with .bin files (or `.<output_suffix>` files).
+/// * `options` - Read options (resample timeframe, bar timestamp mode).
 ///
 /// # Returns
 /// * `anyhow::Result<()>`
 pub fn read_flatbuffers<P: AsRef<std::path::Path> + Send + Sync>(
     output_dir_path: P,
-    resample: Option<String>,
+    options: &ReadOptions,
 ) -> anyhow::Result<()> {
     let paths = std::fs::read_dir(output_dir_path.as_ref())?
         .filter_map(|entry| entry.ok())
         .filter(|entry| {
             let path = entry.path();
-            path.extension().map_or(false, |ext| ext == "bin")
+            path.extension().is_some_and(|ext| ext == options.output_suffix.as_str() || ext == bundle::BUNDLE_EXTENSION)
+                && !resample_cache::is_cache_file(&path)
         })
         .collect::<Vec<_>>();
 
-    paths.par_iter().try_for_each(|entry| {
-        let path = entry.path();
-        if let Some(format) = determine_storage_format_from_path(&path) {
-            process_file(&path, &resample, format)?;
-        } else {
-            println!("⚠️ Skipping file with unknown format: {}", path.display());
-        }
+    // Skipped files are only logged at debug level as they go by; the summary at the end
+    // lists them together, so they don't scroll away in a large directory.
+    let skipped = paths
+        .par_iter()
+        .map(|entry| {
+            let path = entry.path();
+            if let Some(format) = storage_format_of(&path, &options.output_suffix)? {
+                process_file(&path, options, format)?;
+                anyhow::Ok(None)
+            } else {
+                tracing::debug!("Skipping file with unknown format: {}", path.display());
+                anyhow::Ok(Some(path))
+            }
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut skipped: Vec<std::path::PathBuf> = skipped.into_iter().flatten().collect();
+    skipped.sort();
 
-        Ok::<_, anyhow::Error>(())
-    })?;
-    
+    if skipped.is_empty() {
+        tracing::info!("📖 {} file(s) read", paths.len());
+    } else {
+        tracing::warn!(
+            "⚠️ {} file(s) read, {} skipped (unknown format){}",
+            paths.len() - skipped.len(),
+            skipped.len(),
+            describe_skipped(&skipped, options.list_skipped)
+        );
+    }
     Ok(())
 }
 
+/// Formats the tail of the skipped-files summary: `: a.bin, b.bin and 3 more`, naming at
+/// most `limit` files (by file name), or nothing when `limit` is 0.
+fn describe_skipped(skipped: &[std::path::PathBuf], limit: usize) -> String {
+    if limit == 0 {
+        return String::new();
+    }
+    let names: Vec<String> = skipped
+        .iter()
+        .take(limit)
+        .map(|path| path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned()))
+        .collect();
+    let more = if skipped.len() > limit {
+        format!(" and {} more", skipped.len() - limit)
+    } else {
+        String::new()
+    };
+    format!(": {}{}", names.join(", "), more)
+}
+
 /// Processes a single .bin file: reads, resamples, prints.
 /// 
 /// This function handles the core logic for reading a FlatBuffer file,
 /// performing optional resampling based on the specified format (AOS/SOA),
 /// and printing results. It uses `mmap` for efficient, zero-copy access.
+/// A `.ohlcv` bundle is read the same way, with the index taken from the bundle.
 ///
 /// # Arguments
-/// * `path` - Path to the .bin file.
-/// * `resample` - Optional timeframe string (e.g., "1min", "5min", "1d").
+/// * `path` - Path to the .bin file, or `.ohlcv` bundle.
+/// * `options` - Read options (resample timeframe, bar timestamp mode).
 /// * `storage_format` - The format of the FlatBuffer data (AOS or SOA).
 ///
 /// # Returns
 /// * `anyhow::Result<()>`
 fn process_file<P: AsRef<std::path::Path>>(
     path: P,
-    resample: &Option<String>,
+    options: &ReadOptions,
     storage_format: cli::StorageFormat,
 ) -> anyhow::Result<()> {
-    println!("Processing reading in thread: {:?} fo file {:?}", std::thread::current().id(), path.as_ref());
+    tracing::debug!("Processing reading in thread: {:?} fo file {:?}", std::thread::current().id(), path.as_ref());
     
-    let file = std::fs::File::open(&path)?;
-    let mmap = unsafe {memmap2::Mmap::map(&file)? };
+    let description = path.as_ref().display().to_string();
+    let mmap = file_processing::retry_io(options.io_retries, &description, || {
+        let file = std::fs::File::open(&path).map_err(|e| file_processing::io_error(e, "open", &path))?;
+        let mmap = unsafe { memmap2::Mmap::map(&file) }.map_err(|e| file_processing::io_error(e, "map", &path))?;
+        anyhow::Ok(mmap)
+    })?;
 
+    // A `.ohlcv` bundle carries its index; its FlatBuffer data is read in place.
+    let bundle = bundle::Bundle::parse(&mmap)?;
+    let buf = bundle.as_ref().map_or(&mmap[..], |bundle| bundle.payload);
     let idx_path = path.as_ref().with_extension("idx");
-    let full_index = utils::load_full_index(&idx_path)?;
+    // Raw reads only cross-check the bar count against the index, so files written with
+    // `--no-index` can still be read as long as nothing needs resampling.
+    let full_index = match &bundle {
+        Some(bundle) => Some(bundle.full_index()?),
+        None if options.needs_index() || idx_path.exists() => {
+            Some(file_processing::retry_io(options.io_retries, &description, || utils::load_full_index(&idx_path))?)
+        }
+        None => None,
+    };
+    if let Some(symbol) = full_index.as_ref().and_then(|i| i.symbol.as_ref()) {
+        tracing::info!("🏷️ Symbol: {} ({})", symbol, path.as_ref().display());
+    }
+    if options.daily_report {
+        let full_index = require_index(&full_index, &idx_path)?;
+        let segments = split_segments(buf, full_index.segment_offsets.as_deref())?;
+        let (report_path, days) = daily_report::write_daily_report(path.as_ref(), &segments, &storage_format, &full_index.daily_index)?;
+        tracing::info!("📅 Wrote daily report for {} day(s) to {}", days, report_path.display());
+    }
+    if let Some(timeframe) = options.resample.as_deref().filter(|_| options.grid_mark_missing) {
+        let full_index = require_index(&full_index, &idx_path)?;
+        let segments = split_segments(buf, full_index.segment_offsets.as_deref())?;
+        let (grid_path, rows, missing) = grid_export::write_grid(path.as_ref(), &segments, &storage_format, full_index, timeframe)?;
+        tracing::info!("🧱 Wrote {} grid of {} bar(s), {} missing, to {}", timeframe, rows, missing, grid_path.display());
+    }
     let start = std::time::Instant::now();
+    if let Some(full_index) = full_index.as_ref().filter(|i| i.segment_offsets.is_some()) {
+        let segments = split_segments(buf, full_index.segment_offsets.as_deref())?;
+        process_segments(path.as_ref(), &segments, &storage_format, full_index, options)?;
+        tracing::info!(
+            "✅ Resampling completed in {:?} seconds",
+            start.elapsed().as_secs_f64()
+        );
+        return anyhow::Ok(());
+    }
 
     match storage_format {
         cli::StorageFormat::Aos => {
             // --- AOS Processing ---
-            let ohlcv_list = ohlcv_generated::root_as_ohlcvlist(&mmap)
-                .map_err(|_| anyhow::anyhow!("Failed to parse root as OHLCVList"))?;
+            let ohlcv_list = if options.prints_head_only() {
+                // SAFETY: raw reads only look at the first 5 bars (`print_items`).
+                unsafe { root_as_ohlcvlist_head(buf, 5)? }
+            } else {
+                ohlcv_generated::root_as_ohlcvlist(buf)
+                    .map_err(|_| anyhow::anyhow!("Failed to parse root as OHLCVList"))?
+            };
+            utils::check_schema_version(ohlcv_list.version())?;
             let items = ohlcv_list.items().unwrap_or_default();
+            let has_volume = ohlcv_list.has_volume();
+            if let Some(full_index) = &full_index {
+                utils::check_index_matches(full_index, items.len())?;
+            }
 
-            match resample.as_deref() {
-                Some("1min") => {
-                    println!("📄 Read first 5 1min bars (AOS)");
-                    utils::print_bars_aos(&items, 5)?;
-                }
-                Some(tf) if ["2min", "3min", "4min", "5min"].contains(&tf) => {
-                    let timeframe_sec = match tf {
-                        "2min" => 120,
-                        "3min" => 180,
-                        "4min" => 240,
-                        "5min" => 300,
-                        _ => unreachable!(),
-                    };
-                    let resampled = resample::resample_ohlcv_aos(&items, &full_index.time_index, timeframe_sec)?;
-                    println!("📈 Resampled to {} timeframe (AOS)", tf);
-                    utils::print_bars_resampled(&resampled, 5)?;
-                }
-                Some("1d") => {
-                    let daily_bars = resample::resample_daily_aos(&items, &full_index.daily_index)?;
-                    println!("📈 Resampled to daily timeframe (AOS)");
-                    utils::print_bars_resampled(&daily_bars, 5)?;
-                }
-                _ => {
-                    println!("📄 Read first 5 OHLCV entries for file {} (AOS)", path.as_ref().display());
-                    utils::print_bars_aos(&items, 5)?;
-                }
+            if let Some(timeframes) = &options.resample_all {
+                print_resampled_all(&[buf], &storage_format, timeframes, options, has_volume, "AOS")?;
+            } else {
+                print_source(&items, "AOS", path.as_ref(), &full_index, &idx_path, options, has_volume)?;
             }
             
-            println!(
+            tracing::info!(
                 "✅ Resampling completed in {:?} seconds",
                 start.elapsed().as_secs_f64()
             );
         }
         cli::StorageFormat::Soa => {
             // --- SOA Processing ---
-            let ohlcv_list_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&mmap)
+            let ohlcv_list_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(buf)
                 .map_err(|_| anyhow::anyhow!("Failed to parse root as OHLCVListSOA (SOA)"))?;
-            let data_soa = ohlcv_list_soa.data().unwrap();
+            utils::check_schema_version(ohlcv_list_soa.version())?;
+            // A file written with zero bars may have no `data` table at all; it reads as 0 bars
+            // rather than failing (or panicking) the whole directory.
+            let data_soa = ohlcv_list_soa.data();
+            let has_volume = ohlcv_list_soa.has_volume();
+            if let Some(full_index) = &full_index {
+                utils::check_index_matches(full_index, data_soa.and_then(|d| d.timestamps()).map_or(0, |t| t.len()))?;
+            }
 
-            match resample.as_deref() {
-                Some("1min") => {
-                    println!("📄 Read first 5 1min bars (SOA)");
-                    utils::print_bars_soa(data_soa, 5)?;
-                }
-                Some(tf) if ["2min", "3min", "4min", "5min"].contains(&tf) => {
-                    let timeframe_sec = match tf {
-                        "2min" => 120,
-                        "3min" => 180,
-                        "4min" => 240,
-                        "5min" => 300,
-                        _ => unreachable!(),
-                    };
-                    let resampled = resample::resample_ohlcv_soa(data_soa, &full_index.time_index, timeframe_sec)?;
-                    println!("📈 Resampled to {} timeframe (SOA)", tf);
-                    utils::print_bars_resampled(&resampled, 5)?;
-                }
-                Some("1d") => {
-                    let daily_bars = resample::resample_daily_soa(data_soa, &full_index.daily_index)?;
-                    println!("📈 Resampled to daily timeframe (SOA)");
-                    utils::print_bars_resampled(&daily_bars, 5)?;
-                }
-                _ => {
-                    println!("📄 Read first 5 OHLCV entries for file {}", path.as_ref().display());
-                    utils::print_bars_soa(data_soa, 5)?;
-                }
+            if let Some(timeframes) = &options.resample_all {
+                print_resampled_all(&[buf], &storage_format, timeframes, options, has_volume, "SOA")?;
+            } else {
+                let columns = match data_soa {
+                    Some(data_soa) if options.prints_head_only() => SoaColumns::truncated(data_soa),
+                    Some(data_soa) => SoaColumns::new(data_soa)?,
+                    None => {
+                        tracing::info!("📭 {} has 0 bars (no SOA data)", path.as_ref().display());
+                        SoaColumns::empty()
+                    }
+                };
+                print_source(&columns, "SOA", path.as_ref(), &full_index, &idx_path, options, has_volume)?;
             }
 
-            println!(
+            tracing::info!(
                 "✅ Resampling completed in {:?} seconds",
                 start.elapsed().as_secs_f64()
             );
@@ -175,3 +790,660 @@ fn process_file<P: AsRef<std::path::Path>>(
 
     anyhow::Ok(())
 }
+
+/// Prints the bars of a single-buffer file as selected by `options`, other than
+/// `--resample-all`: raw bars, `--target-bars`, or one `-r` timeframe.
+///
+/// # Arguments
+/// * `source` - The file's bars (AOS items or SOA columns).
+/// * `layout` - "AOS" or "SOA", for messages.
+/// * `path` - Path to the .bin file, for messages.
+/// * `full_index` - The file's index, if one was loaded.
+/// * `idx_path` - Path of the index, for the error when one is needed but missing.
+/// * `options` - Read options (resampling, output format, ...).
+/// * `has_volume` - Whether the file stores volumes.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success or an error if resampling or printing fails.
+fn print_source<B: BarSource>(
+    source: &B,
+    layout: &str,
+    path: &std::path::Path,
+    full_index: &Option<index::FullIndex>,
+    idx_path: &std::path::Path,
+    options: &ReadOptions,
+    has_volume: bool,
+) -> anyhow::Result<()> {
+    if !options.prints_head_only() {
+        check_nan_policy((0..source.len()).map(|i| source.bar(i)), options)?;
+        if options.nan_policy == cli::NanPolicy::Skip {
+            return print_source_bars(&NanSkipping(source), layout, path, full_index, idx_path, options, has_volume);
+        }
+    }
+    print_source_bars(source, layout, path, full_index, idx_path, options, has_volume)
+}
+
+/// `print_source` once `--nan-policy` has been applied to `source`.
+fn print_source_bars<B: BarSource>(
+    source: &B,
+    layout: &str,
+    path: &std::path::Path,
+    full_index: &Option<index::FullIndex>,
+    idx_path: &std::path::Path,
+    options: &ReadOptions,
+    has_volume: bool,
+) -> anyhow::Result<()> {
+    if let Some(target_count) = options.target_bars {
+        let time_index = &require_index(full_index, idx_path)?.time_index;
+        let timeframe_sec = resample::timeframe_for_count(time_index, target_count, &options.bucket_edge, options.bucket_anchor)?;
+        let resampled = resample::resample_ohlcv(source, time_index, timeframe_sec, &options.bar_timestamp, &options.bucket_edge, options.bucket_anchor)?;
+        let unique = unique_timestamps(time_index.iter().map(|e| e.timestamp), resample::Timeframe::Intraday(timeframe_sec), options);
+        tracing::info!("📈 Resampled to {} bars, target {} ({})", resampled.len(), target_count, layout);
+        return print_bars(&resampled, unique.as_deref(), options, has_volume);
+    }
+    if let Some(threshold) = options.threshold_bars() {
+        let time_index = &require_index(full_index, idx_path)?.time_index;
+        // The time index visits the bars in time order, also for `--order desc` files.
+        let bars = time_index.iter().map(|e| source.bar(e.index as usize));
+        let resampled = threshold_bars(bars, threshold, options, has_volume)?;
+        tracing::info!("📈 Resampled to {} {} bars ({})", resampled.len(), threshold, layout);
+        return print_bars(&resampled, None, options, has_volume);
+    }
+    match options.resample.as_deref() {
+        Some("1min") => {
+            tracing::info!("📄 Read first 5 1min bars ({})", layout);
+            print_items(source, options, has_volume)?;
+        }
+        Some(tf) if ["2min", "3min", "4min", "5min", "90min"].contains(&tf) => {
+            let timeframe_sec = match tf {
+                "2min" => 120,
+                "3min" => 180,
+                "4min" => 240,
+                "5min" => 300,
+                "90min" => 5400,
+                _ => unreachable!(),
+            };
+            let indexed = require_index(full_index, idx_path)?;
+            if options.anchor_to_session {
+                let resampled = cached_resample(path, Some(tf), options, || {
+                    resample::resample_by_session(source, &indexed.daily_index, timeframe_sec, &options.bar_timestamp, &options.bucket_edge)
+                })?;
+                tracing::info!("📈 Resampled to {} timeframe, anchored to each session ({})", tf, layout);
+                return print_bars(&resampled, None, options, has_volume);
+            }
+            let resampled = cached_resample(path, Some(tf), options, || {
+                // With --precompute-tf-positions each bucket is read from its stored position.
+                let bar_at = |i: usize| (i < source.len()).then(|| source.bar(i));
+                let mut resampled = match resample::resample_positioned(indexed, timeframe_sec, &options.bar_timestamp, &options.bucket_edge, options.bucket_anchor, bar_at) {
+                    Some(resampled) => resampled,
+                    None => resample::resample_ohlcv(source, &indexed.time_index, timeframe_sec, &options.bar_timestamp, &options.bucket_edge, options.bucket_anchor)?,
+                };
+                drop_partial_bar(&mut resampled, &indexed.time_index, resample::Timeframe::Intraday(timeframe_sec), options);
+                anyhow::Ok(resampled)
+            })?;
+            let unique = unique_timestamps(indexed.time_index.iter().map(|e| e.timestamp), resample::Timeframe::Intraday(timeframe_sec), options);
+            tracing::info!("📈 Resampled to {} timeframe ({})", tf, layout);
+            print_bars(&resampled, unique.as_deref(), options, has_volume)?;
+        }
+        Some("1d") => {
+            let indexed = require_index(full_index, idx_path)?;
+            let daily_bars = cached_resample(path, Some("1d"), options, || {
+                let mut daily_bars = resample::resample_daily(source, &indexed.daily_index, &options.bar_timestamp)?;
+                drop_partial_bar(&mut daily_bars, &indexed.time_index, resample::Timeframe::Daily, options);
+                anyhow::Ok(daily_bars)
+            })?;
+            let unique = options.unique_timestamps.then(|| resample::unique_timestamps_daily(source, &indexed.daily_index));
+            tracing::info!("📈 Resampled to daily timeframe ({})", layout);
+            print_bars(&daily_bars, unique.as_deref(), options, has_volume)?;
+        }
+        _ => {
+            tracing::info!("📄 Read first 5 OHLCV entries for file {} ({})", path.display(), layout);
+            print_items(source, options, has_volume)?;
+        }
+    }
+    anyhow::Ok(())
+}
+
+/// Reads a file written in SOA segments (`--soa-segment-size`) and prints it like
+/// `process_file` does for single-buffer files.
+///
+/// Index positions span all segments, while the index-driven resamplers work on one
+/// buffer, so the bars of every segment are collected in time order and resampled with
+/// `resample::resample_many` instead. `--target-bars` picks its timeframe from the index
+/// as usual (`resample::timeframe_for_count`).
+///
+/// # Arguments
+/// * `path` - Path to the .bin file, for messages.
+/// * `segments` - The file's segments (`split_segments`).
+/// * `storage_format` - Layout of the segments.
+/// * `full_index` - The file's index.
+/// * `options` - Read options.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success or an error if a segment can't be read or the bar
+///   count doesn't match the index.
+fn process_segments(
+    path: &std::path::Path,
+    segments: &[&[u8]],
+    storage_format: &cli::StorageFormat,
+    full_index: &index::FullIndex,
+    options: &ReadOptions,
+) -> anyhow::Result<()> {
+    let first_segment = segments.first().ok_or_else(|| anyhow::anyhow!("{} has no segments", path.display()))?;
+    let has_volume = ohlcv_soa_generated::root_as_ohlcvlist_soa(first_segment)
+        .map_err(|_| anyhow::anyhow!("Failed to parse root as OHLCVListSOA (SOA)"))?
+        .has_volume();
+    let mut bars: Vec<resample::OHLCVBar> = iter_segments(segments, storage_format)?.collect();
+    utils::check_index_matches(full_index, bars.len())?;
+
+    if let Some(timeframes) = &options.resample_all {
+        return print_resampled_all(segments, storage_format, timeframes, options, has_volume, "SOA");
+    }
+    if let Some(threshold) = options.threshold_bars() {
+        if stored_newest_first(segments, storage_format)? {
+            bars.reverse();
+        }
+        check_nan_policy(bars.iter().copied(), options)?;
+        if options.nan_policy == cli::NanPolicy::Skip {
+            bars = bars.into_iter().map(resample::skip_nan_extremes).collect();
+        }
+        let resampled = threshold_bars(bars, threshold, options, has_volume)?;
+        tracing::info!("📈 Resampled to {} {} bars ({} SOA segments)", resampled.len(), threshold, segments.len());
+        return print_bars(&resampled, None, options, has_volume);
+    }
+    let timeframe = match (options.target_bars, options.resample.as_deref()) {
+        (Some(target_count), _) => Some(resample::Timeframe::Intraday(resample::timeframe_for_count(
+            &full_index.time_index,
+            target_count,
+            &options.bucket_edge,
+            options.bucket_anchor,
+        )?)),
+        (None, Some(tf)) if tf != "1min" => Some(resample::Timeframe::parse(tf)?),
+        _ => None,
+    };
+    // `--target-bars` picks a timeframe per file, so only `-r` results are cached.
+    let cache_timeframe = options.resample.as_deref().filter(|_| options.target_bars.is_none());
+    match timeframe {
+        Some(resample::Timeframe::Intraday(timeframe_sec)) if options.anchor_to_session => {
+            // `daily_index` holds storage positions, so the bars stay in storage order.
+            check_nan_policy(bars.iter().copied(), options)?;
+            if options.nan_policy == cli::NanPolicy::Skip {
+                bars = bars.into_iter().map(resample::skip_nan_extremes).collect();
+            }
+            let resampled = cached_resample(path, cache_timeframe, options, || {
+                resample::resample_by_session(bars.as_slice(), &full_index.daily_index, timeframe_sec, &options.bar_timestamp, &options.bucket_edge)
+            })?;
+            tracing::info!("📈 Resampled to {} bars, anchored to each session ({} SOA segments)", resampled.len(), segments.len());
+            print_bars(&resampled, None, options, has_volume)
+        }
+        Some(timeframe) => {
+            if stored_newest_first(segments, storage_format)? {
+                bars.reverse();
+            }
+            check_nan_policy(bars.iter().copied(), options)?;
+            if options.nan_policy == cli::NanPolicy::Skip {
+                bars = bars.into_iter().map(resample::skip_nan_extremes).collect();
+            }
+            let resampled = cached_resample(path, cache_timeframe, options, || {
+                let mut resampled = resample::resample_many(bars, &[timeframe], &options.bar_timestamp, &options.bucket_edge, options.bucket_anchor)
+                    .pop()
+                    .unwrap_or_default();
+                drop_partial_bar(&mut resampled, &full_index.time_index, timeframe, options);
+                anyhow::Ok(resampled)
+            })?;
+            let unique = unique_timestamps(full_index.time_index.iter().map(|e| e.timestamp), timeframe, options);
+            tracing::info!("📈 Resampled to {} bars ({} SOA segments)", resampled.len(), segments.len());
+            print_bars(&resampled, unique.as_deref(), options, has_volume)
+        }
+        None => {
+            tracing::info!("📄 Read first 5 OHLCV entries for file {} ({} SOA segments)", path.display(), segments.len());
+            print_emitted(&bars, options, false, None, None, has_volume)
+        }
+    }
+}
+
+/// Runs `compute`, or with `--cache-resample` takes its bars from the file's resample cache
+/// while that is fresh (`resample_cache::load_or_compute`).
+///
+/// # Arguments
+/// * `path` - Path to the .bin file (or bundle) being resampled.
+/// * `timeframe` - Timeframe name the cache file is named after; `None` never caches.
+/// * `options` - Read options.
+/// * `compute` - Resamples the file.
+///
+/// # Errors
+/// * If `compute` fails, or the cache can't be checked or written.
+fn cached_resample(
+    path: &std::path::Path,
+    timeframe: Option<&str>,
+    options: &ReadOptions,
+    compute: impl FnOnce() -> anyhow::Result<Vec<resample::OHLCVBar>>,
+) -> anyhow::Result<Vec<resample::OHLCVBar>> {
+    let Some(timeframe) = timeframe.filter(|_| options.cache_resample) else {
+        return compute();
+    };
+    let (bars, cached) = resample_cache::load_or_compute(path, timeframe, options.resample_params(), compute)?;
+    if cached {
+        tracing::info!("♻️ Reused cached {} bars from {}", timeframe, resample_cache::cache_path(path, timeframe).display());
+    }
+    anyhow::Ok(bars)
+}
+
+/// Enforces `--nan-policy` on the source bars about to be resampled.
+///
+/// With `error` the first bar with a NaN high or low fails the read; with `skip` the NaN
+/// values are counted in a warning, and the caller feeds the resampler
+/// `resample::skip_nan_extremes` bars. `propagate` doesn't look at the bars.
+///
+/// # Arguments
+/// * `bars` - The source bars.
+/// * `options` - Read options, for `nan_policy`.
+///
+/// # Errors
+/// * With `--nan-policy error`, if a bar has a NaN high or low.
+fn check_nan_policy(bars: impl IntoIterator<Item = resample::OHLCVBar>, options: &ReadOptions) -> anyhow::Result<()> {
+    if options.nan_policy == cli::NanPolicy::Propagate {
+        return anyhow::Ok(());
+    }
+    let mut skipped = 0;
+    for bar in bars {
+        let nan = resample::nan_extremes(&bar);
+        if nan > 0 && options.nan_policy == cli::NanPolicy::Error {
+            return Err(anyhow::anyhow!(
+                "Bar at {} has a NaN high or low; fix the input or pass --nan-policy propagate|skip",
+                bar.timestamp
+            ));
+        }
+        skipped += nan;
+    }
+    if skipped > 0 {
+        tracing::warn!("⚠️ Skipped {} NaN high/low value(s) while resampling", skipped);
+    }
+    anyhow::Ok(())
+}
+
+/// Aggregates `bars`, in time order, into volume or count bars (`-r vol:N` / `-r count:N`,
+/// see `resample::resample_threshold_bars`), leaving out the partial last bar with
+/// `--drop-partial-bar`.
+///
+/// # Errors
+/// * For volume bars, if the file stores no volumes, so no bar would ever close.
+fn threshold_bars(
+    bars: impl IntoIterator<Item = resample::OHLCVBar>,
+    threshold: resample::BarThreshold,
+    options: &ReadOptions,
+    has_volume: bool,
+) -> anyhow::Result<Vec<resample::OHLCVBar>> {
+    if matches!(threshold, resample::BarThreshold::Volume(_)) && !has_volume {
+        return Err(anyhow::anyhow!("Volume bars (-r {}) need volumes, but the file was converted with --no-volume", threshold));
+    }
+    let mut resampled = resample::resample_threshold_bars(bars, threshold, &options.bar_timestamp);
+    if options.drop_partial_bar {
+        resample::drop_partial_threshold_bar(&mut resampled, threshold);
+    }
+    anyhow::Ok(resampled)
+}
+
+/// Drops the final bar of `resampled` with `--drop-partial-bar` if the bars of `time_index`
+/// end part-way through its bucket (`resample::drop_partial_bar`).
+fn drop_partial_bar(
+    resampled: &mut Vec<resample::OHLCVBar>,
+    time_index: &[index::TimeIndexEntry],
+    timeframe: resample::Timeframe,
+    options: &ReadOptions,
+) {
+    if options.drop_partial_bar {
+        let tail = resample::SourceTail::from_timestamps(time_index.iter().map(|e| e.timestamp));
+        resample::drop_partial_bar(resampled, &tail, timeframe, &options.bucket_edge, options.bucket_anchor);
+    }
+}
+
+/// Counts the distinct source timestamps of every `timeframe` bucket, if
+/// `options.unique_timestamps` asks for them (see `resample::unique_timestamps`).
+fn unique_timestamps(
+    timestamps: impl IntoIterator<Item = u64>,
+    timeframe: resample::Timeframe,
+    options: &ReadOptions,
+) -> Option<Vec<u64>> {
+    options
+        .unique_timestamps
+        .then(|| resample::unique_timestamps(timestamps, timeframe, &options.bucket_edge, options.bucket_anchor))
+}
+
+/// Returns the loaded index, or an error naming the missing `.idx`.
+///
+/// `process_file` only skips loading when `ReadOptions::needs_index` is false, so this
+/// fails only if the index is needed but wasn't loaded.
+fn require_index<'a>(full_index: &'a Option<index::FullIndex>, idx_path: &std::path::Path) -> anyhow::Result<&'a index::FullIndex> {
+    full_index.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("Resampling needs {}; reconvert without --no-index", idx_path.display())
+    })
+}
+
+/// Resamples every bar of `segments` to every timeframe in `timeframes` in one pass and prints
+/// each result.
+///
+/// The file is scanned once, feeding one bucket accumulator per timeframe
+/// (see `resample::resample_many`). Newest-first files are collected and reversed first,
+/// since the accumulators need bars in time order.
+///
+/// # Arguments
+/// * `segments` - The mapped .bin file, split into its segments (a single one unless
+///   written with `--soa-segment-size`).
+/// * `storage_format` - Layout of `segments`.
+/// * `timeframes` - Timeframe names as given to `--timeframes`.
+/// * `options` - Read options (bar timestamp, bucket edge and print format).
+/// * `has_volume` - Whether the file has a volume column.
+/// * `format_label` - "AOS" or "SOA", for the printed headings.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success or an error for an unknown timeframe or failed print.
+fn print_resampled_all(
+    segments: &[&[u8]],
+    storage_format: &cli::StorageFormat,
+    timeframes: &[String],
+    options: &ReadOptions,
+    has_volume: bool,
+    format_label: &str,
+) -> anyhow::Result<()> {
+    let parsed = timeframes
+        .iter()
+        .map(|tf| resample::Timeframe::parse(tf))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    check_nan_policy(iter_segments(segments, storage_format)?, options)?;
+    let skip_nan = options.nan_policy == cli::NanPolicy::Skip;
+    let bars = iter_segments(segments, storage_format)?
+        .map(|b| if skip_nan { resample::skip_nan_extremes(b) } else { b });
+    let mut tail = resample::SourceTail::default();
+    let mut source_timestamps = Vec::new();
+    let mut observe = |b: &resample::OHLCVBar| {
+        tail.observe(b.timestamp);
+        if options.unique_timestamps {
+            source_timestamps.push(b.timestamp);
+        }
+    };
+    let mut resampled = if stored_newest_first(segments, storage_format)? {
+        let mut bars: Vec<resample::OHLCVBar> = bars.collect();
+        bars.reverse();
+        resample::resample_many(bars.into_iter().inspect(&mut observe), &parsed, &options.bar_timestamp, &options.bucket_edge, options.bucket_anchor)
+    } else {
+        resample::resample_many(bars.inspect(&mut observe), &parsed, &options.bar_timestamp, &options.bucket_edge, options.bucket_anchor)
+    };
+    if options.drop_partial_bar {
+        for (&timeframe, bars) in parsed.iter().zip(resampled.iter_mut()) {
+            resample::drop_partial_bar(bars, &tail, timeframe, &options.bucket_edge, options.bucket_anchor);
+        }
+    }
+    for ((tf, &timeframe), bars) in timeframes.iter().zip(parsed.iter()).zip(resampled.iter()) {
+        let unique = unique_timestamps(source_timestamps.iter().copied(), timeframe, options);
+        tracing::info!("📈 Resampled to {} timeframe, {} bars ({})", tf, bars.len(), format_label);
+        print_bars(bars, unique.as_deref(), options, has_volume)?;
+    }
+    anyhow::Ok(())
+}
+
+/// Prints the first 5 bars of `bars` in the format selected by `options`.
+///
+/// Resampled output is checked to be strictly increasing in timestamp: an error with
+/// `options.strict_resample`, otherwise a debug-build assertion.
+/// The volume column is hidden when the file has no volume (`has_volume` is false).
+fn print_bars(bars: &[resample::OHLCVBar], unique_timestamps: Option<&[u64]>, options: &ReadOptions, has_volume: bool) -> anyhow::Result<()> {
+    let restored;
+    let bars = if options.nan_policy == cli::NanPolicy::Skip {
+        let mut copy = bars.to_vec();
+        resample::restore_skipped_nan(&mut copy);
+        restored = copy;
+        &restored[..]
+    } else {
+        bars
+    };
+    if options.strict_resample {
+        resample::check_strictly_increasing(bars)?;
+    } else {
+        debug_assert!(
+            resample::check_strictly_increasing(bars).is_ok(),
+            "resampled bars are not strictly increasing; is the .idx malformed?"
+        );
+    }
+    let true_ranges = options.with_true_range.then(|| resample::true_ranges(bars));
+    print_emitted(bars, options, options.show_count, true_ranges.as_deref(), unique_timestamps, has_volume)
+}
+
+/// Prints the first 5 materialized bars as a table or labelled lines.
+///
+/// This is the output boundary: prices are rounded here with `options.round_decimals` and
+/// timestamps shifted by `options.timestamp_offset`, after any aggregation (and true range
+/// computation) has happened.
+fn print_emitted(
+    bars: &[resample::OHLCVBar],
+    options: &ReadOptions,
+    show_count: bool,
+    true_ranges: Option<&[f64]>,
+    unique_timestamps: Option<&[u64]>,
+    has_volume: bool,
+) -> anyhow::Result<()> {
+    let volume_format = has_volume.then_some(&options.volume_format);
+    let bars: Vec<resample::OHLCVBar> = bars
+        .iter()
+        .take(5)
+        .map(|bar| match options.round_decimals {
+            Some(decimals) => resample::round_bar(bar, decimals),
+            None => *bar,
+        })
+        .map(|bar| resample::OHLCVBar {
+            timestamp: bar.timestamp.saturating_add_signed(options.timestamp_offset),
+            ..bar
+        })
+        .collect();
+    if options.table {
+        utils::print_bars_table(&bars, 5, show_count, volume_format, true_ranges, unique_timestamps)?;
+    } else {
+        utils::print_bars_resampled(&bars, 5, show_count, volume_format, true_ranges, unique_timestamps)?;
+    }
+    if options.sparkline {
+        println!(" {}", utils::sparkline(&bars));
+    }
+    anyhow::Ok(())
+}
+
+/// Prints the first 5 raw bars of `source` in the format selected by `options`.
+fn print_items<B: BarSource>(source: &B, options: &ReadOptions, has_volume: bool) -> anyhow::Result<()> {
+    if options.prints_raw_directly() {
+        return utils::print_bars_source(source, 5, has_volume.then_some(&options.volume_format));
+    }
+    let bars: Vec<resample::OHLCVBar> = (0..std::cmp::min(5, source.len())).map(|i| source.bar(i)).collect();
+    print_emitted(&bars, options, false, None, None, has_volume)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_processor;
+
+    fn buffer(storage_format: cli::StorageFormat) -> Vec<u8> {
+        let records: Vec<csv_processor::ProcessedRecord> = (0..4u64)
+            .map(|i| {
+                let open = 100.0 + i as f64;
+                csv_processor::ProcessedRecord::from(&resample::OHLCVBar {
+                    timestamp: 1705312800 + i * 60,
+                    open,
+                    high: open + 2.0,
+                    low: open - 1.0,
+                    close: open + 1.0,
+                    volume: i + 1,
+                    count: 1,
+                })
+            })
+            .collect();
+        csv_processor::build_flatbuffer(&records, storage_format, utils::SCHEMA_VERSION, true)
+    }
+
+    #[test]
+    fn get_bar_returns_first_and_last_and_none_past_the_end() {
+        for storage_format in [cli::StorageFormat::Aos, cli::StorageFormat::Soa] {
+            let buf = buffer(storage_format.clone());
+
+            let first = get_bar(&buf, &storage_format, 0).unwrap().unwrap();
+            assert_eq!((first.timestamp, first.open, first.volume), (1705312800, 100.0, 1));
+            let last = get_bar(&buf, &storage_format, 3).unwrap().unwrap();
+            assert_eq!((last.timestamp, last.close, last.volume), (1705312980, 104.0, 4));
+            assert!(get_bar(&buf, &storage_format, 4).unwrap().is_none());
+            assert!(get_bar(&buf, &storage_format, usize::MAX).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn head_reads_verify_only_the_bars_they_print() {
+        let mut buf = buffer(cli::StorageFormat::Aos);
+        // Point the last bar's vtable offset outside the buffer.
+        let last = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap().get(3)._tab.loc();
+        buf[last..last + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(ohlcv_generated::root_as_ohlcvlist(&buf).is_err());
+
+        let head = unsafe { root_as_ohlcvlist_head(&buf, 2) }.unwrap();
+        let items = head.items().unwrap();
+        assert_eq!((items.len(), head.has_volume()), (4, true));
+        assert_eq!(resample::OHLCVBar::from(items.get(1)).timestamp, 1705312860);
+        assert!(unsafe { root_as_ohlcvlist_head(&buf, 4) }.is_err());
+        assert!(unsafe { root_as_ohlcvlist_head(b"not a flatbuffer", 5) }.is_err());
+    }
+
+    #[test]
+    fn get_bar_verifies_only_the_bar_it_reads() {
+        let mut buf = buffer(cli::StorageFormat::Aos);
+        // Point the second bar's vtable offset outside the buffer.
+        let corrupt = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap().get(1)._tab.loc();
+        buf[corrupt..corrupt + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(iter_bars(&buf, &cli::StorageFormat::Aos).is_err());
+
+        assert_eq!(get_bar(&buf, &cli::StorageFormat::Aos, 0).unwrap().unwrap().timestamp, 1705312800);
+        assert_eq!(get_bar(&buf, &cli::StorageFormat::Aos, 3).unwrap().unwrap().timestamp, 1705312980);
+        assert!(get_bar(&buf, &cli::StorageFormat::Aos, 4).unwrap().is_none());
+        assert!(get_bar(&buf, &cli::StorageFormat::Aos, 1).is_err());
+        assert!(!stored_newest_first(&[&buf], &cli::StorageFormat::Aos).unwrap());
+    }
+
+    #[test]
+    fn storage_format_follows_the_output_suffix_or_the_contents() {
+        assert!(matches!(determine_storage_format_from_path("data.aos.fb", "fb"), Some(cli::StorageFormat::Aos)));
+        assert!(matches!(determine_storage_format_from_path("data.soa.fb", "fb"), Some(cli::StorageFormat::Soa)));
+        assert!(determine_storage_format_from_path("data.soa.bin", "fb").is_none());
+        assert!(determine_storage_format_from_path("data.fb", "fb").is_none());
+
+        assert!(matches!(detect_storage_format(&buffer(cli::StorageFormat::Aos)), Some(cli::StorageFormat::Aos)));
+        assert!(matches!(detect_storage_format(&buffer(cli::StorageFormat::Soa)), Some(cli::StorageFormat::Soa)));
+        assert!(detect_storage_format(b"not a flatbuffer").is_none());
+    }
+
+    #[test]
+    fn query_time_of_day_keeps_bars_in_the_local_window() {
+        // 10:00 .. 10:03 UTC is 05:00 .. 05:03 in New York (EST).
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let timestamps = |buf: &[u8], storage_format: &cli::StorageFormat, start, end| {
+            query_time_of_day(buf, storage_format, start, end, chrono_tz::America::New_York)
+                .unwrap()
+                .map(|b| b.timestamp)
+                .collect::<Vec<_>>()
+        };
+        for storage_format in [cli::StorageFormat::Aos, cli::StorageFormat::Soa] {
+            let buf = buffer(storage_format.clone());
+
+            // Start inclusive, end exclusive.
+            assert_eq!(timestamps(&buf, &storage_format, time(5, 1), time(5, 3)), vec![1705312860, 1705312920]);
+            // Wraps midnight.
+            assert_eq!(timestamps(&buf, &storage_format, time(23, 0), time(5, 1)), vec![1705312800]);
+            assert!(timestamps(&buf, &storage_format, time(10, 0), time(11, 0)).is_empty());
+            assert!(query_time_of_day(&buf, &storage_format, time(5, 0), time(5, 0), chrono_tz::UTC).is_err());
+        }
+    }
+
+    #[test]
+    fn skipped_summary_names_files_up_to_the_limit() {
+        let skipped: Vec<std::path::PathBuf> = ["out/a.bin", "out/b.bin", "out/c.bin"].iter().map(std::path::PathBuf::from).collect();
+        assert_eq!(describe_skipped(&skipped, 20), ": a.bin, b.bin, c.bin");
+        assert_eq!(describe_skipped(&skipped, 2), ": a.bin, b.bin and 1 more");
+        assert_eq!(describe_skipped(&skipped, 0), "");
+    }
+
+    fn read_options(resample: Option<&str>) -> ReadOptions {
+        ReadOptions {
+            resample: resample.map(str::to_string),
+            bar_timestamp: cli::BarTimestamp::BucketStart,
+            show_count: false,
+            target_bars: None,
+            table: false,
+            sparkline: false,
+            io_retries: 0,
+            strict_resample: false,
+            volume_format: cli::VolumeFormat::Plain,
+            round_decimals: None,
+            with_true_range: false,
+            timestamp_offset: 0,
+            bucket_edge: cli::BucketEdge::Left,
+            bucket_anchor: 0,
+            resample_all: None,
+            output_suffix: "bin".to_string(),
+            daily_report: false,
+            drop_partial_bar: false,
+            nan_policy: cli::NanPolicy::Propagate,
+            unique_timestamps: false,
+            list_skipped: 20,
+            anchor_to_session: false,
+            grid_mark_missing: false,
+            cache_resample: false,
+        }
+    }
+
+    #[test]
+    fn soa_file_without_data_reads_as_zero_bars() {
+        let mut builder = flatbuffers::FlatBufferBuilder::new();
+        let list = ohlcv_soa_generated::OHLCVListSOA::create(
+            &mut builder,
+            &ohlcv_soa_generated::OHLCVListSOAArgs { data: None, version: utils::SCHEMA_VERSION, has_volume: true },
+        );
+        builder.finish(list, None);
+        let buf = builder.finished_data();
+        assert_eq!(iter_bars(buf, &cli::StorageFormat::Soa).unwrap().len(), 0);
+
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_empty_soa_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("empty.soa.bin");
+        std::fs::write(&path, buf).unwrap();
+        std::fs::write(dir.join("full.soa.bin"), buffer(cli::StorageFormat::Soa)).unwrap();
+
+        assert!(process_file(&path, &read_options(None), cli::StorageFormat::Soa).is_ok());
+        assert!(process_file(&path, &read_options(Some("1min")), cli::StorageFormat::Soa).is_ok());
+        // The empty file doesn't stop the rest of the directory from being read.
+        assert!(read_flatbuffers(&dir, &read_options(None)).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn iter_bars_rejects_soa_columns_of_different_lengths() {
+        let mut builder = flatbuffers::FlatBufferBuilder::new();
+        let timestamps = builder.create_vector(&[1705312800u64, 1705312860, 1705312920]);
+        let prices = builder.create_vector(&[1.0f64, 2.0, 3.0]);
+        let closes = builder.create_vector(&[1.0f64, 2.0]);
+        let data = ohlcv_soa_generated::OHLCVSOA::create(
+            &mut builder,
+            &ohlcv_soa_generated::OHLCVSOAArgs {
+                timestamps: Some(timestamps),
+                opens: Some(prices),
+                highs: Some(prices),
+                lows: Some(prices),
+                closes: Some(closes),
+                volumes: None,
+                adj_closes: None,
+            },
+        );
+        let list = ohlcv_soa_generated::OHLCVListSOA::create(
+            &mut builder,
+            &ohlcv_soa_generated::OHLCVListSOAArgs { data: Some(data), version: utils::SCHEMA_VERSION, has_volume: false },
+        );
+        builder.finish(list, None);
+
+        let err = iter_bars(builder.finished_data(), &cli::StorageFormat::Soa).err().unwrap().to_string();
+        assert!(err.contains("timestamps 3") && err.contains("closes 2"), "{}", err);
+    }
+}
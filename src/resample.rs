@@ -1,4 +1,5 @@
-use crate::utils;
+use crate::bar_source::{BarSource, SoaColumns};
+use crate::cli;
 use crate::index;
 use crate::ohlcv_generated;
 use crate::ohlcv_soa_generated;
@@ -9,7 +10,8 @@ use crate::ohlcv_soa_generated;
 /// - `timestamp`: The start time of the bar (Unix timestamp).
 /// - `open`, `high`, `low`, `close`: Price values.
 /// - `volume`: Trading volume during the bar period.
-#[derive(Debug, Clone, Copy)]
+/// - `count`: Number of source bars aggregated into this bar (1 for non-resampled reads).
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct OHLCVBar {
     pub timestamp: u64,
     pub open: f64,
@@ -17,308 +19,2258 @@ pub struct OHLCVBar {
     pub low: f64,
     pub close: f64,
     pub volume: u64,
+    pub count: u64,
 }
 
-// --- AOS Resampling Functions ---
+impl From<ohlcv_generated::OHLCV<'_>> for OHLCVBar {
+    fn from(item: ohlcv_generated::OHLCV<'_>) -> Self {
+        OHLCVBar {
+            timestamp: item.timestamp(),
+            open: item.open(),
+            high: item.high(),
+            low: item.low(),
+            close: item.close(),
+            volume: item.volume(),
+            count: 1,
+        }
+    }
+}
 
-/// Resamples a vector of OHLCV records (AOS format) into daily OHLCV bars using a daily index.
+/// `f64::max`, except that a NaN operand yields NaN.
 ///
-/// This function groups OHLCV records by day using the provided `daily_index`.
-/// Each group is aggregated into a single daily bar with:
-/// - Open: First bar's open
-/// - High: Max high across all bars in the day
-/// - Low: Min low across all bars in the day
-/// - Close: Last bar's close
-/// - Volume: Sum of volumes
+/// `f64::max` returns the other operand, which would silently hide a NaN high. Aggregation
+/// propagates it instead; `--nan-policy skip` gets the `f64::max` behavior by feeding
+/// `skip_nan_extremes` bars.
+fn max_propagating(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() { f64::NAN } else { a.max(b) }
+}
+
+/// `f64::min`, except that a NaN operand yields NaN (see `max_propagating`).
+fn min_propagating(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() { f64::NAN } else { a.min(b) }
+}
+
+/// Number of NaN values among the high and low of `bar` (0, 1 or 2).
+pub fn nan_extremes(bar: &OHLCVBar) -> usize {
+    usize::from(bar.high.is_nan()) + usize::from(bar.low.is_nan())
+}
+
+/// Prepares a source bar for `--nan-policy skip`: a NaN high becomes -inf and a NaN low
+/// +inf, which leave the bucket's max and min to the other bars.
+///
+/// A bucket whose every high (low) was NaN ends up infinite; `restore_skipped_nan` turns
+/// it back into NaN after resampling.
+pub fn skip_nan_extremes(bar: OHLCVBar) -> OHLCVBar {
+    OHLCVBar {
+        high: if bar.high.is_nan() { f64::NEG_INFINITY } else { bar.high },
+        low: if bar.low.is_nan() { f64::INFINITY } else { bar.low },
+        ..bar
+    }
+}
+
+/// Turns the infinite high / low left by `skip_nan_extremes` in buckets that had no valid
+/// value back into NaN.
+pub fn restore_skipped_nan(bars: &mut [OHLCVBar]) {
+    for bar in bars {
+        if bar.high == f64::NEG_INFINITY {
+            bar.high = f64::NAN;
+        }
+        if bar.low == f64::INFINITY {
+            bar.low = f64::NAN;
+        }
+    }
+}
+
+/// Rounds the prices of an emitted bar to `decimals` decimal places.
+///
+/// Meant for the output boundary only (printing, exporting): rounding inside the
+/// aggregation loop would compound. Timestamp, volume and count are left untouched.
 ///
 /// # Arguments
 ///
-/// * `items` - A FlatBuffers vector of OHLCV objects (Array of Structures format).
-/// * `daily_index` - A slice of `DailyIndexEntry` indicating the start and end indices for each day.
+/// * `bar` - The bar to round.
+/// * `decimals` - Number of decimal places to keep.
 ///
 /// # Returns
 ///
-/// * `anyhow::Result<Vec<OHLCVBar>>` - A vector of daily OHLCV bars or an error.
-pub fn resample_daily_aos(
-    items: &flatbuffers::Vector<flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV>>,
-    daily_index: &[index::DailyIndexEntry],
-) -> anyhow::Result<Vec<OHLCVBar>> {
-    let mut resampled = Vec::new();
+/// * `OHLCVBar` - A copy of `bar` with rounded open, high, low and close.
+pub fn round_bar(bar: &OHLCVBar, decimals: u32) -> OHLCVBar {
+    let factor = 10f64.powi(decimals as i32);
+    let round = |price: f64| (price * factor).round() / factor;
+    OHLCVBar {
+        open: round(bar.open),
+        high: round(bar.high),
+        low: round(bar.low),
+        close: round(bar.close),
+        ..*bar
+    }
+}
 
-    for entry in daily_index {
-        let start = entry.start_index as usize;
-        let end = entry.end_index as usize;
+/// Computes the true range of ordered bars:
+/// `max(high - low, |high - previous close|, |low - previous close|)`.
+///
+/// Each bar's true range uses the previous bar's close; the first bar has no previous
+/// close and falls back to `high - low`.
+///
+/// # Arguments
+///
+/// * `bars` - Bars in time order (as returned by the resamplers).
+///
+/// # Returns
+///
+/// * `Vec<f64>` - One true range per input bar, in the same order.
+pub fn true_ranges(bars: &[OHLCVBar]) -> Vec<f64> {
+    let mut previous_close: Option<f64> = None;
+    bars.iter()
+        .map(|bar| {
+            let range = bar.high - bar.low;
+            let true_range = match previous_close {
+                Some(close) => range.max((bar.high - close).abs()).max((bar.low - close).abs()),
+                None => range,
+            };
+            previous_close = Some(bar.close);
+            true_range
+        })
+        .collect()
+}
 
-        if start >= items.len() || end >= items.len() || start > end {
-            continue;
-        }
-        let first = items.get(start);
-        let mut bar = OHLCVBar {
-            timestamp: utils::parse_date_to_timestamp(&entry.date)?,
-            open: first.open(),
-            high: first.high(),
-            low: first.low(),
-            close: first.close(),
-            volume: first.volume(),
-        };
-        for i in start + 1..= end {
-            let item = items.get(i);
-            bar.high = bar.high.max(item.high());
-            bar.low = bar.low.min(item.low());
-            bar.close = item.close();
-            bar.volume += item.volume();
+/// Tracks the start of the current trading session while iterating bars in time order.
+///
+/// A session is a UTC calendar day (the same grouping as `DailyIndexEntry`, which
+/// `resample_by_session` walks), and its start is the timestamp of the day's first bar. A
+/// trading session crossing UTC midnight is therefore split in two; `--resample` and the
+/// README say so.
+#[derive(Debug, Clone, Default)]
+struct SessionTracker {
+    day: Option<u64>,
+    start: u64,
+}
+
+impl SessionTracker {
+    /// Returns the start of the session `ts` belongs to, opening a new one on a new day.
+    fn session_start(&mut self, ts: u64) -> u64 {
+        let day = ts / 86400;
+        if self.day != Some(day) {
+            self.day = Some(day);
+            self.start = ts;
         }
-        resampled.push(bar);
+        self.start
     }
-    Ok(resampled)
-} 
+}
 
-/// Resamples a vector of OHLCV records (AOS format) into bars of a specified timeframe.
+/// Whether buckets of `timeframe_sec` keep the wall-clock alignment of plain modulo
+/// bucketing (see `bucket_start`): timeframes dividing an hour (1min, ..., 60min) and whole
+/// hours dividing a day (2h, 4h, ..., 1d). 90min divides a day too, but not into whole
+/// hours, so it stays anchored to the session start.
+fn is_clock_aligned(timeframe_sec: u64) -> bool {
+    timeframe_sec > 0
+        && (3600u64.is_multiple_of(timeframe_sec)
+            || (timeframe_sec.is_multiple_of(3600) && 86400u64.is_multiple_of(timeframe_sec)))
+}
+
+/// Returns the start of the bucket containing `ts`.
 ///
-/// This function groups OHLCV records into bars of `timeframe_sec` duration.
-/// It aggregates each group into a single bar with:
-/// - Open: First bar's open
-/// - High: Max high across all bars in the timeframe
-/// - Low: Min low across all bars in the timeframe
-/// - Close: Last bar's close
-/// - Volume: Sum of volumes
+/// Clock-aligned timeframes (`is_clock_aligned`: 1min, ..., 60min, 2h, 4h, 1d) keep the
+/// wall-clock alignment of plain modulo bucketing (`ts - ts % tf`), shifted by
+/// `bucket_anchor`: boundaries fall on `((ts - anchor) / tf) * tf + anchor`, so 5min with an
+/// anchor of 120 starts buckets at :02, :07, :12, ... Any other timeframe (e.g. 90min) is
+/// anchored to `session_start` instead: buckets restart at every session's (UTC day's) first
+/// bar, so a bucket never spans the overnight gap and the last bucket of a session may be
+/// shorter.
 ///
-/// # Arguments
+/// With `BucketEdge::Right` buckets are right-closed: a `ts` exactly on a boundary belongs
+/// to the bucket ending there, so 09:05:00 in 5min falls into the bucket starting at 09:00.
+/// Buckets are still keyed by their start. For session-anchored timeframes a session's
+/// first bar opens the session's first bucket rather than closing one of its own.
 ///
-/// * `items` - A FlatBuffers vector of OHLCV objects (Array of Structures format).
-/// * `time_index` - A slice of `TimeIndexEntry` linking timestamps to indices in the AOS vector.
-/// * `timeframe_sec` - The desired timeframe in seconds (e.g., 180 for 3 minutes).
+/// Session-anchored timeframes ignore `bucket_anchor`, since the session start already
+/// anchors them.
 ///
-/// # Returns
+/// `timeframe_sec` must be non-zero: `resample_ohlcv` rejects 0, and the other callers take
+/// it from `Timeframe::parse`, `STANDARD_TIMEFRAMES` or `timeframe_for_count`.
+fn bucket_start(ts: u64, session_start: u64, timeframe_sec: u64, bucket_edge: &cli::BucketEdge, bucket_anchor: u64) -> u64 {
+    if !is_clock_aligned(timeframe_sec) {
+        return session_bucket_start(ts, session_start, timeframe_sec, bucket_edge);
+    }
+    // Reduced modulo tf so `ts < anchor` can't underflow; equivalent boundaries.
+    let anchor = bucket_anchor % timeframe_sec;
+    on_bucket_edge(ts - (ts + timeframe_sec - anchor) % timeframe_sec, ts, timeframe_sec, bucket_edge)
+}
+
+/// Returns the start of the bucket containing `ts` when buckets restart at every session's
+/// first bar, whatever the timeframe (`--resample-anchor-to-session`).
+fn session_bucket_start(ts: u64, session_start: u64, timeframe_sec: u64, bucket_edge: &cli::BucketEdge) -> u64 {
+    if ts == session_start {
+        return session_start;
+    }
+    on_bucket_edge(session_start + (ts - session_start) / timeframe_sec * timeframe_sec, ts, timeframe_sec, bucket_edge)
+}
+
+/// Moves a `ts` exactly on the boundary `start` into the bucket ending there for
+/// right-closed buckets.
+fn on_bucket_edge(start: u64, ts: u64, timeframe_sec: u64, bucket_edge: &cli::BucketEdge) -> u64 {
+    match bucket_edge {
+        cli::BucketEdge::Right if start == ts => start.saturating_sub(timeframe_sec),
+        _ => start,
+    }
+}
+
+/// Returns the storage positions of a day's bars in time order.
 ///
-/// * `anyhow::Result<Vec<OHLCVBar>>` - A vector of resampled OHLCV bars or an error.
-pub fn resample_ohlcv_aos(
-    items: &flatbuffers::Vector<flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV>>,
-    time_index: &[index::TimeIndexEntry],
-    timeframe_sec: u64,
-) -> anyhow::Result<Vec<OHLCVBar>> {
-    let mut resampled = Vec::new();
-    let mut current_bar: Option<OHLCVBar> = None;
+/// `start_index` / `end_index` are the positions of the day's first and last bar in time.
+/// In files written newest-first (`--order desc`) the first bar is stored after the last
+/// one, so the range is walked backwards. `None` if either end is past `len`.
+fn day_positions(entry: &index::DailyIndexEntry, len: usize) -> Option<impl Iterator<Item = usize>> {
+    let (start, end) = (entry.start_index as usize, entry.end_index as usize);
+    if start >= len || end >= len {
+        return None;
+    }
+    let (low, high) = (start.min(end), start.max(end));
+    Some((low..=high).map(move |i| if start <= end { i } else { low + high - i }))
+}
 
-    for entry in time_index {
-        let i = entry.index as usize;
-        if i >= items.len() {
-            continue;
+/// Sets the emitted timestamp of an aggregated bar according to `bar_timestamp`.
+///
+/// During aggregation `bar.timestamp` holds the bucket start, which is also the bucket key.
+/// For daily bars the bucket is the trading session, so its start is the day's first bar.
+/// Only once a bucket is complete is the timestamp replaced, so aggregation is unaffected.
+///
+/// # Arguments
+///
+/// * `bar` - The aggregated bar carrying its bucket start timestamp.
+/// * `trade_span` - Timestamps of the first and last source bars in the bucket.
+/// * `bar_timestamp` - Which timestamp the emitted bar should carry.
+fn stamp_bar(mut bar: OHLCVBar, trade_span: (u64, u64), bar_timestamp: &cli::BarTimestamp) -> OHLCVBar {
+    bar.timestamp = match bar_timestamp {
+        cli::BarTimestamp::BucketStart => bar.timestamp,
+        cli::BarTimestamp::FirstTrade => trade_span.0,
+        cli::BarTimestamp::LastTrade => trade_span.1,
+    };
+    bar
+}
+
+/// Bucket size fed to a `BucketAccumulator`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeframe {
+    /// Fixed-length buckets of this many seconds, placed by `bucket_start`.
+    Intraday(u64),
+    /// One bucket per trading session, the grouping of `resample_daily_aos` / `resample_daily_soa`.
+    Daily,
+}
+
+impl Timeframe {
+    /// Parses a timeframe as accepted by `--resample` / `--timeframes`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tf` - One of "1min", "2min", "3min", "4min", "5min", "90min", "1d".
+    ///
+    /// # Returns
+    ///
+    /// * `anyhow::Result<Timeframe>` - The parsed timeframe or an error for any other value.
+    pub fn parse(tf: &str) -> anyhow::Result<Timeframe> {
+        match tf {
+            "1min" => anyhow::Ok(Timeframe::Intraday(60)),
+            "2min" => anyhow::Ok(Timeframe::Intraday(120)),
+            "3min" => anyhow::Ok(Timeframe::Intraday(180)),
+            "4min" => anyhow::Ok(Timeframe::Intraday(240)),
+            "5min" => anyhow::Ok(Timeframe::Intraday(300)),
+            "90min" => anyhow::Ok(Timeframe::Intraday(5400)),
+            "1d" => anyhow::Ok(Timeframe::Daily),
+            other => Err(anyhow::anyhow!("Unsupported timeframe: {}", other)),
         }
+    }
+}
 
-        let item = items.get(i);
-        let bar_start = item.timestamp() - (item.timestamp() % timeframe_sec);
+/// Assigns bars, fed in time order, to the buckets of one timeframe.
+#[derive(Debug)]
+struct Bucketing<'a> {
+    timeframe: Timeframe,
+    bucket_edge: &'a cli::BucketEdge,
+    bucket_anchor: u64,
+    /// Anchor every intraday timeframe to the session start (`session_bucket_start`).
+    anchor_to_session: bool,
+    session: SessionTracker,
+}
+
+impl<'a> Bucketing<'a> {
+    fn new(timeframe: Timeframe, bucket_edge: &'a cli::BucketEdge, bucket_anchor: u64) -> Self {
+        Bucketing { timeframe, bucket_edge, bucket_anchor, anchor_to_session: false, session: SessionTracker::default() }
+    }
 
-        match current_bar {
-            Some(ref mut bar) if bar.timestamp == bar_start => {
-                bar.high = bar.high.max(item.high());
-                bar.low = bar.low.min(item.low());
-                bar.close = item.close();
-                bar.volume += item.volume();
+    /// Returns the start of the bucket `ts` belongs to, which is also its key.
+    fn bucket_of(&mut self, ts: u64) -> u64 {
+        let session_start = self.session.session_start(ts);
+        match self.timeframe {
+            Timeframe::Intraday(timeframe_sec) if self.anchor_to_session => {
+                session_bucket_start(ts, session_start, timeframe_sec, self.bucket_edge)
             }
-            Some(bar) => {
-                resampled.push(bar);
-                current_bar = Some(OHLCVBar {
-                    timestamp: bar_start,
-                    open: item.open(),
-                    high: item.high(),
-                    low: item.low(),
-                    close: item.close(),
-                    volume: item.volume(),
-                });
+            Timeframe::Intraday(timeframe_sec) => bucket_start(ts, session_start, timeframe_sec, self.bucket_edge, self.bucket_anchor),
+            Timeframe::Daily => session_start,
+        }
+    }
+}
+
+/// The built-in per-bucket aggregation: first open, highest high, lowest low, last close,
+/// summed volume and count.
+///
+/// This is what every `resample_*` function uses; `resample_with` accepts any other.
+#[derive(Debug, Clone, Default)]
+pub struct OhlcvAggregate {
+    bar: Option<OHLCVBar>,
+    trade_span: (u64, u64),
+}
+
+impl OhlcvAggregate {
+    /// Adds `source`, the next bar of the bucket in time order.
+    pub fn add(&mut self, source: &OHLCVBar) {
+        match self.bar {
+            Some(ref mut bar) => {
+                bar.high = max_propagating(bar.high, source.high);
+                bar.low = min_propagating(bar.low, source.low);
+                bar.close = source.close;
+                bar.volume += source.volume;
+                bar.count += source.count;
+                self.trade_span.1 = source.timestamp;
             }
             None => {
-                current_bar = Some(OHLCVBar {
-                    timestamp: bar_start,
-                    open: item.open(),
-                    high: item.high(),
-                    low: item.low(),
-                    close: item.close(),
-                    volume: item.volume(),
-                });
+                self.bar = Some(*source);
+                self.trade_span = (source.timestamp, source.timestamp);
+            }
+        }
+    }
+
+    /// Returns the aggregated bar, stamped according to `bar_timestamp`, or `None` if no
+    /// bar was added.
+    ///
+    /// # Arguments
+    ///
+    /// * `bucket_start` - Start of the bucket, carried with `BarTimestamp::BucketStart`.
+    /// * `bar_timestamp` - Which timestamp the bar carries.
+    pub fn finish(self, bucket_start: u64, bar_timestamp: &cli::BarTimestamp) -> Option<OHLCVBar> {
+        self.bar
+            .map(|bar| stamp_bar(OHLCVBar { timestamp: bucket_start, ..bar }, self.trade_span, bar_timestamp))
+    }
+}
+
+/// Aggregates source bars, fed one at a time in time order, into bars of one timeframe.
+///
+/// Holds only the bucket being built, so any number of accumulators can be fed from the
+/// same scan (see `resample_many`). Completed bars are pushed to the `out` vector passed
+/// to `push`, already stamped according to `bar_timestamp`.
+#[derive(Debug)]
+pub struct BucketAccumulator<'a> {
+    bucketing: Bucketing<'a>,
+    bar_timestamp: &'a cli::BarTimestamp,
+    current: Option<(u64, OhlcvAggregate)>,
+}
+
+impl<'a> BucketAccumulator<'a> {
+    /// Creates an accumulator with no open bucket.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeframe` - Bucket size of the produced bars.
+    /// * `bar_timestamp` - Which timestamp each bar carries (bucket start, first or last source bar).
+    /// * `bucket_edge` - Whether a bar exactly on a bucket boundary starts a bucket (left) or ends one (right).
+    ///   Ignored for `Timeframe::Daily`.
+    /// * `bucket_anchor` - Offset in seconds of intraday bucket boundaries (0 aligns them to the epoch).
+    pub fn new(timeframe: Timeframe, bar_timestamp: &'a cli::BarTimestamp, bucket_edge: &'a cli::BucketEdge, bucket_anchor: u64) -> Self {
+        BucketAccumulator {
+            bucketing: Bucketing::new(timeframe, bucket_edge, bucket_anchor),
+            bar_timestamp,
+            current: None,
+        }
+    }
+
+    /// Creates an accumulator whose buckets restart at every session's first bar, even for
+    /// timeframes that divide an hour (see `session_bucket_start`). `bucket_anchor` doesn't
+    /// apply, since the session start anchors the buckets.
+    pub fn anchored_to_session(timeframe: Timeframe, bar_timestamp: &'a cli::BarTimestamp, bucket_edge: &'a cli::BucketEdge) -> Self {
+        let mut accumulator = BucketAccumulator::new(timeframe, bar_timestamp, bucket_edge, 0);
+        accumulator.bucketing.anchor_to_session = true;
+        accumulator
+    }
+
+    /// Adds `source` to the open bucket, first pushing the open bucket to `out` if
+    /// `source` belongs to a new one.
+    pub fn push(&mut self, source: &OHLCVBar, out: &mut Vec<OHLCVBar>) {
+        let bar_start = self.bucketing.bucket_of(source.timestamp);
+        match self.current {
+            Some((start, ref mut aggregate)) if start == bar_start => aggregate.add(source),
+            _ => {
+                self.flush(out);
+                let mut aggregate = OhlcvAggregate::default();
+                aggregate.add(source);
+                self.current = Some((bar_start, aggregate));
+            }
+        }
+    }
+
+    /// Pushes the open bucket, if any, to `out`. The accumulator can be reused afterwards.
+    pub fn finish(&mut self, out: &mut Vec<OHLCVBar>) {
+        self.flush(out);
+        self.bucketing.session = SessionTracker::default();
+    }
+
+    fn flush(&mut self, out: &mut Vec<OHLCVBar>) {
+        if let Some((start, aggregate)) = self.current.take() {
+            out.extend(aggregate.finish(start, self.bar_timestamp));
+        }
+    }
+}
+
+/// Resamples bars with a caller-supplied aggregation.
+///
+/// Bars are bucketed exactly like the built-in resamplers (see `bucket_start`). Every
+/// bucket starts from `S::default()`, `f` is called with each of its bars in time order,
+/// and `finish` turns the state into one output item once the bucket is complete.
+/// Aggregating with `OhlcvAggregate::add` and `OhlcvAggregate::finish` reproduces
+/// `resample_many`; see `examples/median_resample.rs` for a median close.
+///
+/// # Arguments
+///
+/// * `source` - Source bars in time order (e.g. `read_flatbuffers::iter_bars`).
+/// * `timeframe` - Bucket size.
+/// * `bucket_edge` - Whether a bar exactly on a bucket boundary starts a bucket (left) or ends one (right).
+/// * `bucket_anchor` - Offset in seconds of intraday bucket boundaries (0 aligns them to the epoch).
+/// * `f` - Adds a bar to a bucket's state.
+/// * `finish` - Produces the output of a complete bucket from its start and state.
+///
+/// # Returns
+///
+/// * `Vec<T>` - One item per non-empty bucket, in time order.
+pub fn resample_with<S, T, F, G>(
+    source: impl IntoIterator<Item = OHLCVBar>,
+    timeframe: Timeframe,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+    mut f: F,
+    mut finish: G,
+) -> Vec<T>
+where
+    S: Default,
+    F: FnMut(&mut S, &OHLCVBar),
+    G: FnMut(u64, S) -> T,
+{
+    let mut bucketing = Bucketing::new(timeframe, bucket_edge, bucket_anchor);
+    let mut current: Option<(u64, S)> = None;
+    let mut out = Vec::new();
+
+    for bar in source {
+        let bar_start = bucketing.bucket_of(bar.timestamp);
+        match current {
+            Some((start, ref mut state)) if start == bar_start => f(state, &bar),
+            _ => {
+                if let Some((start, state)) = current.take() {
+                    out.push(finish(start, state));
+                }
+                let mut state = S::default();
+                f(&mut state, &bar);
+                current = Some((bar_start, state));
+            }
+        }
+    }
+    if let Some((start, state)) = current {
+        out.push(finish(start, state));
+    }
+    out
+}
+
+/// Resamples one stream of bars into several timeframes in a single pass.
+///
+/// Every bar is read once and handed to one `BucketAccumulator` per timeframe, so the
+/// source is scanned once no matter how many timeframes are requested. `bars` must be in
+/// time order; bars are taken in the order given rather than through a `time_index`.
+///
+/// # Arguments
+///
+/// * `bars` - Source bars in time order (e.g. `read_flatbuffers::iter_bars`).
+/// * `timeframes` - Timeframes to produce.
+/// * `bar_timestamp` - Which timestamp each bar carries (bucket start, first or last source bar).
+/// * `bucket_edge` - Whether a bar exactly on a bucket boundary starts a bucket (left) or ends one (right).
+/// * `bucket_anchor` - Offset in seconds of intraday bucket boundaries (0 aligns them to the epoch).
+///
+/// # Returns
+///
+/// * `Vec<Vec<OHLCVBar>>` - The resampled bars of each timeframe, in the order of `timeframes`.
+pub fn resample_many(
+    bars: impl IntoIterator<Item = OHLCVBar>,
+    timeframes: &[Timeframe],
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> Vec<Vec<OHLCVBar>> {
+    let mut accumulators: Vec<BucketAccumulator> = timeframes
+        .iter()
+        .map(|&tf| BucketAccumulator::new(tf, bar_timestamp, bucket_edge, bucket_anchor))
+        .collect();
+    let mut outputs: Vec<Vec<OHLCVBar>> = vec![Vec::new(); timeframes.len()];
+
+    for bar in bars {
+        for (accumulator, out) in accumulators.iter_mut().zip(outputs.iter_mut()) {
+            accumulator.push(&bar, out);
+        }
+    }
+    for (accumulator, out) in accumulators.iter_mut().zip(outputs.iter_mut()) {
+        accumulator.finish(out);
+    }
+    outputs
+}
+
+/// Counts the distinct source timestamps of each bucket, in bucket order.
+///
+/// Buckets are placed as `BucketAccumulator` places them, so the counts line up bar for bar
+/// with `resample_many` and the `time_index`-driven resamplers fed the same timestamps.
+/// Unlike a bar's `count`, repeated timestamps (several ticks in one second) count once; the
+/// input is in time order, so that only takes counting changes. For 1-minute bars with one
+/// bar per minute it equals `count`.
+///
+/// # Arguments
+///
+/// * `timestamps` - Source timestamps in time order (e.g. of `time_index`).
+/// * `timeframe` - Bucket size, as passed to the resampler.
+/// * `bucket_edge` - Whether a bar exactly on a bucket boundary starts a bucket (left) or ends one (right).
+/// * `bucket_anchor` - Offset in seconds of intraday bucket boundaries (0 aligns them to the epoch).
+///
+/// # Returns
+///
+/// * `Vec<u64>` - One count per bucket.
+pub fn unique_timestamps(
+    timestamps: impl IntoIterator<Item = u64>,
+    timeframe: Timeframe,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> Vec<u64> {
+    let mut bucketing = Bucketing::new(timeframe, bucket_edge, bucket_anchor);
+    let mut counts: Vec<u64> = Vec::new();
+    let mut current: Option<(u64, u64)> = None;
+    for ts in timestamps {
+        let bucket = bucketing.bucket_of(ts);
+        match (current, counts.last_mut()) {
+            (Some((start, last)), Some(count)) if start == bucket => {
+                if ts != last {
+                    *count += 1;
+                }
             }
+            _ => counts.push(1),
+        }
+        current = Some((bucket, ts));
+    }
+    counts
+}
+
+/// Counts the distinct source timestamps of each day of `daily_index`, lining up with
+/// `resample_daily` (days past the end of `source` are skipped the same way).
+///
+/// # Arguments
+///
+/// * `source` - The stored bars, in either layout (see `BarSource`).
+/// * `daily_index` - A slice of `DailyIndexEntry` indicating the start and end indices for each day.
+///
+/// # Returns
+///
+/// * `Vec<u64>` - One count per daily bar.
+pub fn unique_timestamps_daily<B: BarSource>(source: &B, daily_index: &[index::DailyIndexEntry]) -> Vec<u64> {
+    daily_index
+        .iter()
+        .filter_map(|entry| {
+            let mut last = None;
+            let distinct = day_positions(entry, source.len())?
+                .map(|i| source.timestamp(i))
+                .filter(|&ts| last.replace(ts) != Some(ts))
+                .count();
+            Some(distinct as u64)
+        })
+        .collect()
+}
+
+/// Checks that resampled bars are strictly increasing in timestamp.
+///
+/// The resamplers trust the order of `time_index` / `daily_index`; a malformed `.idx`
+/// (unsorted or with repeated entries) yields out-of-order or duplicate buckets.
+///
+/// # Arguments
+///
+/// * `bars` - Resampled bars in output order.
+///
+/// # Returns
+///
+/// * `anyhow::Result<()>` - Success if every timestamp is greater than the previous one.
+///
+/// # Errors
+///
+/// * If a bar's timestamp is not greater than its predecessor's (the position is reported).
+pub fn check_strictly_increasing(bars: &[OHLCVBar]) -> anyhow::Result<()> {
+    for (i, pair) in bars.windows(2).enumerate() {
+        if pair[1].timestamp <= pair[0].timestamp {
+            return Err(anyhow::anyhow!(
+                "Resampled bars are not strictly increasing: bar {} at {} follows bar {} at {}",
+                i + 1,
+                pair[1].timestamp,
+                i,
+                pair[0].timestamp
+            ));
+        }
+    }
+    anyhow::Ok(())
+}
+
+/// Where the source bars of a resampling end, as needed by `drop_partial_bar`.
+///
+/// Fed the source timestamps in time order (`observe`), it keeps only the last two, the
+/// start of the last session and the last timestamp of the session before it, so it can
+/// ride along with any scan.
+#[derive(Debug, Clone, Default)]
+pub struct SourceTail {
+    last: Option<u64>,
+    previous: Option<u64>,
+    session: SessionTracker,
+    previous_session_last: Option<u64>,
+}
+
+impl SourceTail {
+    /// Builds the tail of `timestamps`, given in time order.
+    pub fn from_timestamps(timestamps: impl IntoIterator<Item = u64>) -> Self {
+        let mut tail = SourceTail::default();
+        timestamps.into_iter().for_each(|ts| tail.observe(ts));
+        tail
+    }
+
+    /// Records `ts`, the next source timestamp in time order.
+    pub fn observe(&mut self, ts: u64) {
+        if self.last.is_some_and(|last| last / 86400 != ts / 86400) {
+            self.previous_session_last = self.last;
         }
+        self.session.session_start(ts);
+        self.previous = self.last;
+        self.last = Some(ts);
     }
 
-    if let Some(bar) = current_bar {
-        resampled.push(bar);
+    /// Whether the data ends before the end of the final bucket of `timeframe`.
+    ///
+    /// Intraday buckets end at their start plus the timeframe. Left-closed, a bar's timestamp
+    /// is its start and it lasts as long as the gap from the previous source bar, so data
+    /// ending with the 10:04 one-minute bar fills the 10:00 5min bucket. Right-closed, the
+    /// bucket is full once a bar lands on its end. The final day is partial if its last bar
+    /// is earlier in the (UTC) day than the previous day's last bar. Without a previous bar
+    /// (left-closed) or day to compare with, the final bucket counts as complete.
+    fn ends_mid_bucket(&self, timeframe: Timeframe, bucket_edge: &cli::BucketEdge, bucket_anchor: u64) -> bool {
+        let Some(last) = self.last else {
+            return false;
+        };
+        match timeframe {
+            Timeframe::Intraday(timeframe_sec) => {
+                let end = bucket_start(last, self.session.start, timeframe_sec, bucket_edge, bucket_anchor) + timeframe_sec;
+                match bucket_edge {
+                    cli::BucketEdge::Left => self.previous.is_some_and(|previous| last + (last - previous) < end),
+                    cli::BucketEdge::Right => last < end,
+                }
+            }
+            Timeframe::Daily => self.previous_session_last.is_some_and(|previous| last % 86400 < previous % 86400),
+        }
     }
+}
 
-    Ok(resampled)
+/// Removes the final bar of `resampled` if its bucket isn't complete (`--drop-partial-bar`).
+///
+/// The resamplers always emit the bucket the data ends in, even when the data stops
+/// part-way through it; a live strategy usually wants only closed bars. Whether the final
+/// bucket is complete is decided from the last source timestamps (see `SourceTail`).
+///
+/// # Arguments
+///
+/// * `resampled` - Bars produced from the source `tail` describes, in time order.
+/// * `tail` - End of the source bars.
+/// * `timeframe` - Timeframe of `resampled`.
+/// * `bucket_edge` - Bucket edge `resampled` was produced with.
+/// * `bucket_anchor` - Bucket anchor `resampled` was produced with.
+///
+/// # Returns
+///
+/// * `bool` - Whether a bar was dropped.
+pub fn drop_partial_bar(
+    resampled: &mut Vec<OHLCVBar>,
+    tail: &SourceTail,
+    timeframe: Timeframe,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> bool {
+    let partial = !resampled.is_empty() && tail.ends_mid_bucket(timeframe, bucket_edge, bucket_anchor);
+    if partial {
+        resampled.pop();
+    }
+    partial
 }
 
-// --- SOA Resampling Functions ---
+// --- Resampling Functions ---
 
-/// Resamples OHLCV data (SOA format) into daily OHLCV bars using a daily index.
+/// Resamples the bars of `source` into daily OHLCV bars using a daily index.
 ///
-/// This function groups OHLCV records by day using the provided `daily_index`.
-/// It accesses data from the separate arrays within the `OHLCVSOA` object (Structure of Arrays).
+/// This function groups bars by day using the provided `daily_index`.
+/// The daily bar's timestamp is the first intraday bar's timestamp (the actual session
+/// start), not midnight UTC.
 /// Each group is aggregated into a single daily bar with:
 /// - Open: First bar's open
 /// - High: Max high across all bars in the day
 /// - Low: Min low across all bars in the day
 /// - Close: Last bar's close
 /// - Volume: Sum of volumes
+/// - Count: Number of source bars
 ///
 /// # Arguments
 ///
-/// * `data_soa` - The FlatBuffers OHLCVSOA object containing separate arrays for each field.
+/// * `source` - The stored bars, in either layout (see `BarSource`).
 /// * `daily_index` - A slice of `DailyIndexEntry` indicating the start and end indices for each day.
+/// * `bar_timestamp` - Which timestamp each daily bar carries (session start, first or last bar of the day).
 ///
 /// # Returns
 ///
 /// * `anyhow::Result<Vec<OHLCVBar>>` - A vector of daily OHLCV bars or an error.
-pub fn resample_daily_soa(
-    data_soa: ohlcv_soa_generated::OHLCVSOA,
+///
+/// # Errors
+///
+/// * If `daily_index` is empty but `source` is not (an index built without indexing), rather
+///   than returning no bars.
+pub fn resample_daily<B: BarSource>(
+    source: &B,
     daily_index: &[index::DailyIndexEntry],
+    bar_timestamp: &cli::BarTimestamp,
 ) -> anyhow::Result<Vec<OHLCVBar>> {
-    let timestamps = data_soa.timestamps().unwrap_or_default();
-    let opens = data_soa.opens().unwrap_or_default();
-    let highs = data_soa.highs().unwrap_or_default();
-    let lows = data_soa.lows().unwrap_or_default();
-    let closes = data_soa.closes().unwrap_or_default();
-    let volumes = data_soa.volumes().unwrap_or_default();
-
     let mut resampled = Vec::new();
-    for entry in daily_index {
-        let start = entry.start_index as usize;
-        let end = entry.end_index as usize;
+    resample_daily_into(&mut resampled, source, daily_index, bar_timestamp)?;
+    anyhow::Ok(resampled)
+}
 
-        let len = std::cmp::min(timestamps.len(), opens.len());
-        let len = std::cmp::min(len, highs.len());
-        let len = std::cmp::min(len, lows.len());
-        let len = std::cmp::min(len, closes.len());
-        let len = std::cmp::min(len, volumes.len());
+/// Like `resample_daily`, but writes into `out` (cleared first, capacity kept).
+///
+/// # Arguments
+///
+/// * `out` - Receives the bars; its previous contents are discarded.
+/// * The remaining arguments are the same as for `resample_daily`.
+///
+/// # Returns
+///
+/// * `anyhow::Result<()>` - Success or an error; on error `out` may hold a partial result.
+pub fn resample_daily_into<B: BarSource>(
+    out: &mut Vec<OHLCVBar>,
+    source: &B,
+    daily_index: &[index::DailyIndexEntry],
+    bar_timestamp: &cli::BarTimestamp,
+) -> anyhow::Result<()> {
+    out.clear();
+    if daily_index.is_empty() && !source.is_empty() {
+        return Err(anyhow::anyhow!("daily_index is empty; regenerate the .idx with indexing enabled"));
+    }
 
-        if start >= len || end >= len || start > end {
+    for entry in daily_index {
+        let Some(positions) = day_positions(entry, source.len()) else {
             continue;
-        }
-
-        // let first_ts = timestamps.get(start);
-        let first_open = opens.get(start);
-        let first_high = highs.get(start);
-        let first_low = lows.get(start);
-        let first_close = closes.get(start);
-        let first_vol = volumes.get(start);
-
-        let mut bar = OHLCVBar {
-            timestamp: utils::parse_date_to_timestamp(&entry.date)?,
-            open: first_open,
-            high: first_high,
-            low: first_low,
-            close: first_close,
-            volume: first_vol,
         };
-        for i in start + 1..= end {
-            // let ts = timestamps.get(i);
-            // let open = opens.get(i);
-            let high = highs.get(i);
-            let low = lows.get(i);
-            let close = closes.get(i);
-            let vol = volumes.get(i);
-
-            bar.high = bar.high.max(high);
-            bar.low = bar.low.min(low);
-            bar.close = close;
-            bar.volume += vol;
-        }
-        resampled.push(bar);
-    }
-    
-    anyhow::Ok(resampled)
+        let mut bar = source.bar(entry.start_index as usize);
+        let first_ts = bar.timestamp;
+        for i in positions.skip(1) {
+            bar.high = max_propagating(bar.high, source.high(i));
+            bar.low = min_propagating(bar.low, source.low(i));
+            bar.close = source.close(i);
+            bar.volume += source.volume(i);
+            bar.count += 1;
+        }
+        let trade_span = (first_ts, source.timestamp(entry.end_index as usize));
+        out.push(stamp_bar(bar, trade_span, bar_timestamp));
+    }
+    anyhow::Ok(())
 }
 
-/// Resamples OHLCV data (SOA format) into bars of a specified timeframe.
+/// Resamples the bars of `source` into bars of a specified timeframe.
 ///
-/// This function groups OHLCV records into bars of `timeframe_sec` duration.
-/// It accesses data from the separate arrays within the `OHLCVSOA` object (Structure of Arrays).
-/// It uses the `time_index` (which maps timestamps to their original vector indices) to find data points.
+/// This function groups bars into buckets of `timeframe_sec` duration, visiting them in
+/// the order of `time_index`, which maps timestamps to storage positions.
+/// Timeframes that aren't clock-aligned (e.g. 90min) are anchored to the session start
+/// rather than the Unix epoch, see `bucket_start`.
 /// It aggregates each group into a single bar with:
 /// - Open: First bar's open
 /// - High: Max high across all bars in the timeframe
 /// - Low: Min low across all bars in the timeframe
 /// - Close: Last bar's close
 /// - Volume: Sum of volumes
+/// - Count: Number of source bars
 ///
 /// # Arguments
 ///
-/// * `data_soa` - The FlatBuffers OHLCVSOA object containing separate arrays for each field.
-/// * `time_index` - A slice of `TimeIndexEntry` linking timestamps to their original vector indices (used to access SOA arrays).
+/// * `source` - The stored bars, in either layout (see `BarSource`).
+/// * `time_index` - A slice of `TimeIndexEntry` linking timestamps to positions in `source`.
 /// * `timeframe_sec` - The desired timeframe in seconds (e.g., 180 for 3 minutes).
+/// * `bar_timestamp` - Which timestamp each bar carries (bucket start, first or last source bar).
+/// * `bucket_edge` - Whether a bar exactly on a bucket boundary starts a bucket (left) or ends one (right).
+/// * `bucket_anchor` - Offset in seconds of intraday bucket boundaries (0 aligns them to the epoch).
 ///
 /// # Returns
 ///
 /// * `anyhow::Result<Vec<OHLCVBar>>` - A vector of resampled OHLCV bars or an error.
-pub fn resample_ohlcv_soa(
-    data_soa: ohlcv_soa_generated::OHLCVSOA,
+///
+/// # Errors
+///
+/// * If `timeframe_sec` is 0.
+/// * If `time_index` is empty but `source` is not (an index built without indexing), rather
+///   than returning no bars.
+pub fn resample_ohlcv<B: BarSource>(
+    source: &B,
     time_index: &[index::TimeIndexEntry],
     timeframe_sec: u64,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
 ) -> anyhow::Result<Vec<OHLCVBar>> {
-    let timestamps = data_soa.timestamps().unwrap_or_default();
-    let opens = data_soa.opens().unwrap_or_default();
-    let highs = data_soa.highs().unwrap_or_default();
-    let lows = data_soa.lows().unwrap_or_default();
-    let closes = data_soa.closes().unwrap_or_default();
-    let volumes = data_soa.volumes().unwrap_or_default();
-
     let mut resampled = Vec::new();
-    let mut current_bar: Option<OHLCVBar> = None;
+    resample_ohlcv_into(&mut resampled, source, time_index, timeframe_sec, bar_timestamp, bucket_edge, bucket_anchor)?;
+    anyhow::Ok(resampled)
+}
+
+/// Like `resample_ohlcv`, but writes into `out` (cleared first, capacity kept).
+///
+/// # Arguments
+///
+/// * `out` - Receives the bars; its previous contents are discarded.
+/// * The remaining arguments are the same as for `resample_ohlcv`.
+///
+/// # Returns
+///
+/// * `anyhow::Result<()>` - Success or an error; on error `out` may hold a partial result.
+pub fn resample_ohlcv_into<B: BarSource>(
+    out: &mut Vec<OHLCVBar>,
+    source: &B,
+    time_index: &[index::TimeIndexEntry],
+    timeframe_sec: u64,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<()> {
+    out.clear();
+    if timeframe_sec == 0 {
+        return Err(anyhow::anyhow!("Resampling needs a non-zero timeframe"));
+    }
+    if time_index.is_empty() && !source.is_empty() {
+        return Err(anyhow::anyhow!("time_index is empty; regenerate the .idx with indexing enabled"));
+    }
+    let mut accumulator = BucketAccumulator::new(Timeframe::Intraday(timeframe_sec), bar_timestamp, bucket_edge, bucket_anchor);
 
     for entry in time_index {
         let i = entry.index as usize;
-
-        let len = std::cmp::min(timestamps.len(), opens.len());
-        let len = std::cmp::min(len, highs.len());
-        let len = std::cmp::min(len, lows.len());
-        let len = std::cmp::min(len, closes.len());
-        let len = std::cmp::min(len, volumes.len());
-
-        if i >= len {
+        if i >= source.len() {
             continue;
         }
-
-        let ts = timestamps.get(i);
-        let open = opens.get(i);
-        let high = highs.get(i);
-        let low = lows.get(i);
-        let close = closes.get(i);
-        let vol = volumes.get(i);
-
-        let bar_start = ts - (ts % timeframe_sec);
-        match current_bar {
-            Some(ref mut bar) if bar.timestamp == bar_start => {
-                bar.high = bar.high.max(high);
-                bar.low = bar.low.min(low);
-                bar.close = close;
-                bar.volume += vol;
-            }
-            Some(bar) => {
-                resampled.push(bar);
-                current_bar = Some(OHLCVBar {
-                    timestamp: bar_start,
-                    open,
-                    high,
-                    low,
-                    close,
-                    volume: vol,
-                }); 
-            }
-            None => {
-                current_bar = Some(OHLCVBar {
-                    timestamp: bar_start,
-                    open,
-                    high,
-                    low,
-                    close,
-                    volume: vol,
-                });
-            }
-        }
+        accumulator.push(&source.bar(i), out);
     }
+    accumulator.finish(out);
 
-    if let Some(bar) = current_bar {
-        resampled.push(bar);
-    }
+    anyhow::Ok(())
+}
 
-    anyhow::Ok(resampled)
+/// Resamples to an intraday timeframe one day at a time, walking the `daily_index` ranges
+/// instead of `time_index`.
+///
+/// Each day's bars are a contiguous range of storage positions, read in order and bucketed
+/// on their own; the open bucket is closed at the end of every day. For a `timeframe_sec`
+/// dividing a day this gives the same bars as `resample_ohlcv` whenever no bucket straddles
+/// UTC midnight, which holds for left-closed buckets with a `bucket_anchor` that is a
+/// multiple of `timeframe_sec`. Otherwise the straddling bucket is split at the day boundary.
+///
+/// # Arguments
+///
+/// * `source` - The stored bars.
+/// * `daily_index` - Day ranges of positions in `source`.
+/// * `timeframe_sec` - The desired timeframe in seconds; must divide 86400.
+/// * `bar_timestamp` - Which timestamp each bar carries (bucket start, first or last source bar).
+/// * `bucket_edge` - Whether a bar exactly on a bucket boundary starts a bucket (left) or ends one (right).
+/// * `bucket_anchor` - Offset in seconds of intraday bucket boundaries (0 aligns them to the epoch).
+///
+/// # Returns
+///
+/// * `anyhow::Result<Vec<OHLCVBar>>` - A vector of resampled OHLCV bars or an error.
+///
+/// # Errors
+///
+/// * If `timeframe_sec` doesn't divide a day.
+/// * If `daily_index` is empty but `source` is not.
+pub fn resample_by_day<B: BarSource>(
+    source: &B,
+    daily_index: &[index::DailyIndexEntry],
+    timeframe_sec: u64,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<Vec<OHLCVBar>> {
+    let mut resampled = Vec::new();
+    resample_by_day_into(&mut resampled, source, daily_index, timeframe_sec, bar_timestamp, bucket_edge, bucket_anchor)?;
+    anyhow::Ok(resampled)
+}
+
+/// Like `resample_by_day`, but writes into `out` (cleared first, capacity kept).
+///
+/// # Arguments
+///
+/// * `out` - Receives the bars; its previous contents are discarded.
+/// * The remaining arguments are the same as for `resample_by_day`.
+///
+/// # Returns
+///
+/// * `anyhow::Result<()>` - Success or an error; on error `out` may hold a partial result.
+pub fn resample_by_day_into<B: BarSource>(
+    out: &mut Vec<OHLCVBar>,
+    source: &B,
+    daily_index: &[index::DailyIndexEntry],
+    timeframe_sec: u64,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<()> {
+    out.clear();
+    if timeframe_sec == 0 || 86400 % timeframe_sec != 0 {
+        return Err(anyhow::anyhow!("Day-chunked resampling needs a timeframe dividing a day, got {}s", timeframe_sec));
+    }
+    if daily_index.is_empty() && !source.is_empty() {
+        return Err(anyhow::anyhow!("daily_index is empty; regenerate the .idx with indexing enabled"));
+    }
+    let mut accumulator = BucketAccumulator::new(Timeframe::Intraday(timeframe_sec), bar_timestamp, bucket_edge, bucket_anchor);
+
+    for entry in daily_index {
+        let Some(positions) = day_positions(entry, source.len()) else {
+            continue;
+        };
+        for i in positions {
+            accumulator.push(&source.bar(i), out);
+        }
+        accumulator.finish(out);
+    }
+
+    anyhow::Ok(())
+}
+
+/// Resamples to an intraday timeframe with buckets restarting at every trading day's first
+/// bar (`--resample-anchor-to-session`).
+///
+/// Like `resample_by_day`, each `daily_index` day is bucketed on its own and its open bucket
+/// closed at the end of the day, but the boundaries are counted from the day's first bar
+/// instead of the epoch: with 4h buckets and a session opening at 09:30, buckets start at
+/// 09:30, 13:30, ... So no bucket ever spans the overnight gap, whatever the timeframe or
+/// `--bucket-anchor`, and the last bucket of a day may be shorter.
+///
+/// # Arguments
+///
+/// * `source` - The stored bars.
+/// * `daily_index` - Day ranges of positions in `source`.
+/// * `timeframe_sec` - The desired timeframe in seconds.
+/// * `bar_timestamp` - Which timestamp each bar carries (bucket start, first or last source bar).
+/// * `bucket_edge` - Whether a bar exactly on a bucket boundary starts a bucket (left) or ends one (right).
+///
+/// # Returns
+///
+/// * `anyhow::Result<Vec<OHLCVBar>>` - A vector of resampled OHLCV bars or an error.
+///
+/// # Errors
+///
+/// * If `timeframe_sec` is 0.
+/// * If `daily_index` is empty but `source` is not.
+pub fn resample_by_session<B: BarSource + ?Sized>(
+    source: &B,
+    daily_index: &[index::DailyIndexEntry],
+    timeframe_sec: u64,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+) -> anyhow::Result<Vec<OHLCVBar>> {
+    if timeframe_sec == 0 {
+        return Err(anyhow::anyhow!("Session-anchored resampling needs a non-zero timeframe"));
+    }
+    if daily_index.is_empty() && !source.is_empty() {
+        return Err(anyhow::anyhow!("daily_index is empty; regenerate the .idx with indexing enabled"));
+    }
+    let mut resampled = Vec::new();
+    let mut accumulator = BucketAccumulator::anchored_to_session(Timeframe::Intraday(timeframe_sec), bar_timestamp, bucket_edge);
+
+    for entry in daily_index {
+        let Some(positions) = day_positions(entry, source.len()) else {
+            continue;
+        };
+        for i in positions {
+            accumulator.push(&source.bar(i), &mut resampled);
+        }
+        // Also resets the session, so the next day's first bar anchors its buckets.
+        accumulator.finish(&mut resampled);
+    }
+
+    anyhow::Ok(resampled)
+}
+
+// --- AOS / SOA Entry Points ---
+//
+// The layout-specific names predate `BarSource` and are kept for callers; each forwards
+// to the generic function of the same name. The SOA forms reject files whose columns
+// have different lengths (see `SoaColumns::new`).
+
+/// `resample_daily` over the items of an AOS file.
+pub fn resample_daily_aos<'a>(
+    items: &flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV<'a>>>,
+    daily_index: &[index::DailyIndexEntry],
+    bar_timestamp: &cli::BarTimestamp,
+) -> anyhow::Result<Vec<OHLCVBar>> {
+    resample_daily(items, daily_index, bar_timestamp)
+}
+
+/// `resample_daily_into` over the items of an AOS file.
+pub fn resample_daily_aos_into<'a>(
+    out: &mut Vec<OHLCVBar>,
+    items: &flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV<'a>>>,
+    daily_index: &[index::DailyIndexEntry],
+    bar_timestamp: &cli::BarTimestamp,
+) -> anyhow::Result<()> {
+    resample_daily_into(out, items, daily_index, bar_timestamp)
+}
+
+/// `resample_ohlcv` over the items of an AOS file.
+pub fn resample_ohlcv_aos<'a>(
+    items: &flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV<'a>>>,
+    time_index: &[index::TimeIndexEntry],
+    timeframe_sec: u64,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<Vec<OHLCVBar>> {
+    resample_ohlcv(items, time_index, timeframe_sec, bar_timestamp, bucket_edge, bucket_anchor)
+}
+
+/// `resample_ohlcv_into` over the items of an AOS file.
+pub fn resample_ohlcv_aos_into<'a>(
+    out: &mut Vec<OHLCVBar>,
+    items: &flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV<'a>>>,
+    time_index: &[index::TimeIndexEntry],
+    timeframe_sec: u64,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<()> {
+    resample_ohlcv_into(out, items, time_index, timeframe_sec, bar_timestamp, bucket_edge, bucket_anchor)
+}
+
+/// `resample_daily` over the columns of an SOA file.
+///
+/// # Errors
+///
+/// * If the SOA columns have different lengths.
+pub fn resample_daily_soa(
+    data_soa: ohlcv_soa_generated::OHLCVSOA,
+    daily_index: &[index::DailyIndexEntry],
+    bar_timestamp: &cli::BarTimestamp,
+) -> anyhow::Result<Vec<OHLCVBar>> {
+    resample_daily(&SoaColumns::new(data_soa)?, daily_index, bar_timestamp)
+}
+
+/// `resample_daily_into` over the columns of an SOA file.
+///
+/// # Errors
+///
+/// * If the SOA columns have different lengths.
+pub fn resample_daily_soa_into(
+    out: &mut Vec<OHLCVBar>,
+    data_soa: ohlcv_soa_generated::OHLCVSOA,
+    daily_index: &[index::DailyIndexEntry],
+    bar_timestamp: &cli::BarTimestamp,
+) -> anyhow::Result<()> {
+    resample_daily_into(out, &SoaColumns::new(data_soa)?, daily_index, bar_timestamp)
+}
+
+/// `resample_ohlcv` over the columns of an SOA file.
+///
+/// # Errors
+///
+/// * If the SOA columns have different lengths.
+pub fn resample_ohlcv_soa(
+    data_soa: ohlcv_soa_generated::OHLCVSOA,
+    time_index: &[index::TimeIndexEntry],
+    timeframe_sec: u64,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<Vec<OHLCVBar>> {
+    resample_ohlcv(&SoaColumns::new(data_soa)?, time_index, timeframe_sec, bar_timestamp, bucket_edge, bucket_anchor)
+}
+
+/// `resample_ohlcv_into` over the columns of an SOA file.
+///
+/// # Errors
+///
+/// * If the SOA columns have different lengths.
+pub fn resample_ohlcv_soa_into(
+    out: &mut Vec<OHLCVBar>,
+    data_soa: ohlcv_soa_generated::OHLCVSOA,
+    time_index: &[index::TimeIndexEntry],
+    timeframe_sec: u64,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<()> {
+    resample_ohlcv_into(out, &SoaColumns::new(data_soa)?, time_index, timeframe_sec, bar_timestamp, bucket_edge, bucket_anchor)
+}
+
+// --- Precomputed Bucket Positions ---
+
+/// Returns the `timeframe_index` key of `timeframe_sec` ("5m" for 300) if that timeframe's
+/// index buckets match the resampler's with `bucket_edge` and `bucket_anchor`.
+///
+/// `timeframe_index` boundaries are epoch-aligned and left-closed, which is how
+/// `bucket_start` places clock-aligned timeframes with `BucketEdge::Left` and an anchor that
+/// is a multiple of the timeframe. Any other combination has to be bucketed bar by bar.
+fn positioned_timeframe(timeframe_sec: u64, bucket_edge: &cli::BucketEdge, bucket_anchor: u64) -> Option<String> {
+    let aligned = is_clock_aligned(timeframe_sec) && bucket_anchor.is_multiple_of(timeframe_sec);
+    (aligned && matches!(bucket_edge, cli::BucketEdge::Left) && timeframe_sec.is_multiple_of(60))
+        .then(|| format!("{}m", timeframe_sec / 60))
+}
+
+/// Aggregates the `n`-th bucket of `timeframe_sec` straight from its precomputed position
+/// (`FullIndex::timeframe_bucket`), without scanning the bars before it.
+///
+/// # Arguments
+///
+/// * `full_index` - Index of the file, written with `--precompute-tf-positions`.
+/// * `timeframe_sec` - The timeframe in seconds (e.g. 300 for 5 minutes).
+/// * `n` - Position of the bucket among the `timeframe_index` boundaries of the timeframe.
+/// * `bar_timestamp` - Which timestamp the bar carries (bucket start, first or last source bar).
+/// * `bar_at` - Returns the source bar at a storage position (e.g. `BarIter::nth` on a clone).
+///
+/// # Returns
+///
+/// * `Option<OHLCVBar>` - The resampled bar; `None` if the bucket is empty, `n` is past the
+///   last boundary, or the positions of `timeframe_sec` weren't precomputed.
+pub fn resampled_bar_at<F>(
+    full_index: &index::FullIndex,
+    timeframe_sec: u64,
+    n: usize,
+    bar_timestamp: &cli::BarTimestamp,
+    mut bar_at: F,
+) -> Option<OHLCVBar>
+where
+    F: FnMut(usize) -> Option<OHLCVBar>,
+{
+    let timeframe = positioned_timeframe(timeframe_sec, &cli::BucketEdge::Left, 0)?;
+    let (start, entries) = full_index.timeframe_bucket(&timeframe, n)?;
+    let mut aggregate = OhlcvAggregate::default();
+    for entry in entries {
+        if let Some(bar) = bar_at(entry.index as usize) {
+            aggregate.add(&bar);
+        }
+    }
+    aggregate.finish(start, bar_timestamp)
+}
+
+/// Resamples a whole file through its precomputed bucket positions, if it has them.
+///
+/// Produces the same bars as `resample_ohlcv_aos` / `resample_ohlcv_soa`, bucket by bucket
+/// (`resampled_bar_at`) instead of bucketing every bar.
+///
+/// # Arguments
+///
+/// * `full_index` - Index of the file.
+/// * `timeframe_sec` - The timeframe in seconds.
+/// * `bar_timestamp` - Which timestamp each bar carries (bucket start, first or last source bar).
+/// * `bucket_edge` - Whether a bar exactly on a bucket boundary starts a bucket (left) or ends one (right).
+/// * `bucket_anchor` - Offset in seconds of intraday bucket boundaries (0 aligns them to the epoch).
+/// * `bar_at` - Returns the source bar at a storage position.
+///
+/// # Returns
+///
+/// * `Option<Vec<OHLCVBar>>` - The resampled bars, or `None` if the index has no positions
+///   for this timeframe or they don't match `bucket_edge` / `bucket_anchor`; the caller then
+///   falls back to the scanning resamplers.
+pub fn resample_positioned<F>(
+    full_index: &index::FullIndex,
+    timeframe_sec: u64,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+    mut bar_at: F,
+) -> Option<Vec<OHLCVBar>>
+where
+    F: FnMut(usize) -> Option<OHLCVBar>,
+{
+    let timeframe = positioned_timeframe(timeframe_sec, bucket_edge, bucket_anchor)?;
+    let buckets = full_index.timeframe_positions.as_ref()?.get(&timeframe)?.len();
+    Some(
+        (0..buckets)
+            .filter_map(|n| resampled_bar_at(full_index, timeframe_sec, n, bar_timestamp, &mut bar_at))
+            .collect(),
+    )
+}
+
+// --- Target Bar Count Resampling ---
+
+/// Standard timeframes (in seconds) tried first by `timeframe_for_count`.
+pub const STANDARD_TIMEFRAMES: [u64; 11] = [60, 120, 180, 240, 300, 600, 900, 1800, 3600, 14400, 86400];
+
+/// Relative distance from the target count within which a standard timeframe is preferred
+/// over a custom bucket size.
+const STANDARD_TIMEFRAME_TOLERANCE: f64 = 0.1;
+
+/// Counts how many bars `time_index` produces when bucketed by `timeframe_sec`.
+///
+/// Uses the same bucketing as `resample_ohlcv_aos` / `resample_ohlcv_soa` (see `bucket_start`),
+/// reading only the timestamps in the index.
+fn bucket_count(time_index: &[index::TimeIndexEntry], timeframe_sec: u64, bucket_edge: &cli::BucketEdge, bucket_anchor: u64) -> usize {
+    let mut count = 0;
+    let mut current: Option<u64> = None;
+    let mut session = SessionTracker::default();
+    for entry in time_index {
+        let bar_start = bucket_start(entry.timestamp, session.session_start(entry.timestamp), timeframe_sec, bucket_edge, bucket_anchor);
+        if current != Some(bar_start) {
+            count += 1;
+            current = Some(bar_start);
+        }
+    }
+    count
+}
+
+/// Chooses a bucket size (in seconds) so that resampling yields about `target_count` bars.
+///
+/// Bar counts are computed exactly from the timestamps in `time_index`, so gaps such as
+/// nights and weekends are taken into account.
+/// - Every timeframe in `STANDARD_TIMEFRAMES` is tried first. The one whose bar count is
+///   closest to `target_count` wins; on a tie the larger timeframe (fewer bars) is chosen.
+/// - If even the best standard timeframe is more than 10% away from the target, a custom
+///   bucket size is searched for: the smallest number of seconds producing at most
+///   `target_count` bars. Since the data range rarely divides evenly, this usually lands
+///   slightly below the target rather than above it. The custom size is used only if it
+///   gets closer to the target than the best standard timeframe.
+///
+/// Custom bucket sizes that aren't clock-aligned are anchored to each session's start
+/// (see `bucket_start`), like any other such timeframe.
+///
+/// # Arguments
+///
+/// * `time_index` - A slice of `TimeIndexEntry` sorted by timestamp.
+/// * `target_count` - Desired number of output bars.
+/// * `bucket_edge` - Bucket of bars exactly on a boundary, as in the resampler that will use the result.
+/// * `bucket_anchor` - Offset in seconds of intraday bucket boundaries (0 aligns them to the epoch).
+///
+/// # Returns
+///
+/// * `anyhow::Result<u64>` - The chosen bucket size in seconds.
+///
+/// # Errors
+///
+/// * If `target_count` is 0.
+pub fn timeframe_for_count(time_index: &[index::TimeIndexEntry], target_count: usize, bucket_edge: &cli::BucketEdge, bucket_anchor: u64) -> anyhow::Result<u64> {
+    if target_count == 0 {
+        return Err(anyhow::anyhow!("Target bar count must be a positive integer"));
+    }
+    let distance = |count: usize| count.abs_diff(target_count);
+
+    let mut best = STANDARD_TIMEFRAMES[0];
+    let mut best_distance = distance(bucket_count(time_index, best, bucket_edge, bucket_anchor));
+    for &timeframe_sec in &STANDARD_TIMEFRAMES[1..] {
+        let d = distance(bucket_count(time_index, timeframe_sec, bucket_edge, bucket_anchor));
+        if d <= best_distance {
+            best = timeframe_sec;
+            best_distance = d;
+        }
+    }
+    if best_distance as f64 <= target_count as f64 * STANDARD_TIMEFRAME_TOLERANCE {
+        return anyhow::Ok(best);
+    }
+
+    let (first, last) = match (time_index.first(), time_index.last()) {
+        (Some(first), Some(last)) => (first.timestamp, last.timestamp),
+        _ => return anyhow::Ok(best),
+    };
+    let (mut low, mut high) = (1u64, last - first + 1);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if bucket_count(time_index, mid, bucket_edge, bucket_anchor) <= target_count {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    if distance(bucket_count(time_index, low, bucket_edge, bucket_anchor)) < best_distance {
+        anyhow::Ok(low)
+    } else {
+        anyhow::Ok(best)
+    }
+}
+
+/// Resamples the bars of `source` into about `target_count` bars.
+///
+/// The bucket size is chosen by `timeframe_for_count`; aggregation is then identical
+/// to `resample_ohlcv`.
+///
+/// # Arguments
+///
+/// * `source` - The stored bars, in either layout (see `BarSource`).
+/// * `time_index` - A slice of `TimeIndexEntry` linking timestamps to positions in `source`.
+/// * `target_count` - Desired number of output bars.
+/// * `bar_timestamp` - Which timestamp each bar carries (bucket start, first or last source bar).
+/// * `bucket_edge` - Whether a bar exactly on a bucket boundary starts a bucket (left) or ends one (right).
+/// * `bucket_anchor` - Offset in seconds of intraday bucket boundaries (0 aligns them to the epoch).
+///
+/// # Returns
+///
+/// * `anyhow::Result<Vec<OHLCVBar>>` - A vector of resampled OHLCV bars or an error.
+pub fn resample_to_count<B: BarSource>(
+    source: &B,
+    time_index: &[index::TimeIndexEntry],
+    target_count: usize,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<Vec<OHLCVBar>> {
+    let mut resampled = Vec::new();
+    resample_to_count_into(&mut resampled, source, time_index, target_count, bar_timestamp, bucket_edge, bucket_anchor)?;
+    anyhow::Ok(resampled)
+}
+
+/// Like `resample_to_count`, but writes into `out` (cleared first, capacity kept).
+///
+/// # Arguments
+///
+/// * `out` - Receives the bars; its previous contents are discarded.
+/// * The remaining arguments are the same as for `resample_to_count`.
+///
+/// # Returns
+///
+/// * `anyhow::Result<()>` - Success or an error; on error `out` may hold a partial result.
+pub fn resample_to_count_into<B: BarSource>(
+    out: &mut Vec<OHLCVBar>,
+    source: &B,
+    time_index: &[index::TimeIndexEntry],
+    target_count: usize,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<()> {
+    let timeframe_sec = timeframe_for_count(time_index, target_count, bucket_edge, bucket_anchor)?;
+    resample_ohlcv_into(out, source, time_index, timeframe_sec, bar_timestamp, bucket_edge, bucket_anchor)
+}
+
+/// `resample_to_count` over the items of an AOS file.
+pub fn resample_to_count_aos<'a>(
+    items: &flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV<'a>>>,
+    time_index: &[index::TimeIndexEntry],
+    target_count: usize,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<Vec<OHLCVBar>> {
+    resample_to_count(items, time_index, target_count, bar_timestamp, bucket_edge, bucket_anchor)
+}
+
+/// `resample_to_count_into` over the items of an AOS file.
+pub fn resample_to_count_aos_into<'a>(
+    out: &mut Vec<OHLCVBar>,
+    items: &flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV<'a>>>,
+    time_index: &[index::TimeIndexEntry],
+    target_count: usize,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<()> {
+    resample_to_count_into(out, items, time_index, target_count, bar_timestamp, bucket_edge, bucket_anchor)
+}
+
+/// `resample_to_count` over the columns of an SOA file.
+///
+/// # Errors
+///
+/// * If the SOA columns have different lengths, or as for `timeframe_for_count`.
+pub fn resample_to_count_soa(
+    data_soa: ohlcv_soa_generated::OHLCVSOA,
+    time_index: &[index::TimeIndexEntry],
+    target_count: usize,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<Vec<OHLCVBar>> {
+    resample_to_count(&SoaColumns::new(data_soa)?, time_index, target_count, bar_timestamp, bucket_edge, bucket_anchor)
+}
+
+/// `resample_to_count_into` over the columns of an SOA file.
+///
+/// # Errors
+///
+/// * If the SOA columns have different lengths, or as for `timeframe_for_count`.
+pub fn resample_to_count_soa_into(
+    out: &mut Vec<OHLCVBar>,
+    data_soa: ohlcv_soa_generated::OHLCVSOA,
+    time_index: &[index::TimeIndexEntry],
+    target_count: usize,
+    bar_timestamp: &cli::BarTimestamp,
+    bucket_edge: &cli::BucketEdge,
+    bucket_anchor: u64,
+) -> anyhow::Result<()> {
+    resample_to_count_into(out, &SoaColumns::new(data_soa)?, time_index, target_count, bar_timestamp, bucket_edge, bucket_anchor)
+}
+
+// --- Volume and Count Bars ---
+
+/// When a volume or count bar closes (`-r vol:N` / `-r count:N`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarThreshold {
+    /// Close once the bar's volume reaches this much.
+    Volume(u64),
+    /// Close every this many source bars.
+    Count(u64),
+}
+
+impl BarThreshold {
+    /// Parses a `--resample` value of the form `vol:N` or `count:N`, with `N > 0`.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<BarThreshold>` - The threshold, or `None` if `tf` is neither form (e.g. a timeframe).
+    pub fn parse(tf: &str) -> Option<BarThreshold> {
+        let (kind, n) = tf.split_once(':')?;
+        let n = n.parse::<u64>().ok().filter(|&n| n > 0)?;
+        match kind {
+            "vol" => Some(BarThreshold::Volume(n)),
+            "count" => Some(BarThreshold::Count(n)),
+            _ => None,
+        }
+    }
+
+    /// Whether `bar`, aggregated so far, is full.
+    fn is_reached(&self, bar: &OHLCVBar) -> bool {
+        match *self {
+            BarThreshold::Volume(volume) => bar.volume >= volume,
+            BarThreshold::Count(count) => bar.count >= count,
+        }
+    }
+}
+
+impl std::fmt::Display for BarThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BarThreshold::Volume(volume) => write!(f, "vol:{}", volume),
+            BarThreshold::Count(count) => write!(f, "count:{}", count),
+        }
+    }
+}
+
+/// Resamples bars into bars that close on `threshold` rather than on the clock.
+///
+/// Source bars are aggregated like a time bucket (`OhlcvAggregate`: first open, highest
+/// high, lowest low, last close, summed volume and count), but the next bar starts after
+/// the source bar that fills the current one instead of at a time boundary. What is left
+/// at the end of `source` is emitted as a final partial bar (see `drop_partial_threshold_bar`).
+///
+/// With `BarTimestamp::BucketStart` a bar carries the timestamp of its first source bar,
+/// since these bars have no bucket of their own.
+///
+/// # Arguments
+///
+/// * `source` - Source bars in time order (e.g. `read_flatbuffers::iter_bars`).
+/// * `threshold` - When a bar closes.
+/// * `bar_timestamp` - Which timestamp each bar carries (first or last source bar).
+///
+/// # Returns
+///
+/// * `Vec<OHLCVBar>` - The bars in time order.
+pub fn resample_threshold_bars(
+    source: impl IntoIterator<Item = OHLCVBar>,
+    threshold: BarThreshold,
+    bar_timestamp: &cli::BarTimestamp,
+) -> Vec<OHLCVBar> {
+    let mut out = Vec::new();
+    let mut aggregate = OhlcvAggregate::default();
+    for bar in source {
+        aggregate.add(&bar);
+        if aggregate.bar.is_some_and(|bar| threshold.is_reached(&bar)) {
+            let full = std::mem::take(&mut aggregate);
+            let first = full.trade_span.0;
+            out.extend(full.finish(first, bar_timestamp));
+        }
+    }
+    let first = aggregate.trade_span.0;
+    out.extend(aggregate.finish(first, bar_timestamp));
+    out
+}
+
+/// Resamples bars into volume bars: a bar closes as soon as its cumulative volume reaches
+/// `threshold`, however long that takes (see `resample_threshold_bars`).
+///
+/// Source bars are never split: a source bar that takes the volume past `threshold`, even
+/// on its own, closes its bar with all of its volume, so bars can exceed the threshold, and
+/// the excess is not carried over into the next bar. The final partial bar has less than
+/// `threshold`. Volumeless data never reaches a threshold and yields a single bar.
+pub fn resample_volume_bars(
+    source: impl IntoIterator<Item = OHLCVBar>,
+    threshold: u64,
+    bar_timestamp: &cli::BarTimestamp,
+) -> Vec<OHLCVBar> {
+    resample_threshold_bars(source, BarThreshold::Volume(threshold), bar_timestamp)
+}
+
+/// Resamples bars into count bars: a bar closes every `count` source bars (see
+/// `resample_threshold_bars`), so the final partial bar has fewer.
+///
+/// Source bars count with their own `count`, which is 1 for bars read from a `.bin`.
+pub fn resample_count_bars(
+    source: impl IntoIterator<Item = OHLCVBar>,
+    count: u64,
+    bar_timestamp: &cli::BarTimestamp,
+) -> Vec<OHLCVBar> {
+    resample_threshold_bars(source, BarThreshold::Count(count), bar_timestamp)
+}
+
+/// Drops the final bar of `resampled` if it didn't reach `threshold`, i.e. it is the
+/// partial bar `resample_threshold_bars` emits for what is left at the end of the data.
+///
+/// # Returns
+///
+/// * `bool` - Whether a bar was dropped.
+pub fn drop_partial_threshold_bar(resampled: &mut Vec<OHLCVBar>, threshold: BarThreshold) -> bool {
+    let partial = resampled.last().is_some_and(|bar| !threshold.is_reached(bar));
+    if partial {
+        resampled.pop();
+    }
+    partial
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use crate::csv_processor;
+
+    fn bar(timestamp: u64, open: f64, high: f64, low: f64, close: f64, volume: u64) -> OHLCVBar {
+        OHLCVBar { timestamp, open, high, low, close, volume, count: 1 }
+    }
+
+    fn records(bars: &[OHLCVBar]) -> Vec<csv_processor::ProcessedRecord> {
+        bars.iter().map(csv_processor::ProcessedRecord::from).collect()
+    }
+
+    // Two sessions opening at 09:30 UTC on 2024-01-15 and 2024-01-16.
+    fn two_sessions() -> Vec<OHLCVBar> {
+        vec![
+            bar(1705311000, 100.0, 101.0, 99.0, 100.5, 10),
+            bar(1705311060, 100.5, 102.0, 100.0, 101.5, 20),
+            bar(1705397400, 101.0, 103.0, 100.5, 102.0, 30),
+        ]
+    }
+
+    #[test]
+    fn daily_bar_timestamp_is_first_intraday_bar_aos() {
+        let bars = two_sessions();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+
+        let daily = resample_daily_aos(&items, &index.daily_index, &cli::BarTimestamp::BucketStart).unwrap();
+
+        assert_eq!(daily.len(), 2);
+        assert_eq!(daily[0].timestamp, 1705311000);
+        assert_eq!(daily[1].timestamp, 1705397400);
+    }
+
+    #[test]
+    fn daily_bar_timestamp_is_first_intraday_bar_soa() {
+        let bars = two_sessions();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let data_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&buf).unwrap().data().unwrap();
+
+        let daily = resample_daily_soa(data_soa, &index.daily_index, &cli::BarTimestamp::BucketStart).unwrap();
+
+        assert_eq!(daily.len(), 2);
+        assert_eq!(daily[0].timestamp, 1705311000);
+        assert_eq!(daily[1].timestamp, 1705397400);
+    }
+
+    #[test]
+    fn empty_indices_are_rejected_instead_of_resampling_to_nothing_aos() {
+        let bars = two_sessions();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+
+        let daily = resample_daily_aos(&items, &[], &cli::BarTimestamp::BucketStart).unwrap_err();
+        let intraday = resample_ohlcv_aos(&items, &[], 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap_err();
+
+        assert_eq!(daily.to_string(), "daily_index is empty; regenerate the .idx with indexing enabled");
+        assert_eq!(intraday.to_string(), "time_index is empty; regenerate the .idx with indexing enabled");
+    }
+
+    #[test]
+    fn empty_indices_are_rejected_instead_of_resampling_to_nothing_soa() {
+        let bars = two_sessions();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let data_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&buf).unwrap().data().unwrap();
+
+        let daily = resample_daily_soa(data_soa, &[], &cli::BarTimestamp::BucketStart).unwrap_err();
+        let intraday = resample_ohlcv_soa(data_soa, &[], 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap_err();
+
+        assert!(daily.to_string().starts_with("daily_index is empty"));
+        assert!(intraday.to_string().starts_with("time_index is empty"));
+    }
+
+    #[test]
+    fn day_chunked_resample_matches_the_flat_path() {
+        let bars: Vec<OHLCVBar> = (0..3u64)
+            .flat_map(|day| (0..200u64).map(move |m| 1705311000 + day * 86400 + m * 60))
+            .enumerate()
+            .map(|(i, ts)| bar(ts, i as f64, i as f64 + 2.0, i as f64 - 1.0, i as f64 + 0.5, i as u64))
+            .collect();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let columns = SoaColumns::new(ohlcv_soa_generated::root_as_ohlcvlist_soa(&buf).unwrap().data().unwrap()).unwrap();
+
+        let fields = |bars: Vec<OHLCVBar>| -> Vec<_> {
+            bars.iter().map(|b| (b.timestamp, b.open, b.high, b.low, b.close, b.volume, b.count)).collect()
+        };
+
+        for timeframe_sec in [300, 3600, 5400] {
+            for bar_timestamp in [cli::BarTimestamp::BucketStart, cli::BarTimestamp::LastTrade] {
+                let flat = resample_ohlcv(&columns, &index.time_index, timeframe_sec, &bar_timestamp, &cli::BucketEdge::Left, 0).unwrap();
+                let by_day = resample_by_day(&columns, &index.daily_index, timeframe_sec, &bar_timestamp, &cli::BucketEdge::Left, 0).unwrap();
+                assert_eq!(fields(by_day), fields(flat), "{}s", timeframe_sec);
+            }
+        }
+        let err = resample_by_day(&columns, &index.daily_index, 7, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap_err();
+        assert!(err.to_string().contains("dividing a day"), "{}", err);
+    }
+
+    #[test]
+    fn session_anchored_buckets_restart_after_the_overnight_gap() {
+        // 23:56 and 23:58 on 2024-01-15, then 00:01 and 00:03 on 2024-01-16.
+        let bars: Vec<OHLCVBar> = [1705362960, 1705363080, 1705363260, 1705363380]
+            .iter()
+            .map(|&ts| bar(ts, 1.0, 2.0, 0.5, 1.5, 1))
+            .collect();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let counts = |bars: Vec<OHLCVBar>| bars.iter().map(|b| (b.timestamp, b.count)).collect::<Vec<_>>();
+
+        // Anchored at :02/:07, the 23:57 bucket takes in both sides of midnight.
+        let plain = resample_many(bars.iter().copied(), &[Timeframe::Intraday(300)], &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 120).pop().unwrap();
+        assert_eq!(counts(plain), vec![(1705362720, 1), (1705363020, 2), (1705363320, 1)]);
+
+        let by_session = resample_by_session(bars.as_slice(), &index.daily_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left).unwrap();
+        assert_eq!(counts(by_session), vec![(1705362960, 2), (1705363260, 2)]);
+
+        // Right-closed, each session's first bar still opens the session's first bucket.
+        let right = resample_by_session(bars.as_slice(), &index.daily_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Right).unwrap();
+        assert_eq!(counts(right), vec![(1705362960, 2), (1705363260, 2)]);
+    }
+
+    #[test]
+    fn nan_high_propagates_unless_skipped() {
+        let bars = vec![
+            bar(1705311000, 100.0, 101.0, 99.0, 100.5, 10),
+            bar(1705311060, 100.5, f64::NAN, 100.0, 101.5, 20),
+            bar(1705311120, 101.5, 102.0, 101.0, 101.5, 30),
+        ];
+        let resample = |bars: Vec<OHLCVBar>| {
+            resample_many(bars, &[Timeframe::Intraday(300)], &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).remove(0)
+        };
+
+        let propagated = resample(bars.clone());
+        let mut skipped = resample(bars.iter().copied().map(skip_nan_extremes).collect());
+        restore_skipped_nan(&mut skipped);
+        let mut all_nan = resample(vec![skip_nan_extremes(bars[1])]);
+        restore_skipped_nan(&mut all_nan);
+
+        assert_eq!(bars.iter().map(nan_extremes).sum::<usize>(), 1);
+        assert!(propagated[0].high.is_nan());
+        assert_eq!(propagated[0].low, 99.0);
+        assert_eq!((skipped[0].high, skipped[0].low), (102.0, 99.0));
+        assert!(all_nan[0].high.is_nan());
+    }
+
+    #[test]
+    fn unique_timestamps_count_each_second_once_per_bucket() {
+        // Ticks at 10:00:00 (x2), 10:00:30, 10:04:59 and 10:05:00 (x3).
+        let timestamps = [1705312800, 1705312800, 1705312830, 1705313099, 1705313100, 1705313100, 1705313100];
+        let bars: Vec<OHLCVBar> = timestamps.iter().map(|&ts| bar(ts, 1.0, 2.0, 0.5, 1.5, 1)).collect();
+        let records = records(&bars);
+        let index = csv_processor::build_index(&records).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records, cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let data_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&buf).unwrap().data().unwrap();
+
+        let counts = unique_timestamps(timestamps, Timeframe::Intraday(300), &cli::BucketEdge::Left, 0);
+        let resampled = resample_ohlcv_soa(data_soa, &index.time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+
+        assert_eq!(counts, vec![3, 1]);
+        assert_eq!(resampled.iter().map(|b| b.count).collect::<Vec<_>>(), vec![4, 3]);
+        assert_eq!(unique_timestamps_daily(&SoaColumns::new(data_soa).unwrap(), &index.daily_index), vec![4]);
+    }
+
+    // 600 contiguous minute bars starting at 2024-01-15 09:30 UTC.
+    fn minute_index() -> Vec<index::TimeIndexEntry> {
+        (0..600u64)
+            .map(|i| index::TimeIndexEntry { timestamp: 1705311000 + i * 60, index: i })
+            .collect()
+    }
+
+    #[test]
+    fn target_count_prefers_matching_standard_timeframe() {
+        let time_index = minute_index();
+
+        assert_eq!(timeframe_for_count(&time_index, 120, &cli::BucketEdge::Left, 0).unwrap(), 300);
+        assert_eq!(bucket_count(&time_index, 300, &cli::BucketEdge::Left, 0), 120);
+    }
+
+    #[test]
+    fn target_count_falls_back_to_custom_bucket_size() {
+        let time_index = minute_index();
+
+        let timeframe_sec = timeframe_for_count(&time_index, 7, &cli::BucketEdge::Left, 0).unwrap();
+        let count = bucket_count(&time_index, timeframe_sec, &cli::BucketEdge::Left, 0);
+
+        assert!(!STANDARD_TIMEFRAMES.contains(&timeframe_sec));
+        assert!((6..=7).contains(&count), "got {} bars", count);
+    }
+
+    #[test]
+    fn target_count_must_be_positive() {
+        assert!(timeframe_for_count(&minute_index(), 0, &cli::BucketEdge::Left, 0).is_err());
+    }
+
+    // Two 390-minute sessions (09:30-16:00 UTC) of one-minute bars, on 2024-01-15 and 2024-01-16.
+    fn two_full_sessions() -> Vec<OHLCVBar> {
+        [1705311000u64, 1705397400]
+            .iter()
+            .flat_map(|&open| (0..390u64).map(move |m| bar(open + m * 60, 100.0, 101.0, 99.0, 100.5, 1)))
+            .collect()
+    }
+
+    fn assert_90min_buckets(resampled: &[OHLCVBar]) {
+        let session_starts = [1705311000u64, 1705397400];
+        assert_eq!(resampled.len(), 10);
+        for (day, &open) in session_starts.iter().enumerate() {
+            let session = &resampled[day * 5..day * 5 + 5];
+            let starts: Vec<u64> = session.iter().map(|b| b.timestamp).collect();
+            let counts: Vec<u64> = session.iter().map(|b| b.count).collect();
+            assert_eq!(starts, (0..5).map(|k| open + k * 5400).collect::<Vec<_>>());
+            assert_eq!(counts, vec![90, 90, 90, 90, 30]);
+        }
+    }
+
+    #[test]
+    fn non_divisor_timeframe_is_anchored_to_session_start_aos() {
+        let bars = two_full_sessions();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+
+        let resampled = resample_ohlcv_aos(&items, &index.time_index, 5400, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+
+        assert_90min_buckets(&resampled);
+    }
+
+    #[test]
+    fn whole_hour_timeframes_dividing_a_day_stay_epoch_aligned() {
+        let bars = two_full_sessions();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+
+        let resampled = resample_ohlcv_aos(&items, &index.time_index, 14400, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+
+        // The 09:30 sessions fall into the 08:00 and 12:00 buckets, not ones opening at 09:30.
+        let starts: Vec<u64> = resampled.iter().map(|b| b.timestamp).collect();
+        assert_eq!(starts, vec![1705305600, 1705320000, 1705392000, 1705406400]);
+        assert!(starts.iter().all(|start| start % 14400 == 0));
+        assert_eq!(resampled.iter().map(|b| b.count).collect::<Vec<_>>(), vec![150, 240, 150, 240]);
+        assert!(resample_ohlcv_aos(&items, &index.time_index, 0, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).is_err());
+    }
+
+    #[test]
+    fn right_closed_session_buckets_include_the_opening_bar() {
+        let bars = two_full_sessions();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+
+        let resampled = resample_ohlcv_aos(&items, &index.time_index, 5400, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Right, 0).unwrap();
+
+        // 09:30 opens the first bucket and 11:00 closes it; 15:31-15:59 are left over.
+        assert_eq!(resampled.len(), 10);
+        for (day, &open) in [1705311000u64, 1705397400].iter().enumerate() {
+            let session = &resampled[day * 5..day * 5 + 5];
+            let starts: Vec<u64> = session.iter().map(|b| b.timestamp).collect();
+            let counts: Vec<u64> = session.iter().map(|b| b.count).collect();
+            assert_eq!(starts, (0..5).map(|k| open + k * 5400).collect::<Vec<_>>());
+            assert_eq!(counts, vec![91, 90, 90, 90, 29]);
+        }
+    }
+
+    #[test]
+    fn resample_into_clears_and_reuses_the_vector() {
+        let bars = two_full_sessions();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+
+        let mut out = Vec::new();
+        resample_ohlcv_aos_into(&mut out, &items, &index.time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+        let capacity = out.capacity();
+        resample_ohlcv_aos_into(&mut out, &items, &index.time_index, 5400, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+
+        assert_90min_buckets(&out);
+        assert_eq!(out.capacity(), capacity);
+    }
+
+    #[test]
+    fn non_divisor_timeframe_is_anchored_to_session_start_soa() {
+        let bars = two_full_sessions();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let data_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&buf).unwrap().data().unwrap();
+
+        let resampled = resample_ohlcv_soa(data_soa, &index.time_index, 5400, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+
+        assert_90min_buckets(&resampled);
+    }
+
+    #[test]
+    fn sorted_time_index_yields_strictly_increasing_bars() {
+        let time_index = minute_index();
+        let bars: Vec<OHLCVBar> = time_index.iter().map(|e| bar(e.timestamp, 1.0, 1.0, 1.0, 1.0, 1)).collect();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+
+        let resampled = resample_ohlcv_aos(&items, &time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+
+        assert!(check_strictly_increasing(&resampled).is_ok());
+    }
+
+    #[test]
+    fn strict_check_catches_unsorted_time_index_aos() {
+        let mut time_index = minute_index();
+        let bars: Vec<OHLCVBar> = time_index.iter().map(|e| bar(e.timestamp, 1.0, 1.0, 1.0, 1.0, 1)).collect();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+        time_index.reverse();
+
+        let resampled = resample_ohlcv_aos(&items, &time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+
+        assert!(check_strictly_increasing(&resampled).is_err());
+    }
+
+    #[test]
+    fn strict_check_catches_duplicate_buckets_soa() {
+        let time_index = minute_index();
+        let bars: Vec<OHLCVBar> = time_index.iter().map(|e| bar(e.timestamp, 1.0, 1.0, 1.0, 1.0, 1)).collect();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let data_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&buf).unwrap().data().unwrap();
+        // The index visits the first ten minutes twice, reopening an already emitted bucket.
+        let malformed: Vec<index::TimeIndexEntry> = time_index[..10].iter().chain(&time_index[..10]).cloned().collect();
+
+        let resampled = resample_ohlcv_soa(data_soa, &malformed, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+
+        assert!(check_strictly_increasing(&resampled).is_err());
+    }
+
+    // Crude oil on 2020-04-20: three minute bars trading through zero into negative prices.
+    fn negative_prices() -> Vec<OHLCVBar> {
+        vec![
+            bar(1587398400, 1.50, 2.00, -1.25, -0.75, 10),
+            bar(1587398460, -0.75, 0.50, -37.63, -30.00, 20),
+            bar(1587398520, -30.00, -5.00, -35.00, -10.25, 30),
+        ]
+    }
+
+    fn assert_negative_aggregate(bar: &OHLCVBar) {
+        assert_eq!(bar.open, 1.50);
+        assert_eq!(bar.high, 2.00);
+        assert_eq!(bar.low, -37.63);
+        assert_eq!(bar.close, -10.25);
+        assert_eq!(bar.volume, 60);
+    }
+
+    #[test]
+    fn negative_prices_resample_aos() {
+        let bars = negative_prices();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+
+        let resampled = resample_ohlcv_aos(&items, &index.time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+        let daily = resample_daily_aos(&items, &index.daily_index, &cli::BarTimestamp::BucketStart).unwrap();
+
+        assert_eq!(resampled.len(), 1);
+        assert_negative_aggregate(&resampled[0]);
+        assert_eq!(daily.len(), 1);
+        assert_negative_aggregate(&daily[0]);
+    }
+
+    #[test]
+    fn negative_prices_resample_soa() {
+        let bars = negative_prices();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let data_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&buf).unwrap().data().unwrap();
+
+        let resampled = resample_ohlcv_soa(data_soa, &index.time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap();
+        let daily = resample_daily_soa(data_soa, &index.daily_index, &cli::BarTimestamp::BucketStart).unwrap();
+
+        assert_eq!(resampled.len(), 1);
+        assert_negative_aggregate(&resampled[0]);
+        assert_eq!(daily.len(), 1);
+        assert_negative_aggregate(&daily[0]);
+    }
+
+    // Seven minute bars from 2024-01-15 10:00 UTC, i.e. starting exactly on a 5-minute boundary.
+    fn boundary_minutes() -> Vec<OHLCVBar> {
+        vec![
+            bar(1705312800, 10.0, 12.0, 9.5, 11.0, 1),
+            bar(1705312860, 11.0, 11.5, 8.0, 9.0, 2),
+            bar(1705312920, 9.0, 13.0, 9.0, 12.5, 3),
+            bar(1705312980, 12.5, 12.75, 11.0, 11.5, 4),
+            bar(1705313040, 11.5, 12.0, 10.5, 10.75, 5),
+            bar(1705313100, 10.75, 11.0, 10.0, 10.25, 6),
+            bar(1705313160, 10.25, 14.0, 10.25, 13.5, 7),
+        ]
+    }
+
+    fn ohlcv(bar: &OHLCVBar) -> (u64, f64, f64, f64, f64, u64, u64) {
+        (bar.timestamp, bar.open, bar.high, bar.low, bar.close, bar.volume, bar.count)
+    }
+
+    fn resample_5min_both(bars: &[OHLCVBar]) -> [Vec<OHLCVBar>; 2] {
+        let index = csv_processor::build_index(&records(bars)).unwrap();
+        let aos = csv_processor::build_flatbuffer(&records(bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let soa = csv_processor::build_flatbuffer(&records(bars), cli::StorageFormat::Soa, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&aos).unwrap().items().unwrap();
+        let data_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&soa).unwrap().data().unwrap();
+
+        [
+            resample_ohlcv_aos(&items, &index.time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap(),
+            resample_ohlcv_soa(data_soa, &index.time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn first_bucket_keeps_first_open_when_data_starts_on_boundary() {
+        for resampled in resample_5min_both(&boundary_minutes()) {
+            assert_eq!(resampled.len(), 2);
+            assert_eq!(ohlcv(&resampled[0]), (1705312800, 10.0, 13.0, 8.0, 10.75, 15, 5));
+            assert_eq!(ohlcv(&resampled[1]), (1705313100, 10.75, 14.0, 10.0, 13.5, 13, 2));
+        }
+    }
+
+    #[test]
+    fn first_bucket_keeps_first_open_when_data_starts_mid_bucket() {
+        for resampled in resample_5min_both(&boundary_minutes()[3..]) {
+            assert_eq!(resampled.len(), 2);
+            assert_eq!(ohlcv(&resampled[0]), (1705312800, 12.5, 12.75, 10.5, 10.75, 9, 2));
+            assert_eq!(ohlcv(&resampled[1]), (1705313100, 10.75, 14.0, 10.0, 13.5, 13, 2));
+        }
+    }
+
+    #[test]
+    fn round_bar_rounds_prices_only() {
+        let rounded = round_bar(&bar(1705312800, 100.123456, 101.98765, -37.625, 100.5, 42), 2);
+
+        assert_eq!(ohlcv(&rounded), (1705312800, 100.12, 101.99, -37.63, 100.5, 42, 1));
+    }
+
+    #[test]
+    fn true_range_uses_previous_close() {
+        let bars = vec![
+            bar(1705312800, 10.0, 12.0, 9.0, 11.0, 1),
+            // Gap up: |high - previous close| dominates.
+            bar(1705313100, 14.0, 15.0, 13.5, 14.5, 1),
+            // Gap down: |low - previous close| dominates.
+            bar(1705313400, 12.0, 12.5, 11.0, 11.5, 1),
+            // Inside bar: high - low dominates.
+            bar(1705313700, 11.5, 13.0, 10.0, 12.0, 1),
+        ];
+
+        let ranges = true_ranges(&bars);
+
+        assert_eq!(ranges, vec![3.0, 4.0, 3.5, 3.0]);
+    }
+
+    #[test]
+    fn boundary_bar_placement_follows_bucket_edge() {
+        // 10:01 .. 10:05 and 10:06; 10:05:00 is a 5min boundary.
+        let bars = boundary_minutes()[1..].to_vec();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+        let resample = |bucket_edge: cli::BucketEdge| {
+            resample_ohlcv_aos(&items, &index.time_index, 300, &cli::BarTimestamp::BucketStart, &bucket_edge, 0).unwrap()
+        };
+
+        let left = resample(cli::BucketEdge::Left);
+        assert_eq!(left.iter().map(|b| (b.timestamp, b.count)).collect::<Vec<_>>(), vec![(1705312800, 4), (1705313100, 2)]);
+
+        let right = resample(cli::BucketEdge::Right);
+        assert_eq!(right.iter().map(|b| (b.timestamp, b.count)).collect::<Vec<_>>(), vec![(1705312800, 5), (1705313100, 1)]);
+        assert_eq!(right[0].close, 10.25);
+    }
+
+    #[test]
+    fn bucket_anchor_shifts_intraday_boundaries() {
+        // 10:00 .. 10:09, one bar per minute.
+        let bars: Vec<OHLCVBar> = (0..10u64).map(|i| bar(1705312800 + i * 60, 1.0, 2.0, 0.5, 1.5, 1)).collect();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+        let resample = |bucket_edge: cli::BucketEdge, bucket_anchor: u64| {
+            resample_ohlcv_aos(&items, &index.time_index, 300, &cli::BarTimestamp::BucketStart, &bucket_edge, bucket_anchor)
+                .unwrap()
+                .iter()
+                .map(|b| (b.timestamp, b.count))
+                .collect::<Vec<_>>()
+        };
+
+        // Buckets at 09:57, 10:02 and 10:07.
+        let anchored = vec![(1705312620, 2), (1705312920, 5), (1705313220, 3)];
+        assert_eq!(resample(cli::BucketEdge::Left, 120), anchored);
+        // Anchors are taken modulo the timeframe, so a whole number of buckets is no shift.
+        assert_eq!(resample(cli::BucketEdge::Left, 420), anchored);
+        assert_eq!(resample(cli::BucketEdge::Left, 300), resample(cli::BucketEdge::Left, 0));
+        // Right-closed: 10:02 and 10:07 close the buckets they end.
+        assert_eq!(resample(cli::BucketEdge::Right, 120), vec![(1705312620, 3), (1705312920, 5), (1705313220, 2)]);
+    }
+
+    #[test]
+    fn precomputed_positions_resample_like_the_scan() {
+        // Two sessions with an overnight gap, varying prices so every aggregate is checked.
+        let bars: Vec<OHLCVBar> = two_full_sessions()
+            .iter()
+            .enumerate()
+            .map(|(i, b)| OHLCVBar { high: 101.0 + (i % 7) as f64, low: 99.0 - (i % 5) as f64, close: i as f64, volume: i as u64, ..*b })
+            .collect();
+        let processed = csv_processor::build_index(&records(&bars)).unwrap();
+        let full_index = index::FullIndex {
+            timeframe_positions: Some(index::timeframe_positions(&processed.time_index, &processed.timeframe_index)),
+            time_index: processed.time_index,
+            daily_index: processed.daily_index,
+            timeframe_index: processed.timeframe_index,
+            source_line_index: None,
+            symbol: None,
+            segment_offsets: None,
+        };
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+        let bar_at = |i: usize| (i < items.len()).then(|| OHLCVBar::from(items.get(i)));
+        let key = |b: &OHLCVBar| (b.timestamp, b.open, b.high, b.low, b.close, b.volume, b.count);
+        let stamp = cli::BarTimestamp::LastTrade;
+
+        for timeframe_sec in [60, 180, 300] {
+            let scanned = resample_ohlcv_aos(&items, &full_index.time_index, timeframe_sec, &stamp, &cli::BucketEdge::Left, 0).unwrap();
+            let positioned = resample_positioned(&full_index, timeframe_sec, &stamp, &cli::BucketEdge::Left, 0, bar_at).unwrap();
+            assert_eq!(positioned.iter().map(key).collect::<Vec<_>>(), scanned.iter().map(key).collect::<Vec<_>>());
+        }
+
+        // The first bucket after the overnight gap is found directly; gap buckets are empty.
+        let first_5m_of_day_2 = ((1705397400 - 1705311000) / 300) as usize;
+        assert_eq!(resampled_bar_at(&full_index, 300, first_5m_of_day_2, &stamp, bar_at).map(|b| b.count), Some(5));
+        assert!(resampled_bar_at(&full_index, 300, first_5m_of_day_2 - 1, &stamp, bar_at).is_none());
+        // Right-closed buckets, anchored buckets and 90min aren't what the index stores.
+        assert!(resample_positioned(&full_index, 300, &stamp, &cli::BucketEdge::Right, 0, bar_at).is_none());
+        assert!(resample_positioned(&full_index, 300, &stamp, &cli::BucketEdge::Left, 120, bar_at).is_none());
+        assert!(resample_positioned(&full_index, 5400, &stamp, &cli::BucketEdge::Left, 0, bar_at).is_none());
+    }
+
+    #[test]
+    fn partial_final_bar_is_dropped_only_when_the_data_ends_mid_bucket() {
+        // One-minute bars from 10:00 (a 5min boundary) for `minutes` minutes.
+        let resampled = |minutes: u64, edge: cli::BucketEdge| {
+            let bars: Vec<OHLCVBar> = (0..minutes).map(|m| bar(1705312800 + m * 60, 1.0, 2.0, 0.5, 1.5, 1)).collect();
+            let tail = SourceTail::from_timestamps(bars.iter().map(|b| b.timestamp));
+            let mut out = resample_many(bars, &[Timeframe::Intraday(300)], &cli::BarTimestamp::BucketStart, &edge, 0).pop().unwrap();
+            let dropped = drop_partial_bar(&mut out, &tail, Timeframe::Intraday(300), &edge, 0);
+            (out.iter().map(|b| b.count).collect::<Vec<_>>(), dropped)
+        };
+
+        // Ending exactly on the boundary: the 10:09 bar fills the 10:05 bucket.
+        assert_eq!(resampled(10, cli::BucketEdge::Left), (vec![5, 5], false));
+        // Ending before it, or with a bar opening the next bucket.
+        assert_eq!(resampled(9, cli::BucketEdge::Left), (vec![5], true));
+        assert_eq!(resampled(11, cli::BucketEdge::Left), (vec![5, 5], true));
+        // Right-closed, the 10:05 and 10:10 bars close their buckets.
+        assert_eq!(resampled(11, cli::BucketEdge::Right), (vec![1, 5, 5], false));
+        assert_eq!(resampled(10, cli::BucketEdge::Right), (vec![1, 5], true));
+        // A single bar has no cadence to tell by.
+        assert_eq!(resampled(1, cli::BucketEdge::Left), (vec![1], false));
+    }
+
+    #[test]
+    fn partial_final_day_ends_earlier_than_the_day_before() {
+        let days = |last_close: u64| {
+            let bars = vec![
+                bar(1705311000, 1.0, 2.0, 0.5, 1.5, 1),  // 2024-01-15 09:30
+                bar(1705334400, 1.0, 2.0, 0.5, 1.5, 1),  // 2024-01-15 16:00
+                bar(1705397400, 1.0, 2.0, 0.5, 1.5, 1),  // 2024-01-16 09:30
+                bar(last_close, 1.0, 2.0, 0.5, 1.5, 1),
+            ];
+            let tail = SourceTail::from_timestamps(bars.iter().map(|b| b.timestamp));
+            let mut out = resample_many(bars, &[Timeframe::Daily], &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).pop().unwrap();
+            drop_partial_bar(&mut out, &tail, Timeframe::Daily, &cli::BucketEdge::Left, 0);
+            out.len()
+        };
+
+        assert_eq!(days(1705420800), 2); // 2024-01-16 16:00: as long as the day before
+        assert_eq!(days(1705413600), 1); // 2024-01-16 14:00: still in progress
+    }
+
+    #[test]
+    fn resample_many_matches_the_single_timeframe_resamplers() {
+        let bars = two_full_sessions();
+        let index = csv_processor::build_index(&records(&bars)).unwrap();
+        let buf = csv_processor::build_flatbuffer(&records(&bars), cli::StorageFormat::Aos, utils::SCHEMA_VERSION, true);
+        let items = ohlcv_generated::root_as_ohlcvlist(&buf).unwrap().items().unwrap();
+        let stamp = cli::BarTimestamp::LastTrade;
+        let edge = cli::BucketEdge::Right;
+
+        let many = resample_many(
+            items.iter().map(OHLCVBar::from),
+            &[Timeframe::Intraday(300), Timeframe::Intraday(5400), Timeframe::Daily],
+            &stamp,
+            &edge,
+            0,
+        );
+
+        let expected = [
+            resample_ohlcv_aos(&items, &index.time_index, 300, &stamp, &edge, 0).unwrap(),
+            resample_ohlcv_aos(&items, &index.time_index, 5400, &stamp, &edge, 0).unwrap(),
+            resample_daily_aos(&items, &index.daily_index, &stamp).unwrap(),
+        ];
+        for (got, want) in many.iter().zip(expected.iter()) {
+            assert_eq!(got.iter().map(ohlcv).collect::<Vec<_>>(), want.iter().map(ohlcv).collect::<Vec<_>>());
+        }
+        assert_eq!(many[2].len(), 2);
+    }
+
+    #[test]
+    fn timeframe_parse_accepts_the_resample_values() {
+        assert_eq!(Timeframe::parse("90min").unwrap(), Timeframe::Intraday(5400));
+        assert_eq!(Timeframe::parse("1d").unwrap(), Timeframe::Daily);
+        assert!(Timeframe::parse("7min").is_err());
+    }
+
+    #[test]
+    fn resample_with_default_aggregate_matches_the_builtin_resampler() {
+        let bars = two_full_sessions();
+        let stamp = cli::BarTimestamp::FirstTrade;
+        let edge = cli::BucketEdge::Left;
+
+        let custom = resample_with(bars.iter().copied(), Timeframe::Intraday(5400), &edge, 0, OhlcvAggregate::add, |start, aggregate: OhlcvAggregate| {
+            aggregate.finish(start, &stamp)
+        });
+        let builtin = resample_many(bars.iter().copied(), &[Timeframe::Intraday(5400)], &stamp, &edge, 0);
+
+        assert_eq!(custom.iter().flatten().map(ohlcv).collect::<Vec<_>>(), builtin[0].iter().map(ohlcv).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn resample_with_supports_custom_aggregations() {
+        // 10:00 .. 10:06 with closes 1, 5, 2, 4, 3 | 10, 20.
+        let closes = [1.0, 5.0, 2.0, 4.0, 3.0, 10.0, 20.0];
+        let bars: Vec<OHLCVBar> = closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| bar(1705312800 + i as u64 * 60, close, close, close, close, 1))
+            .collect();
+
+        let medians = resample_with(bars, Timeframe::Intraday(300), &cli::BucketEdge::Left, 0, |closes: &mut Vec<f64>, bar| closes.push(bar.close), |start, mut closes| {
+            closes.sort_by(f64::total_cmp);
+            (start, closes[closes.len() / 2])
+        });
+
+        assert_eq!(medians, vec![(1705312800, 3.0), (1705313100, 20.0)]);
+    }
+
+    fn truncated_opens_soa() -> Vec<u8> {
+        let mut builder = flatbuffers::FlatBufferBuilder::new();
+        let timestamps = builder.create_vector(&[1705312800u64, 1705312860, 1705312920]);
+        let prices = builder.create_vector(&[1.0f64, 2.0, 3.0]);
+        let opens = builder.create_vector(&[1.0f64, 2.0]);
+        let volumes = builder.create_vector(&[1u64, 1, 1]);
+        let data = {
+            let mut soa = ohlcv_soa_generated::OHLCVSOABuilder::new(&mut builder);
+            soa.add_timestamps(timestamps);
+            soa.add_opens(opens);
+            soa.add_highs(prices);
+            soa.add_lows(prices);
+            soa.add_closes(prices);
+            soa.add_volumes(volumes);
+            soa.finish()
+        };
+        let root = {
+            let mut list = ohlcv_soa_generated::OHLCVListSOABuilder::new(&mut builder);
+            list.add_data(data);
+            list.add_version(utils::SCHEMA_VERSION);
+            list.add_has_volume(true);
+            list.finish()
+        };
+        builder.finish(root, None);
+        builder.finished_data().to_vec()
+    }
+
+    #[test]
+    fn mismatched_soa_columns_are_rejected() {
+        let buf = truncated_opens_soa();
+        let data_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&buf).unwrap().data().unwrap();
+        let daily_index = [index::DailyIndexEntry { date: "2024-01-15".to_string(), start_index: 0, end_index: 2 }];
+        let time_index: Vec<index::TimeIndexEntry> = (0..3).map(|i| index::TimeIndexEntry { timestamp: 1705312800 + i * 60, index: i }).collect();
+
+        let err = resample_daily_soa(data_soa, &daily_index, &cli::BarTimestamp::BucketStart).unwrap_err().to_string();
+        assert!(err.contains("timestamps 3, opens 2"), "{}", err);
+        assert!(resample_ohlcv_soa(data_soa, &time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0).is_err());
+    }
+
+    #[test]
+    fn volume_bars_close_at_the_threshold_and_keep_the_partial_tail() {
+        let source = vec![
+            bar(60, 10.0, 11.0, 9.0, 10.5, 400),
+            bar(120, 10.5, 12.0, 10.0, 11.0, 700),
+            bar(180, 11.0, 11.5, 8.0, 9.0, 2500),
+            bar(240, 9.0, 9.5, 8.5, 9.25, 300),
+        ];
+
+        let volume_bars = resample_volume_bars(source.clone(), 1000, &cli::BarTimestamp::BucketStart);
+
+        // 400 + 700 fills the first bar; the 2500 bar alone overshoots the second.
+        assert_eq!(
+            volume_bars.iter().map(|b| (b.timestamp, b.open, b.high, b.low, b.close, b.volume, b.count)).collect::<Vec<_>>(),
+            vec![
+                (60, 10.0, 12.0, 9.0, 11.0, 1100, 2),
+                (180, 11.0, 11.5, 8.0, 9.0, 2500, 1),
+                (240, 9.0, 9.5, 8.5, 9.25, 300, 1),
+            ]
+        );
+        let last_trade = resample_volume_bars(source, 1000, &cli::BarTimestamp::LastTrade);
+        assert_eq!(last_trade.iter().map(|b| b.timestamp).collect::<Vec<_>>(), vec![120, 180, 240]);
+
+        let mut complete = volume_bars.clone();
+        assert!(drop_partial_threshold_bar(&mut complete, BarThreshold::Volume(1000)));
+        assert_eq!(complete.len(), 2);
+        assert!(!drop_partial_threshold_bar(&mut complete, BarThreshold::Volume(1000)));
+        assert!(resample_volume_bars(Vec::new(), 1000, &cli::BarTimestamp::BucketStart).is_empty());
+        assert_eq!(BarThreshold::parse("vol:10000"), Some(BarThreshold::Volume(10000)));
+        assert_eq!(BarThreshold::parse("count:500"), Some(BarThreshold::Count(500)));
+        assert_eq!(BarThreshold::parse("vol:0"), None);
+        assert_eq!(BarThreshold::parse("5min"), None);
+    }
+
+    #[test]
+    fn count_bars_aggregate_a_fixed_number_of_source_bars() {
+        let source: Vec<OHLCVBar> = (0..1000)
+            .map(|i| bar(60 * (i + 1), 100.0 + i as f64, 101.0 + i as f64, 99.0 + i as f64, 100.5 + i as f64, 10))
+            .collect();
+
+        let count_bars = resample_count_bars(source.iter().copied(), 500, &cli::BarTimestamp::BucketStart);
+
+        assert_eq!(
+            count_bars.iter().map(|b| (b.timestamp, b.open, b.high, b.low, b.close, b.volume, b.count)).collect::<Vec<_>>(),
+            vec![
+                (60, 100.0, 600.0, 99.0, 599.5, 5000, 500),
+                (30060, 600.0, 1100.0, 599.0, 1099.5, 5000, 500),
+            ]
+        );
+        let mut with_tail = resample_count_bars(source[..750].iter().copied(), 500, &cli::BarTimestamp::BucketStart);
+        assert_eq!(with_tail.iter().map(|b| b.count).collect::<Vec<_>>(), vec![500, 250]);
+        assert!(drop_partial_threshold_bar(&mut with_tail, BarThreshold::Count(500)));
+        assert_eq!(with_tail.len(), 1);
+    }
 }
@@ -0,0 +1,152 @@
+use crate::resample;
+use crate::file_processing;
+
+/// Magic bytes at the start of every resample cache file.
+pub const RESAMPLE_CACHE_MAGIC: &[u8; 4] = b"RSCH";
+
+/// Version of the cache layout, stored in the byte right after `RESAMPLE_CACHE_MAGIC`.
+///
+/// A cache with another version is treated as stale and rewritten.
+pub const RESAMPLE_CACHE_VERSION: u8 = 1;
+
+/// What a cached series was computed from (`--cache-resample`).
+///
+/// A cache is fresh only if its key equals the current one: the source file has the same
+/// modification time and size, and the resampling parameters are the same.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CacheKey {
+    /// Modification time of the source `.bin` (or `.ohlcv` bundle).
+    pub source_modified: std::time::SystemTime,
+    /// Size of the source in bytes.
+    pub source_len: u64,
+    /// The read options that change the resampled bars (`--bar-timestamp`,
+    /// `--bucket-edge`, ...), as text.
+    pub params: String,
+}
+
+impl CacheKey {
+    /// Builds the current key of `source_path`.
+    ///
+    /// # Errors
+    /// * If the source's metadata can't be read.
+    pub fn of(source_path: &std::path::Path, params: String) -> anyhow::Result<Self> {
+        let metadata = std::fs::metadata(source_path).map_err(|e| file_processing::io_error(e, "stat", source_path))?;
+        anyhow::Ok(CacheKey { source_modified: metadata.modified()?, source_len: metadata.len(), params })
+    }
+}
+
+/// Contents of a cache file after its magic and version byte.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CacheFile {
+    key: CacheKey,
+    bars: Vec<resample::OHLCVBar>,
+}
+
+/// Path of the cache of `source_path` resampled to `timeframe`, next to the source:
+/// `data.aos.bin` at `5min` is cached in `data.aos.5min.cache.bin`.
+pub fn cache_path(source_path: &std::path::Path, timeframe: &str) -> std::path::PathBuf {
+    source_path.with_extension(format!("{}.cache.bin", timeframe))
+}
+
+/// Whether `path` is a resample cache (`cache_path`), which readers of a directory of
+/// `.bin` files must skip.
+pub fn is_cache_file(path: &std::path::Path) -> bool {
+    path.file_stem().and_then(|stem| stem.to_str()).is_some_and(|stem| stem.ends_with(".cache"))
+}
+
+/// Reads a cache file.
+///
+/// # Returns
+/// * `anyhow::Result<Option<CacheFile>>` - The cache, `None` if there is none or it was
+///   written in another layout version.
+///
+/// # Errors
+/// * If the file can't be read or isn't a cache.
+fn read_cache(path: &std::path::Path) -> anyhow::Result<Option<CacheFile>> {
+    if !path.exists() {
+        return anyhow::Ok(None);
+    }
+    let data = std::fs::read(path).map_err(|e| file_processing::io_error(e, "read resample cache", path))?;
+    let rest = data
+        .strip_prefix(RESAMPLE_CACHE_MAGIC.as_slice())
+        .ok_or_else(|| anyhow::anyhow!("{} is not a resample cache", path.display()))?;
+    match rest.split_first() {
+        Some((&RESAMPLE_CACHE_VERSION, body)) => anyhow::Ok(Some(bincode::deserialize(body)?)),
+        Some(_) => anyhow::Ok(None),
+        None => Err(anyhow::anyhow!("Resample cache {} is missing its version byte", path.display())),
+    }
+}
+
+/// Returns the bars of `source_path` resampled to `timeframe`, from the cache if it is
+/// fresh, else from `compute`, whose result then replaces the cache.
+///
+/// A cache is reused only if its `CacheKey` equals the current one, so touching or
+/// rewriting the source, or changing `params`, invalidates it. An unreadable cache is
+/// logged and rebuilt rather than failing the read.
+///
+/// # Arguments
+/// * `source_path` - Path to the source `.bin` (or `.ohlcv` bundle).
+/// * `timeframe` - Timeframe name as given to `--resample`, e.g. `5min`.
+/// * `params` - The read options that change the resampled bars, as text.
+/// * `compute` - Resamples the source.
+///
+/// # Returns
+/// * `anyhow::Result<(Vec<resample::OHLCVBar>, bool)>` - The bars, and whether they came
+///   from the cache.
+///
+/// # Errors
+/// * If the source's metadata can't be read, `compute` fails or the cache can't be written.
+pub fn load_or_compute(
+    source_path: &std::path::Path,
+    timeframe: &str,
+    params: String,
+    compute: impl FnOnce() -> anyhow::Result<Vec<resample::OHLCVBar>>,
+) -> anyhow::Result<(Vec<resample::OHLCVBar>, bool)> {
+    let key = CacheKey::of(source_path, params)?;
+    let path = cache_path(source_path, timeframe);
+    match read_cache(&path) {
+        Ok(Some(cache)) if cache.key == key => return anyhow::Ok((cache.bars, true)),
+        Ok(_) => {}
+        Err(e) => tracing::warn!("⚠️ Rebuilding unreadable resample cache {}: {}", path.display(), e),
+    }
+
+    let cache = CacheFile { key, bars: compute()? };
+    let mut data = RESAMPLE_CACHE_MAGIC.to_vec();
+    data.push(RESAMPLE_CACHE_VERSION);
+    data.extend(bincode::serialize(&cache)?);
+    file_processing::write_atomic(&path, &data)?;
+    anyhow::Ok((cache.bars, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(timestamp: u64, close: f64) -> resample::OHLCVBar {
+        resample::OHLCVBar { timestamp, open: close, high: close, low: close, close, volume: 1, count: 1 }
+    }
+
+    #[test]
+    fn cache_is_reused_until_the_source_or_params_change() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_resample_cache_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("data.aos.bin");
+        std::fs::write(&source, b"v1").unwrap();
+        assert_eq!(cache_path(&source, "5min"), dir.join("data.aos.5min.cache.bin"));
+        assert!(is_cache_file(&cache_path(&source, "5min")) && !is_cache_file(&source));
+
+        let (bars, cached) = load_or_compute(&source, "5min", "a".into(), || Ok(vec![bar(300, 1.0)])).unwrap();
+        assert_eq!((bars.len(), cached), (1, false));
+        let (bars, cached) = load_or_compute(&source, "5min", "a".into(), || panic!("cache should be fresh")).unwrap();
+        assert_eq!((bars[0].timestamp, bars[0].close, cached), (300, 1.0, true));
+
+        // Other parameters, then a rewritten source, both recompute.
+        let (_, cached) = load_or_compute(&source, "5min", "b".into(), || Ok(vec![bar(300, 2.0)])).unwrap();
+        assert!(!cached);
+        std::fs::write(&source, b"v2, longer").unwrap();
+        let (bars, cached) = load_or_compute(&source, "5min", "b".into(), || Ok(vec![bar(300, 3.0)])).unwrap();
+        assert_eq!((bars[0].close, cached), (3.0, false));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+use crate::file_processing;
+
+/// The AOS schema (`ohlcv.fbs`) this binary was built against, embedded at compile time.
+pub const OHLCV_FBS: &str = include_str!("../ohlcv.fbs");
+
+/// The SOA schema (`ohlcv_soa.fbs`) this binary was built against, embedded at compile time.
+pub const OHLCV_SOA_FBS: &str = include_str!("../ohlcv_soa.fbs");
+
+/// Writes the embedded `.fbs` schemas into `output_dir` (`emit-schema`).
+///
+/// The files are the exact text `build.rs` compiled the readers and writers from, so
+/// consumers in other languages can run `flatc` on them and get the field order and
+/// defaults of the `.bin` files this binary writes. Both schemas are written, since a
+/// directory may hold AOS and SOA files; the schema version they carry is
+/// `utils::SCHEMA_VERSION`.
+///
+/// # Arguments
+/// * `output_dir` - Directory to write `ohlcv.fbs` and `ohlcv_soa.fbs` into; created if missing.
+///
+/// # Returns
+/// * `anyhow::Result<Vec<std::path::PathBuf>>` - Paths of the written files.
+///
+/// # Errors
+/// * If `output_dir` is an existing file, or a file can't be written.
+pub fn emit_schema(output_dir: &std::path::Path) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    file_processing::check_output_dir(output_dir)?;
+    std::fs::create_dir_all(output_dir).map_err(|e| file_processing::io_error(e, "create", output_dir))?;
+
+    let mut written = Vec::with_capacity(2);
+    for (name, text) in [("ohlcv.fbs", OHLCV_FBS), ("ohlcv_soa.fbs", OHLCV_SOA_FBS)] {
+        let path = output_dir.join(name);
+        file_processing::write_atomic(&path, text.as_bytes())?;
+        written.push(path);
+    }
+
+    anyhow::Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emitted_schemas_are_the_embedded_ones() {
+        let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_emit_schema_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let written = emit_schema(&dir).unwrap();
+        assert_eq!(written, vec![dir.join("ohlcv.fbs"), dir.join("ohlcv_soa.fbs")]);
+        assert_eq!(std::fs::read_to_string(&written[0]).unwrap(), OHLCV_FBS);
+        assert!(OHLCV_FBS.contains("root_type OHLCVList;"));
+        assert!(OHLCV_SOA_FBS.contains("root_type OHLCVListSOA;"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
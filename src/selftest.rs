@@ -0,0 +1,161 @@
+use crate::cli;
+use crate::utils;
+use crate::resample;
+use crate::csv_processor;
+use crate::read_flatbuffers;
+use crate::ohlcv_generated;
+use crate::ohlcv_soa_generated;
+
+/// Trading days of the synthetic series: 2024-01-15 and 2024-01-16, 10:00 UTC.
+const SESSION_STARTS: [u64; 2] = [1705312800, 1705399200];
+/// One-minute bars per day.
+const BARS_PER_DAY: u64 = 10;
+
+/// Expected (timestamp, open, high, low, close, volume) of the 5-minute bars.
+const EXPECTED_5MIN: [(u64, f64, f64, f64, f64, u64); 4] = [
+    (1705312800, 100.0, 106.0, 99.0, 105.0, 15),
+    (1705313100, 105.0, 111.0, 104.0, 110.0, 40),
+    (1705399200, 110.0, 116.0, 109.0, 115.0, 15),
+    (1705399500, 115.0, 121.0, 114.0, 120.0, 40),
+];
+/// Expected (timestamp, open, high, low, close, volume) of the daily bars.
+const EXPECTED_1D: [(u64, f64, f64, f64, f64, u64); 2] = [
+    (1705312800, 100.0, 111.0, 99.0, 110.0, 55),
+    (1705399200, 110.0, 121.0, 109.0, 120.0, 55),
+];
+
+/// Runs the whole pipeline on a tiny synthetic dataset and checks the results.
+///
+/// Two days of ten one-minute bars are written as CSV to a temporary directory, converted
+/// with `convert_csv_to_flatbuffer` in both AOS and SOA, read back, and resampled to 5min
+/// and 1d. Every step prints PASS or FAIL; the temporary directory is removed afterwards.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success if every check passed.
+///
+/// # Errors
+/// * If any check fails, or the temporary files can't be written.
+pub fn run() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("csv_to_flatbuffer_selftest_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let result = run_in(&dir);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let failures = result?;
+    if failures > 0 {
+        return Err(anyhow::anyhow!("Self-test failed: {} check(s) failed", failures));
+    }
+    tracing::info!("✅ Self-test passed");
+    anyhow::Ok(())
+}
+
+/// Runs every check inside `dir` and returns the number of failed checks.
+fn run_in(dir: &std::path::Path) -> anyhow::Result<usize> {
+    let input = dir.join("selftest.txt");
+    std::fs::write(&input, synthetic_csv())?;
+    let mut failures = 0;
+
+    for storage_format in [cli::StorageFormat::Aos, cli::StorageFormat::Soa] {
+        let label = match storage_format {
+            cli::StorageFormat::Aos => "AOS",
+            cli::StorageFormat::Soa => "SOA",
+        };
+        let output = dir.join(format!("selftest{}", storage_format.file_suffix()));
+        // The self-test also exercises the buffer cross-checks the CLI leaves off by default.
+        let options = csv_processor::ConversionOptions {
+            self_verify: true,
+            verify_output: true,
+            ..conversion_options(storage_format.clone())
+        };
+        let stats = csv_processor::convert_csv_to_flatbuffer(input.as_path(), output.as_path(), &options)?;
+        failures += check(&format!("{} convert", label), stats.bars == synthetic_bars().count());
+
+        let bars = read_flatbuffers::load_bars(&output)?;
+        let expected_raw: Vec<(u64, f64, f64, f64, f64, u64)> = synthetic_bars().collect();
+        failures += check(&format!("{} read back", label), bars.iter().map(ohlcv).eq(expected_raw));
+
+        let (resampled, daily) = resample_both(&output, &storage_format)?;
+        failures += check(&format!("{} resample 5min", label), resampled.iter().map(ohlcv).eq(EXPECTED_5MIN));
+        failures += check(&format!("{} resample 1d", label), daily.iter().map(ohlcv).eq(EXPECTED_1D));
+    }
+
+    anyhow::Ok(failures)
+}
+
+/// Prints PASS/FAIL for one check and returns 1 if it failed.
+fn check(name: &str, passed: bool) -> usize {
+    if passed {
+        println!("PASS {}", name);
+        0
+    } else {
+        println!("FAIL {}", name);
+        1
+    }
+}
+
+/// The synthetic series: on day `d`, minute `m` opens at `100 + 10d + m`, with
+/// high = open + 2, low = open - 1, close = open + 1 and volume `m + 1`.
+fn synthetic_bars() -> impl Iterator<Item = (u64, f64, f64, f64, f64, u64)> {
+    SESSION_STARTS.iter().enumerate().flat_map(|(day, &start)| {
+        (0..BARS_PER_DAY).map(move |minute| {
+            let open = 100.0 + 10.0 * day as f64 + minute as f64;
+            (start + minute * 60, open, open + 2.0, open - 1.0, open + 1.0, minute + 1)
+        })
+    })
+}
+
+/// Renders the synthetic series as CSV input.
+fn synthetic_csv() -> String {
+    let mut csv = String::from("<DATE>,<TIME>,<OPEN>,<HIGH>,<LOW>,<CLOSE>,<VOL>\n");
+    for (timestamp, open, high, low, close, volume) in synthetic_bars() {
+        let dt = chrono::DateTime::from_timestamp(timestamp as i64, 0).unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            dt.format("%Y%m%d"),
+            dt.format("%H%M%S"),
+            open,
+            high,
+            low,
+            close,
+            volume
+        ));
+    }
+    csv
+}
+
+fn ohlcv(bar: &resample::OHLCVBar) -> (u64, f64, f64, f64, f64, u64) {
+    (bar.timestamp, bar.open, bar.high, bar.low, bar.close, bar.volume)
+}
+
+/// Defaults for every conversion option, as if no optional flag was given.
+pub(crate) fn conversion_options(storage_format: cli::StorageFormat) -> csv_processor::ConversionOptions {
+    csv_processor::ConversionOptions { storage_format, ..csv_processor::ConversionOptions::default() }
+}
+
+/// Resamples a converted file to 5min and 1d using its `.idx`.
+fn resample_both(
+    path: &std::path::Path,
+    storage_format: &cli::StorageFormat,
+) -> anyhow::Result<(Vec<resample::OHLCVBar>, Vec<resample::OHLCVBar>)> {
+    let buf = std::fs::read(path)?;
+    let full_index = utils::load_full_index(path.with_extension("idx"))?;
+
+    match storage_format {
+        cli::StorageFormat::Aos => {
+            let items = ohlcv_generated::root_as_ohlcvlist(&buf)?.items().unwrap_or_default();
+            anyhow::Ok((
+                resample::resample_ohlcv_aos(&items, &full_index.time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0)?,
+                resample::resample_daily_aos(&items, &full_index.daily_index, &cli::BarTimestamp::BucketStart)?,
+            ))
+        }
+        cli::StorageFormat::Soa => {
+            let data_soa = ohlcv_soa_generated::root_as_ohlcvlist_soa(&buf)?
+                .data()
+                .ok_or_else(|| anyhow::anyhow!("SOA file has no data"))?;
+            anyhow::Ok((
+                resample::resample_ohlcv_soa(data_soa, &full_index.time_index, 300, &cli::BarTimestamp::BucketStart, &cli::BucketEdge::Left, 0)?,
+                resample::resample_daily_soa(data_soa, &full_index.daily_index, &cli::BarTimestamp::BucketStart)?,
+            ))
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::cli;
+
+use chrono::{TimeZone, Timelike};
+
+/// Time zone of the input timestamps and how DST edge cases are resolved.
+///
+/// The default (UTC) never hits an ambiguous or nonexistent local time, so it
+/// behaves exactly like the original UTC-only parser.
+#[derive(Debug, Clone)]
+pub struct InputTimezone {
+    pub tz: chrono_tz::Tz,
+    pub dst_policy: cli::DstPolicy,
+}
+
+impl Default for InputTimezone {
+    fn default() -> Self {
+        InputTimezone { tz: chrono_tz::UTC, dst_policy: cli::DstPolicy::Error }
+    }
+}
+
+/// Longest gap searched for the next valid instant (Samoa skipped a whole day in 2011).
+const MAX_GAP_MINUTES: i64 = 2 * 24 * 60;
+
+impl InputTimezone {
+    /// Converts a local date-time in this time zone into a Unix timestamp (UTC seconds).
+    ///
+    /// - Ambiguous times (fall-back overlap, e.g. 01:30 on the first Sunday of November in
+    ///   New York) resolve to the earlier or later instant, or fail with `DstPolicy::Error`.
+    /// - Nonexistent times (spring-forward gap, e.g. 02:30 on the second Sunday of March)
+    ///   fail with `DstPolicy::Error`; otherwise they move to the next valid instant, the
+    ///   end of the gap (03:00).
+    ///
+    /// # Arguments
+    /// * `local` - Wall-clock date-time as written in the input.
+    ///
+    /// # Returns
+    /// * `anyhow::Result<u64>` - The Unix timestamp, or an error as described above.
+    pub fn to_timestamp(&self, local: chrono::NaiveDateTime) -> anyhow::Result<u64> {
+        match self.tz.from_local_datetime(&local) {
+            chrono::LocalResult::Single(dt) => anyhow::Ok(dt.timestamp() as u64),
+            chrono::LocalResult::Ambiguous(earliest, latest) => match self.dst_policy {
+                cli::DstPolicy::Earliest => anyhow::Ok(earliest.timestamp() as u64),
+                cli::DstPolicy::Latest => anyhow::Ok(latest.timestamp() as u64),
+                cli::DstPolicy::Error => Err(anyhow::anyhow!(
+                    "Ambiguous local time {} in {} (DST overlap); use --dst-policy earliest|latest",
+                    local,
+                    self.tz
+                )),
+            },
+            chrono::LocalResult::None => match self.dst_policy {
+                cli::DstPolicy::Error => Err(anyhow::anyhow!(
+                    "Nonexistent local time {} in {} (DST gap); use --dst-policy earliest|latest to take the next valid time",
+                    local,
+                    self.tz
+                )),
+                _ => self.next_valid_timestamp(local),
+            },
+        }
+    }
+
+    /// Returns the first valid instant after a nonexistent local time.
+    ///
+    /// Transitions happen on whole minutes, so stepping minute by minute from the
+    /// start of `local`'s minute lands exactly on the end of the gap.
+    fn next_valid_timestamp(&self, local: chrono::NaiveDateTime) -> anyhow::Result<u64> {
+        let minute_start = local.with_second(0).and_then(|t| t.with_nanosecond(0))
+            .ok_or_else(|| anyhow::anyhow!("Invalid local time {}", local))?;
+        for minutes in 1..=MAX_GAP_MINUTES {
+            let candidate = minute_start + chrono::Duration::minutes(minutes);
+            if let Some(dt) = self.tz.from_local_datetime(&candidate).earliest() {
+                return anyhow::Ok(dt.timestamp() as u64);
+            }
+        }
+        Err(anyhow::anyhow!("No valid local time in {} after {}", self.tz, local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_york(dst_policy: cli::DstPolicy) -> InputTimezone {
+        InputTimezone { tz: chrono_tz::America::New_York, dst_policy }
+    }
+
+    fn local(s: &str) -> chrono::NaiveDateTime {
+        chrono::NaiveDateTime::parse_from_str(s, "%Y%m%d %H%M%S").unwrap()
+    }
+
+    #[test]
+    fn regular_local_time_uses_the_zone_offset() {
+        // 09:30 EDT is 13:30 UTC.
+        assert_eq!(new_york(cli::DstPolicy::Error).to_timestamp(local("20240701 093000")).unwrap(), 1719840600);
+    }
+
+    #[test]
+    fn ambiguous_fall_back_time_follows_the_policy() {
+        let overlap = local("20241103 013000");
+
+        assert_eq!(new_york(cli::DstPolicy::Earliest).to_timestamp(overlap).unwrap(), 1730611800);
+        assert_eq!(new_york(cli::DstPolicy::Latest).to_timestamp(overlap).unwrap(), 1730615400);
+        assert!(new_york(cli::DstPolicy::Error).to_timestamp(overlap).is_err());
+    }
+
+    #[test]
+    fn nonexistent_spring_forward_time_moves_to_the_end_of_the_gap() {
+        let gap = local("20240310 023000");
+
+        assert_eq!(new_york(cli::DstPolicy::Earliest).to_timestamp(gap).unwrap(), 1710054000);
+        assert_eq!(new_york(cli::DstPolicy::Latest).to_timestamp(gap).unwrap(), 1710054000);
+        assert!(new_york(cli::DstPolicy::Error).to_timestamp(gap).is_err());
+    }
+
+    #[test]
+    fn utc_default_has_no_edge_cases() {
+        assert_eq!(InputTimezone::default().to_timestamp(local("20240310 023000")).unwrap(), 1710037800);
+    }
+}
@@ -1,10 +1,65 @@
+use crate::bar_source::{BarSource, SoaColumns};
+use crate::cli;
 use crate::index;
 use crate::resample;
+use crate::file_processing;
 use crate::ohlcv_generated;
 use crate::ohlcv_soa_generated;
 
 use chrono::TimeZone;
 
+/// Latest FlatBuffer schema version written and understood by this binary.
+///
+/// Stored in the `version` field of the root table (`OHLCVList` / `OHLCVListSOA`).
+/// Files written before versioning existed have no such field and read as version 1.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Checks that a file's schema version can be read by this binary.
+///
+/// # Arguments
+/// * `version` - Version read from the root table of a .bin file.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success if the version is supported.
+///
+/// # Errors
+/// * If the file was written by a newer tool with an unknown schema version.
+pub fn check_schema_version(version: u32) -> anyhow::Result<()> {
+    if version == 0 || version > SCHEMA_VERSION {
+        return Err(anyhow::anyhow!(
+            "Unsupported schema version {} (this build supports 1..={})",
+            version,
+            SCHEMA_VERSION
+        ));
+    }
+    anyhow::Ok(())
+}
+
+/// Checks that a `.idx` was written for the same data as its `.bin`.
+///
+/// `.bin` and `.idx` are each replaced atomically, but not together: a conversion killed
+/// between the two writes leaves a new `.bin` next to the previous `.idx`.
+///
+/// # Arguments
+/// * `index` - Index loaded from the `.idx` file.
+/// * `bars` - Number of bars in the `.bin` file.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Success if the index covers exactly `bars` bars.
+///
+/// # Errors
+/// * If the index and the data disagree on the number of bars.
+pub fn check_index_matches(index: &index::FullIndex, bars: usize) -> anyhow::Result<()> {
+    if index.time_index.len() != bars {
+        return Err(anyhow::anyhow!(
+            "Index has {} entries but the data has {} bars (interrupted conversion?); reconvert the file",
+            index.time_index.len(),
+            bars
+        ));
+    }
+    anyhow::Ok(())
+}
+
 /// Configures a custom Rayon thread pool with specified size.
 ///
 /// This function creates a new Rayon thread pool with the exact number of threads specified.
@@ -23,48 +78,204 @@ pub fn configure_thread_pool(num_threads: usize) -> anyhow::Result<rayon::Thread
         .map_err(|e| anyhow::anyhow!("Failed to build thread pool: {}", e))
 }
 
+/// Caps a thread count at the number of files to process.
+///
+/// Each file is handled by a single rayon worker, so a pool larger than the file
+/// count only adds idle threads. Logs when the count is reduced.
+///
+/// # Arguments
+/// * `threads` - Requested number of threads.
+/// * `file_count` - Number of files the pool will process.
+/// * `phase` - Phase name used in the log message (e.g., "conversion").
+///
+/// # Returns
+/// * `usize` - `min(threads, file_count)`, but at least 1.
+pub fn cap_threads_to_work(threads: usize, file_count: usize, phase: &str) -> usize {
+    let capped = threads.min(file_count.max(1));
+    if capped < threads {
+        tracing::warn!(
+            "⚠️ Reducing {} threads from {} to {} ({} file(s) to process)",
+            phase,
+            threads,
+            capped,
+            file_count
+        );
+    }
+    capped
+}
+
+/// Returns the peak resident set size (high-water mark) of the current process in kilobytes.
+///
+/// On Linux this reads the `VmHWM` line from `/proc/self/status`, which is cheap and
+/// requires no extra dependencies. On other platforms the file doesn't exist and `None`
+/// is returned.
+///
+/// # Returns
+/// * `Option<u64>` - Peak RSS in kilobytes, or `None` if it can't be determined.
+pub fn peak_rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find(|line| line.starts_with("VmHWM:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|value| value.parse::<u64>().ok())
+}
+
 /// Loads FullIndex from .idx file serialized with bincode.
 ///
 /// This function reads the binary data from the specified .idx file path,
 /// deserializes it using `bincode`, and returns the resulting `index::FullIndex` struct.
 /// The .idx file contains precomputed indices for fast data access and resampling.
+/// The layout is picked by the header: `index::INDEX_MAGIC` or, for compact files
+/// (`--compact-index`), `index::COMPACT_INDEX_MAGIC`, each followed by a version byte.
+/// A compact file's delta/varint `time_index` is expanded back to absolute values, and
+/// files without a header, written before it existed, are read as `index::LegacyFullIndex`.
 ///
 /// # Arguments
 /// * `idx_path` - Path to the .idx file.
 ///
 /// # Returns
 /// * `anyhow::Result<index::FullIndex>` - Deserialized `FullIndex` struct or an error.
+///
+/// # Errors
+/// * If the file has a version this binary doesn't understand.
 pub fn load_full_index<P: AsRef<std::path::Path> + Send + Sync>(idx_path: P) -> anyhow::Result<index::FullIndex> {
-    let data = std::fs::read(idx_path)?;
-    let index = bincode::deserialize(&data)?;
-    anyhow::Ok(index)
+    let data = std::fs::read(idx_path.as_ref()).map_err(|e| file_processing::io_error(e, "read index", &idx_path))?;
+    decode_full_index(&data)
+}
+
+/// Decodes the contents of a `.idx` file, in any of the layouts `load_full_index` reads.
+///
+/// Used directly for indices that don't live in a file of their own, like the index
+/// section of a `.ohlcv` bundle (`bundle::Bundle`).
+///
+/// # Errors
+/// * If the index has a version this binary doesn't understand, or `data` isn't an index.
+pub fn decode_full_index(data: &[u8]) -> anyhow::Result<index::FullIndex> {
+    if let Some(rest) = data.strip_prefix(index::INDEX_MAGIC.as_slice()) {
+        return match rest.split_first() {
+            Some((&index::INDEX_VERSION, body)) => anyhow::Ok(bincode::deserialize::<index::FullIndex>(body)?),
+            Some((version, _)) => Err(anyhow::anyhow!(
+                "Unsupported index version {} (this binary reads version {})",
+                version,
+                index::INDEX_VERSION
+            )),
+            None => Err(anyhow::anyhow!("Index is missing its version byte")),
+        };
+    }
+    if let Some(rest) = data.strip_prefix(index::COMPACT_INDEX_MAGIC.as_slice()) {
+        return match rest.split_first() {
+            Some((&index::COMPACT_INDEX_VERSION, body)) => {
+                bincode::deserialize::<index::CompactFullIndex>(body)?.try_into()
+            }
+            Some((2, body)) => {
+                index::CompactFullIndex::from(bincode::deserialize::<index::CompactFullIndexV2>(body)?).try_into()
+            }
+            Some((1, body)) => {
+                index::CompactFullIndex::from(bincode::deserialize::<index::CompactFullIndexV1>(body)?).try_into()
+            }
+            Some((version, _)) => Err(anyhow::anyhow!(
+                "Unsupported compact index version {} (this binary reads version {})",
+                version,
+                index::COMPACT_INDEX_VERSION
+            )),
+            None => Err(anyhow::anyhow!("Compact index is missing its version byte")),
+        };
+    }
+    anyhow::Ok(bincode::deserialize::<index::LegacyFullIndex>(data)?.into())
+}
+
+/// Bar count and time range of a converted file, as returned by `read_metadata`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub bars: u64,
+    /// Timestamp of the first bar; `None` for an empty file.
+    pub first_timestamp: Option<u64>,
+    /// Timestamp of the last bar; `None` for an empty file.
+    pub last_timestamp: Option<u64>,
 }
 
-/// Converts a date string in the format `%Y-%m-%d` to a Unix timestamp (in seconds).
+/// Size of one bincode-encoded `index::TimeIndexEntry` (two fixed-width `u64`s).
+const TIME_INDEX_ENTRY_BYTES: u64 = 16;
+
+/// Size of the `index::INDEX_MAGIC` header and its version byte.
+const INDEX_HEADER_BYTES: u64 = index::INDEX_MAGIC.len() as u64 + 1;
+
+/// Reads the bar count and time range of a converted file from its `.idx`.
 ///
-/// This function assumes that the time part is midnight (00:00:00 UTC).
-/// It's primarily used for converting date strings from the daily index
-/// into Unix timestamps for OHLCV bar creation.
+/// Every plain `.idx` layout starts with `time_index` right after its header (legacy files
+/// have none), bincode-encoded as a `u64` length followed by fixed-size entries, so only
+/// three 8-byte reads are needed: the length and the timestamps of the first and last entries. The `.bin` is never opened, which makes
+/// this cheap enough to inventory thousands of files. Compact indices (`--compact-index`)
+/// have no fixed-size entries and are decoded in full.
 ///
 /// # Arguments
-///
-/// * `date_str` - A string slice representing the date in the format `%Y-%m-%d` (e.g., "2025-07-08").
+/// * `path` - Path to a `.idx` file, or to the `.bin` (or `--output-suffix` file) next to it.
 ///
 /// # Returns
+/// * `anyhow::Result<Metadata>` - Bar count and first/last timestamps.
 ///
-/// * `anyhow::Result<u64>` - The corresponding Unix timestamp (seconds since epoch), or an error if parsing fails.
-///
-/// # Examples
-///
-/// ```
-/// let ts = parse_date_to_timestamp("2025-07-08").unwrap();
-/// // The timestamp corresponds to 2025-07-08 00:00:00 UTC
-/// ```
-pub fn parse_date_to_timestamp(date_str: &str) -> anyhow::Result<u64> {
-    let dt = chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d")?;
-    let dt = dt.and_hms_opt(0, 0, 0).unwrap();
-    let timestamp = dt.and_utc().timestamp() as u64;
-    Ok(timestamp)
+/// # Errors
+/// * If the `.idx` is missing, has a version this binary doesn't understand, or is too short
+///   for the length it declares.
+pub fn read_metadata<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Metadata> {
+    use std::io::{Read, Seek};
+
+    let path = path.as_ref();
+    let idx_path = if path.extension().is_some_and(|ext| ext != "idx") {
+        path.with_extension("idx")
+    } else {
+        path.to_path_buf()
+    };
+    let mut file = std::fs::File::open(&idx_path).map_err(|e| file_processing::io_error(e, "open index", &idx_path))?;
+
+    let mut word = [0u8; 8];
+    file.read_exact(&mut word)?;
+    if word.starts_with(index::COMPACT_INDEX_MAGIC) {
+        let time_index = load_full_index(&idx_path)?.time_index;
+        return anyhow::Ok(Metadata {
+            bars: time_index.len() as u64,
+            first_timestamp: time_index.first().map(|e| e.timestamp),
+            last_timestamp: time_index.last().map(|e| e.timestamp),
+        });
+    }
+
+    let start = if word.starts_with(index::INDEX_MAGIC) {
+        if word[index::INDEX_MAGIC.len()] != index::INDEX_VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported index version {} in {} (this binary reads version {})",
+                word[index::INDEX_MAGIC.len()],
+                idx_path.display(),
+                index::INDEX_VERSION
+            ));
+        }
+        INDEX_HEADER_BYTES
+    } else {
+        0
+    };
+
+    let file_len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut word_at = |offset: u64| -> anyhow::Result<u64> {
+        file.seek(std::io::SeekFrom::Start(offset))?;
+        file.read_exact(&mut word)?;
+        anyhow::Ok(u64::from_le_bytes(word))
+    };
+    let bars = word_at(start)?;
+    if bars == 0 {
+        return anyhow::Ok(Metadata { bars, first_timestamp: None, last_timestamp: None });
+    }
+    let first_offset = start + 8;
+    let last_offset = (bars - 1)
+        .checked_mul(TIME_INDEX_ENTRY_BYTES)
+        .and_then(|offset| offset.checked_add(first_offset))
+        .filter(|&offset| offset.saturating_add(8) <= file_len)
+        .ok_or_else(|| anyhow::anyhow!("Index {} is truncated or not a .idx file", idx_path.display()))?;
+
+    anyhow::Ok(Metadata {
+        bars,
+        first_timestamp: Some(word_at(first_offset)?),
+        last_timestamp: Some(word_at(last_offset)?),
+    })
 }
 
 /// Formats Unix timestamp into readable string: YYYYMMDD HHMMSS.
@@ -84,64 +295,89 @@ pub fn format_timestamp(ts: u64) -> anyhow::Result<String> {
     anyhow::Ok(output)
 }
 
-/// Prints the first `count` OHLCV bars from a FlatBuffers Vector (AOS format).
-///
-/// This function iterates through the first `count` elements of a `flatbuffers::Vector<OHLCV>`,
-/// retrieves each bar's fields using the generated FlatBuffers accessor methods (e.g., `.timestamp()`, `.open()`),
-/// formats the timestamp into a human-readable string, and prints the data.
+/// Formats Unix timestamp into its UTC calendar date: YYYY-MM-DD.
 ///
-/// It is designed for displaying raw, unmodified OHLCV data loaded directly from an AOS `.bin` file.
+/// This is the key format used by `DailyIndexEntry::date`.
 ///
 /// # Arguments
-/// * `items` - A reference to the FlatBuffers vector containing `OHLCV` objects (Array of Structures).
-/// * `count` - The maximum number of bars to print (e.g., first 5).
+/// * `ts` - Unix timestamp in seconds.
 ///
 /// # Returns
-/// * `anyhow::Result<()>` - Indicates success or an error during timestamp formatting or printing.
+/// * `anyhow::Result<String>` - Formatted date (e.g., "2024-06-13") or error if invalid timestamp.
+pub fn format_date(ts: u64) -> anyhow::Result<String> {
+    let dt = chrono::DateTime::from_timestamp(ts as i64, 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid timestamp: {}", ts))?;
+    anyhow::Ok(dt.format("%Y-%m-%d").to_string())
+}
+
+/// Formats a volume for display.
 ///
-/// # Example Output
-/// ```text
-///  - ts: 20231214 090000, open: 90302.00, high: 90399.00, low: 90120.00, close: 90265.00, vol: 1320
-///  - ts: 20231214 090100, open: 90252.00, high: 90255.00, low: 90224.00, close: 90234.00, vol: 154
-/// ```
+/// * `Plain` - the raw integer: `1320000000`.
+/// * `Grouped` - thousands separators: `1,320,000,000`.
+/// * `Si` - SI suffix with up to two decimals: `1.32G` (`K`, `M`, `G`, `T`, `P`, `E`);
+///   volumes below 1000 are printed as-is.
 ///
-/// # Notes
-/// * Uses zero-copy access via `items.get(i)`.
-/// * Relies on `utils::format_timestamp` for readable datetime strings.
-pub fn print_bars_aos(
-    items: &flatbuffers::Vector<flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV<'_>>>,
-    count: usize
-) -> anyhow::Result<()>
-{
-    for i in 0..std::cmp::min(count, items.len()) {
-        let item = items.get(i);
-        let ts = item.timestamp();
-        let formated = format_timestamp(ts)?;
-        println!(
-            " - ts: {}, open: {:.2}, high: {:.2}, low: {:.2}, close: {:.2}, vol: {}",
-            formated,
-            item.open(),
-            item.high(),
-            item.low(),
-            item.close(),
-            item.volume(),
-        );
+/// # Arguments
+/// * `volume` - Volume to format.
+/// * `volume_format` - Notation to use.
+///
+/// # Returns
+/// * `String` - The formatted volume.
+pub fn format_volume(volume: u64, volume_format: &cli::VolumeFormat) -> String {
+    match volume_format {
+        cli::VolumeFormat::Plain => volume.to_string(),
+        cli::VolumeFormat::Grouped => {
+            let digits = volume.to_string();
+            let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+            for (i, c) in digits.chars().enumerate() {
+                if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                    grouped.push(',');
+                }
+                grouped.push(c);
+            }
+            grouped
+        }
+        cli::VolumeFormat::Si => {
+            const SUFFIXES: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+            if volume < 1000 {
+                return volume.to_string();
+            }
+            let mut value = volume as f64;
+            let mut suffix = "";
+            for s in SUFFIXES {
+                // Values that would round up to 1000.00 move to the next suffix instead.
+                if value < 999.995 {
+                    break;
+                }
+                value /= 1000.0;
+                suffix = s;
+            }
+            let number = format!("{:.2}", value);
+            let number = number.trim_end_matches('0').trim_end_matches('.');
+            format!("{}{}", number, suffix)
+        }
     }
-    
-    anyhow::Ok(())
 }
 
-/// Prints the first `count` OHLCV bars from a FlatBuffers SOA object.
-///
-/// This function accesses the separate arrays within the `OHLCVSOA` object (Structure of Arrays),
-/// retrieves the first `count` elements from each array, formats the timestamp into a human-readable string,
-/// and prints the data.
+/// Renders the `, vol: N` suffix of a printed bar, or nothing when volume is hidden.
+fn volume_field(volume: u64, volume_format: Option<&cli::VolumeFormat>) -> String {
+    match volume_format {
+        Some(volume_format) => format!(", vol: {}", format_volume(volume, volume_format)),
+        None => String::new(),
+    }
+}
+
+/// Prints the first `count` bars of a `BarSource`.
 ///
-/// It is designed for displaying raw, unmodified OHLCV data loaded directly from an SOA `.bin` file.
+/// It is designed for displaying raw, unmodified OHLCV data loaded directly from a `.bin`
+/// file of either layout; it formats each timestamp into a human-readable string and
+/// prints the bar's fields as stored.
 ///
 /// # Arguments
-/// * `data_soa` - The FlatBuffers OHLCVSOA object containing separate arrays for each field.
+/// * `source` - The stored bars (AOS items or SOA columns, see `bar_source`).
 /// * `count` - The maximum number of bars to print (e.g., first 5).
+/// * `volume_format` - How volumes are rendered (see `format_volume`); `None` hides the volume
+///   (files written with `--no-volume`).
 ///
 /// # Returns
 /// * `anyhow::Result<()>` - Indicates success or an error during timestamp formatting or printing.
@@ -153,47 +389,51 @@ pub fn print_bars_aos(
 /// ```
 ///
 /// # Notes
-/// * Accesses data from separate arrays: `timestamps`, `opens`, `highs`, etc.
+/// * Uses zero-copy access through the source's accessors.
 /// * Relies on `utils::format_timestamp` for readable datetime strings.
-pub fn print_bars_soa(
-    data_soa: ohlcv_soa_generated::OHLCVSOA,
+pub fn print_bars_source<B: BarSource>(
+    source: &B,
     count: usize,
+    volume_format: Option<&cli::VolumeFormat>,
 ) -> anyhow::Result<()> {
-    let timestamps = data_soa.timestamps().unwrap_or_default();
-    let opens = data_soa.opens().unwrap_or_default();
-    let highs = data_soa.highs().unwrap_or_default();
-    let lows = data_soa.lows().unwrap_or_default();
-    let closes = data_soa.closes().unwrap_or_default();
-    let volumes = data_soa.volumes().unwrap_or_default();
-
-    let len = std::cmp::min(timestamps.len(), opens.len());
-    let len = std::cmp::min(len, highs.len());
-    let len = std::cmp::min(len, lows.len());
-    let len = std::cmp::min(len, closes.len());
-    let len = std::cmp::min(len, volumes.len());
-
-    for i in 0..std::cmp::min(count, len) {
-        let ts = timestamps.get(i);
-        let open = opens.get(i);
-        let high = highs.get(i);
-        let low = lows.get(i);
-        let close = closes.get(i);
-        let vol = volumes.get(i);
-
-        let formated = format_timestamp(ts)?;
+    for i in 0..std::cmp::min(count, source.len()) {
+        let bar = source.bar(i);
+        let formated = format_timestamp(bar.timestamp)?;
         println!(
-            " - ts: {}, open: {:.2}, high: {:.2}, low: {:.2}, close: {:.2}, vol: {}",
+            " - ts: {}, open: {:.2}, high: {:.2}, low: {:.2}, close: {:.2}{}",
             formated,
-            open,
-            high,
-            low,
-            close,
-            vol,
+            bar.open,
+            bar.high,
+            bar.low,
+            bar.close,
+            volume_field(bar.volume, volume_format),
         );
     }
 
     anyhow::Ok(())
+}
 
+/// Prints the first `count` OHLCV bars from a FlatBuffers Vector (AOS format).
+///
+/// See `print_bars_source`, which this forwards to.
+pub fn print_bars_aos<'a>(
+    items: &flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ohlcv_generated::OHLCV<'a>>>,
+    count: usize,
+    volume_format: Option<&cli::VolumeFormat>,
+) -> anyhow::Result<()> {
+    print_bars_source(items, count, volume_format)
+}
+
+/// Prints the first `count` OHLCV bars from a FlatBuffers SOA object.
+///
+/// See `print_bars_source`, which this forwards to. Columns of different lengths are
+/// read up to the shortest one (`SoaColumns::truncated`).
+pub fn print_bars_soa(
+    data_soa: ohlcv_soa_generated::OHLCVSOA,
+    count: usize,
+    volume_format: Option<&cli::VolumeFormat>,
+) -> anyhow::Result<()> {
+    print_bars_source(&SoaColumns::truncated(data_soa), count, volume_format)
 }
 
 /// Prints the first `count` resampled OHLCV bars from a slice of `OHLCVBar` structs.
@@ -206,6 +446,12 @@ pub fn print_bars_soa(
 /// # Arguments
 /// * `items` - A slice of `OHLCVBar` structs produced by resampling logic (either AOS or SOA).
 /// * `count` - The maximum number of bars to print (e.g., first 5).
+/// * `show_count` - Append the number of aggregated source bars (`bars: N`) to each line.
+/// * `volume_format` - How volumes are rendered (see `format_volume`); `None` hides the volume
+///   (files written with `--no-volume`).
+/// * `true_ranges` - True range of each bar (`resample::true_ranges`), appended as `tr: X`.
+/// * `unique_timestamps` - Distinct source timestamps of each bar (`resample::unique_timestamps`),
+///   appended as `uniq: N`.
 ///
 /// # Returns
 /// * `anyhow::Result<()>` - Indicates success or an error during timestamp formatting or printing.
@@ -222,23 +468,204 @@ pub fn print_bars_soa(
 /// * Uses `utils::format_timestamp` for readable datetime strings.
 pub fn print_bars_resampled(
     items: &[resample::OHLCVBar],
-    count: usize
+    count: usize,
+    show_count: bool,
+    volume_format: Option<&cli::VolumeFormat>,
+    true_ranges: Option<&[f64]>,
+    unique_timestamps: Option<&[u64]>,
 ) -> anyhow::Result<()>
 {
-    for i in 0..std::cmp::min(count, items.len()) {
-        let item = &items[i];
+    for (i, item) in items.iter().take(count).enumerate() {
         let ts = item.timestamp;
         let formated = format_timestamp(ts)?;
+        let bars = if show_count { format!(", bars: {}", item.count) } else { String::new() };
+        let true_range = match true_ranges.and_then(|tr| tr.get(i)) {
+            Some(tr) => format!(", tr: {:.2}", tr),
+            None => String::new(),
+        };
+        let unique = match unique_timestamps.and_then(|u| u.get(i)) {
+            Some(unique) => format!(", uniq: {}", unique),
+            None => String::new(),
+        };
         println!(
-            " - ts: {}, open: {:.2}, high: {:.2}, low: {:.2}, close: {:.2}, vol: {}",
+            " - ts: {}, open: {:.2}, high: {:.2}, low: {:.2}, close: {:.2}{}{}{}{}",
             formated,
             item.open,
             item.high,
             item.low,
             item.close,
-            item.volume,
+            volume_field(item.volume, volume_format),
+            bars,
+            true_range,
+            unique,
         );
     }
     
     anyhow::Ok(())
 }
+
+/// Block characters of a sparkline, from the lowest value to the highest.
+const SPARKLINE_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Renders the closes of `items` as a Unicode block sparkline, one character per bar.
+///
+/// Closes are scaled linearly between the lowest and the highest of the slice, so the
+/// lowest maps to `▁` and the highest to `█`. A flat series (every close equal, or a
+/// single bar) has no range to scale by and is drawn as a row of middle blocks.
+///
+/// # Arguments
+/// * `items` - Bars in the order they are printed.
+///
+/// # Returns
+/// * `String` - The sparkline; empty for no bars.
+///
+/// # Example Output
+/// ```text
+///  ▁▃▂▆█
+/// ```
+pub fn sparkline(items: &[resample::OHLCVBar]) -> String {
+    let min = items.iter().map(|b| b.close).fold(f64::INFINITY, f64::min);
+    let max = items.iter().map(|b| b.close).fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+    let top = (SPARKLINE_BLOCKS.len() - 1) as f64;
+    items
+        .iter()
+        .map(|b| {
+            if range > 0.0 && range.is_finite() {
+                SPARKLINE_BLOCKS[((b.close - min) / range * top).round() as usize]
+            } else {
+                SPARKLINE_BLOCKS[SPARKLINE_BLOCKS.len() / 2 - 1]
+            }
+        })
+        .collect()
+}
+
+/// Prints the first `count` bars as a fixed-width aligned table with a header row.
+///
+/// Column widths are computed from the widest cell in each column (header included),
+/// so large prices or volumes never break the alignment. Numbers are right-aligned.
+///
+/// # Arguments
+/// * `items` - A slice of `OHLCVBar` structs (raw or resampled).
+/// * `count` - The maximum number of bars to print (e.g., first 5).
+/// * `show_count` - Add a `BARS` column with the number of aggregated source bars.
+/// * `volume_format` - How volumes are rendered (see `format_volume`); `None` hides the volume
+///   (files written with `--no-volume`).
+///
+/// * `true_ranges` - True range of each bar (`resample::true_ranges`), shown in a `TR` column.
+/// * `unique_timestamps` - Distinct source timestamps of each bar, shown in a `UNIQ` column.
+///
+/// # Returns
+/// * `anyhow::Result<()>` - Indicates success or an error during timestamp formatting.
+///
+/// # Example Output
+/// ```text
+///  TS              |     OPEN |     HIGH |      LOW |    CLOSE |  VOL
+/// -----------------+----------+----------+----------+----------+------
+///  20231214 090000 | 90302.00 | 90399.00 | 90120.00 | 90265.00 | 1320
+///  20231214 090100 | 90252.00 | 90255.00 | 90224.00 | 90234.00 |  154
+/// ```
+pub fn print_bars_table(
+    items: &[resample::OHLCVBar],
+    count: usize,
+    show_count: bool,
+    volume_format: Option<&cli::VolumeFormat>,
+    true_ranges: Option<&[f64]>,
+    unique_timestamps: Option<&[u64]>,
+) -> anyhow::Result<()> {
+    let mut header = vec!["TS", "OPEN", "HIGH", "LOW", "CLOSE"];
+    if volume_format.is_some() {
+        header.push("VOL");
+    }
+    if show_count {
+        header.push("BARS");
+    }
+    if true_ranges.is_some() {
+        header.push("TR");
+    }
+    if unique_timestamps.is_some() {
+        header.push("UNIQ");
+    }
+
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for (i, item) in items.iter().take(count).enumerate() {
+        let mut row = vec![
+            format_timestamp(item.timestamp)?,
+            format!("{:.2}", item.open),
+            format!("{:.2}", item.high),
+            format!("{:.2}", item.low),
+            format!("{:.2}", item.close),
+        ];
+        if let Some(volume_format) = volume_format {
+            row.push(format_volume(item.volume, volume_format));
+        }
+        if show_count {
+            row.push(item.count.to_string());
+        }
+        if let Some(true_ranges) = true_ranges {
+            row.push(true_ranges.get(i).map(|tr| format!("{:.2}", tr)).unwrap_or_default());
+        }
+        if let Some(unique_timestamps) = unique_timestamps {
+            row.push(unique_timestamps.get(i).map(u64::to_string).unwrap_or_default());
+        }
+        rows.push(row);
+    }
+
+    let widths: Vec<usize> = header
+        .iter()
+        .enumerate()
+        .map(|(col, name)| rows.iter().map(|row| row[col].len()).fold(name.len(), usize::max))
+        .collect();
+
+    // The timestamp column is left-aligned, numeric columns are right-aligned.
+    let format_row = |cells: &[&str]| -> String {
+        cells
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(col, (cell, width))| {
+                if col == 0 {
+                    format!(" {:<width$} ", cell, width = width)
+                } else {
+                    format!(" {:>width$} ", cell, width = width)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+
+    println!("{}", format_row(&header));
+    println!(
+        "{}",
+        widths.iter().map(|w| "-".repeat(w + 2)).collect::<Vec<_>>().join("+")
+    );
+    for row in &rows {
+        let cells: Vec<&str> = row.iter().map(String::as_str).collect();
+        println!("{}", format_row(&cells));
+    }
+
+    anyhow::Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closes(closes: &[f64]) -> Vec<resample::OHLCVBar> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| resample::OHLCVBar { timestamp: 1705312800 + i as u64 * 60, open: close, high: close, low: close, close, volume: 1, count: 1 })
+            .collect()
+    }
+
+    #[test]
+    fn sparkline_scales_closes_between_min_and_max() {
+        assert_eq!(sparkline(&closes(&[10.0, 13.0, 11.0, 17.0])), "▁▄▂█");
+        assert_eq!(sparkline(&closes(&[-2.0, -1.0])), "▁█");
+        // Flat series and single bars have no range and don't divide by zero.
+        assert_eq!(sparkline(&closes(&[5.0, 5.0, 5.0])), "▄▄▄");
+        assert_eq!(sparkline(&closes(&[5.0])), "▄");
+        assert_eq!(sparkline(&[]), "");
+    }
+}